@@ -0,0 +1,33 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{env, process::Command};
+
+// Build facts for `meta-hybrid info`; a tree without git (a source tarball) reports "unknown".
+fn main() {
+    let git_hash = env::var("META_HYBRID_GIT_HASH")
+        .ok()
+        .filter(|h| !h.is_empty())
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=META_HYBRID_GIT_HASH={}", git_hash);
+    println!(
+        "cargo:rustc-env=META_HYBRID_PROFILE={}",
+        env::var("PROFILE").unwrap_or_default()
+    );
+    println!(
+        "cargo:rustc-env=META_HYBRID_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+    println!("cargo:rerun-if-env-changed=META_HYBRID_GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+}
@@ -24,6 +24,9 @@ ui_print "- Installing binary for $ABI..."
 cp -f "$BIN_SOURCE" "$BIN_TARGET"
 set_perm "$BIN_TARGET" 0 0 0755
 rm -rf "$MODPATH/binaries"
+for TOOL_DIR in "$MODPATH"/tools/*/; do
+  [ "$(basename "$TOOL_DIR")" = "$ABI" ] || rm -rf "$TOOL_DIR"
+done
 rm -rf "$MODPATH/system"
 BASE_DIR="/data/adb/meta-hybrid"
 mkdir -p "$BASE_DIR"
@@ -72,8 +75,12 @@ else
   ui_print "- Existing config found"
   ui_print "- Skipping setup wizard to preserve settings"
 fi
+set_perm "$BASE_DIR/config.toml" 0 0 0600
 
 set_perm_recursive "$MODPATH" 0 0 0755 0644
 set_perm "$BIN_TARGET" 0 0 0755
-set_perm "$MODPATH/tools/mkfs.erofs" 0 0 0755
+"$BIN_TARGET" harden >&2 || ui_print "! Failed to harden $BASE_DIR"
+for TOOL in "$MODPATH"/tools/* "$MODPATH"/tools/"$ABI"/*; do
+  [ -f "$TOOL" ] && [ "$(basename "$TOOL")" != "checksums.json" ] && set_perm "$TOOL" 0 0 0755
+done
 ui_print "- Installation complete"
\ No newline at end of file
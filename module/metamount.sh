@@ -2,9 +2,6 @@ MODDIR="${0%/*}"
 BASE_DIR="/data/adb/meta-hybrid"
 LOG_FILE="$BASE_DIR/daemon.log"
 mkdir -p "$BASE_DIR"
-if [ -f "$LOG_FILE" ]; then
-    rm "$LOG_FILE"
-fi
 log() {
     echo "[Wrapper] $1" >> "$LOG_FILE"
 }
@@ -20,7 +17,7 @@ if [ -f "/data/adb/hybrid_mount/daemon.log" ]; then
 fi
 
 chmod 755 "$BINARY"
-"$BINARY" >> "$LOG_FILE" 2>&1
+"$BINARY" --stage post-fs-data >> "$LOG_FILE" 2>&1
 EXIT_CODE=$?
 log "Hybrid Mount exited with code $EXIT_CODE"
 if [ "$EXIT_CODE" = "0" ]; then
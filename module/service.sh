@@ -0,0 +1,10 @@
+MODDIR="${0%/*}"
+LOG_FILE="/data/adb/meta-hybrid/daemon.log"
+# Second pass of the boot: mounts modules whose rules ask for the service stage.
+"$MODDIR/meta-hybrid" --stage service >> "$LOG_FILE" 2>&1
+# App-scoped modules and denylist unmounting are handled by the daemon as each app starts, and
+# scheduled maintenance runs from it too.
+if grep -qE '^\[visibility\]|^\[maintenance\]|^denylist_umount *= *true' /data/adb/meta-hybrid/config.toml 2>/dev/null; then
+  "$MODDIR/meta-hybrid" daemon >> "$LOG_FILE" 2>&1 &
+fi
+exit 0
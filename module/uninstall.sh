@@ -4,6 +4,9 @@
 # Cleanup script for metamodule removal
 ############################################
 
+MODDIR="${0%/*}"
+# Detaches our mounts before deleting, so a still-attached image cannot keep its space.
+"$MODDIR/meta-hybrid" uninstall --purge --yes >/dev/null 2>&1
 rm -rf "/data/adb/meta-hybrid"
 
 exit 0
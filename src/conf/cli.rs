@@ -1,11 +1,18 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::path::PathBuf;
+use std::{
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+};
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
-use crate::defs;
+use crate::{
+    conf::{config::MountStage, output::OutputFormat},
+    core::inventory::files,
+    defs,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "meta-hybrid", version, about = "Hybrid Mount Metamodule")]
@@ -18,17 +25,70 @@ pub struct Cli {
     pub mountsource: Option<String>,
     #[arg(short = 'p', long = "partitions", value_delimiter = ',')]
     pub partitions: Vec<String>,
+    #[arg(long = "stage", value_enum)]
+    pub stage: Option<MountStage>,
+    #[arg(long = "output", global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+    // Failures go to stdout as a JSON object instead of text on stderr.
+    #[arg(long = "json-errors", global = true)]
+    pub json_errors: bool,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+impl Cli {
+    pub fn try_parse_compat() -> Result<Self, clap::Error> {
+        Self::try_parse_from(legacy_output(std::env::args_os().collect()))
+    }
+
+    // Commands the WebUI consumes default to JSON; the rest default to human-readable text.
+    pub fn output_format(&self, default: OutputFormat) -> OutputFormat {
+        self.output.unwrap_or(default)
+    }
+}
+
+fn is_format(value: &OsStr) -> bool {
+    value
+        .to_str()
+        .is_some_and(|v| OutputFormat::from_str(v, true).is_ok())
+}
+
+// Commands whose `--output <path>` names a file, and predates or clashes with the global format
+// flag.
+const FILE_OUTPUT_COMMANDS: &[&str] = &["gen-config", "export-module"];
+
+// After one of FILE_OUTPUT_COMMANDS, an `--output` value that is not a format name is still taken
+// as the destination.
+fn legacy_output(mut args: Vec<OsString>) -> Vec<OsString> {
+    let Some(start) = args
+        .iter()
+        .position(|a| FILE_OUTPUT_COMMANDS.iter().any(|c| a == *c))
+    else {
+        return args;
+    };
+    for i in start + 1..args.len() {
+        if args[i] == "--output" && args.get(i + 1).is_some_and(|v| !is_format(v)) {
+            args[i] = "--file".into();
+        } else if let Some(value) = args[i].to_str().and_then(|a| a.strip_prefix("--output="))
+            && !is_format(OsStr::new(value))
+        {
+            args[i] = format!("--file={}", value).into();
+        }
+    }
+    args
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     GenConfig {
-        #[arg(short = 'o', long = "output", default_value = defs::CONFIG_FILE)]
-        output: PathBuf,
+        #[arg(short = 'o', long, default_value = defs::CONFIG_FILE)]
+        file: PathBuf,
     },
     ShowConfig,
+    #[command(name = "validate-config")]
+    ValidateConfig {
+        path: Option<PathBuf>,
+    },
     #[command(name = "save-config")]
     SaveConfig {
         #[arg(long)]
@@ -41,9 +101,170 @@ pub enum Commands {
         #[arg(long)]
         payload: String,
     },
-    Modules,
-    Conflicts,
-    Diagnostics,
+    #[command(alias = "module")]
+    Modules {
+        #[command(subcommand)]
+        action: Option<ModulesAction>,
+    },
+    #[command(args_conflicts_with_subcommands = true)]
+    Conflicts {
+        #[command(subcommand)]
+        action: Option<ConflictsAction>,
+        // Only the conflicts this module takes part in, from cached listings of the others.
+        #[arg(long)]
+        module: Option<String>,
+    },
+    Diagnostics {
+        #[arg(long)]
+        fix_privapp: bool,
+        #[arg(long)]
+        summary: bool,
+    },
+    Lint {
+        #[arg(long)]
+        module: Option<String>,
+    },
+    Suppress {
+        rule: String,
+        #[arg(long)]
+        remove: bool,
+    },
+    Capabilities,
+    CompatReport,
+    Remount {
+        #[arg(long)]
+        module: String,
+    },
+    Unmount {
+        #[arg(long)]
+        module: String,
+    },
+    #[command(name = "boot-completed", hide = true)]
+    BootCompleted,
+    CaptureNextBoot {
+        #[arg(long)]
+        cancel: bool,
+    },
+    Daemon,
+    Ctl {
+        payload: String,
+    },
+    Plan {
+        #[command(subcommand)]
+        action: PlanAction,
+    },
+    Watch,
+    Uninstall {
+        #[arg(long)]
+        purge: bool,
+        // Skip the confirmation prompt, for the module's removal script.
+        #[arg(long)]
+        yes: bool,
+    },
+    Rescue {
+        #[arg(long)]
+        disable_all: bool,
+    },
+    // Disable modules added or updated since the last boot that completed.
+    #[command(name = "rollback-modules")]
+    RollbackModules {
+        // Unmount them now instead of on the next boot.
+        #[arg(long)]
+        live: bool,
+        #[arg(long)]
+        dry_run: bool,
+    },
+    Metrics,
+    Doctor,
+    // Build and environment facts to paste into a bug report.
+    Info,
+    Maintenance,
+    Logs {
+        #[arg(long)]
+        tail: Option<usize>,
+        // Only records at this level or more severe.
+        #[arg(long)]
+        level: Option<log::Level>,
+    },
+    StealthTest {
+        #[arg(long)]
+        sweep: bool,
+    },
+    Namespaces {
+        #[arg(long)]
+        replay: bool,
+    },
+    // Goes through init's property service, so unlike Magisk's resetprop it cannot override ro.*.
+    Setprop {
+        name: String,
+        value: String,
+    },
+    Sepolicy {
+        #[arg(long)]
+        dry_run: bool,
+    },
+    Winnow {
+        #[command(subcommand)]
+        action: WinnowAction,
+    },
+    #[command(args_conflicts_with_subcommands = true)]
+    Storage {
+        #[command(subcommand)]
+        action: Option<StorageAction>,
+        // Free space and inodes of the modules image around each recent boot's sync.
+        #[arg(long)]
+        history: bool,
+    },
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    Webui {
+        #[command(subcommand)]
+        action: WebuiAction,
+    },
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+    Debloat {
+        #[command(subcommand)]
+        action: DebloatAction,
+    },
+    Rw {
+        #[command(subcommand)]
+        action: RwAction,
+    },
+    ExportModule {
+        #[arg(long, conflicts_with = "module", required_unless_present = "module")]
+        from_rw: bool,
+        #[arg(long)]
+        module: Option<String>,
+        // `--output <path>` is rewritten to this, as for gen-config.
+        #[arg(short = 'o', long)]
+        file: PathBuf,
+        #[arg(long)]
+        id: Option<String>,
+        #[arg(long)]
+        name: Option<String>,
+    },
+    Integrity {
+        #[command(subcommand)]
+        action: Option<IntegrityAction>,
+    },
+    // Makes files under the base directory root:root 0600; the installer runs it once.
+    Harden,
+    Install {
+        file: PathBuf,
+        #[arg(long)]
+        force: bool,
+        #[arg(long)]
+        dry_run: bool,
+    },
+    Denylist {
+        #[command(subcommand)]
+        action: DenylistAction,
+    },
     Poaceae {
         #[arg(short, long, default_value = defs::POACEAE_MOUNT_POINT)]
         target: String,
@@ -53,6 +274,183 @@ pub enum Commands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ModulesAction {
+    Files {
+        id: String,
+        #[arg(long, default_value = "")]
+        path: String,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long, default_value_t = files::DEFAULT_PAGE_SIZE)]
+        limit: usize,
+    },
+    Enable {
+        id: String,
+        // Remount the module now instead of on the next boot.
+        #[arg(long)]
+        live: bool,
+    },
+    Disable {
+        id: String,
+        // Unmount the module now instead of on the next boot.
+        #[arg(long)]
+        live: bool,
+    },
+    Remove {
+        id: String,
+        #[arg(long)]
+        live: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IntegrityAction {
+    // Signs the config and state as they are now, after a deliberate manual edit.
+    Sign,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConflictsAction {
+    Start,
+    Poll {
+        id: String,
+    },
+    Result {
+        id: String,
+    },
+    Cancel {
+        id: String,
+    },
+    #[command(name = "run-job", hide = true)]
+    RunJob {
+        id: String,
+    },
+    Resolve {
+        path: String,
+        #[arg(required_unless_present = "remove")]
+        module: Option<String>,
+        #[arg(long)]
+        remove: bool,
+    },
+    // Lets a deferred module mount on the next boot whatever it takes over.
+    Accept {
+        module: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WinnowAction {
+    Review {
+        #[arg(long)]
+        prune_stale: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PlanAction {
+    Diff,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StorageAction {
+    Resize { size: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProfileAction {
+    List,
+    // Captures the current config and module states under this name.
+    Save {
+        name: String,
+    },
+    // Makes the profile the config for the next boot.
+    #[command(alias = "switch")]
+    Use {
+        name: String,
+    },
+    Delete {
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WebuiAction {
+    // Writes the WebUI packed into an `embed-webui` build.
+    Extract {
+        #[arg(long, default_value = defs::WEBROOT_DIR)]
+        dest: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotAction {
+    List,
+    Create {
+        #[arg(long, default_value = "Manual Backup")]
+        label: String,
+        // Also record every enabled module's file hashes and rules.
+        #[arg(long)]
+        deep: bool,
+    },
+    Restore {
+        #[arg(required_unless_present = "last_good")]
+        id: Option<String>,
+        #[arg(long, conflicts_with = "id")]
+        last_good: bool,
+        // Disable modules installed after a deep snapshot was taken.
+        #[arg(long)]
+        disable_added: bool,
+    },
+    // Modules added, removed or changed since a deep snapshot.
+    Diff {
+        id: String,
+    },
+    // Writes a snapshot to a .tar.zst archive, e.g. to keep it off /data.
+    Export {
+        id: String,
+        path: PathBuf,
+        // Also pack every module directory as installed now.
+        #[arg(long)]
+        modules: bool,
+    },
+    Import {
+        path: PathBuf,
+        // Install modules from the archive that are not installed here.
+        #[arg(long)]
+        modules: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DenylistAction {
+    Sync,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RwAction {
+    Enable {
+        partition: String,
+    },
+    Disable {
+        partition: String,
+        #[arg(long)]
+        purge: bool,
+    },
+    Diff {
+        partition: String,
+        #[arg(long, value_name = "MODULE_ID")]
+        export: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DebloatAction {
+    Add { path: String },
+    List,
+    Remove { path: String },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum PoaceaeAction {
     Hide {
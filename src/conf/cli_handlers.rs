@@ -1,29 +1,50 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::{fs::File, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{IsTerminal, Write},
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Command, Stdio},
+};
 
-use anyhow::{Context, Result};
-use serde::Serialize;
+use anyhow::{Context, Result, bail};
 
 use crate::{
     conf::{
-        cli::{Cli, PoaceaeAction},
+        cli::{
+            Cli, ConflictsAction, DebloatAction, DenylistAction, IntegrityAction, ModulesAction,
+            PlanAction, PoaceaeAction, ProfileAction, RwAction, SnapshotAction, StorageAction,
+            WebuiAction, WinnowAction,
+        },
         config::{self, Config},
+        output::{self, OutputFormat, Table},
+        validate,
+    },
+    core::{
+        artifacts, capacity, capture, coexist, compat, daemon, debloat, denylist,
+        diagnostics::{self, DiagnosticIssue, DiagnosticLevel, IssueCode},
+        doctor::{self, Grade},
+        granary, info, installer,
+        integrity::{self, Verdict},
+        inventory,
+        inventory::{files, lifecycle, model as modules},
+        jobs, known_good, lint, live, maintenance, namespaces, notify,
+        ops::{
+            plan_diff::{self, PlanRecord},
+            planner, preflight, scoped, sync, verify, winnow,
+        },
+        packager, privapp, profile, recovery, rw, sepolicy,
+        state::RuntimeState,
+        stealth, storage, uninstall, webui,
     },
-    core::{inventory, inventory::model as modules, ops::planner},
     defs,
-    sys::poaceae,
+    sys::{capabilities, poaceae},
     utils,
 };
 
-#[derive(Serialize)]
-struct DiagnosticIssueJson {
-    level: String,
-    context: String,
-    message: String,
-}
-
 fn load_config(cli: &Cli) -> Result<Config> {
     if let Some(config_path) = &cli.config {
         return Config::from_file(config_path).with_context(|| {
@@ -55,10 +76,19 @@ fn load_config(cli: &Cli) -> Result<Config> {
     }
 }
 
-pub fn handle_gen_config(output: &Path) -> Result<()> {
+pub fn handle_gen_config(cli: &Cli, output: &Path) -> Result<()> {
+    // Often run to replace a broken config, so that one only matters for where output goes.
+    let config = load_config(cli).unwrap_or_default();
+    let output = artifacts::output_path(&config.artifacts, output);
+    if let Some(parent) = output.parent() {
+        utils::ensure_dir_exists(parent)?;
+    }
+
     Config::default()
-        .save_to_file(output)
-        .with_context(|| format!("Failed to save generated config to {}", output.display()))
+        .save_to_file(&output)
+        .with_context(|| format!("Failed to save generated config to {}", output.display()))?;
+    artifacts::record(&config.artifacts, &output);
+    Ok(())
 }
 
 pub fn handle_show_config(cli: &Cli) -> Result<()> {
@@ -71,6 +101,40 @@ pub fn handle_show_config(cli: &Cli) -> Result<()> {
     Ok(())
 }
 
+pub fn handle_validate_config(cli: &Cli, path: Option<&Path>) -> Result<()> {
+    let path = path
+        .or(cli.config.as_deref())
+        .unwrap_or(Path::new(defs::CONFIG_FILE));
+    let issues = validate::validate(path)?;
+
+    let format = cli.output_format(OutputFormat::Table);
+    if format == OutputFormat::Json {
+        output::print_json(&issues).context("Failed to serialize config issues")?;
+    } else if issues.is_empty() {
+        println!("{}: OK", path.display());
+    } else {
+        let mut table = Table::new(&["SEVERITY", "LINE", "KEY", "MESSAGE"]);
+        for issue in &issues {
+            table.row(vec![
+                issue.severity.as_str().to_string(),
+                issue.line.map(|l| l.to_string()).unwrap_or_default(),
+                issue.key.clone(),
+                issue.message.clone(),
+            ]);
+        }
+        println!("{}", table.render(format));
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == validate::Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("{} has {} error(s)", path.display(), errors);
+    }
+    Ok(())
+}
+
 pub fn handle_save_config(payload: &str) -> Result<()> {
     let json_bytes = (0..payload.len())
         .step_by(2)
@@ -113,63 +177,1560 @@ pub fn handle_save_module_rules(module_id: &str, payload: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn handle_modules(cli: &Cli) -> Result<()> {
+pub fn handle_modules(cli: &Cli, action: Option<&ModulesAction>) -> Result<()> {
     let config = load_config(cli)?;
 
-    modules::print_list(&config).context("Failed to list modules")
+    let (id, path, offset, limit) = match action {
+        None => {
+            return modules::print_list(&config, cli.output_format(OutputFormat::Json))
+                .context("Failed to list modules");
+        }
+        Some(ModulesAction::Enable { id, live }) => {
+            return handle_module_change(cli, &config, id, lifecycle::Action::Enable, *live);
+        }
+        Some(ModulesAction::Disable { id, live }) => {
+            return handle_module_change(cli, &config, id, lifecycle::Action::Disable, *live);
+        }
+        Some(ModulesAction::Remove { id, live }) => {
+            return handle_module_change(cli, &config, id, lifecycle::Action::Remove, *live);
+        }
+        Some(ModulesAction::Files {
+            id,
+            path,
+            offset,
+            limit,
+        }) => (id, path, offset, limit),
+    };
+
+    let page = files::list(&config, id, path, *offset, *limit)?;
+    let format = cli.output_format(OutputFormat::Json);
+    if format == OutputFormat::Json {
+        return output::print_json(&page).context("Failed to serialize module files");
+    }
+
+    let mut table = Table::new(&["KIND", "MODE", "SIZE", "CONTEXT", "NAME"]);
+    for entry in &page.entries {
+        let name = match &entry.target {
+            Some(target) => format!("{} -> {}", entry.name, target),
+            None => entry.name.clone(),
+        };
+        table.row(vec![
+            entry.kind.as_str().to_string(),
+            format!("{:04o}", entry.mode),
+            entry.size.to_string(),
+            entry.context.clone().unwrap_or_default(),
+            name,
+        ]);
+    }
+    println!("{}", table.render(format));
+    Ok(())
+}
+
+fn handle_module_change(
+    cli: &Cli,
+    config: &Config,
+    id: &str,
+    action: lifecycle::Action,
+    live: bool,
+) -> Result<()> {
+    let change = lifecycle::apply(config, id, action, live)?;
+
+    let format = cli.output_format(OutputFormat::Plain);
+    if format == OutputFormat::Json {
+        return output::print_json(&change).context("Failed to serialize module change");
+    }
+    output::print_fields(
+        &[
+            ("module", change.module.clone()),
+            ("action", change.action.as_str().to_string()),
+            ("path", change.path.display().to_string()),
+            ("pruned", change.pruned.to_string()),
+            ("targets", change.targets.join(",")),
+            ("pending", change.pending.to_string()),
+        ],
+        format,
+    );
+    if let Some(e) = &change.live_error {
+        println!("Live {} failed: {}", action.as_str(), e);
+    }
+    if change.pending {
+        println!("Takes effect on next boot");
+    }
+    Ok(())
+}
+
+fn spawn_conflicts_job(cli: &Cli, id: &str) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to locate own executable")?;
+    let mut command = Command::new(exe);
+    if let Some(config_path) = &cli.config {
+        command.arg("--config").arg(config_path);
+    }
+    command
+        .args(["conflicts", "run-job", id])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .context("Failed to start conflict analysis job")?;
+    Ok(())
+}
+
+fn print_job_status(status: &jobs::JobStatus) -> Result<()> {
+    let json = serde_json::to_string(status).context("Failed to serialize job status")?;
+    println!("{}", json);
+    Ok(())
 }
 
-pub fn handle_conflicts(cli: &Cli) -> Result<()> {
+pub fn handle_conflicts(
+    cli: &Cli,
+    action: Option<&ConflictsAction>,
+    module: Option<&str>,
+) -> Result<()> {
+    match action {
+        None => {}
+        Some(ConflictsAction::Start) => {
+            let status = jobs::create("conflicts")?;
+            if let Err(e) = spawn_conflicts_job(cli, &status.id) {
+                jobs::fail(status, &e)?;
+                return Err(e);
+            }
+            println!("{}", serde_json::json!({ "job_id": status.id }));
+            return Ok(());
+        }
+        Some(ConflictsAction::Poll { id }) => return print_job_status(&jobs::status(id)?),
+        Some(ConflictsAction::Result { id }) => {
+            println!("{}", jobs::result(id)?);
+            return Ok(());
+        }
+        Some(ConflictsAction::Cancel { id }) => return print_job_status(&jobs::cancel(id)?),
+        Some(ConflictsAction::RunJob { id }) => {
+            let config = match load_config(cli) {
+                Ok(config) => config,
+                Err(e) => {
+                    jobs::fail(jobs::status(id)?, &e)?;
+                    return Err(e);
+                }
+            };
+            return jobs::run_conflicts(id, &config);
+        }
+        Some(ConflictsAction::Resolve {
+            path,
+            module,
+            remove,
+        }) => return handle_conflicts_resolve(cli, path, module.as_deref(), *remove),
+        Some(ConflictsAction::Accept { module }) => return handle_conflicts_accept(cli, module),
+    }
+
     let config = load_config(cli)?;
 
-    let module_list = inventory::scan(&config.moduledir, &config)
-        .context("Failed to scan modules for conflict analysis")?;
+    let conflicts = match module {
+        Some(id) => scoped::check(&config, id)?,
+        None => {
+            let module_list = inventory::scan(&config.moduledir, &config)
+                .context("Failed to scan modules for conflict analysis")?;
 
-    let plan = planner::generate(&config, &module_list, &config.moduledir)
-        .context("Failed to generate plan for conflict analysis")?;
+            let plan = planner::generate(&config, &module_list, config.primary_moduledir())
+                .context("Failed to generate plan for conflict analysis")?;
 
-    let report = plan.analyze();
+            plan.analyze().conflicts
+        }
+    };
 
-    let json =
-        serde_json::to_string(&report.conflicts).context("Failed to serialize conflict report")?;
+    let format = cli.output_format(OutputFormat::Json);
+    if format == OutputFormat::Json {
+        return output::print_json(&conflicts).context("Failed to serialize conflict report");
+    }
 
-    println!("{}", json);
+    let mut table = Table::new(&["PARTITION", "PATH", "WINNER", "MODULES"]);
+    for c in &conflicts {
+        table.row(vec![
+            c.partition.clone(),
+            c.relative_path.clone(),
+            c.winner.clone(),
+            c.contending_modules.join(","),
+        ]);
+    }
+    println!("{}", table.render(format));
 
     Ok(())
 }
 
-pub fn handle_diagnostics(cli: &Cli) -> Result<()> {
+fn handle_conflicts_resolve(
+    cli: &Cli,
+    path: &str,
+    module: Option<&str>,
+    remove: bool,
+) -> Result<()> {
+    let mut config = load_config(cli)?;
+    let path = path.trim().trim_end_matches('/').to_string();
+    if !path.starts_with('/') {
+        bail!("Conflict path must be absolute: {}", path);
+    }
+
+    match module {
+        Some(module) if !remove => {
+            utils::validate_module_id(module)?;
+            config.resolve.insert(path.clone(), module.to_string());
+        }
+        _ => {
+            config.resolve.remove(&path);
+        }
+    }
+
+    let config_path = cli
+        .config
+        .as_deref()
+        .unwrap_or(Path::new(defs::CONFIG_FILE));
+    config
+        .save_to_file(config_path)
+        .with_context(|| format!("Failed to save config to {}", config_path.display()))?;
+
+    match config.resolve.get(&path) {
+        Some(module) => println!("Resolved: {} -> {}", path, module),
+        None => println!("Unresolved: {}", path),
+    }
+
+    Ok(())
+}
+
+fn handle_conflicts_accept(cli: &Cli, module: &str) -> Result<()> {
+    let mut config = load_config(cli)?;
+    utils::validate_module_id(module)?;
+    if !config.defer.allow.iter().any(|m| m == module) {
+        config.defer.allow.push(module.to_string());
+    }
+
+    let config_path = cli
+        .config
+        .as_deref()
+        .unwrap_or(Path::new(defs::CONFIG_FILE));
+    config
+        .save_to_file(config_path)
+        .with_context(|| format!("Failed to save config to {}", config_path.display()))?;
+
+    println!("Accepted: {} mounts on next boot", module);
+    Ok(())
+}
+
+pub fn handle_diagnostics(cli: &Cli, fix_privapp: bool, summary: bool) -> Result<()> {
     let config = load_config(cli)?;
 
-    let module_list = inventory::scan(&config.moduledir, &config)
+    let (module_list, scan_errors) = inventory::scan_with_errors(&config.moduledir, &config)
         .context("Failed to scan modules for diagnostics")?;
 
-    let plan = planner::generate(&config, &module_list, &config.moduledir)
+    let plan = planner::generate(&config, &module_list, config.primary_moduledir())
         .context("Failed to generate plan for diagnostics")?;
 
-    let report = plan.analyze();
+    let mut report = plan.analyze();
+    report.diagnostics.extend(scan_errors.into_iter().map(|e| {
+        let (code, message) = match e.kind {
+            inventory::ScanErrorKind::Unreadable => (
+                IssueCode::ModuleUnreadable,
+                format!("Module {} unreadable: {}", e.id, e.error),
+            ),
+            inventory::ScanErrorKind::TooLarge => (
+                IssueCode::ModuleTooLarge,
+                format!(
+                    "Module {} skipped, {} under {}",
+                    e.id,
+                    e.error,
+                    e.path.display()
+                ),
+            ),
+        };
+        DiagnosticIssue {
+            code,
+            level: DiagnosticLevel::Critical,
+            message,
+            context: e.id,
+            path: Some(e.path.to_string_lossy().to_string()),
+        }
+    }));
+    report
+        .diagnostics
+        .extend(preflight::check_storage(&module_list, &config));
+    report.diagnostics.extend(preflight::check_plan(
+        &plan,
+        &module_list,
+        &config,
+        config.primary_moduledir(),
+    ));
+
+    report.diagnostics.extend(lint::check_scripts(&module_list));
+    report.diagnostics.extend(coexist::diagnostics(&config));
 
-    let json_issues: Vec<DiagnosticIssueJson> = report
+    let unlisted_privapps = privapp::find_unlisted(&module_list, config.primary_moduledir());
+    report
         .diagnostics
-        .into_iter()
-        .map(|i| DiagnosticIssueJson {
-            level: match i.level {
-                planner::DiagnosticLevel::Warning => "Warning".to_string(),
-                planner::DiagnosticLevel::Critical => "Critical".to_string(),
+        .extend(privapp::to_diagnostics(&unlisted_privapps));
+
+    if fix_privapp && !unlisted_privapps.is_empty() {
+        privapp::generate_managed_module(&unlisted_privapps)
+            .context("Failed to generate privapp-permissions module")?;
+    }
+
+    let state = RuntimeState::load().unwrap_or_default();
+    let unverified = state.unverified_mounts.len();
+    report.diagnostics.extend(
+        state
+            .unverified_mounts
+            .into_iter()
+            .map(|u| DiagnosticIssue {
+                code: IssueCode::MountMissing,
+                level: DiagnosticLevel::Critical,
+                path: Some(u.target.clone()),
+                message: format!(
+                    "Last boot {} mount of {} failed verification: {}",
+                    u.kind.as_str(),
+                    u.modules.join(","),
+                    u.reason
+                ),
+                context: u.target,
+            }),
+    );
+    report
+        .diagnostics
+        .extend(
+            verify::recheck(&state.fingerprints)
+                .into_iter()
+                .map(|d| DiagnosticIssue {
+                    code: IssueCode::MountTampered,
+                    level: DiagnosticLevel::Critical,
+                    path: Some(d.target.clone()),
+                    message: format!(
+                        "Files under {} changed since they were mounted (fingerprint {:.12}, now \
+                         {:.12}); something replaced or hid part of the merged view",
+                        d.target, d.expected, d.found
+                    ),
+                    context: d.target,
+                }),
+        );
+    report
+        .diagnostics
+        .extend(state.mount_failures.into_iter().map(|f| DiagnosticIssue {
+            code: IssueCode::MountFailed,
+            level: DiagnosticLevel::Warning,
+            path: Some(f.target.clone()),
+            context: f.target,
+            message: match f.hint {
+                Some(hint) => {
+                    format!("Last boot {} mount failed: {} ({})", f.op, f.message, hint)
+                }
+                None => format!("Last boot {} mount failed: {}", f.op, f.message),
             },
-            context: i.context,
-            message: i.message,
-        })
-        .collect();
+        }));
+    report.diagnostics.extend(
+        sync::changed_since_sync(&module_list)
+            .into_iter()
+            .map(|id| DiagnosticIssue {
+                code: IssueCode::SourceChanged,
+                level: DiagnosticLevel::Warning,
+                message: format!(
+                    "Module {} was modified after it was synced; run `meta-hybrid remount \
+                     --module {}` or reboot to apply the changes",
+                    id, id
+                ),
+                path: module_list
+                    .iter()
+                    .find(|m| m.id == id)
+                    .map(|m| m.source_path.to_string_lossy().to_string()),
+                context: id,
+            }),
+    );
+
+    report.diagnostics.extend(
+        integrity::check()
+            .into_iter()
+            .filter(|f| f.verdict == Verdict::Tampered)
+            .map(|f| {
+                let path = f.path.to_string_lossy().to_string();
+                DiagnosticIssue {
+                    code: IssueCode::IntegrityMismatch,
+                    level: DiagnosticLevel::Warning,
+                    context: "integrity".to_string(),
+                    message: format!(
+                        "{} does not match its signature and was ignored; run `meta-hybrid \
+                         integrity sign` if you edited it yourself",
+                        path
+                    ),
+                    path: Some(path),
+                }
+            }),
+    );
+
+    report
+        .diagnostics
+        .extend(state.deferred_modules.iter().map(|d| DiagnosticIssue {
+            code: IssueCode::ModuleDeferred,
+            level: DiagnosticLevel::Warning,
+            context: d.module.clone(),
+            message: format!(
+                "Module {} was left unmounted: it would take over {} file(s) from mounted \
+                 modules; pin them with `meta-hybrid conflicts resolve` or run `meta-hybrid \
+                 conflicts accept {}`",
+                d.module, d.conflicts, d.module
+            ),
+            path: None,
+        }));
+
+    if let Some(message) = capacity::project(&capacity::history())
+        .and_then(|projection| capacity::warning(&config, &projection))
+    {
+        report.diagnostics.push(DiagnosticIssue {
+            code: IssueCode::StorageTrend,
+            level: DiagnosticLevel::Warning,
+            context: "storage".to_string(),
+            message: format!(
+                "{}; run `meta-hybrid storage --history` for the trend",
+                message
+            ),
+            path: Some(defs::MODULES_IMG_FILE.to_string()),
+        });
+    }
+
+    report
+        .diagnostics
+        .extend(namespaces::scan(&config).into_iter().map(|ns| {
+            let hint = if ns.allowlisted {
+                "run `meta-hybrid namespaces --replay`"
+            } else {
+                "add it to early_namespaces to replay them"
+            };
+            DiagnosticIssue {
+                code: IssueCode::EarlyNamespace,
+                level: DiagnosticLevel::Warning,
+                context: ns.name.clone(),
+                message: format!(
+                    "{} (pid {}) runs in a namespace created before our mounts and misses {} \
+                     of them; {}",
+                    ns.name,
+                    ns.pid,
+                    ns.missing.len(),
+                    hint
+                ),
+                path: None,
+            }
+        }));
+
+    let suppressed = diagnostics::suppress(&mut report.diagnostics, &config.suppressed_issues);
+
+    let count = |level| {
+        report
+            .diagnostics
+            .iter()
+            .filter(|i| i.level == level)
+            .count()
+    };
 
-    let json =
-        serde_json::to_string(&json_issues).context("Failed to serialize diagnostics report")?;
+    let format = cli.output_format(OutputFormat::Json);
+    if format != OutputFormat::Json {
+        let mut table = Table::new(&["LEVEL", "CODE", "CONTEXT", "MESSAGE"]);
+        for issue in &report.diagnostics {
+            table.row(vec![
+                format!("{:?}", issue.level),
+                issue.code.as_str().to_string(),
+                issue.context.clone(),
+                issue.message.clone(),
+            ]);
+        }
+        println!("{}", table.render(format));
+        if summary {
+            println!(
+                "{} critical, {} warning, {} suppressed, {} unverified mount(s)",
+                count(DiagnosticLevel::Critical),
+                count(DiagnosticLevel::Warning),
+                suppressed,
+                unverified
+            );
+        }
+        return Ok(());
+    }
+
+    let json = if summary {
+        serde_json::to_string(&serde_json::json!({
+            "issues": report.diagnostics,
+            "summary": {
+                "critical": count(DiagnosticLevel::Critical),
+                "warning": count(DiagnosticLevel::Warning),
+                "suppressed": suppressed,
+                "unverified_mounts": unverified,
+            },
+        }))
+    } else {
+        serde_json::to_string(&report.diagnostics)
+    }
+    .context("Failed to serialize diagnostics report")?;
 
     println!("{}", json);
 
     Ok(())
 }
 
+pub fn handle_lint(cli: &Cli, module_id: Option<&str>) -> Result<()> {
+    let config = load_config(cli)?;
+
+    let mut module_list =
+        inventory::scan(&config.moduledir, &config).context("Failed to scan modules for lint")?;
+    if let Some(id) = module_id {
+        module_list.retain(|m| m.id == id);
+        if module_list.is_empty() {
+            bail!("Module {} not found or disabled", id);
+        }
+    }
+
+    let mut issues = lint::check_scripts(&module_list);
+    diagnostics::suppress(&mut issues, &config.suppressed_issues);
+
+    let mut report: BTreeMap<&str, Vec<&DiagnosticIssue>> = module_list
+        .iter()
+        .map(|m| (m.id.as_str(), Vec::new()))
+        .collect();
+    for issue in &issues {
+        if let Some(entry) = report.get_mut(issue.context.as_str()) {
+            entry.push(issue);
+        }
+    }
+
+    let json = serde_json::to_string(&report).context("Failed to serialize lint report")?;
+    println!("{}", json);
+
+    Ok(())
+}
+
+pub fn handle_suppress(cli: &Cli, rule: &str, remove: bool) -> Result<()> {
+    let mut config = load_config(cli)?;
+    let rule = rule.trim().to_string();
+
+    if remove {
+        config.suppressed_issues.retain(|r| *r != rule);
+    } else if !config.suppressed_issues.contains(&rule) {
+        config.suppressed_issues.push(rule.clone());
+    }
+
+    let path = cli
+        .config
+        .as_deref()
+        .unwrap_or(Path::new(defs::CONFIG_FILE));
+    config
+        .save_to_file(path)
+        .with_context(|| format!("Failed to save config to {}", path.display()))?;
+
+    if remove {
+        println!("Unsuppressed: {}", rule);
+    } else {
+        println!("Suppressed: {}", rule);
+    }
+
+    Ok(())
+}
+
+pub fn handle_capabilities(cli: &Cli) -> Result<()> {
+    let caps = capabilities::probe();
+
+    if cli.output_format(OutputFormat::Plain) == OutputFormat::Json {
+        return output::print_json(&caps).context("Failed to serialize capabilities");
+    }
+
+    println!("Version: {} ({})", caps.version, caps.arch);
+    println!("Root manager: {}", caps.root_manager);
+    println!(
+        "Engines: overlay={} magic={}",
+        caps.engines.overlay, caps.engines.magic
+    );
+    println!("Storage modes: {}", caps.storage_modes.join(", "));
+    println!(
+        "Hiding: try_umount={} poaceae={}",
+        caps.hiding.try_umount, caps.hiding.poaceae
+    );
+    println!(
+        "Subsystems: new_mount_api={} rootdir={} dlkm={} module_sig_enforced={}",
+        caps.subsystems.new_mount_api,
+        caps.subsystems.rootdir,
+        caps.subsystems.dlkm,
+        caps.subsystems.module_sig_enforced
+    );
+
+    Ok(())
+}
+
+pub fn handle_compat_report(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
+    let report = compat::generate(&config)?;
+
+    if cli.output_format(OutputFormat::Plain) == OutputFormat::Json {
+        return output::print_json(&report).context("Failed to serialize compat report");
+    }
+
+    println!(
+        "ksud mechanism: {} | {} module(s), {} handled differently",
+        report.ksud_mechanism,
+        report.modules.len(),
+        report.differences
+    );
+    for module in &report.modules {
+        if let Some(reason) = &module.reason {
+            println!(
+                "{}: ksud={} hybrid={} ({})",
+                module.id, module.ksud, module.hybrid, reason
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_rescue(cli: &Cli, disable_all: bool) -> Result<()> {
+    if let Some(step) = recovery::ensure_data_mounted()? {
+        println!("{}", step);
+    }
+
+    let config = load_config(cli).unwrap_or_else(|e| {
+        println!("config unreadable ({:#}), using defaults", e);
+        Config::default()
+    });
+    let config_path = cli
+        .config
+        .as_deref()
+        .unwrap_or(Path::new(defs::CONFIG_FILE));
+
+    for step in recovery::rescue(&config, config_path, disable_all)? {
+        println!("{}", step);
+    }
+    println!("done, reboot to apply");
+    Ok(())
+}
+
+pub fn handle_rollback_modules(cli: &Cli, live: bool, dry_run: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let rollbacks = known_good::rollback(&config, live, dry_run)?;
+
+    let format = cli.output_format(OutputFormat::Plain);
+    if format == OutputFormat::Json {
+        return output::print_json(&rollbacks);
+    }
+    if rollbacks.is_empty() {
+        println!("No modules added or updated since the last completed boot");
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["MODULE", "CHANGE", "KNOWN GOOD", "CURRENT", "STATUS"]);
+    for rollback in &rollbacks {
+        let status = match &rollback.applied {
+            None => "would disable".to_string(),
+            Some(change) if let Some(e) = &change.live_error => {
+                format!("live unmount failed: {}", e)
+            }
+            Some(change) if change.pending => "disabled, takes effect on next boot".to_string(),
+            Some(_) => "disabled".to_string(),
+        };
+        table.row(vec![
+            rollback.module.clone(),
+            rollback.change.as_str().to_string(),
+            rollback
+                .known_good
+                .clone()
+                .unwrap_or_else(|| "-".to_string()),
+            rollback.current.clone(),
+            status,
+        ]);
+    }
+    println!("{}", table.render(format));
+    Ok(())
+}
+
+pub fn handle_uninstall(cli: &Cli, purge: bool, yes: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let removals = uninstall::removals(purge);
+    println!("Active mounts will be detached. This removes:");
+    for removal in &removals {
+        println!("  {} ({} bytes)", removal.path.display(), removal.size);
+    }
+    let total: u64 = removals.iter().map(|r| r.size).sum();
+    println!("{} bytes in total", total);
+
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            bail!("Refusing to uninstall without confirmation; pass --yes");
+        }
+        print!("Proceed? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("aborted");
+            return Ok(());
+        }
+    }
+
+    for step in uninstall::run(&config, purge) {
+        println!("{}", step);
+    }
+    Ok(())
+}
+
+pub fn handle_info(cli: &Cli) -> Result<()> {
+    let info = info::collect();
+    let format = cli.output_format(OutputFormat::Json);
+    if format == OutputFormat::Json {
+        return output::print_json(&info);
+    }
+    output::print_fields(
+        &[
+            ("version", info.build.version.to_string()),
+            (
+                "module_version",
+                info.module_version.unwrap_or_else(|| "-".into()),
+            ),
+            ("git_hash", info.build.git_hash.to_string()),
+            ("profile", info.build.profile.to_string()),
+            ("features", info.build.features.join(",")),
+            ("target", info.build.target.to_string()),
+            ("kernel", info.kernel),
+            ("arch", info.arch.to_string()),
+            (
+                "kernelsu",
+                info.root
+                    .kernelsu
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "-".into()),
+            ),
+            ("apatch", info.root.apatch.to_string()),
+            ("magisk", info.root.magisk.to_string()),
+            ("mount_source", info.root.mount_source),
+        ],
+        format,
+    );
+    Ok(())
+}
+
+pub fn handle_doctor(cli: &Cli) -> Result<()> {
+    let config = load_config(cli).unwrap_or_default();
+    let checks = doctor::run(&config);
+
+    let format = cli.output_format(OutputFormat::Table);
+    if format == OutputFormat::Json {
+        output::print_json(&checks).context("Failed to serialize doctor report")?;
+    } else {
+        let mut table = Table::new(&["GRADE", "CHECK", "DETAIL", "HINT"]);
+        for check in &checks {
+            table.row(vec![
+                format!("{:?}", check.grade).to_uppercase(),
+                check.name.to_string(),
+                check.detail.clone(),
+                check.hint.unwrap_or_default().to_string(),
+            ]);
+        }
+        println!("{}", table.render(format));
+    }
+
+    let failed = checks.iter().filter(|c| c.grade == Grade::Fail).count();
+    if failed > 0 {
+        bail!("{} check(s) failed", failed);
+    }
+    Ok(())
+}
+
+pub fn handle_metrics() -> Result<()> {
+    let state = RuntimeState::load().context("No runtime state recorded yet")?;
+    output::print_json(&state.metrics).context("Failed to serialize metrics")
+}
+
+pub fn handle_logs(cli: &Cli, tail: Option<usize>, level: Option<log::Level>) {
+    // Lines written before a rule was added are redacted on the way out too.
+    if let Ok(config) = load_config(cli) {
+        utils::set_redact_rules(&config.logging.redact);
+    }
+    for line in utils::read_logs(level, tail) {
+        println!("{}", line);
+    }
+}
+
+pub fn handle_namespaces(cli: &Cli, replay: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let namespaces = if replay {
+        namespaces::replay(&config)?
+    } else {
+        namespaces::scan(&config)
+    };
+
+    let format = cli.output_format(OutputFormat::Table);
+    if format == OutputFormat::Json {
+        return output::print_json(&namespaces).context("Failed to serialize namespaces");
+    }
+
+    let mut table = Table::new(&["PID", "NAME", "PROCESSES", "MISSING", "ALLOWED", "REPLAYED"]);
+    for ns in &namespaces {
+        table.row(vec![
+            ns.pid.to_string(),
+            ns.name.clone(),
+            ns.processes.to_string(),
+            ns.missing.len().to_string(),
+            ns.allowlisted.to_string(),
+            ns.replayed.len().to_string(),
+        ]);
+    }
+    println!("{}", table.render(format));
+    Ok(())
+}
+
+pub fn handle_stealth_test(cli: &Cli, sweep: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let coverage = stealth::self_test(&config, sweep);
+
+    let format = cli.output_format(OutputFormat::Table);
+    if format == OutputFormat::Json {
+        return output::print_json(&coverage).context("Failed to serialize stealth report");
+    }
+
+    let mut table = Table::new(&["USER", "PROCESSES", "HIDDEN", "LEAKING"]);
+    for user in &coverage {
+        table.row(vec![
+            user.user.to_string(),
+            user.processes.to_string(),
+            user.hidden.to_string(),
+            user.leaking
+                .iter()
+                .map(|pid| pid.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ]);
+    }
+    println!("{}", table.render(format));
+    Ok(())
+}
+
+pub fn handle_sepolicy(cli: &Cli, dry_run: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let modules = inventory::scan(&config.moduledir, &config).context("Failed to scan modules")?;
+
+    if dry_run {
+        print!("{}", sepolicy::combined(&modules));
+        return Ok(());
+    }
+
+    let count = sepolicy::apply(&config, &modules)?;
+    println!("Applied sepolicy rules from {} module(s)", count);
+    Ok(())
+}
+
+pub fn handle_winnow(cli: &Cli, action: &WinnowAction) -> Result<()> {
+    let WinnowAction::Review { prune_stale } = action;
+    let mut config = load_config(cli)?;
+
+    let modules = inventory::scan(&config.moduledir, &config).context("Failed to scan modules")?;
+    let plan = planner::generate(&config, &modules, config.primary_moduledir())
+        .context("Failed to generate mount plan")?;
+    let reviews = winnow::review(&config, &plan, config.primary_moduledir());
+
+    let mut pruned = Vec::new();
+    if *prune_stale {
+        for review in reviews.iter().filter(|r| r.stale.is_some()) {
+            config.resolve.remove(&review.path);
+            pruned.push(review.path.clone());
+        }
+        if !pruned.is_empty() {
+            let path = cli
+                .config
+                .as_deref()
+                .unwrap_or(Path::new(defs::CONFIG_FILE));
+            config
+                .save_to_file(path)
+                .with_context(|| format!("Failed to save config to {}", path.display()))?;
+        }
+    }
+
+    if cli.output_format(OutputFormat::Plain) == OutputFormat::Json {
+        return output::print_json(&serde_json::json!({ "rules": reviews, "pruned": pruned }));
+    }
+
+    if reviews.is_empty() {
+        println!("No resolve rules configured.");
+        return Ok(());
+    }
+
+    for review in &reviews {
+        let status = match (&review.stale, review.forced) {
+            (Some(reason), _) => format!("stale: {}", reason),
+            (None, true) => format!(
+                "forced over {}",
+                review.natural_winner.as_deref().unwrap_or("-")
+            ),
+            (None, false) => "already the natural winner".to_string(),
+        };
+        println!("{} -> {} ({})", review.path, review.module, status);
+        if !review.contenders.is_empty() {
+            println!("  contenders: {}", review.contenders.join(", "));
+        }
+    }
+    for path in &pruned {
+        println!("Pruned: {}", path);
+    }
+
+    Ok(())
+}
+
+pub fn handle_plan(cli: &Cli, action: &PlanAction) -> Result<()> {
+    let PlanAction::Diff = action;
+    let config = load_config(cli)?;
+
+    let baseline = PlanRecord::load()?;
+    let modules = inventory::scan(&config.moduledir, &config).context("Failed to scan modules")?;
+    let plan = planner::generate(&config, &modules, config.primary_moduledir())
+        .context("Failed to generate mount plan")?;
+    let diff = plan_diff::diff(
+        &baseline,
+        &PlanRecord::from_plan(&plan, config.primary_moduledir()),
+    );
+
+    if cli.output_format(OutputFormat::Plain) == OutputFormat::Json {
+        return output::print_json(&diff).context("Failed to serialize plan diff");
+    }
+
+    if diff.is_empty() {
+        println!("No changes since the last boot.");
+        return Ok(());
+    }
+
+    for target in &diff.targets {
+        println!("{}:", target.target);
+        for id in &target.added {
+            println!("  + {}", id);
+        }
+        for id in &target.removed {
+            println!("  - {}", id);
+        }
+        if target.reordered {
+            println!(
+                "  ~ order: {} -> {}",
+                target.before.join(","),
+                target.after.join(",")
+            );
+        }
+    }
+    for change in &diff.mode_changes {
+        println!("{}: {} -> {}", change.module, change.before, change.after);
+    }
+
+    Ok(())
+}
+
+pub fn handle_remount(cli: &Cli, module_id: &str) -> Result<()> {
+    let config = load_config(cli)?;
+
+    let targets = live::remount_module(&config, module_id)
+        .with_context(|| format!("Failed to remount module {}", module_id))?;
+
+    println!(
+        "{}",
+        serde_json::json!({ "module": module_id, "targets": targets })
+    );
+
+    Ok(())
+}
+
+pub fn handle_unmount(cli: &Cli, module_id: &str) -> Result<()> {
+    let config = load_config(cli)?;
+
+    let targets = live::unmount_module(&config, module_id)
+        .with_context(|| format!("Failed to unmount module {}", module_id))?;
+
+    println!(
+        "{}",
+        serde_json::json!({ "module": module_id, "targets": targets })
+    );
+
+    Ok(())
+}
+
+pub fn handle_boot_completed(cli: &Cli) -> Result<()> {
+    // The boot counter has to be cleared even when the config is unusable; the boot itself got
+    // this far on defaults.
+    let last_good = recovery::boot_completed().unwrap_or_else(|e| {
+        log::warn!("Failed to record the completed boot: {:#}", e);
+        None
+    });
+    let config = load_config(cli).unwrap_or_else(|e| {
+        log::warn!("Failed to load config, using defaults: {:#}", e);
+        Config::default()
+    });
+    let known_good = known_good::boot_completed(&config).unwrap_or_else(|e| {
+        log::warn!("Failed to record the known-good modules: {:#}", e);
+        None
+    });
+    let bisect = recovery::bisect_completed(&config)?;
+    let webui_restored = webui::restore_missing();
+    let notified = notify::flush(&config.notify)?;
+    let pruned = artifacts::prune(&config.artifacts).unwrap_or_else(|e| {
+        log::warn!("Failed to prune exported artifacts: {:#}", e);
+        0
+    });
+    println!(
+        "{}",
+        serde_json::json!({
+            "last_good": last_good,
+            "known_good_modules": known_good,
+            "bisect": bisect,
+            "webui_restored": webui_restored,
+            "notified": notified,
+            "pruned_artifacts": pruned
+        })
+    );
+    Ok(())
+}
+
+pub fn handle_capture_next_boot(cancel: bool) -> Result<()> {
+    if cancel {
+        capture::disarm()?;
+    } else {
+        capture::arm()?;
+    }
+
+    println!(
+        "{}",
+        serde_json::json!({ "armed": capture::is_armed(), "output": defs::CAPTURES_DIR })
+    );
+
+    Ok(())
+}
+
+pub fn handle_ctl(payload: &str) -> Result<()> {
+    println!("{}", daemon::request(payload)?);
+    Ok(())
+}
+
+fn handle_storage_history(cli: &Cli, config: &Config) -> Result<()> {
+    let samples = capacity::history();
+    let projection = capacity::project(&samples);
+    let warning = projection
+        .as_ref()
+        .and_then(|p| capacity::warning(config, p));
+
+    let format = cli.output_format(OutputFormat::Json);
+    if format == OutputFormat::Json {
+        return output::print_json(&serde_json::json!({
+            "samples": samples,
+            "projection": projection,
+            "warning": warning,
+        }));
+    }
+
+    let mut table = Table::new(&[
+        "TIMESTAMP",
+        "MODULES_USED",
+        "MODULES_INODES",
+        "DATA_FREE",
+        "DATA_INODES_FREE",
+    ]);
+    for s in &samples {
+        table.row(vec![
+            s.timestamp.to_string(),
+            s.modules.used().to_string(),
+            s.modules.inodes_used().to_string(),
+            s.data.free.to_string(),
+            s.data.inodes_free.to_string(),
+        ]);
+    }
+    println!("{}", table.render(format));
+
+    if let Some(p) = &projection {
+        let boots_left = p
+            .boots_left
+            .map(|b| b.to_string())
+            .unwrap_or_else(|| "-".into());
+        println!();
+        output::print_fields(
+            &[
+                ("bytes_per_boot", p.bytes_per_boot.to_string()),
+                ("inodes_per_boot", p.inodes_per_boot.to_string()),
+                ("boots_left", boots_left),
+            ],
+            format,
+        );
+    }
+    if let Some(warning) = warning {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(())
+}
+
+pub fn handle_storage(cli: &Cli, action: Option<&StorageAction>, history: bool) -> Result<()> {
+    let mut config = load_config(cli)?;
+
+    if history {
+        return handle_storage_history(cli, &config);
+    }
+
+    if let Some(StorageAction::Resize { size }) = action {
+        let bytes = storage::parse_size(size)?;
+        config.image_size = Some(size.trim().to_string());
+
+        let path = cli
+            .config
+            .as_deref()
+            .unwrap_or(Path::new(defs::CONFIG_FILE));
+        config
+            .save_to_file(path)
+            .with_context(|| format!("Failed to save config to {}", path.display()))?;
+
+        println!(
+            "Modules image will be at least {} bytes (takes effect after reboot)",
+            bytes
+        );
+        return Ok(());
+    }
+
+    let state = RuntimeState::load().unwrap_or_default();
+    let image_size = std::fs::metadata(defs::MODULES_IMG_FILE)
+        .ok()
+        .map(|m| m.len());
+    let image_min_size = storage::ImageSpec::from_config(&config).min_size;
+    let snapshots = granary::list().len();
+    let usage = granary::usage();
+    let quota = storage::parse_size(&config.backup.max_size).ok();
+
+    let format = cli.output_format(OutputFormat::Json);
+    if format != OutputFormat::Json {
+        let opt = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_else(|| "-".into());
+        output::print_fields(
+            &[
+                ("mode", state.storage_mode.clone()),
+                ("mount_point", state.mount_point.display().to_string()),
+                ("image", defs::MODULES_IMG_FILE.to_string()),
+                ("image_size", opt(image_size)),
+                ("image_min_size", image_min_size.to_string()),
+                ("snapshots", snapshots.to_string()),
+                ("snapshot_usage", usage.to_string()),
+                ("snapshot_quota", opt(quota)),
+            ],
+            format,
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        serde_json::json!({
+            "mode": state.storage_mode,
+            "mount_point": state.mount_point,
+            "image": defs::MODULES_IMG_FILE,
+            "image_size": image_size,
+            "image_min_size": image_min_size,
+            "granary": {
+                "snapshots": snapshots,
+                "usage": usage,
+                "quota": quota,
+            },
+        })
+    );
+
+    Ok(())
+}
+
+pub fn handle_denylist(action: &DenylistAction) -> Result<()> {
+    match action {
+        DenylistAction::Sync => {
+            let packages = denylist::sync().context("Failed to sync denylist")?;
+            println!("{} denylisted package(s)", packages.len());
+            for package in packages {
+                println!("{}", package);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_snapshot(cli: &Cli, action: &SnapshotAction) -> Result<()> {
+    match action {
+        SnapshotAction::List => {
+            let snapshots = granary::list();
+            let format = cli.output_format(OutputFormat::Json);
+            if format == OutputFormat::Json {
+                return output::print_json(&snapshots).context("Failed to serialize snapshots");
+            }
+
+            let mut table = Table::new(&[
+                "ID",
+                "LABEL",
+                "REASON",
+                "TIMESTAMP",
+                "SIZE",
+                "PATCH",
+                "FLAGS",
+            ]);
+            for s in &snapshots {
+                let flags: Vec<&str> = [(s.last_good, "last-good"), (s.protected, "protected")]
+                    .into_iter()
+                    .filter_map(|(set, name)| set.then_some(name))
+                    .collect();
+                table.row(vec![
+                    s.id.clone(),
+                    s.label.clone(),
+                    s.reason.clone(),
+                    s.timestamp.to_string(),
+                    s.size.to_string(),
+                    s.security_patch.clone(),
+                    flags.join(","),
+                ]);
+            }
+            println!("{}", table.render(format));
+        }
+        SnapshotAction::Create { label, deep } => {
+            let config = load_config(cli)?;
+            let meta = granary::create_snapshot(&config, label, "Manual", *deep)?;
+            println!("{}", serde_json::to_string(&meta)?);
+        }
+        SnapshotAction::Diff { id } => {
+            let snapshot = granary::load(id)?;
+            if !snapshot.meta.deep {
+                bail!(
+                    "Snapshot {} has no module content; take one with `snapshot create --deep`",
+                    id
+                );
+            }
+            let changes = granary::content_changes(&snapshot)?;
+            let format = cli.output_format(OutputFormat::Json);
+            if format == OutputFormat::Json {
+                return output::print_json(&changes);
+            }
+
+            let mut table = Table::new(&["MODULE", "CHANGE", "FILES", "REMOVED", "RULES"]);
+            for c in &changes {
+                table.row(vec![
+                    c.module.clone(),
+                    c.kind.as_str().to_string(),
+                    c.changed_files.to_string(),
+                    c.removed_files.to_string(),
+                    if c.rules_changed { "changed" } else { "" }.to_string(),
+                ]);
+            }
+            println!("{}", table.render(format));
+        }
+        SnapshotAction::Restore {
+            id,
+            last_good,
+            disable_added,
+        } => {
+            let id = match id {
+                Some(id) => id.clone(),
+                None if *last_good => {
+                    granary::last_good()
+                        .context("No last-good snapshot recorded yet")?
+                        .id
+                }
+                None => bail!("Specify a snapshot id or --last-good"),
+            };
+            let config_path = cli
+                .config
+                .as_deref()
+                .unwrap_or(Path::new(defs::CONFIG_FILE));
+            let restored = granary::restore(&id, config_path, *disable_added)?;
+            if let Some(mismatch) = granary::build_mismatch(&restored.meta) {
+                eprintln!("Warning: {}", mismatch);
+            }
+            for change in &restored.changes {
+                eprintln!(
+                    "Note: {} {} since the snapshot",
+                    change.module,
+                    change.kind.as_str()
+                );
+            }
+            for module in &restored.disabled {
+                eprintln!("Note: disabled {}", module);
+            }
+            println!("{}", serde_json::to_string(&restored.meta)?);
+        }
+        SnapshotAction::Export { id, path, modules } => {
+            let exported = granary::export(id, path, *modules)?;
+            println!("{}", serde_json::to_string(&exported)?);
+        }
+        SnapshotAction::Import { path, modules } => {
+            let config = load_config(cli)?;
+            let imported = granary::import(path, &config, *modules)?;
+            if let Some(mismatch) = granary::build_mismatch(&imported.meta) {
+                eprintln!("Warning: {}", mismatch);
+            }
+            for module in &imported.skipped {
+                eprintln!("Note: {} is already installed, kept the local copy", module);
+            }
+            println!("{}", serde_json::to_string(&imported)?);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_webui(action: &WebuiAction) -> Result<()> {
+    match action {
+        WebuiAction::Extract { dest } => {
+            webui::extract(dest)?;
+            println!("{}", serde_json::json!({ "extracted": dest }));
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_profile(cli: &Cli, action: &ProfileAction) -> Result<()> {
+    match action {
+        ProfileAction::List => {
+            let profiles = profile::list();
+            let format = cli.output_format(OutputFormat::Json);
+            if format == OutputFormat::Json {
+                return output::print_json(&profiles).context("Failed to serialize profiles");
+            }
+
+            let mut table = Table::new(&["NAME", "FORMAT", "CREATED", "DISABLED", "ACTIVE"]);
+            for p in &profiles {
+                table.row(vec![
+                    p.name.clone(),
+                    p.format.as_str().to_string(),
+                    p.created.to_string(),
+                    p.disabled_modules.to_string(),
+                    if p.active { "*" } else { "" }.to_string(),
+                ]);
+            }
+            println!("{}", table.render(format));
+        }
+        ProfileAction::Save { name } => {
+            let config = load_config(cli)?;
+            let saved = profile::save(name, &config)?;
+            println!(
+                "saved profile {} ({} disabled module(s))",
+                saved.name,
+                saved.disabled_modules.len()
+            );
+        }
+        ProfileAction::Use { name } => {
+            let config_path = cli
+                .config
+                .as_deref()
+                .unwrap_or(Path::new(defs::CONFIG_FILE));
+            profile::switch(name, config_path)?;
+            println!("switched to profile {}, mounts change on next boot", name);
+
+            // A running daemon picks up logging, notify and similar settings right away.
+            match daemon::request(r#"{"cmd":"reload"}"#) {
+                Ok(_) => println!("daemon reloaded live settings"),
+                Err(e) => log::debug!("Daemon not reloaded: {:#}", e),
+            }
+        }
+        ProfileAction::Delete { name } => {
+            profile::delete(name)?;
+            println!("deleted profile {}", name);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_debloat(cli: &Cli, action: &DebloatAction) -> Result<()> {
+    let config = load_config(cli)?;
+
+    match action {
+        DebloatAction::Add { path } => {
+            debloat::add(&config, path)?;
+            println!("Removed systemlessly: {} (takes effect after reboot)", path);
+        }
+        DebloatAction::List => {
+            let json = serde_json::to_string(&debloat::list())
+                .context("Failed to serialize debloat list")?;
+            println!("{}", json);
+        }
+        DebloatAction::Remove { path } => {
+            if debloat::remove(&config, path)? {
+                println!("Restored: {} (takes effect after reboot)", path);
+            } else {
+                println!("Not debloated: {}", path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_maintenance(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
+    let summary = maintenance::run(&config);
+
+    let format = cli.output_format(OutputFormat::Table);
+    if format == OutputFormat::Json {
+        return output::print_json(&summary).context("Failed to serialize maintenance summary");
+    }
+
+    let mut table = Table::new(&["TASK", "STATUS", "DETAIL"]);
+    for task in &summary.tasks {
+        table.row(vec![
+            task.task.clone(),
+            task.status.as_str().to_string(),
+            task.detail.clone(),
+        ]);
+    }
+    println!("{}", table.render(format));
+    Ok(())
+}
+
+pub fn handle_export_module(
+    cli: &Cli,
+    module: Option<&str>,
+    output: &Path,
+    id: Option<&str>,
+    name: Option<&str>,
+) -> Result<()> {
+    let config = load_config(cli)?;
+    let source = match module {
+        Some(module) => packager::Source::Module(module.to_string()),
+        None => packager::Source::Rw,
+    };
+    let output = artifacts::output_path(&config.artifacts, output);
+
+    let summary = packager::export(&config, &source, id, name, &output)?;
+    artifacts::record(&config.artifacts, &output);
+
+    let format = cli.output_format(OutputFormat::Plain);
+    if format == OutputFormat::Json {
+        return output::print_json(&summary).context("Failed to serialize export summary");
+    }
+    output::print_fields(
+        &[
+            ("id", summary.id.clone()),
+            ("output", summary.output.display().to_string()),
+            ("files", summary.files.to_string()),
+            ("removed", summary.removed.len().to_string()),
+            ("replaced", summary.replaced.len().to_string()),
+        ],
+        format,
+    );
+    Ok(())
+}
+
+pub fn handle_install(cli: &Cli, file: &Path, force: bool, dry_run: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    let summary = installer::install(&config, file, force, dry_run)?;
+
+    let format = cli.output_format(OutputFormat::Plain);
+    if format == OutputFormat::Json {
+        return output::print_json(&summary).context("Failed to serialize install summary");
+    }
+    output::print_fields(
+        &[
+            ("id", summary.id.clone()),
+            ("name", summary.name.clone()),
+            ("version", summary.version.clone()),
+            ("path", summary.path.display().to_string()),
+            ("files", summary.files.to_string()),
+            ("updated", summary.updated.to_string()),
+            ("installed", summary.installed.to_string()),
+            ("conflicts", summary.conflicts.len().to_string()),
+            ("critical", summary.critical.len().to_string()),
+        ],
+        format,
+    );
+    if !summary.critical.is_empty() {
+        let mut table = Table::new(&["CODE", "CONTEXT", "MESSAGE"]);
+        for issue in &summary.critical {
+            table.row(vec![
+                issue.code.as_str().to_string(),
+                issue.context.clone(),
+                issue.message.clone(),
+            ]);
+        }
+        println!("\n{}", table.render(format));
+    }
+    if !summary.conflicts.is_empty() {
+        let mut table = Table::new(&["PARTITION", "PATH", "WINNER", "MODULES"]);
+        for c in &summary.conflicts {
+            table.row(vec![
+                c.partition.clone(),
+                c.relative_path.clone(),
+                c.winner.clone(),
+                c.contending_modules.join(","),
+            ]);
+        }
+        println!("\n{}", table.render(format));
+    }
+    if summary.installed {
+        println!("Reboot to mount {}", summary.id);
+    }
+    Ok(())
+}
+
+pub fn handle_integrity(cli: &Cli, action: Option<&IntegrityAction>) -> Result<()> {
+    let verdicts = match action {
+        Some(IntegrityAction::Sign) => integrity::resign()?,
+        None => integrity::check(),
+    };
+
+    let format = cli.output_format(OutputFormat::Plain);
+    if format == OutputFormat::Json {
+        return output::print_json(&verdicts).context("Failed to serialize integrity status");
+    }
+    let mut table = Table::new(&["FILE", "STATUS"]);
+    for v in &verdicts {
+        table.row(vec![
+            v.path.display().to_string(),
+            v.verdict.as_str().to_string(),
+        ]);
+    }
+    println!("{}", table.render(format));
+    Ok(())
+}
+
+pub fn handle_harden() {
+    let fixed = config::harden_storage();
+    println!("{} file(s) under {} hardened", fixed, defs::BASE_DIR);
+}
+
+pub fn handle_rw(cli: &Cli, action: &RwAction) -> Result<()> {
+    let config = load_config(cli)?;
+
+    match action {
+        RwAction::Enable { partition } => {
+            let upper = rw::enable(&config, partition)?;
+            println!(
+                "/{} becomes read-write on next boot, changes land in {}",
+                partition,
+                upper.display()
+            );
+        }
+        RwAction::Disable { partition, purge } => {
+            if !rw::disable(&config, partition, *purge)? {
+                println!("/{} is not read-write", partition);
+            } else if *purge {
+                println!(
+                    "/{} is read-only on next boot, its changes are discarded",
+                    partition
+                );
+            } else {
+                println!(
+                    "/{} is read-only on next boot, its changes are kept",
+                    partition
+                );
+            }
+        }
+        RwAction::Diff { partition, export } => {
+            if let Some(id) = export {
+                let module = rw::export(&config, partition, id)?;
+                println!("exported /{} changes to {}", partition, module.display());
+                return Ok(());
+            }
+
+            let changes = rw::diff(&config, partition)?;
+            let format = cli.output_format(OutputFormat::Table);
+            if format == OutputFormat::Json {
+                return output::print_json(&changes).context("Failed to serialize rw diff");
+            }
+
+            let mut table = Table::new(&["KIND", "PATH"]);
+            for change in &changes {
+                table.row(vec![change.kind.as_str().to_string(), change.path.clone()]);
+            }
+            println!("{}", table.render(format));
+        }
+    }
+
+    Ok(())
+}
+
 pub fn handle_poaceae(target_path: &str, action: &PoaceaeAction) -> Result<()> {
     let file = File::open(target_path)
         .with_context(|| format!("Failed to open PoaceaeFS root at {}", target_path))?;
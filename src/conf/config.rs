@@ -2,15 +2,26 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs,
+    os::unix::fs::{MetadataExt, PermissionsExt, chown},
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
 
-use crate::defs;
+use crate::{
+    core::{
+        integrity::{self, Verdict},
+        storage,
+    },
+    defs,
+    sys::mount,
+    utils,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BackupConfig {
@@ -18,6 +29,11 @@ pub struct BackupConfig {
     pub max_backups: usize,
     #[serde(default = "default_retention_days")]
     pub retention_days: u64,
+    #[serde(default = "default_backup_max_size")]
+    pub max_size: String,
+    // Boot snapshots also record per-module file hashes and rules.
+    #[serde(default)]
+    pub deep: bool,
 }
 
 fn default_max_backups() -> usize {
@@ -28,11 +44,175 @@ fn default_retention_days() -> u64 {
     0
 }
 
+fn default_backup_max_size() -> String {
+    "4M".to_string()
+}
+
 impl Default for BackupConfig {
     fn default() -> Self {
         Self {
             max_backups: default_max_backups(),
             retention_days: default_retention_days(),
+            max_size: default_backup_max_size(),
+            deep: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NotifyConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broadcast: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ntfy_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+}
+
+impl NotifyConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.broadcast.is_some() || self.ntfy_url.is_some() || self.script.is_some()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArtifactsConfig {
+    #[serde(default = "default_artifacts_dir")]
+    pub dir: PathBuf,
+    #[serde(default)]
+    pub retention_days: u64,
+}
+
+fn default_artifacts_dir() -> PathBuf {
+    PathBuf::from(defs::EXPORTS_DIR)
+}
+
+impl Default for ArtifactsConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_artifacts_dir(),
+            retention_days: 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_max_size")]
+    pub max_size: String,
+    #[serde(default = "default_log_keep")]
+    pub keep: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact: Vec<utils::RedactRule>,
+}
+
+fn default_log_max_size() -> String {
+    "1M".to_string()
+}
+
+fn default_log_keep() -> usize {
+    utils::DEFAULT_LOG_KEEP
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            max_size: default_log_max_size(),
+            keep: default_log_keep(),
+            redact: Vec::new(),
+        }
+    }
+}
+
+// Only scheduled when the table is present, since that is also what starts the daemon.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MaintenanceConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_maintenance_interval_days")]
+    pub interval_days: u64,
+    #[serde(default = "default_true")]
+    pub require_charging: bool,
+    #[serde(default = "default_maintenance_max_load")]
+    pub max_load: f64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_maintenance_interval_days() -> u64 {
+    7
+}
+
+fn default_maintenance_max_load() -> f64 {
+    4.0
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LimitsConfig {
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+}
+
+fn default_max_entries() -> usize {
+    100_000
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: default_max_entries(),
+            max_size: None,
+        }
+    }
+}
+
+// Off while `max_conflicts` is 0.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DeferConfig {
+    #[serde(default)]
+    pub max_conflicts: usize,
+    // Modules mounted whatever they take over.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+}
+
+// Off by default: KernelSU and APatch already load sepolicy.rule for regular modules.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SepolicyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<PathBuf>,
+}
+
+// Off by default too: the root manager normally runs module boot scripts itself.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScriptsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_script_timeout")]
+    pub timeout: u64,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub timeouts: HashMap<String, u64>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub after: HashMap<String, Vec<String>>,
+}
+
+fn default_script_timeout() -> u64 {
+    10
+}
+
+impl Default for ScriptsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout: default_script_timeout(),
+            timeouts: HashMap::new(),
+            after: HashMap::new(),
         }
     }
 }
@@ -44,6 +224,16 @@ pub enum OverlayMode {
     Tmpfs,
     Ext4,
     Erofs,
+    Squashfs,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFs {
+    #[default]
+    Ext4,
+    F2fs,
+    Auto,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
@@ -54,6 +244,35 @@ pub enum DefaultMode {
     Magic,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KernelObjectPolicy {
+    #[default]
+    Warn,
+    Skip,
+}
+
+// What to do about modules ksud or Magisk has already mounted. `unmount` is the old name for
+// takeover, from when only ksud was handled.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Coexistence {
+    #[default]
+    Off,
+    Skip,
+    #[serde(alias = "unmount")]
+    Takeover,
+    Abort,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum MountStage {
+    #[default]
+    PostFsData,
+    Service,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum MountMode {
@@ -65,35 +284,99 @@ pub enum MountMode {
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ModuleRules {
-    #[serde(default)]
-    pub default_mode: MountMode,
+    // None leaves the choice to the template or the global default_mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_mode: Option<MountMode>,
     #[serde(default)]
     pub paths: HashMap<String, MountMode>,
+    #[serde(default)]
+    pub skip_umount: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<MountStage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    // Destination under a `companion_paths` directory to the module file copied there.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub companions: BTreeMap<String, String>,
 }
 
 impl ModuleRules {
+    // A rule extending a template takes its default mode and stage unless it sets them itself;
+    // paths and skip_umount are layered on top, the rule's own paths winning.
+    pub fn inherit(&self, template: &ModuleRules) -> ModuleRules {
+        let mut rules = template.clone();
+        if self.default_mode.is_some() {
+            rules.default_mode = self.default_mode.clone();
+        }
+        if self.stage.is_some() {
+            rules.stage = self.stage;
+        }
+        rules.paths.extend(self.paths.clone());
+        rules.companions.extend(self.companions.clone());
+        rules.skip_umount |= self.skip_umount;
+        rules.extends = self.extends.clone();
+        rules
+    }
+
+    pub fn mode(&self) -> MountMode {
+        self.default_mode.clone().unwrap_or_default()
+    }
+
+    pub fn stage(&self) -> MountStage {
+        self.stage.unwrap_or_default()
+    }
+
     pub fn get_mode(&self, relative_path: &str) -> MountMode {
         if let Some(mode) = self.paths.get(relative_path) {
             return mode.clone();
         }
-        self.default_mode.clone()
+        self.mode()
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
-    #[serde(default = "default_moduledir")]
-    pub moduledir: PathBuf,
+    #[serde(
+        default = "default_moduledir",
+        deserialize_with = "deserialize_moduledir_flexible",
+        serialize_with = "serialize_moduledir"
+    )]
+    pub moduledir: Vec<PathBuf>,
     #[serde(default = "default_mountsource")]
     pub mountsource: String,
+    #[serde(
+        default = "default_builtin_partitions",
+        deserialize_with = "deserialize_partitions_flexible"
+    )]
+    pub builtin_partitions: Vec<String>,
     #[serde(default, deserialize_with = "deserialize_partitions_flexible")]
     pub partitions: Vec<String>,
     #[serde(default)]
+    pub auto_partitions: bool,
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+    #[serde(default)]
+    pub companion_paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub integrity: bool,
+    // Names (as in /proc/<pid>/comm) of early services whose namespaces get our mounts replayed.
+    #[serde(default)]
+    pub early_namespaces: Vec<String>,
+    #[serde(default)]
     pub overlay_mode: OverlayMode,
     #[serde(default)]
+    pub image_fs: ImageFs,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_size: Option<String>,
+    // Warn once the modules image is projected to fill up within this many boots; 0 turns it off.
+    #[serde(default = "default_space_warn_boots")]
+    pub space_warn_boots: u32,
+    #[serde(default)]
     pub disable_umount: bool,
     #[serde(default)]
     pub allow_umount_coexistence: bool,
+    #[serde(default)]
+    pub denylist_umount: bool,
     #[serde(default, alias = "granary")]
     pub backup: BackupConfig,
     #[serde(default = "default_hybrid_mnt_dir")]
@@ -102,14 +385,58 @@ pub struct Config {
     pub default_mode: DefaultMode,
     #[serde(default)]
     pub rules: HashMap<String, ModuleRules>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rule_templates: HashMap<String, ModuleRules>,
+    #[serde(default)]
+    pub priority: HashMap<String, i32>,
+    #[serde(default)]
+    pub resolve: HashMap<String, String>,
+    #[serde(default)]
+    pub defer: DeferConfig,
+    #[serde(default)]
+    pub kernel_object_policy: KernelObjectPolicy,
+    #[serde(default, deserialize_with = "deserialize_partitions_flexible")]
+    pub suppressed_issues: Vec<String>,
+    #[serde(default)]
+    #[serde(alias = "ksud_handover")]
+    pub coexistence: Coexistence,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    #[serde(default)]
+    pub sepolicy: SepolicyConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub log_format: utils::LogFormat,
+    #[serde(default)]
+    pub scripts: ScriptsConfig,
+    #[serde(default)]
+    pub artifacts: ArtifactsConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<MaintenanceConfig>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub visibility: HashMap<String, Vec<String>>,
+}
+
+fn default_space_warn_boots() -> u32 {
+    5
 }
 
 fn default_hybrid_mnt_dir() -> String {
     defs::DEFAULT_HYBRID_MNT_DIR.to_string()
 }
 
-fn default_moduledir() -> PathBuf {
-    PathBuf::from(defs::MODULES_DIR)
+fn default_moduledir() -> Vec<PathBuf> {
+    vec![PathBuf::from(defs::MODULES_DIR)]
+}
+
+fn default_builtin_partitions() -> Vec<String> {
+    defs::BUILTIN_PARTITIONS
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
 }
 
 fn default_mountsource() -> String {
@@ -137,25 +464,86 @@ where
     }
 }
 
+fn deserialize_moduledir_flexible<'de, D>(deserializer: D) -> Result<Vec<PathBuf>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PathOrVec {
+        Path(PathBuf),
+        Vec(Vec<PathBuf>),
+    }
+
+    let dirs = match PathOrVec::deserialize(deserializer)? {
+        PathOrVec::Path(p) => vec![p],
+        PathOrVec::Vec(v) => v,
+    };
+
+    Ok(if dirs.is_empty() {
+        default_moduledir()
+    } else {
+        dirs
+    })
+}
+
+// A single directory stays a plain string so older WebUI builds keep working.
+fn serialize_moduledir<S>(dirs: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match dirs {
+        [single] => single.serialize(serializer),
+        _ => dirs.serialize(serializer),
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             moduledir: default_moduledir(),
             mountsource: default_mountsource(),
+            builtin_partitions: default_builtin_partitions(),
             partitions: Vec::new(),
+            auto_partitions: false,
+            protected_paths: Vec::new(),
+            companion_paths: Vec::new(),
+            integrity: false,
+            early_namespaces: Vec::new(),
             overlay_mode: OverlayMode::default(),
+            image_fs: ImageFs::default(),
+            image_size: None,
+            space_warn_boots: default_space_warn_boots(),
             disable_umount: false,
             allow_umount_coexistence: false,
+            denylist_umount: false,
             backup: BackupConfig::default(),
             hybrid_mnt_dir: default_hybrid_mnt_dir(),
             default_mode: DefaultMode::default(),
             rules: HashMap::new(),
+            rule_templates: HashMap::new(),
+            priority: HashMap::new(),
+            resolve: HashMap::new(),
+            defer: DeferConfig::default(),
+            kernel_object_policy: KernelObjectPolicy::default(),
+            suppressed_issues: Vec::new(),
+            coexistence: Coexistence::default(),
+            notify: NotifyConfig::default(),
+            sepolicy: SepolicyConfig::default(),
+            limits: LimitsConfig::default(),
+            logging: LoggingConfig::default(),
+            log_format: utils::LogFormat::default(),
+            scripts: ScriptsConfig::default(),
+            artifacts: ArtifactsConfig::default(),
+            maintenance: None,
+            visibility: HashMap::new(),
         }
     }
 }
 
 impl Config {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        check_writers(path.as_ref())?;
         let content = fs::read_to_string(path.as_ref()).context("failed to read config file")?;
 
         let config: Config = toml::from_str(&content).context("failed to parse config file")?;
@@ -163,11 +551,17 @@ impl Config {
         Ok(config)
     }
 
+    // A config that fails its signature is treated as hostile and replaced by the defaults.
     pub fn load_default() -> Result<Self> {
+        if integrity::verify(Path::new(defs::CONFIG_FILE)) == Verdict::Tampered {
+            log::warn!("Config failed its integrity check, using defaults");
+            return Ok(Self::default());
+        }
         Self::from_file(defs::CONFIG_FILE)
     }
 
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        integrity::guard_config(path.as_ref())?;
         let content = toml::to_string_pretty(self).context("failed to serialize config")?;
 
         if let Some(parent) = path.as_ref().parent() {
@@ -175,10 +569,82 @@ impl Config {
         }
 
         fs::write(path.as_ref(), content).context("failed to write config file")?;
+        harden_file(path.as_ref());
+        integrity::sign_config(path.as_ref(), self.integrity)
+            .context("failed to sign config file")?;
 
         Ok(())
     }
 
+    pub fn apply_log_settings(&self) {
+        let max_size = storage::parse_size(&self.logging.max_size).unwrap_or_else(|e| {
+            log::warn!("Ignoring logging.max_size: {:#}", e);
+            utils::DEFAULT_LOG_MAX_SIZE
+        });
+        utils::set_log_rotation(max_size, self.logging.keep);
+        utils::set_log_format(self.log_format);
+        utils::set_redact_rules(&self.logging.redact);
+    }
+
+    pub fn primary_moduledir(&self) -> &Path {
+        self.moduledir
+            .first()
+            .map(PathBuf::as_path)
+            .unwrap_or(Path::new(defs::MODULES_DIR))
+    }
+
+    // Higher wins; modules without an entry sit at 0 and keep scan order among themselves.
+    pub fn module_priority(&self, id: &str) -> i32 {
+        self.priority.get(id).copied().unwrap_or(0)
+    }
+
+    // Configured extras plus, with auto_partitions, whatever discovery found. Discovery runs once
+    // per process.
+    pub fn extra_partitions(&self) -> Vec<String> {
+        static DISCOVERED: OnceLock<Vec<String>> = OnceLock::new();
+
+        let mut partitions = self.partitions.clone();
+        if self.auto_partitions {
+            let discovered = DISCOVERED.get_or_init(|| {
+                let mut known = self.builtin_partitions.clone();
+                known.extend(self.partitions.iter().cloned());
+                let found = mount::discover_partitions(&self.moduledir, &known);
+                if !found.is_empty() {
+                    log::info!("Discovered partitions: {}", found.join(", "));
+                }
+                found
+            });
+            partitions.extend(discovered.iter().cloned());
+        }
+        partitions
+    }
+
+    pub fn effective_partitions(&self) -> Vec<String> {
+        let mut partitions = self.builtin_partitions.clone();
+        for extra in self.extra_partitions() {
+            if !partitions.contains(&extra) {
+                partitions.push(extra);
+            }
+        }
+        partitions
+    }
+
+    // The built-in paths always apply; `protected_paths` can only add to them.
+    pub fn effective_protected_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for path in defs::BUILTIN_PROTECTED_PATHS
+            .iter()
+            .copied()
+            .chain(self.protected_paths.iter().map(String::as_str))
+        {
+            let path = PathBuf::from(path.trim_end_matches('/'));
+            if path.is_absolute() && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
     pub fn merge_with_cli(
         &mut self,
         moduledir: Option<PathBuf>,
@@ -186,7 +652,7 @@ impl Config {
         partitions: Vec<String>,
     ) {
         if let Some(dir) = moduledir {
-            self.moduledir = dir;
+            self.moduledir = vec![dir];
         }
 
         if let Some(source) = mountsource {
@@ -198,3 +664,118 @@ impl Config {
         }
     }
 }
+
+// `module_mode.conf` pins a module's mount mode on top of whatever config or profile is active, one
+// `<id>=<overlay|magic|ignore>` per line.
+pub fn mode_overrides() -> HashMap<String, MountMode> {
+    let Ok(content) = fs::read_to_string(defs::MODE_CONFIG_FILE) else {
+        return HashMap::new();
+    };
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let parsed = line.split_once('=').and_then(|(id, mode)| {
+                let mode = match mode.trim() {
+                    "overlay" => MountMode::Overlay,
+                    "magic" => MountMode::Magic,
+                    "ignore" => MountMode::Ignore,
+                    _ => return None,
+                };
+                Some((id.trim().to_string(), mode))
+            });
+            if parsed.is_none() {
+                log::warn!("Ignoring line in {}: {}", defs::MODE_CONFIG_FILE, line);
+            }
+            parsed
+        })
+        .collect()
+}
+
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+// Anything an unprivileged app can write to could steer what gets mounted.
+pub fn check_writers(path: &Path) -> Result<()> {
+    if !is_root() {
+        return Ok(());
+    }
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(());
+    };
+    let mode = metadata.mode();
+    if metadata.uid() != 0 || mode & 0o002 != 0 || (mode & 0o020 != 0 && metadata.gid() != 0) {
+        bail!(
+            "refusing to load {}: writable by non-root (owner {}:{}, mode {:o})",
+            path.display(),
+            metadata.uid(),
+            metadata.gid(),
+            mode & 0o7777
+        );
+    }
+    Ok(())
+}
+
+// Module trees, mount points and other tools' files keep their own ownership and modes.
+const HARDEN_SKIP: &[&str] = &[
+    defs::IMAGE_MNT_DIR,
+    defs::MANAGED_MODULES_DIR,
+    defs::SYSTEM_RW_DIR,
+    defs::COMPANION_BACKUP_DIR,
+    defs::INSTALL_STAGE_DIR,
+    defs::OVERLAY_STAGE_DIR,
+    defs::LAYER_MNT_DIR,
+    defs::PINS_DIR,
+];
+
+// Every other regular file under the base directory becomes root:root 0600. Walking the whole
+// tree is too slow for every config load, so it runs at install and on `meta-hybrid harden`.
+pub fn harden_storage() -> usize {
+    let mut fixed = 0;
+    if !is_root() {
+        return fixed;
+    }
+    let walker = WalkDir::new(defs::BASE_DIR)
+        .same_file_system(true)
+        .into_iter()
+        .filter_entry(|e| !HARDEN_SKIP.iter().any(|skip| e.path() == Path::new(skip)));
+    for entry in walker.flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let path = entry.path();
+        if metadata.uid() != 0 || metadata.gid() != 0 || metadata.mode() & 0o7777 != 0o600 {
+            fixed += 1;
+        }
+        if (metadata.uid() != 0 || metadata.gid() != 0)
+            && let Err(e) = chown(path, Some(0), Some(0))
+        {
+            log::warn!("Failed to chown {}: {}", path.display(), e);
+        }
+        if metadata.mode() & 0o7777 != 0o600
+            && let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        {
+            log::warn!("Failed to chmod {}: {}", path.display(), e);
+        }
+    }
+    fixed
+}
+
+// What harden_storage does for one file, for the files we rewrite in place under the base
+// directory. A file written anywhere else, e.g. by gen-config, is left alone.
+pub fn harden_file(path: &Path) {
+    if !is_root() || !path.starts_with(defs::BASE_DIR) {
+        return;
+    }
+    if let Err(e) = chown(path, Some(0), Some(0)) {
+        log::warn!("Failed to chown {}: {}", path.display(), e);
+    }
+    if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+        log::warn!("Failed to chmod {}: {}", path.display(), e);
+    }
+}
@@ -4,3 +4,5 @@
 pub mod cli;
 pub mod cli_handlers;
 pub mod config;
+pub mod output;
+pub mod validate;
@@ -0,0 +1,129 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::io;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Table,
+    Plain,
+}
+
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    // Plain output is one tab-separated record per line with no header, for shell pipelines.
+    pub fn render(&self, format: OutputFormat) -> String {
+        if format == OutputFormat::Plain {
+            return self
+                .rows
+                .iter()
+                .map(|row| row.join("\t"))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                if let Some(w) = widths.get_mut(i) {
+                    *w = (*w).max(cell.chars().count());
+                }
+            }
+        }
+
+        let line = |cells: &mut dyn Iterator<Item = &str>| {
+            cells
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        let mut out = vec![
+            line(&mut self.headers.iter().copied()),
+            line(&mut rule.iter().map(String::as_str)),
+        ];
+        for row in &self.rows {
+            out.push(line(&mut row.iter().map(String::as_str)));
+        }
+        out.join("\n")
+    }
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+// Key/value records: a two-column table, or `key=value` lines in plain mode.
+pub fn print_fields(fields: &[(&str, String)], format: OutputFormat) {
+    if format == OutputFormat::Plain {
+        for (key, value) in fields {
+            println!("{}={}", key, value);
+        }
+        return;
+    }
+
+    let mut table = Table::new(&["KEY", "VALUE"]);
+    for (key, value) in fields {
+        table.row(vec![key.to_string(), value.clone()]);
+    }
+    println!("{}", table.render(format));
+}
+
+// A coarse class for callers to branch on; the context chain carries the detail.
+fn error_code(error: &anyhow::Error) -> &'static str {
+    let root = error.root_cause();
+    if let Some(io) = root.downcast_ref::<io::Error>() {
+        return match io.kind() {
+            io::ErrorKind::NotFound => "NOT_FOUND",
+            io::ErrorKind::PermissionDenied => "PERMISSION_DENIED",
+            io::ErrorKind::AlreadyExists => "ALREADY_EXISTS",
+            _ => "IO",
+        };
+    }
+    if root.is::<toml::de::Error>() || root.is::<serde_json::Error>() {
+        return "INVALID_INPUT";
+    }
+    if root.is::<clap::Error>() {
+        return "USAGE";
+    }
+    "FAILED"
+}
+
+// `{"error":{"code","message","context_chain"}}`, where message is the root cause and the chain
+// runs from the outermost context inwards.
+pub fn print_error(error: &anyhow::Error) {
+    let chain: Vec<String> = error.chain().map(|e| e.to_string()).collect();
+    let value = serde_json::json!({
+        "error": {
+            "code": error_code(error),
+            "message": error.root_cause().to_string(),
+            "context_chain": chain,
+        }
+    });
+    println!("{}", value);
+}
@@ -0,0 +1,358 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    conf::config::{self, Config, ImageFs, OverlayMode},
+    core::inventory::lifecycle,
+};
+
+// Keys that are left out of a serialized default config but still accepted, aliases included.
+const OPTIONAL_KEYS: &[&str] = &[
+    "image_size",
+    "rule_templates",
+    "maintenance",
+    "visibility",
+    "granary",
+    "ksud_handover",
+];
+
+// /proc/<pid>/comm is cut off after this many bytes.
+const COMM_LEN: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+struct Checker<'a> {
+    content: &'a str,
+    issues: Vec<ConfigIssue>,
+}
+
+fn quote(key: &str) -> String {
+    if key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        format!("\"{}\"", key)
+    }
+}
+
+fn assigns(line: &str, key: &str) -> bool {
+    line.strip_prefix(key)
+        .is_some_and(|rest| matches!(rest.trim_start().chars().next(), Some('=' | '.')))
+}
+
+// Best effort: the line that opens `path` as a table, or assigns its last key inside the parent
+// table. Inline tables and dotted keys across tables are not followed.
+fn line_of(content: &str, path: &[&str]) -> Option<usize> {
+    let (last, parents) = path.split_last()?;
+    let quoted: Vec<String> = path.iter().map(|k| quote(k)).collect();
+    let full = quoted.join(".");
+    let parent = quoted[..parents.len()].join(".");
+    let last = quote(last);
+
+    let mut table = String::new();
+    for (i, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+        if line.starts_with('[') {
+            table = line
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .to_string();
+            if table == full {
+                return Some(i + 1);
+            }
+            continue;
+        }
+        if table == parent && assigns(line, &last) {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+fn line_at(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+impl Checker<'_> {
+    fn push(&mut self, severity: Severity, path: &[&str], message: String) {
+        self.issues.push(ConfigIssue {
+            severity,
+            key: path.join("."),
+            line: line_of(self.content, path),
+            message,
+        });
+    }
+
+    fn error(&mut self, path: &[&str], message: String) {
+        self.push(Severity::Error, path, message);
+    }
+
+    fn warn(&mut self, path: &[&str], message: String) {
+        self.push(Severity::Warning, path, message);
+    }
+
+    fn unknown_keys(&mut self) {
+        let Ok(table) = self.content.parse::<toml::Table>() else {
+            return;
+        };
+        let known: HashSet<String> = toml::Table::try_from(Config::default())
+            .map(|t| t.keys().cloned().collect())
+            .unwrap_or_default();
+        for key in table.keys() {
+            if !known.contains(key) && !OPTIONAL_KEYS.contains(&key.as_str()) {
+                self.warn(&[key], "unknown key, ignored".to_string());
+            }
+        }
+    }
+
+    fn paths(&mut self, config: &Config) {
+        for dir in &config.moduledir {
+            if !dir.is_dir() {
+                self.error(
+                    &["moduledir"],
+                    format!("{} is not a directory", dir.display()),
+                );
+            }
+        }
+
+        let mnt = Path::new(&config.hybrid_mnt_dir);
+        if !mnt.is_absolute() {
+            self.error(
+                &["hybrid_mnt_dir"],
+                format!("{} is not an absolute path", mnt.display()),
+            );
+        } else if !mnt.is_dir() {
+            self.warn(
+                &["hybrid_mnt_dir"],
+                format!("{} does not exist", mnt.display()),
+            );
+        }
+
+        for path in &config.protected_paths {
+            if !path.starts_with('/') {
+                self.error(
+                    &["protected_paths"],
+                    format!("{} is not an absolute path", path),
+                );
+            }
+        }
+
+        for dir in &config.companion_paths {
+            if !dir.starts_with("/data/adb") {
+                self.error(
+                    &["companion_paths"],
+                    format!("{} is not under /data/adb", dir.display()),
+                );
+            } else if !dir.is_dir() {
+                self.warn(
+                    &["companion_paths"],
+                    format!("{} does not exist", dir.display()),
+                );
+            }
+        }
+    }
+
+    fn partitions(&mut self, config: &Config) {
+        for partition in &config.partitions {
+            let sane = !partition.is_empty()
+                && partition.split('/').all(|c| {
+                    !c.is_empty()
+                        && c != "."
+                        && c != ".."
+                        && c.chars()
+                            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+                });
+            if !sane {
+                self.error(
+                    &["partitions"],
+                    format!(
+                        "\"{}\" is not a partition name (relative, like `my_product` or \
+                         `mnt/vendor/persist`)",
+                        partition
+                    ),
+                );
+            } else if config.builtin_partitions.contains(partition) {
+                self.warn(
+                    &["partitions"],
+                    format!("\"{}\" is already a builtin partition", partition),
+                );
+            }
+        }
+    }
+
+    fn module_refs(&mut self, config: &Config) {
+        let known = |id: &str| lifecycle::locate(config, id).is_ok();
+
+        let mut rules: Vec<(&String, &config::ModuleRules)> = config.rules.iter().collect();
+        rules.sort_by_key(|(id, _)| *id);
+        for (id, rule) in rules {
+            if !known(id) {
+                self.warn(
+                    &["rules", id],
+                    format!("no installed module is called {}", id),
+                );
+            }
+            if let Some(template) = &rule.extends
+                && !config.rule_templates.contains_key(template)
+            {
+                self.error(
+                    &["rules", id, "extends"],
+                    format!("rule template {} does not exist", template),
+                );
+            }
+        }
+
+        let mut priority: Vec<&String> = config.priority.keys().collect();
+        priority.sort();
+        for id in priority {
+            if !known(id) {
+                self.warn(
+                    &["priority", id],
+                    format!("no installed module is called {}", id),
+                );
+            }
+        }
+
+        let mut resolve: Vec<(&String, &String)> = config.resolve.iter().collect();
+        resolve.sort();
+        for (path, id) in resolve {
+            if !path.starts_with('/') {
+                self.error(
+                    &["resolve", path],
+                    format!("{} is not an absolute path", path),
+                );
+            }
+            if !known(id) {
+                self.warn(
+                    &["resolve", path],
+                    format!("pinned module {} is not installed", id),
+                );
+            }
+        }
+
+        let mut visibility: Vec<&String> = config.visibility.keys().collect();
+        visibility.sort();
+        for id in visibility {
+            if !known(id) {
+                self.warn(
+                    &["visibility", id],
+                    format!("no installed module is called {}", id),
+                );
+            }
+        }
+    }
+
+    fn combinations(&mut self, config: &Config) {
+        let packed = matches!(
+            config.overlay_mode,
+            OverlayMode::Erofs | OverlayMode::Squashfs
+        );
+        if packed && config.disable_umount {
+            self.warn(
+                &["disable_umount"],
+                "with a read-only image and no umount, the image mount stays visible to every app"
+                    .to_string(),
+            );
+        }
+        if config.denylist_umount && config.disable_umount {
+            self.warn(
+                &["denylist_umount"],
+                "has no effect while disable_umount is on".to_string(),
+            );
+        }
+        if config.overlay_mode != OverlayMode::Ext4 {
+            if config.image_fs != ImageFs::default() {
+                self.warn(
+                    &["image_fs"],
+                    "only used with overlay_mode = \"ext4\"".to_string(),
+                );
+            }
+            if config.image_size.is_some() {
+                self.warn(
+                    &["image_size"],
+                    "only used with overlay_mode = \"ext4\"".to_string(),
+                );
+            }
+        }
+        for name in &config.early_namespaces {
+            if name.len() > COMM_LEN {
+                self.warn(
+                    &["early_namespaces"],
+                    format!(
+                        "\"{}\" is longer than a process name can be ({} characters)",
+                        name, COMM_LEN
+                    ),
+                );
+            }
+        }
+    }
+}
+
+// Only an unreadable file is an Err; everything wrong inside it comes back as issues.
+pub fn validate(path: &Path) -> Result<Vec<ConfigIssue>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut checker = Checker {
+        content: &content,
+        issues: Vec::new(),
+    };
+
+    if let Err(e) = config::check_writers(path) {
+        checker.issues.push(ConfigIssue {
+            severity: Severity::Error,
+            key: String::new(),
+            line: None,
+            message: format!("{:#}", e),
+        });
+    }
+
+    let config: Config = match toml::from_str(&content) {
+        Ok(config) => config,
+        Err(e) => {
+            checker.issues.push(ConfigIssue {
+                severity: Severity::Error,
+                key: String::new(),
+                line: e.span().map(|span| line_at(&content, span.start)),
+                message: e.message().to_string(),
+            });
+            return Ok(checker.issues);
+        }
+    };
+
+    checker.unknown_keys();
+    checker.paths(&config);
+    checker.partitions(&config);
+    checker.module_refs(&config);
+    checker.combinations(&config);
+    Ok(checker.issues)
+}
@@ -0,0 +1,156 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{conf::config::ArtifactsConfig, defs, utils};
+
+// Every path apps can reach through the emulated-storage views.
+const SHARED_PREFIXES: &[&str] = &[
+    "/sdcard",
+    "/storage",
+    "/mnt/user",
+    "/mnt/runtime",
+    "/data/media",
+];
+const FUSE_SUPER_MAGIC: i64 = 0x6573_5546;
+const SDCARDFS_SUPER_MAGIC: i64 = 0x5dca_2df5;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Export {
+    path: PathBuf,
+    created: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+pub fn is_shared_storage(path: &Path) -> bool {
+    if SHARED_PREFIXES.iter().any(|p| path.starts_with(p)) {
+        return true;
+    }
+
+    // Catches bind mounts and symlinks into emulated storage the prefixes miss.
+    path.ancestors()
+        .find_map(|p| rustix::fs::statfs(p).ok())
+        .is_some_and(|s| {
+            // f_type is narrower on 32-bit targets.
+            #[allow(clippy::unnecessary_cast)]
+            let magic = s.f_type as i64;
+            magic == FUSE_SUPER_MAGIC || magic == SDCARDFS_SUPER_MAGIC
+        })
+}
+
+// Relative output paths land in the managed directory rather than wherever the shell happens to be.
+pub fn output_path(config: &ArtifactsConfig, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        config.dir.join(path)
+    }
+}
+
+// Kept with the artifacts it describes, so it goes wherever `dir` is moved.
+fn index_path(config: &ArtifactsConfig) -> PathBuf {
+    config.dir.join(defs::EXPORTS_INDEX_NAME)
+}
+
+fn load_index(config: &ArtifactsConfig) -> Vec<Export> {
+    fs::read_to_string(index_path(config))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+fn save_index(config: &ArtifactsConfig, exports: &[Export]) -> Result<()> {
+    let mut content = String::new();
+    for export in exports {
+        content.push_str(&serde_json::to_string(export)?);
+        content.push('\n');
+    }
+    utils::ensure_dir_exists(&config.dir)?;
+    utils::atomic_write(index_path(config), content)
+}
+
+// Every export is remembered for `retention_days` cleanup, which touches nothing else. Anything
+// written to emulated storage is also flagged, since every app holding the storage permission
+// can read it.
+pub fn record(config: &ArtifactsConfig, path: &Path) {
+    if is_shared_storage(path) {
+        log::warn!(
+            "{} is on shared storage, visible to all apps",
+            path.display()
+        );
+        eprintln!(
+            "warning: {} is on shared storage and readable by other apps",
+            path.display()
+        );
+    }
+
+    let mut exports = load_index(config);
+    exports.retain(|e| e.path != path);
+    exports.push(Export {
+        path: path.to_path_buf(),
+        created: now(),
+    });
+    if let Err(e) = save_index(config, &exports) {
+        log::warn!("Failed to record export {}: {:#}", path.display(), e);
+    }
+}
+
+fn remove(path: &Path) -> bool {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Ok(()) => true,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+        Err(e) => {
+            log::warn!(
+                "Failed to remove expired artifact {}: {}",
+                path.display(),
+                e
+            );
+            false
+        }
+    }
+}
+
+// Returns how many artifacts were deleted. Only files we exported are candidates; whatever else
+// lives in `dir` is left alone.
+pub fn prune(config: &ArtifactsConfig) -> Result<usize> {
+    if config.retention_days == 0 {
+        return Ok(0);
+    }
+    let cutoff = now().saturating_sub(config.retention_days * 24 * 60 * 60);
+    let exports = load_index(config);
+    if exports.is_empty() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    let mut kept = Vec::new();
+    for export in exports {
+        if export.created >= cutoff {
+            kept.push(export);
+        } else if remove(&export.path) {
+            removed += 1;
+        }
+    }
+    save_index(config, &kept)?;
+    Ok(removed)
+}
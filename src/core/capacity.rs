@@ -0,0 +1,165 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use rustix::fs::statvfs;
+use serde::{Deserialize, Serialize};
+
+use crate::{conf::config::Config, core::notify, defs, utils};
+
+const HISTORY_LEN: usize = 30;
+// Only the recent boots set the growth rate; older ones saw a different set of modules.
+const TREND_WINDOW: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub total: u64,
+    pub free: u64,
+    pub inodes_total: u64,
+    pub inodes_free: u64,
+}
+
+impl Usage {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    pub fn inodes_used(&self) -> u64 {
+        self.inodes_total.saturating_sub(self.inodes_free)
+    }
+}
+
+// One boot after the sync. The image is rebuilt to fit the module content every boot, so what it
+// uses is the content and what bounds it is the free space of /data it is rebuilt on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootSample {
+    pub timestamp: u64,
+    pub modules: Usage,
+    pub data: Usage,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Projection {
+    pub boots: usize,
+    pub bytes_per_boot: i64,
+    pub inodes_per_boot: i64,
+    // None while usage is flat or shrinking.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boots_left: Option<u64>,
+}
+
+pub fn measure(mount_point: &Path) -> Option<Usage> {
+    let s = statvfs(mount_point).ok()?;
+    Some(Usage {
+        total: s.f_blocks * s.f_frsize,
+        free: s.f_bavail * s.f_frsize,
+        inodes_total: s.f_files,
+        inodes_free: s.f_favail,
+    })
+}
+
+pub fn history() -> Vec<BootSample> {
+    fs::read_to_string(defs::STORAGE_HISTORY_FILE)
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn record(sample: BootSample) -> Result<Vec<BootSample>> {
+    let mut samples = history();
+    samples.push(sample);
+    let excess = samples.len().saturating_sub(HISTORY_LEN);
+    samples.drain(..excess);
+
+    let mut content = String::new();
+    for sample in &samples {
+        content.push_str(&serde_json::to_string(sample)?);
+        content.push('\n');
+    }
+    utils::atomic_write(defs::STORAGE_HISTORY_FILE, content)?;
+    Ok(samples)
+}
+
+// Growth is read off the module content, and the boots left off what /data still has for it.
+pub fn project(samples: &[BootSample]) -> Option<Projection> {
+    let window = &samples[samples.len().saturating_sub(TREND_WINDOW)..];
+    let (first, last) = (window.first()?, window.last()?);
+    let boots = window.len() - 1;
+    if boots == 0 {
+        return None;
+    }
+
+    let rate = |from: u64, to: u64| (to as i64 - from as i64) / boots as i64;
+    let bytes_per_boot = rate(first.modules.used(), last.modules.used());
+    let inodes_per_boot = rate(first.modules.inodes_used(), last.modules.inodes_used());
+    let left = |free: u64, per_boot: i64| (per_boot > 0).then(|| free / per_boot as u64);
+
+    Some(Projection {
+        boots,
+        bytes_per_boot,
+        inodes_per_boot,
+        boots_left: match (
+            left(last.data.free, bytes_per_boot),
+            left(last.data.inodes_free, inodes_per_boot),
+        ) {
+            (Some(bytes), Some(inodes)) => Some(bytes.min(inodes)),
+            (bytes, inodes) => bytes.or(inodes),
+        },
+    })
+}
+
+pub fn warning(config: &Config, projection: &Projection) -> Option<String> {
+    let boots_left = projection.boots_left?;
+    (config.space_warn_boots > 0 && boots_left <= config.space_warn_boots as u64).then(|| {
+        format!(
+            "Modules image outgrows the free space on /data in about {} boot(s) at the current \
+             growth of {} bytes and {} inodes per boot",
+            boots_left, projection.bytes_per_boot, projection.inodes_per_boot
+        )
+    })
+}
+
+pub fn track(config: &Config, mount_point: &Path) {
+    let (Some(modules), Some(data)) = (measure(mount_point), measure(Path::new(defs::BASE_DIR)))
+    else {
+        return;
+    };
+    let sample = BootSample {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        modules,
+        data,
+    };
+    let samples = match record(sample) {
+        Ok(samples) => samples,
+        Err(e) => {
+            log::warn!("Failed to record storage usage: {:#}", e);
+            return;
+        }
+    };
+
+    let Some(projection) = project(&samples) else {
+        return;
+    };
+    if let Some(message) = warning(config, &projection) {
+        log::warn!("{}", message);
+        notify::queue(
+            &config.notify,
+            "storage_trend",
+            &message,
+            serde_json::json!({ "projection": projection }),
+        );
+    }
+}
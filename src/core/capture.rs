@@ -0,0 +1,252 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use flate2::{Compression, write::GzEncoder};
+use log::LevelFilter;
+use serde::Serialize;
+
+use crate::{
+    core::state::{KvStore, MountRecord, RuntimeState},
+    defs,
+    sys::mount::is_mounted,
+    utils::{self, tar},
+};
+
+pub const LOG_LEVEL: LevelFilter = LevelFilter::Trace;
+
+const LOG_FILE: &str = "boot.log";
+const MOUNTINFO_BEFORE_FILE: &str = "mountinfo.before";
+const MOUNTINFO_AFTER_FILE: &str = "mountinfo.after";
+const JOURNAL_FILE: &str = "mount_journal.txt";
+const AUDIT_FILE: &str = "audit.json";
+const STATE_COPY_FILE: &str = "state.json";
+const MAX_KEPT_CAPTURES: usize = 3;
+pub const ARMED_KEY: &str = "capture_next_boot";
+
+#[derive(Debug, Serialize)]
+struct Audit {
+    recorded: usize,
+    missing: Vec<MountRecord>,
+    failures: usize,
+    unexpected: Vec<String>,
+}
+
+pub fn arm() -> Result<()> {
+    KvStore::set(ARMED_KEY, &true).context("Failed to arm boot capture")
+}
+
+pub fn disarm() -> Result<bool> {
+    let previous = KvStore::remove(ARMED_KEY).context("Failed to disarm boot capture")?;
+    Ok(previous.is_some())
+}
+
+pub fn is_armed() -> bool {
+    KvStore::get::<bool>(ARMED_KEY).unwrap_or(false)
+}
+
+fn mountinfo() -> String {
+    fs::read_to_string("/proc/self/mountinfo").unwrap_or_default()
+}
+
+fn mount_points(mountinfo: &str) -> HashSet<&str> {
+    mountinfo
+        .lines()
+        .filter_map(|l| l.split_whitespace().nth(4))
+        .collect()
+}
+
+fn prune_old_captures() {
+    let Ok(entries) = fs::read_dir(defs::CAPTURES_DIR) else {
+        return;
+    };
+
+    let mut bundles: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    bundles.sort();
+
+    let excess = bundles.len().saturating_sub(MAX_KEPT_CAPTURES);
+    for bundle in bundles.into_iter().take(excess) {
+        let result = if bundle.is_dir() {
+            fs::remove_dir_all(&bundle)
+        } else {
+            fs::remove_file(&bundle)
+        };
+        if let Err(e) = result {
+            log::debug!("Failed to prune capture {}: {}", bundle.display(), e);
+        }
+    }
+}
+
+fn write_bundle(dir: &Path, bundle: &Path) -> Result<()> {
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut archive = tar::Writer::new(
+        GzEncoder::new(File::create(bundle)?, Compression::default()),
+        mtime,
+    );
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+
+    // Bundles get shared publicly, so text files go through the same redaction as the logs.
+    for file in files {
+        let data = fs::read(&file)?;
+        let data = match String::from_utf8(data) {
+            Ok(text) => utils::redact(&text).into_owned().into_bytes(),
+            Err(e) => e.into_bytes(),
+        };
+        let entry = format!(
+            "{}/{}",
+            name,
+            file.file_name().unwrap_or_default().to_string_lossy()
+        );
+        archive.append_data(&entry, 0o644, &data)?;
+    }
+    archive.finish()?.finish()?;
+
+    Ok(())
+}
+
+pub struct Capture {
+    dir: PathBuf,
+    mountinfo_before: String,
+}
+
+impl Capture {
+    // Consumes the marker up front so a crash mid-boot can't leave verbose mode on.
+    pub fn begin() -> Option<Self> {
+        if !disarm().unwrap_or(false) {
+            return None;
+        }
+
+        prune_old_captures();
+
+        let dir = Path::new(defs::CAPTURES_DIR).join(
+            chrono::Local::now()
+                .format("capture-%Y%m%d-%H%M%S")
+                .to_string(),
+        );
+        if let Err(e) = utils::ensure_dir_exists(&dir) {
+            eprintln!(
+                "Failed to create capture directory {}: {}",
+                dir.display(),
+                e
+            );
+            return None;
+        }
+
+        let mountinfo_before = mountinfo();
+        let _ = fs::write(dir.join(MOUNTINFO_BEFORE_FILE), &mountinfo_before);
+
+        Some(Self {
+            dir,
+            mountinfo_before,
+        })
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        self.dir.join(LOG_FILE)
+    }
+
+    fn write_journal(&self, mountinfo_after: &str) -> Result<()> {
+        let before = mount_points(&self.mountinfo_before);
+        let after = mount_points(mountinfo_after);
+
+        let mut journal = String::new();
+        for line in mountinfo_after.lines() {
+            if let Some(point) = line.split_whitespace().nth(4)
+                && !before.contains(point)
+            {
+                journal.push_str(&format!("+ {}\n", line));
+            }
+        }
+        for line in self.mountinfo_before.lines() {
+            if let Some(point) = line.split_whitespace().nth(4)
+                && !after.contains(point)
+            {
+                journal.push_str(&format!("- {}\n", line));
+            }
+        }
+
+        fs::write(self.dir.join(JOURNAL_FILE), journal)?;
+        Ok(())
+    }
+
+    fn write_audit(&self, mountinfo_after: &str) -> Result<()> {
+        let state = RuntimeState::load().unwrap_or_default();
+        let before = mount_points(&self.mountinfo_before);
+        let after = mount_points(mountinfo_after);
+        let recorded: HashSet<&str> = state.mounts.iter().map(|r| r.target.as_str()).collect();
+
+        let audit = Audit {
+            recorded: state.mounts.len(),
+            missing: state
+                .mounts
+                .iter()
+                .filter(|r| !is_mounted(&r.target))
+                .cloned()
+                .collect(),
+            failures: state.mount_failures.len(),
+            unexpected: after
+                .difference(&before)
+                .filter(|p| !recorded.iter().any(|r| Path::new(p).starts_with(r)))
+                .map(|p| p.to_string())
+                .collect(),
+        };
+
+        fs::write(
+            self.dir.join(AUDIT_FILE),
+            serde_json::to_string_pretty(&audit)?,
+        )?;
+        if let Ok(state) = fs::read(defs::STATE_FILE) {
+            fs::write(self.dir.join(STATE_COPY_FILE), state)?;
+        }
+
+        log::info!(
+            "Capture audit: {} recorded mount(s), {} missing, {} unexpected",
+            audit.recorded,
+            audit.missing.len(),
+            audit.unexpected.len()
+        );
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<PathBuf> {
+        let mountinfo_after = mountinfo();
+        fs::write(self.dir.join(MOUNTINFO_AFTER_FILE), &mountinfo_after)?;
+
+        if let Err(e) = self.write_journal(&mountinfo_after) {
+            log::warn!("Capture: failed to write mount journal: {:#}", e);
+        }
+        if let Err(e) = self.write_audit(&mountinfo_after) {
+            log::warn!("Capture: failed to run post-mount audit: {:#}", e);
+        }
+
+        log::logger().flush();
+
+        let bundle = self.dir.with_extension("tar.gz");
+        write_bundle(&self.dir, &bundle)
+            .with_context(|| format!("Failed to bundle capture {}", self.dir.display()))?;
+        fs::remove_dir_all(&self.dir)?;
+
+        Ok(bundle)
+    }
+}
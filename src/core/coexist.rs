@@ -0,0 +1,224 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use procfs::process::Process;
+use rustix::mount::{UnmountFlags, unmount};
+
+use crate::{
+    conf::config::{Coexistence, Config},
+    core::{
+        diagnostics::{DiagnosticIssue, DiagnosticLevel, IssueCode},
+        inventory::Module,
+        state::RuntimeState,
+    },
+    defs,
+};
+
+const MAGISK_DIR: &str = "/data/adb/magisk";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mounter {
+    Ksud,
+    Magisk,
+}
+
+impl fmt::Display for Mounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ksud => "ksud",
+            Self::Magisk => "Magisk",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ForeignMount {
+    pub target: PathBuf,
+    pub modules: BTreeSet<String>,
+    pub by: Mounter,
+}
+
+fn module_ids_in(text: &str, marker: &str, ids: &mut BTreeSet<String>) {
+    for (idx, _) in text.match_indices(marker) {
+        let rest = &text[idx + marker.len()..];
+        let id = rest.split(['/', ':', ',']).next().unwrap_or_default();
+        if !id.is_empty() {
+            ids.insert(id.to_string());
+        }
+    }
+}
+
+// Mounts that still point into the stock module directory were made by ksud or Magisk, not by
+// us: our layers always come from the staged copy. ksud stacks overlays and labels its tmpfs
+// "KSU"; Magisk's magic mount binds files straight from the module and labels its tmpfs "magisk".
+// Our own mounts from this boot, and anything carrying our mount source, are never counted.
+pub fn detect(config: &Config) -> Vec<ForeignMount> {
+    let Ok(mountinfo) = Process::myself().and_then(|p| p.mountinfo()) else {
+        return Vec::new();
+    };
+    let own: BTreeSet<PathBuf> = RuntimeState::load()
+        .ok()
+        .filter(|s| s.is_this_boot())
+        .map(|s| s.mounts.iter().map(|m| PathBuf::from(&m.target)).collect())
+        .unwrap_or_default();
+
+    let modules_dir = format!("{}/", defs::MODULES_DIR.trim_end_matches('/'));
+    let modules_root = modules_dir.strip_prefix("/data").unwrap_or(&modules_dir);
+    let partitions = config.effective_partitions();
+    let magisk_installed = Path::new(MAGISK_DIR).is_dir();
+
+    let mut mounts = Vec::new();
+    for m in mountinfo {
+        let on_partition = partitions
+            .iter()
+            .any(|p| m.mount_point.starts_with(PathBuf::from("/").join(p)));
+        if !on_partition || own.contains(&m.mount_point) {
+            continue;
+        }
+        let label = m
+            .mount_source
+            .as_deref()
+            .filter(|s| *s != config.mountsource);
+
+        let mut ids = BTreeSet::new();
+        let by = match (m.fs_type.as_str(), label) {
+            ("overlay", _) => {
+                for value in m.super_options.values().flatten() {
+                    module_ids_in(value, &modules_dir, &mut ids);
+                }
+                Mounter::Ksud
+            }
+            ("tmpfs", Some("KSU")) => Mounter::Ksud,
+            ("tmpfs", Some("magisk" | "worker")) => Mounter::Magisk,
+            _ => {
+                module_ids_in(&m.root, modules_root, &mut ids);
+                if magisk_installed {
+                    Mounter::Magisk
+                } else {
+                    Mounter::Ksud
+                }
+            }
+        };
+
+        let labelled_tmpfs =
+            m.fs_type == "tmpfs" && matches!(label, Some("KSU" | "magisk" | "worker"));
+        if !ids.is_empty() || labelled_tmpfs {
+            mounts.push(ForeignMount {
+                target: m.mount_point,
+                modules: ids,
+                by,
+            });
+        }
+    }
+
+    mounts
+}
+
+fn mounters(mounts: &[ForeignMount]) -> String {
+    let by: BTreeSet<String> = mounts.iter().map(|m| m.by.to_string()).collect();
+    by.into_iter().collect::<Vec<_>>().join(" and ")
+}
+
+fn to_issue(m: &ForeignMount, level: DiagnosticLevel) -> DiagnosticIssue {
+    let message = if m.modules.is_empty() {
+        format!("{} has a mount on {}", m.by, m.target.display())
+    } else {
+        format!(
+            "{} has mounted {} for {}",
+            m.by,
+            m.target.display(),
+            m.modules.iter().cloned().collect::<Vec<_>>().join(", ")
+        )
+    };
+    DiagnosticIssue {
+        code: IssueCode::ForeignMount,
+        level,
+        context: m.by.to_string(),
+        message,
+        path: Some(m.target.to_string_lossy().to_string()),
+    }
+}
+
+// Skip and takeover resolve the overlap at boot, so only the other two policies are reported.
+pub fn diagnostics(config: &Config) -> Vec<DiagnosticIssue> {
+    let level = match config.coexistence {
+        Coexistence::Abort => DiagnosticLevel::Critical,
+        Coexistence::Off => DiagnosticLevel::Warning,
+        Coexistence::Skip | Coexistence::Takeover => return Vec::new(),
+    };
+    detect(config).iter().map(|m| to_issue(m, level)).collect()
+}
+
+pub fn apply(config: &Config, modules: &mut Vec<Module>) -> Result<()> {
+    if config.coexistence == Coexistence::Off {
+        return Ok(());
+    }
+
+    let mounts = detect(config);
+    if mounts.is_empty() {
+        return Ok(());
+    }
+
+    let handled: BTreeSet<&String> = mounts.iter().flat_map(|m| m.modules.iter()).collect();
+    log::warn!(
+        ">> {} has already mounted {} target(s) for {} module(s)",
+        mounters(&mounts),
+        mounts.len(),
+        handled.len()
+    );
+
+    match config.coexistence {
+        Coexistence::Skip => {
+            modules.retain(|m| {
+                let keep = !handled.contains(&m.id);
+                if !keep {
+                    log::info!("Coexist: leaving {} to the other mounter", m.id);
+                }
+                keep
+            });
+        }
+        Coexistence::Takeover => {
+            // Later mounts sit on top of earlier ones, so peel them off in reverse.
+            for mount in mounts.iter().rev() {
+                match unmount(&mount.target, UnmountFlags::DETACH) {
+                    Ok(()) => log::info!(
+                        "Coexist: detached {} mount {}",
+                        mount.by,
+                        mount.target.display()
+                    ),
+                    Err(e) => log::warn!(
+                        "Coexist: failed to detach {}: {}",
+                        mount.target.display(),
+                        e
+                    ),
+                }
+            }
+        }
+        Coexistence::Abort => {
+            for issue in mounts
+                .iter()
+                .map(|m| to_issue(m, DiagnosticLevel::Critical))
+            {
+                log::error!(
+                    "Preflight {} [{}]: {}",
+                    issue.code.as_str(),
+                    issue.context,
+                    issue.message
+                );
+            }
+            bail!(
+                "{} is already mounting modules; refusing to double-mount",
+                mounters(&mounts)
+            );
+        }
+        Coexistence::Off => {}
+    }
+    Ok(())
+}
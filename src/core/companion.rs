@@ -0,0 +1,240 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::Config,
+    core::{inventory::Module, ops::manifest},
+    defs, utils,
+};
+
+const SHARED_ROOT: &str = "/data/adb";
+
+// A file a module placed in a shared directory. `backup` holds whatever it replaced, which comes
+// back once the module lets go of the path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanionFile {
+    pub module: String,
+    pub path: PathBuf,
+    pub hash: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+}
+
+pub fn tracked() -> Vec<CompanionFile> {
+    fs::read_to_string(defs::COMPANIONS_FILE)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save(files: &[CompanionFile]) -> Result<()> {
+    utils::atomic_write(defs::COMPANIONS_FILE, serde_json::to_string_pretty(files)?)
+}
+
+fn is_plain(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::RootDir | Component::Normal(_)))
+}
+
+// Only directories other tools own under /data/adb qualify; module trees and our own state never do.
+fn allowed_dirs(config: &Config) -> Vec<&Path> {
+    let reserved = [
+        Path::new(defs::MODULES_DIR),
+        Path::new("/data/adb/modules_update"),
+        Path::new(defs::BASE_DIR),
+    ];
+    config
+        .companion_paths
+        .iter()
+        .map(PathBuf::as_path)
+        .filter(|dir| {
+            let ok = is_plain(dir)
+                && dir.starts_with(SHARED_ROOT)
+                && *dir != Path::new(SHARED_ROOT)
+                && !reserved
+                    .iter()
+                    .any(|r| dir.starts_with(r) || r.starts_with(dir));
+            if !ok {
+                log::warn!("Ignoring companion path {}", dir.display());
+            }
+            ok
+        })
+        .collect()
+}
+
+fn resolve(
+    module: &Module,
+    dest: &str,
+    source: &str,
+    allowed: &[&Path],
+) -> Result<(PathBuf, PathBuf)> {
+    let dest = PathBuf::from(dest);
+    if !dest.is_absolute() || !is_plain(&dest) || !allowed.iter().any(|d| dest.starts_with(d)) {
+        bail!("{} is not under an allowed companion path", dest.display());
+    }
+    if fs::symlink_metadata(&dest).is_ok_and(|m| !m.is_file()) {
+        bail!("{} exists and is not a regular file", dest.display());
+    }
+    let rel = Path::new(source);
+    if rel.is_absolute() || !is_plain(rel) {
+        bail!("{} must be a path inside the module", source);
+    }
+    let source = module.source_path.join(rel);
+    if !fs::symlink_metadata(&source).is_ok_and(|m| m.is_file()) {
+        bail!("{} is not a regular file", source.display());
+    }
+    Ok((dest, source))
+}
+
+// The file is only removed while it still holds what the module put there.
+fn release(file: &CompanionFile) {
+    let ours = manifest::hash_file(&file.path).is_ok_and(|h| h == file.hash);
+    if ours {
+        if let Err(e) = fs::remove_file(&file.path) {
+            log::warn!("Companion: failed to remove {}: {}", file.path.display(), e);
+            return;
+        }
+    } else if file.path.exists() {
+        log::warn!(
+            "Companion: {} was changed since {} placed it, leaving it",
+            file.path.display(),
+            file.module
+        );
+    }
+
+    if let Some(backup) = &file.backup {
+        let restored = if file.path.exists() {
+            fs::remove_file(backup)
+        } else {
+            fs::rename(backup, &file.path)
+        };
+        if let Err(e) = restored {
+            log::warn!(
+                "Companion: failed to restore {}: {}",
+                file.path.display(),
+                e
+            );
+        }
+    }
+    log::info!(
+        "Companion: released {} from {}",
+        file.path.display(),
+        file.module
+    );
+}
+
+fn place(module: &str, dest: &Path, source: &Path, hash: u32) -> Result<Option<PathBuf>> {
+    let backup = if dest.exists() {
+        let backup = Path::new(defs::COMPANION_BACKUP_DIR)
+            .join(module)
+            .join(dest.strip_prefix("/")?);
+        if let Some(parent) = backup.parent() {
+            utils::ensure_dir_exists(parent)?;
+        }
+        utils::copy_entry(dest, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    copy_into(dest, source)?;
+    log::info!(
+        "Companion: {} placed {} ({:08x})",
+        module,
+        dest.display(),
+        hash
+    );
+    Ok(backup)
+}
+
+// The copy takes the label of the directory it lands in, not the module's.
+fn copy_into(dest: &Path, source: &Path) -> Result<()> {
+    let parent = dest.parent().unwrap_or(Path::new("/"));
+    utils::ensure_dir_exists(parent)?;
+    utils::copy_entry(source, dest)?;
+    if let Ok(context) = utils::lgetfilecon(parent) {
+        let _ = utils::lsetfilecon(dest, &context);
+    }
+    Ok(())
+}
+
+// Brings shared directories in line with the enabled modules: files of modules that are gone or
+// no longer ask for a path are released, new ones are placed and changed ones refreshed.
+pub fn apply(config: &Config, modules: &[Module]) -> Result<Vec<CompanionFile>> {
+    let allowed = allowed_dirs(config);
+    let mut wanted = Vec::new();
+    for module in modules {
+        for (dest, source) in &module.rules.companions {
+            match resolve(module, dest, source, &allowed) {
+                Ok((dest, source)) => wanted.push((module.id.as_str(), dest, source)),
+                Err(e) => log::warn!("Companion: {} skipped: {:#}", module.id, e),
+            }
+        }
+    }
+
+    let mut files = tracked();
+    let keep: HashSet<(&str, &Path)> = wanted
+        .iter()
+        .map(|(id, dest, _)| (*id, dest.as_path()))
+        .collect();
+    files.retain(|f| {
+        let kept = keep.contains(&(f.module.as_str(), f.path.as_path()));
+        if !kept {
+            release(f);
+        }
+        kept
+    });
+
+    for (id, dest, source) in &wanted {
+        let hash = match manifest::hash_file(source) {
+            Ok(hash) => hash,
+            Err(e) => {
+                log::warn!("Companion: failed to read {}: {}", source.display(), e);
+                continue;
+            }
+        };
+
+        match files.iter_mut().find(|f| f.path == *dest) {
+            Some(f) if f.module != *id => log::warn!(
+                "Companion: {} wants {}, already provided by {}",
+                id,
+                dest.display(),
+                f.module
+            ),
+            Some(f) => {
+                if manifest::hash_file(dest).is_ok_and(|h| h == hash) {
+                    continue;
+                }
+                match copy_into(dest, source) {
+                    Ok(()) => f.hash = hash,
+                    Err(e) => {
+                        log::warn!("Companion: failed to refresh {}: {:#}", dest.display(), e)
+                    }
+                }
+            }
+            None => match place(id, dest, source, hash) {
+                Ok(backup) => files.push(CompanionFile {
+                    module: id.to_string(),
+                    path: dest.clone(),
+                    hash,
+                    backup,
+                }),
+                Err(e) => log::warn!("Companion: failed to place {}: {:#}", dest.display(), e),
+            },
+        }
+    }
+
+    if !files.is_empty() || Path::new(defs::COMPANIONS_FILE).exists() {
+        save(&files)?;
+    }
+    Ok(files)
+}
@@ -0,0 +1,123 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+
+use crate::{
+    conf::config::Config,
+    core::{inventory, ops::planner},
+    defs,
+};
+
+// Stock ksud only looks at <module>/system and has used magic mount since KernelSU 1.0.
+const KSUD_MECHANISM: &str = "magic";
+const KSUD_PARTITION: &str = "system";
+
+#[derive(Debug, Serialize)]
+pub struct ModuleComparison {
+    pub id: String,
+    pub ksud: String,
+    pub hybrid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompatReport {
+    pub ksud_mechanism: &'static str,
+    pub modules: Vec<ModuleComparison>,
+    pub differences: usize,
+}
+
+pub fn is_ksud_present() -> bool {
+    Path::new(defs::KSUD_PATH).exists() || ksu::version().is_some()
+}
+
+fn ksud_handling(module_dir: &Path) -> &'static str {
+    if module_dir.join(defs::DISABLE_FILE_NAME).exists()
+        || module_dir.join(defs::REMOVE_FILE_NAME).exists()
+        || module_dir.join(defs::SKIP_MOUNT_FILE_NAME).exists()
+        || !module_dir.join(KSUD_PARTITION).is_dir()
+    {
+        "skipped"
+    } else {
+        KSUD_MECHANISM
+    }
+}
+
+fn reason(ksud: &str, hybrid: &str) -> Option<String> {
+    let reason = match (ksud, hybrid) {
+        _ if ksud == hybrid => return None,
+        ("absent", _) => format!("Not in {}; ksud never sees it", defs::MODULES_DIR),
+        ("skipped", _) => "ksud skips modules without a system directory".to_string(),
+        (_, "skipped") => "Nothing to mount for the configured partitions".to_string(),
+        _ => "Mounted with a different mechanism".to_string(),
+    };
+    Some(reason)
+}
+
+pub fn generate(config: &Config) -> Result<CompatReport> {
+    if !is_ksud_present() {
+        bail!("ksud not detected; nothing to compare against");
+    }
+
+    let mut entries: BTreeMap<String, (String, String)> = BTreeMap::new();
+
+    if let Ok(dir) = fs::read_dir(defs::MODULES_DIR) {
+        for entry in dir.flatten() {
+            let path = entry.path();
+            let id = entry.file_name().to_string_lossy().to_string();
+            if !path.is_dir() || id == "meta-hybrid" || id == "lost+found" {
+                continue;
+            }
+            entries.insert(
+                id,
+                (ksud_handling(&path).to_string(), "skipped".to_string()),
+            );
+        }
+    }
+
+    let modules = inventory::scan(&config.moduledir, config).context("Failed to scan modules")?;
+    let plan = planner::generate(config, &modules, config.primary_moduledir())
+        .context("Failed to generate mount plan")?;
+
+    for module in &modules {
+        let overlay = plan.overlay_module_ids.contains(&module.id);
+        let magic = plan.magic_module_ids.contains(&module.id);
+        let rootdir = plan.rootdir_ops.iter().any(|op| op.module_id == module.id);
+
+        let hybrid = match (overlay, magic) {
+            (true, true) => "overlay+magic",
+            (true, false) => "overlay",
+            (false, true) => "magic",
+            (false, false) if rootdir => "rootdir",
+            (false, false) => "skipped",
+        };
+
+        // Managed modules and extra module directories are invisible to ksud.
+        entries
+            .entry(module.id.clone())
+            .or_insert_with(|| ("absent".to_string(), String::new()))
+            .1 = hybrid.to_string();
+    }
+
+    let modules: Vec<ModuleComparison> = entries
+        .into_iter()
+        .map(|(id, (ksud, hybrid))| ModuleComparison {
+            reason: reason(&ksud, &hybrid),
+            id,
+            ksud,
+            hybrid,
+        })
+        .collect();
+    let differences = modules.iter().filter(|m| m.reason.is_some()).count();
+
+    Ok(CompatReport {
+        ksud_mechanism: KSUD_MECHANISM,
+        modules,
+        differences,
+    })
+}
@@ -0,0 +1,221 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    conf::config::Config,
+    core::{
+        denylist, inventory::files, live, maintenance, profile, state::RuntimeState, visibility,
+    },
+    defs, utils,
+};
+
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum Request {
+    Ping,
+    Status,
+    Toggle {
+        module: String,
+        enabled: bool,
+    },
+    Remount {
+        module: String,
+    },
+    Unmount {
+        module: String,
+    },
+    Reload,
+    Files {
+        module: String,
+        #[serde(default)]
+        path: String,
+        #[serde(default)]
+        offset: usize,
+        #[serde(default = "default_page_size")]
+        limit: usize,
+    },
+}
+
+fn default_page_size() -> usize {
+    files::DEFAULT_PAGE_SIZE
+}
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<Result<Value>> for Response {
+    fn from(result: Result<Value>) -> Self {
+        match result {
+            Ok(data) => Self {
+                ok: true,
+                data: Some(data),
+                error: None,
+            },
+            Err(e) => Self {
+                ok: false,
+                data: None,
+                error: Some(format!("{:#}", e)),
+            },
+        }
+    }
+}
+
+fn find_module_dir(config: &Config, id: &str) -> Result<PathBuf> {
+    utils::validate_module_id(id)?;
+
+    config
+        .moduledir
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(std::iter::once(Path::new(defs::MANAGED_MODULES_DIR)))
+        .map(|dir| dir.join(id))
+        .find(|path| path.is_dir())
+        .with_context(|| format!("Module {} not found", id))
+}
+
+fn toggle_module(config: &Config, id: &str, enabled: bool) -> Result<Value> {
+    let marker = find_module_dir(config, id)?.join(defs::DISABLE_FILE_NAME);
+
+    if enabled {
+        if marker.exists() {
+            fs::remove_file(&marker).with_context(|| format!("Failed to enable module {}", id))?;
+        }
+    } else {
+        fs::write(&marker, "").with_context(|| format!("Failed to disable module {}", id))?;
+    }
+
+    Ok(serde_json::json!({ "module": id, "enabled": enabled }))
+}
+
+fn dispatch(config: &mut Config, request: Request) -> Result<Value> {
+    match request {
+        Request::Ping => Ok(Value::String("pong".to_string())),
+        Request::Status => Ok(serde_json::to_value(RuntimeState::load()?)?),
+        Request::Toggle { module, enabled } => toggle_module(config, &module, enabled),
+        Request::Remount { module } => {
+            let targets = live::remount_module(config, &module)?;
+            Ok(serde_json::json!({ "module": module, "targets": targets }))
+        }
+        Request::Unmount { module } => {
+            let targets = live::unmount_module(config, &module)?;
+            Ok(serde_json::json!({ "module": module, "targets": targets }))
+        }
+        Request::Reload => {
+            profile::apply_live(config, Config::load_default()?);
+            Ok(Value::String("reloaded".to_string()))
+        }
+        Request::Files {
+            module,
+            path,
+            offset,
+            limit,
+        } => Ok(serde_json::to_value(files::list(
+            config, &module, &path, offset, limit,
+        )?)?),
+    }
+}
+
+fn handle_client(config: &mut Config, stream: UnixStream) -> Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let response: Response = match serde_json::from_str::<Request>(line.trim()) {
+        Ok(request) => {
+            log::debug!("Control request: {:?}", request);
+            dispatch(config, request).into()
+        }
+        Err(e) => Err(anyhow::Error::from(e).context("Invalid request")).into(),
+    };
+
+    let mut writer = &stream;
+    writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+    Ok(())
+}
+
+pub fn serve(config: &Config) -> Result<()> {
+    let socket = Path::new(defs::CTL_SOCKET);
+
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            bail!(
+                "Another daemon is already listening on {}",
+                socket.display()
+            );
+        }
+        fs::remove_file(socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("Failed to bind control socket {}", socket.display()))?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+
+    log::info!(">> Control daemon listening on {}", socket.display());
+
+    // The watcher only backs up try_umount for listed apps; it never stands in for it.
+    if config.denylist_umount && !config.disable_umount {
+        thread::spawn(denylist::watch);
+    }
+    if !config.visibility.is_empty() {
+        let config = config.clone();
+        thread::spawn(move || visibility::watch(config));
+    }
+
+    if let Some(settings) = config.maintenance.clone().filter(|m| m.enabled) {
+        let config = config.clone();
+        thread::spawn(move || maintenance::watch(config, settings));
+    }
+
+    // Reload swaps in settings that do not affect mounts; the rest waits for the next boot.
+    let mut config = config.clone();
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle_client(&mut config, stream) {
+                    log::warn!("Control client error: {:#}", e);
+                }
+            }
+            Err(e) => log::warn!("Failed to accept control client: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn request(payload: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(defs::CTL_SOCKET)
+        .with_context(|| format!("Daemon is not running ({})", defs::CTL_SOCKET))?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+
+    writeln!(stream, "{}", payload.trim())?;
+
+    let mut response = String::new();
+    BufReader::new(&stream).read_line(&mut response)?;
+    Ok(response.trim_end().to_string())
+}
@@ -0,0 +1,127 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use walkdir::WalkDir;
+
+use crate::{conf::config::Config, defs, utils};
+
+pub const MANAGED_MODULE_ID: &str = "meta-hybrid.debloat";
+
+fn module_dir() -> PathBuf {
+    Path::new(defs::MANAGED_MODULES_DIR).join(MANAGED_MODULE_ID)
+}
+
+fn relative_target(config: &Config, target: &str) -> Result<PathBuf> {
+    let path = Path::new(target);
+    if !path.is_absolute() {
+        bail!("Debloat path must be absolute: {}", target);
+    }
+
+    let rel: PathBuf = path
+        .components()
+        .filter(|c| !matches!(c, Component::RootDir | Component::CurDir))
+        .map(|c| match c {
+            Component::Normal(name) => Ok(name),
+            _ => bail!("Debloat path must not contain '..': {}", target),
+        })
+        .collect::<Result<_>>()?;
+
+    let partition = rel
+        .iter()
+        .next()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if rel.iter().count() < 2 || !config.effective_partitions().contains(&partition) {
+        bail!(
+            "{} is not inside a managed partition ({})",
+            target,
+            config.effective_partitions().join(", ")
+        );
+    }
+
+    Ok(rel)
+}
+
+fn write_module_prop(dir: &Path) -> Result<()> {
+    utils::atomic_write(
+        dir.join("module.prop"),
+        format!(
+            "id={}\nname=Hybrid Mount debloat\nversion=1\nversionCode=1\n\
+             author=meta-hybrid\ndescription=Systemless removal of stock apps and files\n",
+            MANAGED_MODULE_ID
+        ),
+    )
+}
+
+pub fn add(config: &Config, target: &str) -> Result<PathBuf> {
+    let rel = relative_target(config, target)?;
+
+    if fs::symlink_metadata(Path::new("/").join(&rel)).is_err() {
+        bail!("{} does not exist on this device", target);
+    }
+
+    let dir = module_dir();
+    let whiteout = dir.join(&rel);
+    if utils::is_whiteout(&whiteout) {
+        return Ok(whiteout);
+    }
+    if fs::symlink_metadata(&whiteout).is_ok() {
+        bail!(
+            "{} already exists in the debloat module and is not a whiteout",
+            whiteout.display()
+        );
+    }
+
+    if let Some(parent) = whiteout.parent() {
+        utils::ensure_dir_exists(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    utils::create_whiteout(&whiteout)?;
+    write_module_prop(&dir)?;
+
+    Ok(whiteout)
+}
+
+pub fn list() -> Vec<String> {
+    let dir = module_dir();
+    let mut entries: Vec<String> = WalkDir::new(&dir)
+        .min_depth(2)
+        .into_iter()
+        .flatten()
+        .filter(|e| utils::is_whiteout(e.path()))
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(&dir)
+                .ok()
+                .map(|rel| Path::new("/").join(rel).to_string_lossy().to_string())
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+pub fn remove(config: &Config, target: &str) -> Result<bool> {
+    let rel = relative_target(config, target)?;
+    let dir = module_dir();
+    let whiteout = dir.join(&rel);
+
+    if !utils::is_whiteout(&whiteout) {
+        return Ok(false);
+    }
+
+    fs::remove_file(&whiteout)
+        .with_context(|| format!("Failed to remove {}", whiteout.display()))?;
+    utils::prune_empty_dirs(&dir)?;
+
+    if list().is_empty() {
+        fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {}", dir.display()))?;
+    }
+
+    Ok(true)
+}
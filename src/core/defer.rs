@@ -0,0 +1,135 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashSet},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::Config,
+    core::{
+        inventory::Module,
+        ops::scoped::{split, walk_files},
+        state::{KvStore, RuntimeState},
+    },
+};
+
+// Paths listed per deferred module; the count covers the rest.
+const SAMPLE_LEN: usize = 10;
+pub const DEFERRED_KEY: &str = "deferred_modules";
+
+// A module enabled since the last boot that was held back for taking over too many files the
+// mounted modules provide.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeferredModule {
+    pub module: String,
+    pub conflicts: usize,
+    pub paths: Vec<String>,
+}
+
+pub fn last() -> Vec<DeferredModule> {
+    KvStore::get(DEFERRED_KEY).unwrap_or_default()
+}
+
+fn save(deferred: &[DeferredModule]) {
+    if let Err(e) = KvStore::set(DEFERRED_KEY, &deferred) {
+        log::warn!("Failed to record deferred modules: {:#}", e);
+    }
+}
+
+// Files `module` would win over a mounted module's copy of, with no `resolve` pin deciding them.
+fn takeovers(
+    config: &Config,
+    module: &Module,
+    owners: &BTreeMap<String, Vec<(i32, &str)>>,
+) -> Vec<String> {
+    let partitions = config.effective_partitions();
+    let rank = (config.module_priority(&module.id), module.id.as_str());
+    walk_files(&module.source_path)
+        .into_iter()
+        .filter(|path| split(path, &partitions).is_some())
+        .filter(|path| !config.resolve.contains_key(&format!("/{}", path)))
+        .filter(|path| {
+            owners.get(path).is_some_and(|existing| {
+                existing
+                    .iter()
+                    .all(|&(priority, id)| Reverse(rank) < Reverse((priority, id)))
+            })
+        })
+        .collect()
+}
+
+// The first boot mounts everything: with nothing mounted before, nothing is being shadowed.
+fn decide(config: &Config, modules: &[Module]) -> Vec<DeferredModule> {
+    let limit = config.defer.max_conflicts;
+    let previous = RuntimeState::load().unwrap_or_default();
+    let mounted: HashSet<&str> = previous
+        .overlay_modules
+        .iter()
+        .chain(previous.magic_modules.iter())
+        .map(String::as_str)
+        .collect();
+    if limit == 0 || mounted.is_empty() {
+        return Vec::new();
+    }
+
+    let (existing, new): (Vec<&Module>, Vec<&Module>) = modules
+        .iter()
+        .partition(|m| mounted.contains(m.id.as_str()));
+    let new: Vec<&Module> = new
+        .into_iter()
+        .filter(|m| !config.defer.allow.contains(&m.id))
+        .collect();
+    if new.is_empty() {
+        return Vec::new();
+    }
+
+    let mut owners: BTreeMap<String, Vec<(i32, &str)>> = BTreeMap::new();
+    for module in &existing {
+        let priority = config.module_priority(&module.id);
+        for path in walk_files(&module.source_path) {
+            owners
+                .entry(path)
+                .or_default()
+                .push((priority, module.id.as_str()));
+        }
+    }
+
+    new.into_iter()
+        .filter_map(|module| {
+            let paths = takeovers(config, module, &owners);
+            (paths.len() > limit).then(|| DeferredModule {
+                module: module.id.clone(),
+                conflicts: paths.len(),
+                paths: paths.into_iter().take(SAMPLE_LEN).collect(),
+            })
+        })
+        .collect()
+}
+
+// Post-fs-data decides and records; the service stage follows that decision.
+pub fn apply(config: &Config, modules: &mut Vec<Module>, resumed: bool) {
+    let deferred = if resumed {
+        last()
+    } else {
+        let deferred = decide(config, modules);
+        save(&deferred);
+        deferred
+    };
+
+    if !resumed {
+        for d in &deferred {
+            log::warn!(
+                "Defer: {} would take over {} file(s) from mounted modules, leaving it unmounted; \
+                 resolve the conflicts or run `meta-hybrid conflicts accept {}`",
+                d.module,
+                d.conflicts,
+                d.module
+            );
+        }
+    }
+    modules.retain(|m| !deferred.iter().any(|d| d.module == m.id));
+}
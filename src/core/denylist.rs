@@ -0,0 +1,195 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use procfs::process::{Process, all_processes};
+
+use crate::{
+    core::stealth,
+    defs,
+    mount::umount_mgr,
+    sys::proc_events::{ProcEvent, ProcEvents},
+    utils,
+};
+
+// `magisk --denylist ls` prints `package|process` pairs.
+fn magisk_denylist() -> Vec<String> {
+    let Some(magisk) = utils::find_in_path("magisk") else {
+        return Vec::new();
+    };
+    let Ok(output) = Command::new(magisk).args(["--denylist", "ls"]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| l.split('|').next())
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+// User-maintained list, one package per line, for managers whose denylist cannot be read.
+fn local_denylist() -> Vec<String> {
+    fs::read_to_string(defs::DENYLIST_FILE)
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn sync() -> Result<Vec<String>> {
+    let packages: BTreeSet<String> = magisk_denylist()
+        .into_iter()
+        .chain(local_denylist())
+        .collect();
+    let packages: Vec<String> = packages.into_iter().collect();
+    utils::atomic_write(defs::DENYLIST_CACHE_FILE, serde_json::to_string(&packages)?)?;
+    Ok(packages)
+}
+
+fn load() -> HashSet<String> {
+    fs::read_to_string(defs::DENYLIST_CACHE_FILE)
+        .ok()
+        .and_then(|s| serde_json::from_str::<Vec<String>>(&s).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .collect()
+}
+
+// The packages our mounts must stay hidden from, read from the cache and synced on first use.
+pub fn packages() -> HashSet<String> {
+    if !Path::new(defs::DENYLIST_CACHE_FILE).exists()
+        && let Err(e) = sync()
+    {
+        log::warn!("Failed to read denylist: {:#}", e);
+    }
+    load()
+}
+
+// An app process names its package first in argv, with a `:name` suffix for its extra processes.
+pub fn package_of(process: &Process) -> Option<String> {
+    let cmdline = process.cmdline().ok()?;
+    let package = cmdline.first()?.split(':').next()?;
+    (!package.is_empty()).then(|| package.to_string())
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    Path::new(path).metadata().and_then(|m| m.modified()).ok()
+}
+
+fn mounted_in(pid: i32, targets: &HashSet<String>) -> Vec<String> {
+    let Ok(mountinfo) = Process::new(pid).and_then(|p| p.mountinfo()) else {
+        return Vec::new();
+    };
+    mountinfo
+        .into_iter()
+        .map(|m| m.mount_point.to_string_lossy().to_string())
+        .filter(|p| targets.contains(p))
+        .collect()
+}
+
+fn sweep(
+    pid: i32,
+    packages: &HashSet<String>,
+    targets: &HashSet<String>,
+    global: Option<&PathBuf>,
+) {
+    let Ok(process) = Process::new(pid) else {
+        return;
+    };
+    let Some(package) = package_of(&process).filter(|p| packages.contains(p)) else {
+        return;
+    };
+    // Never the global namespace: that would unmount the modules for everyone.
+    let Ok(namespace) = fs::read_link(format!("/proc/{}/ns/mnt", pid)) else {
+        return;
+    };
+    if global == Some(&namespace) {
+        return;
+    }
+
+    let mounts = mounted_in(pid, targets);
+    if !mounts.is_empty() && stealth::sweep_namespace(pid, &mounts) {
+        log::info!(
+            "Denylist: detached {} mount(s) try_umount left in {} (pid {})",
+            mounts.len(),
+            package,
+            pid
+        );
+    }
+}
+
+// Our mounts stay registered with try_umount, which hides them at fork. This only backs it up for
+// the listed apps, checking each one as it starts in case a mount was left behind (a secondary
+// user, a root-granted app, a live remount). `denylist sync` rewrites the cache, which is picked
+// up with the next event.
+pub fn watch() {
+    let mut packages = packages();
+    let mut loaded_at = modified(defs::DENYLIST_CACHE_FILE);
+    let mut targets: HashSet<String> = umount_mgr::registered_targets().into_iter().collect();
+    let mut targets_at = modified(defs::UMOUNT_TARGETS_FILE);
+    let global = fs::read_link("/proc/1/ns/mnt").ok();
+
+    let events = match ProcEvents::subscribe() {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!("Denylist watcher disabled, no process events: {:#}", e);
+            return;
+        }
+    };
+    log::info!(
+        ">> Denylist watcher started: {} package(s), {} mount(s)",
+        packages.len(),
+        targets.len()
+    );
+    for process in all_processes().into_iter().flatten().flatten() {
+        sweep(process.pid, &packages, &targets, global.as_ref());
+    }
+
+    loop {
+        let batch = match events.read() {
+            Ok(batch) => batch,
+            Err(e) => {
+                log::error!("Denylist watcher stopped: {:#}", e);
+                return;
+            }
+        };
+        if batch.is_empty() {
+            continue;
+        }
+
+        let current = modified(defs::DENYLIST_CACHE_FILE);
+        if current != loaded_at {
+            packages = load();
+            loaded_at = current;
+            log::info!("Denylist reloaded: {} package(s)", packages.len());
+        }
+        // Live remounts register new targets while the daemon runs.
+        let current = modified(defs::UMOUNT_TARGETS_FILE);
+        if current != targets_at {
+            targets = umount_mgr::registered_targets().into_iter().collect();
+            targets_at = current;
+        }
+
+        for event in batch {
+            match event {
+                ProcEvent::Named(pid) => sweep(pid, &packages, &targets, global.as_ref()),
+                ProcEvent::Lost => {
+                    for process in all_processes().into_iter().flatten().flatten() {
+                        sweep(process.pid, &packages, &targets, global.as_ref());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DiagnosticLevel {
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum IssueCode {
+    TargetMissing,
+    DeadSymlink,
+    KernelObjectUnsigned,
+    PrivappUnlisted,
+    MountFailed,
+    MountMissing,
+    MountTampered,
+    StorageMemory,
+    StorageSpace,
+    StorageTrend,
+    ModuleDeferred,
+    OverlayTooManyLayers,
+    OverlayArgTooLong,
+    OverlayStackDepth,
+    ProtectedPath,
+    MagicTmpfsMemory,
+    ScriptSyntax,
+    ScriptDangerous,
+    ModuleUnreadable,
+    ModuleTooLarge,
+    SourceChanged,
+    ForeignMount,
+    IntegrityMismatch,
+    EarlyNamespace,
+}
+
+impl IssueCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TargetMissing => "TARGET_MISSING",
+            Self::DeadSymlink => "DEAD_SYMLINK",
+            Self::KernelObjectUnsigned => "KERNEL_OBJECT_UNSIGNED",
+            Self::PrivappUnlisted => "PRIVAPP_UNLISTED",
+            Self::MountFailed => "MOUNT_FAILED",
+            Self::MountMissing => "MOUNT_MISSING",
+            Self::MountTampered => "MOUNT_TAMPERED",
+            Self::StorageMemory => "STORAGE_MEMORY",
+            Self::StorageSpace => "STORAGE_SPACE",
+            Self::StorageTrend => "STORAGE_TREND",
+            Self::ModuleDeferred => "MODULE_DEFERRED",
+            Self::OverlayTooManyLayers => "OVERLAY_TOO_MANY_LAYERS",
+            Self::OverlayArgTooLong => "OVERLAY_ARG_TOO_LONG",
+            Self::OverlayStackDepth => "OVERLAY_STACK_DEPTH",
+            Self::ProtectedPath => "PROTECTED_PATH",
+            Self::MagicTmpfsMemory => "MAGIC_TMPFS_MEMORY",
+            Self::ScriptSyntax => "SCRIPT_SYNTAX",
+            Self::ScriptDangerous => "SCRIPT_DANGEROUS",
+            Self::ModuleUnreadable => "MODULE_UNREADABLE",
+            Self::ModuleTooLarge => "MODULE_TOO_LARGE",
+            Self::SourceChanged => "SOURCE_CHANGED",
+            Self::ForeignMount => "FOREIGN_MOUNT",
+            Self::IntegrityMismatch => "INTEGRITY_MISMATCH",
+            Self::EarlyNamespace => "EARLY_NAMESPACE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticIssue {
+    pub code: IssueCode,
+    pub level: DiagnosticLevel,
+    pub context: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+// Rules are `CODE` or `CODE:scope`, where scope is a context (module id, partition) or path prefix.
+fn is_suppressed(issue: &DiagnosticIssue, rule: &str) -> bool {
+    let (code, scope) = match rule.split_once(':') {
+        Some((code, scope)) => (code.trim(), Some(scope.trim())),
+        None => (rule.trim(), None),
+    };
+
+    code.eq_ignore_ascii_case(issue.code.as_str())
+        && scope.is_none_or(|scope| {
+            issue.context == scope
+                || issue
+                    .path
+                    .as_deref()
+                    .is_some_and(|path| std::path::Path::new(path).starts_with(scope))
+        })
+}
+
+pub fn suppress(issues: &mut Vec<DiagnosticIssue>, rules: &[String]) -> usize {
+    let before = issues.len();
+    issues.retain(|i| !rules.iter().any(|rule| is_suppressed(i, rule)));
+    before - issues.len()
+}
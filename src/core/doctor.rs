@@ -0,0 +1,233 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, path::Path};
+
+use rustix::fs::statvfs;
+use serde::Serialize;
+
+use crate::{
+    conf::config::{Coexistence, Config},
+    core::{coexist, inventory, ops::sync, storage},
+    defs,
+    sys::{mount::is_filesystem_supported, tools},
+    utils,
+};
+
+const OVERLAY_PARAMS: &str = "/sys/module/overlay/parameters";
+const OVERLAY_FEATURES: &[&str] = &["redirect_dir", "index", "metacopy", "xino_auto"];
+const SELINUX_ENFORCE: &str = "/sys/fs/selinux/enforce";
+const HYMOFS_DEVICE: &str = "/dev/hymo_ctl";
+const DATA_FAIL_BYTES: u64 = 64 * 1024 * 1024;
+const DATA_WARN_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grade {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub grade: Grade,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+}
+
+fn check(name: &'static str, grade: Grade, detail: String, hint: &'static str) -> Check {
+    Check {
+        name,
+        grade,
+        detail,
+        hint: (grade != Grade::Ok).then_some(hint),
+    }
+}
+
+fn overlayfs() -> Check {
+    if !is_filesystem_supported("overlay") {
+        return check(
+            "overlayfs",
+            Grade::Fail,
+            "not in /proc/filesystems".to_string(),
+            "Every module falls back to magic mount; set default_mode = \"magic\" to skip the attempt",
+        );
+    }
+
+    let enabled: Vec<&str> = OVERLAY_FEATURES
+        .iter()
+        .copied()
+        .filter(|f| {
+            fs::read_to_string(Path::new(OVERLAY_PARAMS).join(f)).is_ok_and(|v| v.trim() == "Y")
+        })
+        .collect();
+    let detail = if enabled.is_empty() {
+        "supported".to_string()
+    } else {
+        format!("supported ({})", enabled.join(", "))
+    };
+    check("overlayfs", Grade::Ok, detail, "")
+}
+
+fn tmpfs_xattr() -> Check {
+    if utils::is_overlay_xattr_supported().unwrap_or(false) {
+        return check("tmpfs_xattr", Grade::Ok, "supported".to_string(), "");
+    }
+    check(
+        "tmpfs_xattr",
+        Grade::Warn,
+        "CONFIG_TMPFS_XATTR is not set".to_string(),
+        "tmpfs storage cannot hold SELinux labels; use ext4 or erofs storage instead",
+    )
+}
+
+fn erofs() -> Check {
+    let kernel = storage::is_erofs_supported();
+    let tool = tools::find("mkfs.erofs").is_some();
+    match (kernel, tool) {
+        (true, true) => check("erofs", Grade::Ok, "supported".to_string(), ""),
+        (true, false) => check(
+            "erofs",
+            Grade::Warn,
+            "kernel supports it but mkfs.erofs is missing".to_string(),
+            "Reinstall the module to restore the bundled tools",
+        ),
+        (false, _) => check(
+            "erofs",
+            Grade::Warn,
+            "not supported by this kernel".to_string(),
+            "erofs storage falls back to squashfs or ext4",
+        ),
+    }
+}
+
+fn hymofs() -> Check {
+    let detail = if Path::new(HYMOFS_DEVICE).exists() {
+        format!("{} present", HYMOFS_DEVICE)
+    } else {
+        "not present; overlay and magic mount are used".to_string()
+    };
+    check("hymofs", Grade::Ok, detail, "")
+}
+
+fn ksu_driver() -> Check {
+    match ksu::version() {
+        Some(version) => check("ksu_driver", Grade::Ok, format!("version {}", version), ""),
+        None => check(
+            "ksu_driver",
+            Grade::Warn,
+            "KernelSU driver not reachable".to_string(),
+            "try_umount hiding is unavailable; expected on APatch, otherwise update KernelSU",
+        ),
+    }
+}
+
+fn selinux() -> Check {
+    match fs::read_to_string(SELINUX_ENFORCE).map(|s| s.trim().to_string()) {
+        Ok(mode) if mode == "1" => check("selinux", Grade::Ok, "enforcing".to_string(), ""),
+        Ok(_) => check(
+            "selinux",
+            Grade::Warn,
+            "permissive".to_string(),
+            "Something switched SELinux to permissive; run `meta-hybrid lint` to find the module",
+        ),
+        Err(_) => check(
+            "selinux",
+            Grade::Warn,
+            format!("{} unreadable", SELINUX_ENFORCE),
+            "selinuxfs is not mounted; Android expects SELinux to be enabled",
+        ),
+    }
+}
+
+fn data_space() -> Check {
+    let Ok(stat) = statvfs("/data") else {
+        return check(
+            "data_space",
+            Grade::Fail,
+            "statvfs(/data) failed".to_string(),
+            "/data is not mounted; run `meta-hybrid rescue`",
+        );
+    };
+    let free = stat.f_bavail * stat.f_frsize;
+    let grade = if free < DATA_FAIL_BYTES {
+        Grade::Fail
+    } else if free < DATA_WARN_BYTES {
+        Grade::Warn
+    } else {
+        Grade::Ok
+    };
+    check(
+        "data_space",
+        grade,
+        format!("{} MiB free", free / 1024 / 1024),
+        "Module images and snapshots live on /data; free space or prune snapshots",
+    )
+}
+
+fn mount_managers(config: &Config) -> Check {
+    let foreign = coexist::detect(config);
+    let grade = match config.coexistence {
+        _ if foreign.is_empty() => Grade::Ok,
+        Coexistence::Off => Grade::Warn,
+        Coexistence::Abort => Grade::Fail,
+        Coexistence::Skip | Coexistence::Takeover => Grade::Ok,
+    };
+    let detail = match foreign.first() {
+        None => format!("no other manager mounts from {}", defs::MODULES_DIR),
+        Some(m) => format!("{} has mounted {} target(s)", m.by, foreign.len()),
+    };
+    check(
+        "mount_managers",
+        grade,
+        detail,
+        "Set coexistence to \"skip\" or \"takeover\" so two managers do not mount the same module",
+    )
+}
+
+fn source_changed(config: &Config) -> Check {
+    let Ok(modules) = inventory::scan(&config.moduledir, config) else {
+        return check(
+            "source_changed",
+            Grade::Warn,
+            "failed to scan modules".to_string(),
+            "Check that the module directories are readable",
+        );
+    };
+    let changed = sync::changed_since_sync(&modules);
+    let (grade, detail) = if changed.is_empty() {
+        (
+            Grade::Ok,
+            format!("{} module(s) match their last sync", modules.len()),
+        )
+    } else {
+        (
+            Grade::Warn,
+            format!("changed since sync: {}", changed.join(", ")),
+        )
+    };
+    check(
+        "source_changed",
+        grade,
+        detail,
+        "Run `meta-hybrid remount --module <id>` for each listed module, or reboot, to apply the \
+         changes",
+    )
+}
+
+pub fn run(config: &Config) -> Vec<Check> {
+    vec![
+        overlayfs(),
+        tmpfs_xattr(),
+        erofs(),
+        hymofs(),
+        ksu_driver(),
+        selinux(),
+        data_space(),
+        mount_managers(config),
+        source_changed(config),
+    ]
+}
@@ -0,0 +1,689 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+    conf::config::{BackupConfig, Config, ModuleRules},
+    core::{
+        integrity,
+        inventory::{self, lifecycle},
+        ops::manifest::{self, Manifest},
+        storage,
+    },
+    defs,
+    utils::{self, tar},
+};
+
+const SNAPSHOT_EXT: &str = ".json.zst";
+const COMPRESSION_LEVEL: i32 = 19;
+// Archives can carry whole modules, so they trade ratio for speed.
+const ARCHIVE_COMPRESSION_LEVEL: i32 = 3;
+const ARCHIVE_SNAPSHOT: &str = "snapshot.json";
+// A snapshot is a config and a module list; anything near this size is not one.
+const MAX_ARCHIVE_SNAPSHOT_LEN: u64 = 16 << 20;
+const ARCHIVE_MODULES: &str = "modules";
+const IMPORT_STAGING_DIR: &str = ".import";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotMeta {
+    pub id: String,
+    pub label: String,
+    pub reason: String,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub protected: bool,
+    #[serde(default)]
+    pub last_good: bool,
+    #[serde(default, skip_deserializing)]
+    pub size: u64,
+    // The OS build the config was captured on; empty for snapshots taken before this was recorded.
+    #[serde(default)]
+    pub fingerprint: String,
+    #[serde(default)]
+    pub security_patch: String,
+    #[serde(default)]
+    pub kernel: String,
+    // Carries the content of every enabled module, not just config and module states.
+    #[serde(default)]
+    pub deep: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModuleContent {
+    pub id: String,
+    pub rules: ModuleRules,
+    pub files: Manifest,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub meta: SnapshotMeta,
+    pub config: String,
+    #[serde(default)]
+    pub disabled_modules: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<ModuleContent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+// How a module differs from its copy in a deep snapshot.
+#[derive(Debug, Serialize)]
+pub struct ContentChange {
+    pub module: String,
+    pub kind: ChangeKind,
+    pub changed_files: usize,
+    pub removed_files: usize,
+    pub rules_changed: bool,
+}
+
+#[derive(Debug)]
+pub struct Restored {
+    pub meta: SnapshotMeta,
+    pub changes: Vec<ContentChange>,
+    pub disabled: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Exported {
+    pub meta: SnapshotMeta,
+    pub path: PathBuf,
+    pub size: u64,
+    pub modules: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Imported {
+    pub meta: SnapshotMeta,
+    pub installed: Vec<String>,
+    // Modules in the archive that are already installed here; they are left as they are.
+    pub skipped: Vec<String>,
+}
+
+fn snapshot_path(id: &str) -> Result<PathBuf> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        bail!("Invalid snapshot id: {}", id);
+    }
+    Ok(Path::new(defs::GRANARY_DIR).join(format!("{}{}", id, SNAPSHOT_EXT)))
+}
+
+fn current_build() -> (String, String, String) {
+    let kernel = fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+    (
+        utils::get_property("ro.build.fingerprint").unwrap_or_default(),
+        utils::get_property("ro.build.version.security_patch").unwrap_or_default(),
+        kernel,
+    )
+}
+
+// Why a snapshot's config may not suit the running firmware, if it was captured on another build.
+pub fn build_mismatch(meta: &SnapshotMeta) -> Option<String> {
+    let (fingerprint, security_patch, _) = current_build();
+    if meta.fingerprint.is_empty() || fingerprint.is_empty() || meta.fingerprint == fingerprint {
+        return None;
+    }
+    Some(format!(
+        "snapshot {} was captured on {} (patch {}), this device runs {} (patch {})",
+        meta.id, meta.fingerprint, meta.security_patch, fingerprint, security_patch
+    ))
+}
+
+pub fn disabled_modules(config: &Config) -> Vec<String> {
+    let mut disabled: Vec<String> = config
+        .moduledir
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter(|e| e.path().join(defs::DISABLE_FILE_NAME).exists())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    disabled.sort();
+    disabled.dedup();
+    disabled
+}
+
+pub fn load(id: &str) -> Result<Snapshot> {
+    let path = snapshot_path(id)?;
+    let file = File::open(&path).with_context(|| format!("Snapshot {} not found", id))?;
+    let snapshot: Snapshot = serde_json::from_reader(zstd::Decoder::new(file)?)
+        .with_context(|| format!("Failed to decode snapshot {}", id))?;
+    Ok(snapshot)
+}
+
+fn write(snapshot: &Snapshot) -> Result<u64> {
+    let json = serde_json::to_vec(snapshot)?;
+    let compressed = zstd::encode_all(json.as_slice(), COMPRESSION_LEVEL)
+        .context("Failed to compress snapshot")?;
+    utils::atomic_write(snapshot_path(&snapshot.meta.id)?, &compressed)?;
+    Ok(compressed.len() as u64)
+}
+
+pub fn list() -> Vec<SnapshotMeta> {
+    let Ok(entries) = fs::read_dir(defs::GRANARY_DIR) else {
+        return Vec::new();
+    };
+
+    let mut snapshots: Vec<SnapshotMeta> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let id = name.strip_suffix(SNAPSHOT_EXT)?.to_string();
+            let mut meta = load(&id).ok()?.meta;
+            meta.size = e.metadata().map(|m| m.len()).unwrap_or(0);
+            Some(meta)
+        })
+        .collect();
+    snapshots.sort_by(|a, b| a.id.cmp(&b.id));
+    snapshots
+}
+
+pub fn last_good() -> Option<SnapshotMeta> {
+    list().into_iter().rev().find(|s| s.last_good)
+}
+
+// Only one snapshot carries the tag; the previous holder goes back to normal pruning.
+pub fn mark_last_good(id: &str) -> Result<()> {
+    for previous in list().iter().filter(|s| s.last_good && s.id != id) {
+        let mut snapshot = load(&previous.id)?;
+        snapshot.meta.last_good = false;
+        snapshot.meta.protected = false;
+        write(&snapshot)?;
+    }
+
+    let mut snapshot = load(id)?;
+    snapshot.meta.last_good = true;
+    snapshot.meta.protected = true;
+    write(&snapshot)?;
+    Ok(())
+}
+
+fn snapshot_config(snapshot: &Snapshot) -> Result<Config> {
+    toml::from_str(&snapshot.config)
+        .with_context(|| format!("Snapshot {} holds an invalid config", snapshot.meta.id))
+}
+
+// The previous deep snapshot lends its hashes to files whose size and mtime have not moved.
+fn module_contents(config: &Config) -> Result<Vec<ModuleContent>> {
+    let previous: BTreeMap<String, Manifest> = list()
+        .iter()
+        .rev()
+        .find(|s| s.deep)
+        .and_then(|s| load(&s.id).ok())
+        .map(|s| s.modules.into_iter().map(|m| (m.id, m.files)).collect())
+        .unwrap_or_default();
+
+    let modules = inventory::scan(&config.moduledir, config)?;
+    Ok(modules
+        .into_iter()
+        .filter_map(
+            |m| match manifest::build(&m.source_path, previous.get(&m.id)) {
+                Ok(files) => Some(ModuleContent {
+                    id: m.id,
+                    rules: m.rules,
+                    files,
+                }),
+                Err(e) => {
+                    log::warn!("Granary: failed to record content of {}: {:#}", m.id, e);
+                    None
+                }
+            },
+        )
+        .collect())
+}
+
+// Compares what is installed now against a deep snapshot, under the snapshot's own config. A shallow
+// snapshot has nothing to compare.
+pub fn content_changes(snapshot: &Snapshot) -> Result<Vec<ContentChange>> {
+    if !snapshot.meta.deep {
+        return Ok(Vec::new());
+    }
+    let config = snapshot_config(snapshot)?;
+    let current = inventory::scan(&config.moduledir, &config)?;
+
+    let mut changes = Vec::new();
+    for saved in &snapshot.modules {
+        let Ok(path) = lifecycle::locate(&config, &saved.id) else {
+            changes.push(ContentChange {
+                module: saved.id.clone(),
+                kind: ChangeKind::Removed,
+                changed_files: 0,
+                removed_files: saved.files.len(),
+                rules_changed: false,
+            });
+            continue;
+        };
+        let files = manifest::build(&path, Some(&saved.files))?;
+        let delta = manifest::diff(&saved.files, &files);
+        // A module disabled since has no rules in effect to compare.
+        let rules_changed = current.iter().find(|m| m.id == saved.id).is_some_and(|m| {
+            serde_json::to_value(&m.rules).ok() != serde_json::to_value(&saved.rules).ok()
+        });
+        if !delta.is_empty() || rules_changed {
+            changes.push(ContentChange {
+                module: saved.id.clone(),
+                kind: ChangeKind::Changed,
+                changed_files: delta.changed.len(),
+                removed_files: delta.removed.len(),
+                rules_changed,
+            });
+        }
+    }
+
+    let known: BTreeSet<&str> = snapshot
+        .modules
+        .iter()
+        .map(|m| m.id.as_str())
+        .chain(snapshot.disabled_modules.iter().map(String::as_str))
+        .collect();
+    for module in current.iter().filter(|m| !known.contains(m.id.as_str())) {
+        changes.push(ContentChange {
+            module: module.id.clone(),
+            kind: ChangeKind::Added,
+            changed_files: 0,
+            removed_files: 0,
+            rules_changed: false,
+        });
+    }
+    Ok(changes)
+}
+
+// With `disable_added`, modules installed after a deep snapshot are disabled as well.
+pub fn restore(id: &str, config_path: &Path, disable_added: bool) -> Result<Restored> {
+    let snapshot = load(id)?;
+    if let Some(mismatch) = build_mismatch(&snapshot.meta) {
+        log::warn!("Granary: {}", mismatch);
+    }
+    let config = snapshot_config(&snapshot)?;
+    let changes = content_changes(&snapshot).unwrap_or_else(|e| {
+        log::warn!("Granary: failed to compare module content: {:#}", e);
+        Vec::new()
+    });
+
+    integrity::guard_config(config_path)?;
+    utils::atomic_write(config_path, &snapshot.config)
+        .with_context(|| format!("Failed to write {}", config_path.display()))?;
+    integrity::sign_config(config_path, config.integrity)?;
+
+    let mut disabled_modules = snapshot.disabled_modules.clone();
+    let mut disabled = Vec::new();
+    if disable_added {
+        for change in changes.iter().filter(|c| c.kind == ChangeKind::Added) {
+            disabled_modules.push(change.module.clone());
+            disabled.push(change.module.clone());
+        }
+    }
+    apply_module_states(&config, &disabled_modules);
+
+    for change in &changes {
+        log::info!(
+            "Granary: {} {} since snapshot {}",
+            change.module,
+            change.kind.as_str(),
+            id
+        );
+    }
+    log::info!(
+        "Granary: restored snapshot {} ({})",
+        id,
+        snapshot.meta.label
+    );
+    Ok(Restored {
+        meta: snapshot.meta,
+        changes,
+        disabled,
+    })
+}
+
+// Every module in `disabled` gets a disable marker and every other module loses its marker.
+pub fn apply_module_states(config: &Config, disabled: &[String]) {
+    for dir in &config.moduledir {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten().filter(|e| e.path().is_dir()) {
+            let module_id = entry.file_name().to_string_lossy().to_string();
+            let marker = entry.path().join(defs::DISABLE_FILE_NAME);
+            let result = if disabled.contains(&module_id) {
+                fs::write(&marker, "")
+            } else if marker.exists() {
+                fs::remove_file(&marker)
+            } else {
+                Ok(())
+            };
+            if let Err(e) = result {
+                log::warn!("Granary: failed to restore state of {}: {}", module_id, e);
+            }
+        }
+    }
+}
+
+pub fn usage() -> u64 {
+    fs::read_dir(defs::GRANARY_DIR)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn remove(meta: &SnapshotMeta, why: &str) {
+    match snapshot_path(&meta.id).and_then(|p| fs::remove_file(p).map_err(Into::into)) {
+        Ok(()) => log::debug!("Granary: evicted snapshot {} ({})", meta.id, why),
+        Err(e) => log::warn!("Granary: failed to evict snapshot {}: {:#}", meta.id, e),
+    }
+}
+
+// Oldest first; protected snapshots are only dropped if nothing else is left to evict, and `keep`
+// (the snapshot just written) never is. Returns how many were evicted.
+pub fn prune(backup: &BackupConfig, keep: Option<&str>) -> usize {
+    let mut snapshots = list();
+    let quota = storage::parse_size(&backup.max_size).unwrap_or_else(|e| {
+        log::warn!("Granary: ignoring max_size: {:#}", e);
+        u64::MAX
+    });
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut evicted = 0;
+    if backup.retention_days > 0 {
+        let cutoff = now.saturating_sub(backup.retention_days * 24 * 60 * 60);
+        snapshots.retain(|s| {
+            let expired = s.timestamp < cutoff && !s.protected && Some(s.id.as_str()) != keep;
+            if expired {
+                remove(s, "expired");
+                evicted += 1;
+            }
+            !expired
+        });
+    }
+
+    let mut total: u64 = snapshots.iter().map(|s| s.size).sum();
+    while snapshots.len() > backup.max_backups.max(1) || (total > quota && snapshots.len() > 1) {
+        let evictable = |s: &SnapshotMeta| Some(s.id.as_str()) != keep;
+        let Some(victim) = snapshots
+            .iter()
+            .position(|s| !s.protected && evictable(s))
+            .or_else(|| snapshots.iter().position(evictable))
+        else {
+            break;
+        };
+        let meta = snapshots.remove(victim);
+        total -= meta.size;
+        remove(&meta, "over quota");
+        evicted += 1;
+    }
+    evicted
+}
+
+pub fn create_snapshot(
+    config: &Config,
+    label: &str,
+    reason: &str,
+    deep: bool,
+) -> Result<SnapshotMeta> {
+    utils::ensure_dir_exists(defs::GRANARY_DIR)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let id = format!("{:013}", timestamp.as_millis());
+    let (fingerprint, security_patch, kernel) = current_build();
+
+    let snapshot = Snapshot {
+        meta: SnapshotMeta {
+            id: id.clone(),
+            label: label.to_string(),
+            reason: reason.to_string(),
+            timestamp: timestamp.as_secs(),
+            protected: false,
+            last_good: false,
+            size: 0,
+            fingerprint,
+            security_patch,
+            kernel,
+            deep,
+        },
+        config: toml::to_string(config).context("Failed to serialize config")?,
+        disabled_modules: disabled_modules(config),
+        modules: if deep {
+            module_contents(config)?
+        } else {
+            Vec::new()
+        },
+    };
+
+    let size = write(&snapshot)?;
+
+    prune(&config.backup, Some(&id));
+
+    let mut meta = snapshot.meta;
+    meta.size = size;
+    Ok(meta)
+}
+
+// Module directories under the snapshot's own moduledir, disabled ones included.
+fn exported_modules(config: &Config) -> Vec<(String, PathBuf)> {
+    let mut modules: Vec<(String, PathBuf)> = config
+        .moduledir
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter(|e| e.path().is_dir())
+        .map(|e| (e.file_name().to_string_lossy().to_string(), e.path()))
+        .filter(|(id, _)| utils::validate_module_id(id).is_ok())
+        .collect();
+    modules.sort();
+    modules.dedup_by(|a, b| a.0 == b.0);
+    modules
+}
+
+fn write_archive(snapshot: &Snapshot, modules: &[(String, PathBuf)], dest: &Path) -> Result<()> {
+    let encoder = zstd::Encoder::new(
+        BufWriter::new(File::create(dest)?),
+        ARCHIVE_COMPRESSION_LEVEL,
+    )?;
+    let mut archive = tar::Writer::new(encoder, snapshot.meta.timestamp);
+    archive.append_data(ARCHIVE_SNAPSHOT, 0o644, &serde_json::to_vec(snapshot)?)?;
+
+    for (id, root) in modules {
+        for entry in WalkDir::new(root).sort_by_file_name() {
+            let entry = entry?;
+            let rel = entry.path().strip_prefix(root)?;
+            let name = if rel.as_os_str().is_empty() {
+                format!("{}/{}", ARCHIVE_MODULES, id)
+            } else {
+                format!("{}/{}/{}", ARCHIVE_MODULES, id, rel.to_string_lossy())
+            };
+            if !archive.append_path(&name, entry.path())? {
+                log::warn!("Granary: skipping special file {}", entry.path().display());
+            }
+        }
+    }
+
+    archive.finish()?.finish()?.flush()?;
+    Ok(())
+}
+
+// Module content is taken as installed now, which for a deep snapshot may be newer than what the
+// snapshot recorded.
+pub fn export(id: &str, dest: &Path, with_modules: bool) -> Result<Exported> {
+    let snapshot = load(id)?;
+    let modules = if with_modules {
+        if let Ok(changes) = content_changes(&snapshot)
+            && !changes.is_empty()
+        {
+            log::warn!(
+                "Granary: {} module(s) changed since snapshot {}; exporting their current files",
+                changes.len(),
+                id
+            );
+        }
+        exported_modules(&snapshot_config(&snapshot)?)
+    } else {
+        Vec::new()
+    };
+
+    if let Err(e) = write_archive(&snapshot, &modules, dest) {
+        let _ = fs::remove_file(dest);
+        return Err(e.context(format!("Failed to write {}", dest.display())));
+    }
+
+    Ok(Exported {
+        meta: snapshot.meta,
+        path: dest.to_path_buf(),
+        size: fs::metadata(dest).map(|m| m.len()).unwrap_or(0),
+        modules: modules.into_iter().map(|(id, _)| id).collect(),
+    })
+}
+
+// `modules/<id>/<rel>` with a valid id and a relative path that stays inside the module.
+fn module_entry(path: &str) -> Result<Option<(String, PathBuf)>> {
+    let Some(rest) = path
+        .strip_prefix(ARCHIVE_MODULES)
+        .and_then(|r| r.strip_prefix('/'))
+    else {
+        return Ok(None);
+    };
+    let (id, rel) = rest.split_once('/').unwrap_or((rest, ""));
+    utils::validate_module_id(id)?;
+    let rel = PathBuf::from(rel);
+    if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        bail!("Archive entry escapes its module: {}", path);
+    }
+    Ok(Some((id.to_string(), rel)))
+}
+
+fn read_archive(
+    source: &Path,
+    config: &Config,
+    with_modules: bool,
+    staging: &Path,
+) -> Result<(Snapshot, Vec<String>, Vec<String>)> {
+    let decoder = zstd::Decoder::new(BufReader::new(File::open(source)?))?;
+    let mut archive = tar::Reader::new(decoder);
+
+    let mut snapshot = None;
+    let mut installed = BTreeSet::new();
+    let mut skipped = BTreeSet::new();
+    while let Some(entry) = archive.next_entry()? {
+        if entry.path == ARCHIVE_SNAPSHOT {
+            let data = archive.read_small(MAX_ARCHIVE_SNAPSHOT_LEN)?;
+            snapshot = Some(serde_json::from_slice::<Snapshot>(&data)?);
+            continue;
+        }
+        let Some((id, rel)) = module_entry(&entry.path)? else {
+            continue;
+        };
+        if !with_modules || skipped.contains(&id) {
+            continue;
+        }
+        if !installed.contains(&id) {
+            if lifecycle::locate(config, &id).is_ok() {
+                skipped.insert(id);
+                continue;
+            }
+            installed.insert(id.clone());
+        }
+
+        let root = staging.join(&id);
+        utils::ensure_dir_exists(&root)?;
+        archive
+            .unpack(&entry, &root, &rel)
+            .with_context(|| format!("Failed to unpack {}", entry.path))?;
+    }
+
+    let snapshot = snapshot.context("Archive holds no snapshot")?;
+    Ok((
+        snapshot,
+        installed.into_iter().collect(),
+        skipped.into_iter().collect(),
+    ))
+}
+
+fn install_archive(
+    source: &Path,
+    config: &Config,
+    with_modules: bool,
+    staging: &Path,
+) -> Result<Imported> {
+    let (mut snapshot, installed, skipped) = read_archive(source, config, with_modules, staging)
+        .with_context(|| format!("Failed to read {}", source.display()))?;
+    snapshot_config(&snapshot)?;
+    let path = snapshot_path(&snapshot.meta.id)?;
+    if path.exists() {
+        bail!("Snapshot {} is already in the granary", snapshot.meta.id);
+    }
+
+    let moduledir = config.primary_moduledir();
+    for id in &installed {
+        fs::rename(staging.join(id), moduledir.join(id))
+            .with_context(|| format!("Failed to install module {}", id))?;
+        log::info!("Granary: installed {} from {}", id, source.display());
+    }
+
+    snapshot.meta.protected = true;
+    snapshot.meta.last_good = false;
+    snapshot.meta.size = write(&snapshot)?;
+    log::info!(
+        "Granary: imported snapshot {} ({})",
+        snapshot.meta.id,
+        snapshot.meta.label
+    );
+    Ok(Imported {
+        meta: snapshot.meta,
+        installed,
+        skipped,
+    })
+}
+
+// Modules are installed only where no module of that id exists; the snapshot is protected so the
+// granary's pruning does not drop it for its age right away.
+pub fn import(source: &Path, config: &Config, with_modules: bool) -> Result<Imported> {
+    utils::ensure_dir_exists(defs::GRANARY_DIR)?;
+    let staging = Path::new(defs::GRANARY_DIR).join(IMPORT_STAGING_DIR);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    utils::ensure_dir_exists(&staging)?;
+
+    let result = install_archive(source, config, with_modules, &staging);
+
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        log::debug!("Granary: failed to clean import staging: {}", e);
+    }
+    result
+}
@@ -0,0 +1,77 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, path::Path};
+
+use serde::Serialize;
+
+use crate::{defs, sys::mount::detect_mount_source};
+
+const APATCH_DIR: &str = "/data/adb/ap";
+const MAGISK_DIR: &str = "/data/adb/magisk";
+
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub profile: &'static str,
+    pub features: Vec<&'static str>,
+    pub target: &'static str,
+}
+
+// Every root implementation that left a trace; more than one usually means a leftover install.
+#[derive(Debug, Serialize)]
+pub struct RootManagers {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernelsu: Option<i32>,
+    pub apatch: bool,
+    pub magisk: bool,
+    pub mount_source: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Info {
+    pub build: BuildInfo,
+    // The installed module's version= line, which CI builds set to the release tag.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_version: Option<String>,
+    pub kernel: String,
+    pub arch: &'static str,
+    pub root: RootManagers,
+}
+
+fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "embed-webui") {
+        features.push("embed-webui");
+    }
+    features
+}
+
+pub fn collect() -> Info {
+    Info {
+        build: BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: env!("META_HYBRID_GIT_HASH"),
+            profile: env!("META_HYBRID_PROFILE"),
+            features: features(),
+            target: env!("META_HYBRID_TARGET"),
+        },
+        module_version: fs::read_to_string(defs::MODULE_PROP_FILE)
+            .ok()
+            .and_then(|prop| {
+                prop.lines()
+                    .find_map(|l| l.strip_prefix("version=").map(|v| v.trim().to_string()))
+            }),
+        kernel: fs::read_to_string("/proc/sys/kernel/osrelease")
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default(),
+        arch: std::env::consts::ARCH,
+        root: RootManagers {
+            kernelsu: ksu::version(),
+            apatch: Path::new(APATCH_DIR).is_dir(),
+            magisk: Path::new(MAGISK_DIR).is_dir(),
+            mount_source: detect_mount_source(),
+        },
+    }
+}
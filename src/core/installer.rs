@@ -0,0 +1,391 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Read},
+    os::unix::fs::{PermissionsExt, symlink},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+use crate::{
+    conf::config::Config,
+    core::{
+        diagnostics::{DiagnosticIssue, DiagnosticLevel},
+        inventory,
+        ops::{
+            planner::{self, ConflictEntry},
+            preflight,
+        },
+    },
+    defs, utils,
+};
+
+// What KernelSU's restorecon gives module trees and the partition content inside them.
+const MODULE_CONTEXT: &str = "u:object_r:adb_data_file:s0";
+const SYSTEM_CONTEXT: &str = "u:object_r:system_file:s0";
+
+#[derive(Debug, Serialize)]
+pub struct InstallSummary {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    pub files: usize,
+    pub updated: bool,
+    pub installed: bool,
+    pub conflicts: Vec<ConflictEntry>,
+    pub critical: Vec<DiagnosticIssue>,
+}
+
+fn parse_prop(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+// Release archives often wrap the module in one top-level folder.
+fn module_root(zip: &mut ZipArchive<File>) -> Result<String> {
+    let mut roots: Vec<String> = zip
+        .file_names()
+        .filter_map(|n| n.strip_suffix("module.prop"))
+        .filter(|prefix| prefix.is_empty() || prefix.trim_end_matches('/').find('/').is_none())
+        .map(str::to_string)
+        .collect();
+    roots.sort_by_key(String::len);
+    roots
+        .into_iter()
+        .next()
+        .context("Archive has no module.prop; is it a module zip?")
+}
+
+fn read_prop(zip: &mut ZipArchive<File>, root: &str) -> Result<HashMap<String, String>> {
+    let mut content = String::new();
+    zip.by_name(&format!("{}module.prop", root))?
+        .read_to_string(&mut content)
+        .context("module.prop is not valid UTF-8")?;
+    let prop = parse_prop(&content);
+
+    let id = prop.get("id").map(String::as_str).unwrap_or_default();
+    utils::validate_module_id(id).context("module.prop has no usable id")?;
+    if id == "meta-hybrid" {
+        bail!("Refusing to install over meta-hybrid itself");
+    }
+    Ok(prop)
+}
+
+fn extract(zip: &mut ZipArchive<File>, root: &str, dest: &Path) -> Result<usize> {
+    let mut files = 0;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let Some(name) = entry.enclosed_name() else {
+            bail!("Archive entry escapes the module: {}", entry.name());
+        };
+        let Ok(rel) = name.strip_prefix(root) else {
+            continue;
+        };
+        if rel.as_os_str().is_empty() || rel.starts_with("META-INF") {
+            continue;
+        }
+
+        let path = dest.join(rel);
+        if entry.is_dir() {
+            utils::ensure_dir_exists(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            utils::ensure_dir_exists(parent)?;
+        }
+
+        if entry.is_symlink() {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            symlink(&target, &path)?;
+        } else {
+            let mut out = File::create(&path)?;
+            io::copy(&mut entry, &mut out)?;
+            if let Some(mode) = entry.unix_mode() {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o7777))?;
+            }
+        }
+        files += 1;
+    }
+    Ok(files)
+}
+
+// The installer variables a customize.sh may set. Anything else in it needs a root manager's
+// install environment to run, which this installer does not provide.
+#[derive(Debug, Default)]
+struct InstallLists {
+    remove: Vec<String>,
+    replace: Vec<String>,
+}
+
+fn parse_customize(content: &str) -> Result<InstallLists> {
+    let mut lists = InstallLists::default();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, value)) = line.split_once('=') else {
+            bail!("customize.sh runs commands: {}", line);
+        };
+        let list = match name {
+            "REMOVE" => &mut lists.remove,
+            "REPLACE" => &mut lists.replace,
+            "SKIPUNZIP" if value == "0" => continue,
+            _ => bail!("customize.sh runs commands: {}", line),
+        };
+
+        let Some(mut value) = value.strip_prefix('"').map(str::to_string) else {
+            bail!("customize.sh sets {} without quotes", name);
+        };
+        while !value.contains('"') {
+            let Some(next) = lines.next() else {
+                bail!("customize.sh leaves {} unterminated", name);
+            };
+            value.push('\n');
+            value.push_str(next);
+        }
+        let (value, rest) = value.split_once('"').unwrap_or_default();
+        if !rest.trim().is_empty() {
+            bail!("customize.sh runs commands after {}: {}", name, rest.trim());
+        }
+        list.extend(value.split_whitespace().map(str::to_string));
+    }
+    Ok(lists)
+}
+
+// What the managers' installers do with the lists: REPLACE makes a directory opaque, REMOVE hides
+// a stock file behind a whiteout.
+fn apply_install_lists(staged: &Path) -> Result<()> {
+    let script = staged.join("customize.sh");
+    let Ok(content) = fs::read_to_string(&script) else {
+        return Ok(());
+    };
+    let lists = parse_customize(&content).context(
+        "Module needs its own install script; install it through the root manager instead",
+    )?;
+
+    let resolve = |entry: &str| -> Result<PathBuf> {
+        let rel = Path::new(entry.trim_start_matches('/'));
+        if rel.as_os_str().is_empty()
+            || rel
+                .components()
+                .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            bail!("Invalid path in customize.sh: {}", entry);
+        }
+        Ok(staged.join(rel))
+    };
+    for entry in &lists.replace {
+        let dir = resolve(entry)?;
+        utils::ensure_dir_exists(&dir)?;
+        fs::write(dir.join(defs::REPLACE_DIR_FILE_NAME), "")?;
+    }
+    for entry in &lists.remove {
+        let path = resolve(entry)?;
+        if let Some(parent) = path.parent() {
+            utils::ensure_dir_exists(parent)?;
+        }
+        if fs::symlink_metadata(&path).is_ok() {
+            bail!(
+                "customize.sh removes {}, which the module also ships",
+                entry
+            );
+        }
+        utils::create_whiteout(&path)?;
+    }
+    Ok(())
+}
+
+fn label(config: &Config, dir: &Path) {
+    let partitions = config.effective_partitions();
+    for entry in WalkDir::new(dir).into_iter().flatten() {
+        let rel = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        let in_partition = rel
+            .components()
+            .next()
+            .is_some_and(|c| partitions.iter().any(|p| c.as_os_str() == p.as_str()));
+        let context = if in_partition {
+            SYSTEM_CONTEXT
+        } else {
+            MODULE_CONTEXT
+        };
+        if let Err(e) = utils::lsetfilecon(entry.path(), context) {
+            log::debug!("Failed to label {}: {}", entry.path().display(), e);
+        }
+    }
+}
+
+fn issue_key(issue: &DiagnosticIssue) -> String {
+    format!("{}|{}", issue.context, issue.message)
+}
+
+fn critical_issues(
+    config: &Config,
+    modules: &[inventory::Module],
+    storage_root: &Path,
+) -> Result<(Vec<ConflictEntry>, Vec<DiagnosticIssue>)> {
+    let plan = planner::generate(config, modules, storage_root)?;
+    let mut report = plan.analyze();
+    report
+        .diagnostics
+        .extend(preflight::check_plan(&plan, modules, config, storage_root));
+    report
+        .diagnostics
+        .retain(|d| d.level == DiagnosticLevel::Critical);
+    Ok((report.conflicts, report.diagnostics))
+}
+
+// Plans the current modules with and without the new one; only what the new module adds counts.
+// The staging directory is treated as a module directory so the plan stays a preview.
+fn analyze(
+    config: &Config,
+    staged_root: &Path,
+    id: &str,
+) -> Result<(Vec<ConflictEntry>, Vec<DiagnosticIssue>)> {
+    let current = inventory::scan(&config.moduledir, config).context("Failed to scan modules")?;
+    let (_, known) = critical_issues(config, &current, config.primary_moduledir())?;
+
+    let mut candidate_config = config.clone();
+    candidate_config
+        .moduledir
+        .insert(0, staged_root.to_path_buf());
+    let candidate = inventory::scan(&candidate_config.moduledir, &candidate_config)
+        .context("Failed to scan modules")?;
+    let (conflicts, critical) = critical_issues(&candidate_config, &candidate, staged_root)?;
+
+    let known: HashSet<String> = known.iter().map(issue_key).collect();
+    Ok((
+        conflicts
+            .into_iter()
+            .filter(|c| c.contending_modules.iter().any(|m| m == id))
+            .collect(),
+        critical
+            .into_iter()
+            .filter(|d| !known.contains(&issue_key(d)))
+            .collect(),
+    ))
+}
+
+pub fn install(
+    config: &Config,
+    archive: &Path,
+    force: bool,
+    dry_run: bool,
+) -> Result<InstallSummary> {
+    let file =
+        File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    let mut zip = ZipArchive::new(file).context("Not a valid zip archive")?;
+    let root = module_root(&mut zip)?;
+    let prop = read_prop(&mut zip, &root)?;
+
+    let staged_root = Path::new(defs::INSTALL_STAGE_DIR);
+    if staged_root.exists() {
+        fs::remove_dir_all(staged_root)?;
+    }
+    let result = stage(config, &mut zip, &root, &prop, force, dry_run);
+    let _ = fs::remove_dir_all(staged_root);
+    result
+}
+
+fn stage(
+    config: &Config,
+    zip: &mut ZipArchive<File>,
+    root: &str,
+    prop: &HashMap<String, String>,
+    force: bool,
+    dry_run: bool,
+) -> Result<InstallSummary> {
+    let id = prop["id"].clone();
+    let staged_root = Path::new(defs::INSTALL_STAGE_DIR);
+    let staged = staged_root.join(&id);
+    utils::ensure_dir_exists(&staged)?;
+
+    let files = extract(zip, root, &staged).context("Failed to extract module")?;
+    apply_install_lists(&staged)?;
+    let (conflicts, critical) = analyze(config, staged_root, &id)?;
+
+    let live = config.primary_moduledir().join(&id);
+    let dest = Path::new(defs::MODULES_UPDATE_DIR).join(&id);
+    let mut summary = InstallSummary {
+        id: id.clone(),
+        name: prop.get("name").cloned().unwrap_or_default(),
+        version: prop.get("version").cloned().unwrap_or_default(),
+        path: dest.clone(),
+        files,
+        updated: live.exists(),
+        installed: false,
+        conflicts,
+        critical,
+    };
+
+    if dry_run {
+        return Ok(summary);
+    }
+    if !summary.critical.is_empty() {
+        let reasons = summary
+            .critical
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+        if !force {
+            bail!(
+                "{} would introduce critical issues: {}; rerun with --force to install anyway",
+                id,
+                reasons
+            );
+        }
+        log::warn!("Installing {} despite critical issues: {}", id, reasons);
+    }
+
+    label(config, &staged);
+    stage_update(&staged, &dest, &live)?;
+    summary.installed = true;
+    log::info!(
+        "Staged module {} in {}, it replaces {} on the next boot",
+        id,
+        dest.display(),
+        live.display()
+    );
+    Ok(summary)
+}
+
+// The live module is never touched: like the root managers' own installers, the new version waits
+// in modules_update and the `update` flag marks it pending until the next boot moves it in place.
+fn stage_update(staged: &Path, dest: &Path, live: &Path) -> Result<()> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)
+            .with_context(|| format!("Failed to drop pending update {}", dest.display()))?;
+    }
+    if let Some(parent) = dest.parent() {
+        utils::ensure_dir_exists(parent)?;
+    }
+    let moved = fs::rename(staged, dest)
+        .or_else(|_| utils::sync_dir(staged, dest, false).map_err(io::Error::other));
+    if let Err(e) = moved {
+        let _ = fs::remove_dir_all(dest);
+        return Err(e).with_context(|| format!("Failed to stage {}", dest.display()));
+    }
+
+    // Managers list a new module from its module.prop in the live directory.
+    utils::ensure_dir_exists(live)?;
+    let prop = live.join("module.prop");
+    if !prop.exists() {
+        fs::copy(dest.join("module.prop"), &prop)?;
+    }
+    fs::write(live.join(defs::UPDATE_FLAG_FILE_NAME), "")?;
+    Ok(())
+}
@@ -0,0 +1,210 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::{defs, utils};
+
+const KEY_LEN: usize = 32;
+const SIG_EXT: &str = "sig";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    // Signing is off: there is no key to check against.
+    Unsigned,
+    Valid,
+    Tampered,
+}
+
+impl Verdict {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unsigned => "unsigned",
+            Self::Valid => "valid",
+            Self::Tampered => "tampered",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileVerdict {
+    pub path: PathBuf,
+    pub verdict: Verdict,
+}
+
+fn sig_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", SIG_EXT));
+    path.with_file_name(name)
+}
+
+fn key() -> Option<Vec<u8>> {
+    fs::read(defs::INTEGRITY_KEY_FILE)
+        .ok()
+        .filter(|k| k.len() == KEY_LEN)
+}
+
+pub fn is_enabled() -> bool {
+    key().is_some()
+}
+
+// Drawn once from the kernel's entropy pool; only root can read it back.
+fn create_key() -> Result<()> {
+    let mut key = [0u8; KEY_LEN];
+    File::open("/dev/urandom")?.read_exact(&mut key)?;
+    utils::ensure_dir_exists(defs::BASE_DIR)?;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(defs::INTEGRITY_KEY_FILE)
+        .context("Failed to create integrity key")?
+        .write_all(&key)?;
+    log::info!("Integrity: created signing key");
+    Ok(())
+}
+
+fn mac(key: &[u8], content: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(content);
+    mac
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// A no-op while signing is off.
+pub fn sign(path: &Path) -> Result<()> {
+    let Some(key) = key() else {
+        return Ok(());
+    };
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let tag: String = mac(&key, &content)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    utils::atomic_write(sig_path(path), tag)
+}
+
+// A file that is gone cannot have been tampered with; one without a valid signature has.
+pub fn verify(path: &Path) -> Verdict {
+    let Some(key) = key() else {
+        return Verdict::Unsigned;
+    };
+    let Ok(content) = fs::read(path) else {
+        return Verdict::Valid;
+    };
+    let valid = fs::read_to_string(sig_path(path))
+        .ok()
+        .and_then(|s| decode_hex(&s))
+        .is_some_and(|tag| mac(&key, &content).verify_slice(&tag).is_ok());
+    if valid {
+        Verdict::Valid
+    } else {
+        log::warn!("Integrity: {} does not match its signature", path.display());
+        Verdict::Tampered
+    }
+}
+
+fn sign_all() -> Result<()> {
+    for path in [defs::CONFIG_FILE, defs::STATE_FILE] {
+        if Path::new(path).exists() {
+            sign(Path::new(path))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn check() -> Vec<FileVerdict> {
+    [defs::CONFIG_FILE, defs::STATE_FILE]
+        .iter()
+        .map(|p| FileVerdict {
+            path: PathBuf::from(p),
+            verdict: verify(Path::new(p)),
+        })
+        .collect()
+}
+
+// A config that fails its check is left as found, key and signature included, until someone has
+// looked at it and run `integrity sign`; overwriting it would also destroy the evidence.
+pub fn guard_config(path: &Path) -> Result<()> {
+    if path == Path::new(defs::CONFIG_FILE) && verify(path) == Verdict::Tampered {
+        bail!(
+            "{} fails its integrity check; review it and run `meta-hybrid integrity sign` before \
+             changing it",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+// Follows the `integrity` flag of the config being saved: on creates the key if needed, off drops
+// the key and every signature.
+pub fn sign_config(path: &Path, enabled: bool) -> Result<()> {
+    if path != Path::new(defs::CONFIG_FILE) {
+        return Ok(());
+    }
+    if enabled {
+        if !is_enabled() {
+            create_key()?;
+        }
+        return sign(path);
+    }
+    if is_enabled() {
+        disable();
+    }
+    Ok(())
+}
+
+fn disable() {
+    for path in [defs::CONFIG_FILE, defs::STATE_FILE] {
+        let _ = fs::remove_file(sig_path(Path::new(path)));
+    }
+    if let Err(e) = fs::remove_file(defs::INTEGRITY_KEY_FILE) {
+        log::warn!("Integrity: failed to remove signing key: {}", e);
+    }
+    log::info!("Integrity: signing disabled");
+}
+
+// The key comes into being on the first boot with `integrity` on; the files are signed as they
+// stand at that point.
+pub fn init(enabled: bool) {
+    if !enabled || is_enabled() {
+        return;
+    }
+    if let Err(e) = create_key().and_then(|_| sign_all()) {
+        log::warn!("Integrity: failed to set up signing: {:#}", e);
+    }
+}
+
+// Re-signs after a deliberate manual edit.
+pub fn resign() -> Result<Vec<FileVerdict>> {
+    if !is_enabled() {
+        create_key()?;
+    }
+    sign_all()?;
+    Ok(check())
+}
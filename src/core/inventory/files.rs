@@ -0,0 +1,158 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+
+use super::scanner as inventory;
+use crate::{conf::config::Config, core::state::RuntimeState, utils};
+
+pub const DEFAULT_PAGE_SIZE: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Dir,
+    File,
+    Symlink,
+    Whiteout,
+    Other,
+}
+
+impl FileKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dir => "dir",
+            Self::File => "file",
+            Self::Symlink => "symlink",
+            Self::Whiteout => "whiteout",
+            Self::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileEntry {
+    pub name: String,
+    pub kind: FileKind,
+    pub size: u64,
+    pub mode: u32,
+    pub mtime: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub opaque: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FilePage {
+    pub module: String,
+    pub path: String,
+    pub root: PathBuf,
+    pub total: usize,
+    pub offset: usize,
+    pub entries: Vec<FileEntry>,
+}
+
+// The synced copy is what actually got mounted, so it wins over the module's source tree.
+pub fn content_root(config: &Config, id: &str) -> Result<PathBuf> {
+    utils::validate_module_id(id)?;
+    let synced = RuntimeState::load()
+        .unwrap_or_default()
+        .mount_point
+        .join(id);
+    if synced.is_absolute() && synced.is_dir() {
+        return Ok(synced);
+    }
+    inventory::scan(&config.moduledir, config)
+        .context("Failed to scan modules")?
+        .into_iter()
+        .find(|m| m.id == id)
+        .map(|m| m.source_path)
+        .with_context(|| format!("Module {} not found", id))
+}
+
+fn entry(path: &Path, name: String) -> Result<FileEntry> {
+    let metadata = fs::symlink_metadata(path)?;
+    let ft = metadata.file_type();
+    let kind = if utils::is_whiteout(path) || utils::is_xattr_whiteout(path) {
+        FileKind::Whiteout
+    } else if ft.is_dir() {
+        FileKind::Dir
+    } else if ft.is_symlink() {
+        FileKind::Symlink
+    } else if ft.is_file() {
+        FileKind::File
+    } else {
+        FileKind::Other
+    };
+
+    Ok(FileEntry {
+        name,
+        kind,
+        size: metadata.len(),
+        mode: metadata.permissions().mode() & 0o7777,
+        mtime: metadata.mtime(),
+        context: utils::lgetfilecon(path).ok(),
+        target: ft
+            .is_symlink()
+            .then(|| fs::read_link(path).ok())
+            .flatten()
+            .map(|t| t.to_string_lossy().to_string()),
+        opaque: kind == FileKind::Dir && utils::is_overlay_opaque(path),
+    })
+}
+
+// One directory level, directories first, so a browser can page through large trees lazily.
+pub fn list(
+    config: &Config,
+    id: &str,
+    path: &str,
+    offset: usize,
+    limit: usize,
+) -> Result<FilePage> {
+    let rel = Path::new(path.trim_start_matches('/'));
+    if rel
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("Path must stay inside the module: {}", path);
+    }
+
+    let root = content_root(config, id)?;
+    let dir = root.join(rel);
+    let mut names: Vec<(bool, String)> = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .flatten()
+        .map(|e| {
+            let is_dir = e.file_type().is_ok_and(|t| t.is_dir());
+            (!is_dir, e.file_name().to_string_lossy().to_string())
+        })
+        .collect();
+    names.sort();
+
+    let total = names.len();
+    let entries = names
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(_, name)| entry(&dir.join(&name), name))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(FilePage {
+        module: id.to_string(),
+        path: rel.to_string_lossy().to_string(),
+        root,
+        total,
+        offset,
+        entries,
+    })
+}
@@ -0,0 +1,168 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::Config,
+    core::{live, ops::manifest, state::RuntimeState},
+    defs, utils,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Enable,
+    Disable,
+    Remove,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Enable => "enable",
+            Self::Disable => "disable",
+            Self::Remove => "remove",
+        }
+    }
+}
+
+// A marker change the mounts have not caught up with yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingChange {
+    pub module: String,
+    pub action: Action,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModuleChange {
+    pub module: String,
+    pub action: Action,
+    pub path: PathBuf,
+    pub pruned: bool,
+    pub targets: Vec<String>,
+    pub pending: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub live_error: Option<String>,
+}
+
+// Disabled modules drop out of the scan, so the module directories are searched directly.
+pub fn locate(config: &Config, id: &str) -> Result<PathBuf> {
+    utils::validate_module_id(id)?;
+    config
+        .moduledir
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(std::iter::once(Path::new(defs::MANAGED_MODULES_DIR)))
+        .map(|dir| dir.join(id))
+        .find(|path| path.is_dir())
+        .with_context(|| format!("Module {} not found", id))
+}
+
+fn write_markers(path: &Path, action: Action) -> Result<()> {
+    let disable = path.join(defs::DISABLE_FILE_NAME);
+    let remove = path.join(defs::REMOVE_FILE_NAME);
+    match action {
+        Action::Enable => {
+            for marker in [disable, remove] {
+                if marker.exists() {
+                    fs::remove_file(&marker)
+                        .with_context(|| format!("Failed to remove {}", marker.display()))?;
+                }
+            }
+        }
+        Action::Disable => fs::write(&disable, "")
+            .with_context(|| format!("Failed to create {}", disable.display()))?,
+        Action::Remove => fs::write(&remove, "")
+            .with_context(|| format!("Failed to create {}", remove.display()))?,
+    }
+    Ok(())
+}
+
+// Overlays still reading from the synced copy keep it alive until they are gone.
+fn prune_storage(state: &RuntimeState, id: &str) -> Result<bool> {
+    let storage = &state.mount_point;
+    let synced = storage.join(id);
+    if !storage.is_absolute() || !synced.exists() {
+        return Ok(false);
+    }
+    if state
+        .mounts
+        .iter()
+        .any(|r| r.modules.iter().any(|m| m == id))
+        || state.magic_modules.iter().any(|m| m == id)
+    {
+        return Ok(false);
+    }
+
+    fs::remove_dir_all(&synced)
+        .with_context(|| format!("Failed to remove {}", synced.display()))?;
+    let _ = fs::remove_file(manifest::manifest_path(id));
+    Ok(true)
+}
+
+pub fn apply(config: &Config, id: &str, action: Action, live: bool) -> Result<ModuleChange> {
+    let path = locate(config, id)?;
+    write_markers(&path, action)?;
+    log::info!("Module {}: {}", id, action.as_str());
+
+    let mut change = ModuleChange {
+        module: id.to_string(),
+        action,
+        path,
+        pruned: false,
+        targets: Vec::new(),
+        pending: true,
+        live_error: None,
+    };
+
+    if live {
+        let result = match action {
+            Action::Enable => live::remount_module(config, id),
+            Action::Disable | Action::Remove => live::unmount_module(config, id),
+        };
+        match result {
+            Ok(targets) => {
+                change.targets = targets;
+                change.pending = false;
+            }
+            Err(e) => {
+                log::warn!("Module {}: live {} failed: {:#}", id, action.as_str(), e);
+                change.live_error = Some(format!("{:#}", e));
+            }
+        }
+    }
+
+    // No root manager looks after our own managed modules, so removing one is immediate.
+    if action == Action::Remove && change.path.starts_with(defs::MANAGED_MODULES_DIR) {
+        fs::remove_dir_all(&change.path)
+            .with_context(|| format!("Failed to remove {}", change.path.display()))?;
+    }
+
+    if !Path::new(defs::STATE_FILE).exists() {
+        return Ok(change);
+    }
+    let mut state = RuntimeState::load()?;
+    if action != Action::Enable {
+        change.pruned = prune_storage(&state, id).unwrap_or_else(|e| {
+            log::warn!("Module {}: failed to prune synced copy: {:#}", id, e);
+            false
+        });
+    }
+    state.pending_changes.retain(|p| p.module != id);
+    if change.pending {
+        state.pending_changes.push(PendingChange {
+            module: id.to_string(),
+            action,
+        });
+    }
+    state.save()?;
+
+    Ok(change)
+}
@@ -1,6 +1,8 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod files;
+pub mod lifecycle;
 pub mod model;
 pub mod scanner;
 
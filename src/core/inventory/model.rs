@@ -15,8 +15,11 @@ use serde::Serialize;
 
 use super::scanner as inventory;
 use crate::{
-    conf::config::{self, MountMode},
-    core::state::RuntimeState,
+    conf::{
+        config::{self, MountMode},
+        output::{self, OutputFormat, Table},
+    },
+    core::{ops::sync, state::RuntimeState},
     defs, utils,
 };
 
@@ -65,15 +68,22 @@ struct ModuleInfo {
     author: String,
     description: String,
     mode: String,
+    source: String,
     is_mounted: bool,
     rules: config::ModuleRules,
+    source_changed: bool,
+    // Conflicts that kept the module from mounting on the last boot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deferred: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
 impl ModuleInfo {
     fn new(m: inventory::Module, mounted_set: &HashSet<&str>) -> Self {
         let prop = ModuleProp::from(m.source_path.join("module.prop").as_path());
 
-        let mode_str = match m.rules.default_mode {
+        let mode_str = match m.rules.mode() {
             MountMode::Overlay => "auto",
             MountMode::Magic => "magic",
             MountMode::Ignore => "ignore",
@@ -87,13 +97,47 @@ impl ModuleInfo {
             author: prop.author,
             description: prop.description,
             mode: mode_str.to_string(),
+            source: m.source_dir.to_string_lossy().to_string(),
             rules: m.rules,
+            source_changed: false,
+            deferred: None,
+            error: None,
+        }
+    }
+
+    fn unreadable(e: inventory::ScanError) -> Self {
+        let (description, source) = match e.kind {
+            inventory::ScanErrorKind::Unreadable => {
+                (format!("Module unreadable: {}", e.error), e.path.parent())
+            }
+            inventory::ScanErrorKind::TooLarge => (
+                format!("Module skipped: {} under {}", e.error, e.path.display()),
+                e.path
+                    .ancestors()
+                    .find(|p| p.file_name().is_some_and(|n| n == e.id.as_str())),
+            ),
+        };
+        Self {
+            name: e.id.clone(),
+            id: e.id,
+            version: String::new(),
+            author: String::new(),
+            description,
+            mode: "error".to_string(),
+            source: source
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            is_mounted: false,
+            rules: config::ModuleRules::default(),
+            source_changed: false,
+            deferred: None,
+            error: Some(e.error),
         }
     }
 }
 
-pub fn print_list(config: &config::Config) -> Result<()> {
-    let modules = inventory::scan(&config.moduledir, config)?;
+pub fn print_list(config: &config::Config, format: OutputFormat) -> Result<()> {
+    let (modules, errors) = inventory::scan_with_errors(&config.moduledir, config)?;
 
     let state = RuntimeState::load().unwrap_or_default();
 
@@ -104,39 +148,82 @@ pub fn print_list(config: &config::Config) -> Result<()> {
         .map(|s| s.as_str())
         .collect();
 
+    let changed: HashSet<String> = sync::changed_since_sync(&modules).into_iter().collect();
+
     let infos: Vec<ModuleInfo> = modules
         .into_iter()
-        .map(|m| ModuleInfo::new(m, &mounted_ids))
+        .map(|m| {
+            let source_changed = changed.contains(&m.id);
+            let deferred = state
+                .deferred_modules
+                .iter()
+                .find(|d| d.module == m.id)
+                .map(|d| d.conflicts);
+            ModuleInfo {
+                source_changed,
+                deferred,
+                ..ModuleInfo::new(m, &mounted_ids)
+            }
+        })
+        .chain(errors.into_iter().map(ModuleInfo::unreadable))
         .collect();
 
-    println!("{}", serde_json::to_string(&infos)?);
+    if format == OutputFormat::Json {
+        return output::print_json(&infos);
+    }
+
+    let mut table = Table::new(&["ID", "VERSION", "MODE", "MOUNTED", "STATUS"]);
+    for info in &infos {
+        let status = match &info.error {
+            Some(e) => format!("unreadable: {}", e),
+            None if let Some(conflicts) = info.deferred => {
+                format!("deferred: {} conflicts", conflicts)
+            }
+            None if info.source_changed => "source changed".to_string(),
+            None => "ok".to_string(),
+        };
+        table.row(vec![
+            info.id.clone(),
+            info.version.clone(),
+            info.mode.clone(),
+            if info.is_mounted { "yes" } else { "no" }.to_string(),
+            status,
+        ]);
+    }
+    println!("{}", table.render(format));
 
     Ok(())
 }
 
 pub fn update_description(storage_mode: &str, overlay_count: usize, magic_count: usize) {
-    let prop_path = Path::new(defs::MODULE_PROP_FILE);
-
-    if !prop_path.exists() {
-        return;
-    }
-
     let mode_str = match storage_mode {
         "tmpfs" => "Tmpfs",
         "erofs" => "EROFS",
+        "squashfs" => "SquashFS",
+        "f2fs" => "F2FS",
         _ => "Ext4",
     };
 
     let status_emoji = match storage_mode {
         "tmpfs" => "🐾",
-        "erofs" => "🚀",
+        "erofs" | "squashfs" => "🚀",
         _ => "💿",
     };
 
-    let desc_text = format!(
-        "description=😋 运行中喵～ ({}) {} | Overlay: {} | Magic: {}",
+    set_description(&format!(
+        "😋 运行中喵～ ({}) {} | Overlay: {} | Magic: {}",
         mode_str, status_emoji, overlay_count, magic_count
-    );
+    ));
+}
+
+pub fn set_description(text: &str) {
+    let prop_path = Path::new(defs::MODULE_PROP_FILE);
+
+    if !prop_path.exists() {
+        return;
+    }
+
+    let desc_text = format!("description={}", text);
 
     let lines: Vec<String> = match fs::File::open(prop_path) {
         Ok(file) => BufReader::new(file)
@@ -2,17 +2,19 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
-    collections::HashMap,
-    fs,
+    collections::{BTreeMap, HashMap},
+    fs, io,
     path::{Path, PathBuf},
 };
 
 use anyhow::Result;
 use rayon::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
 
 use crate::{
-    conf::config::{self, ModuleRules, MountMode},
+    conf::config::{self, ModuleRules, MountMode, MountStage},
+    core::storage,
     defs,
 };
 
@@ -20,14 +22,35 @@ use crate::{
 struct PartialRules {
     default_mode: Option<MountMode>,
     paths: Option<HashMap<String, MountMode>>,
+    skip_umount: Option<bool>,
+    stage: Option<MountStage>,
+    extends: Option<String>,
+    companions: Option<BTreeMap<String, String>>,
 }
 
-fn load_module_rules(module_dir: &Path, module_id: &str, cfg: &config::Config) -> ModuleRules {
+fn template<'a>(cfg: &'a config::Config, name: &str, module_id: &str) -> Option<&'a ModuleRules> {
+    let template = cfg.rule_templates.get(name);
+    if template.is_none() {
+        log::warn!(
+            "Module '{}' extends unknown rule template '{}'",
+            module_id,
+            name
+        );
+    }
+    template
+}
+
+fn load_module_rules(
+    module_dir: &Path,
+    module_id: &str,
+    cfg: &config::Config,
+    overrides: &HashMap<String, MountMode>,
+) -> ModuleRules {
     let mut rules = ModuleRules {
-        default_mode: match cfg.default_mode {
+        default_mode: Some(match cfg.default_mode {
             config::DefaultMode::Overlay => MountMode::Overlay,
             config::DefaultMode::Magic => MountMode::Magic,
-        },
+        }),
         ..Default::default()
     };
 
@@ -37,11 +60,32 @@ fn load_module_rules(module_dir: &Path, module_id: &str, cfg: &config::Config) -
         match fs::read_to_string(&internal_config) {
             Ok(content) => match serde_json::from_str::<PartialRules>(&content) {
                 Ok(partial) => {
+                    if let Some(base) = partial
+                        .extends
+                        .as_deref()
+                        .and_then(|name| template(cfg, name, module_id))
+                    {
+                        let default_mode = rules.default_mode.clone();
+                        rules = ModuleRules {
+                            extends: partial.extends.clone(),
+                            ..base.clone()
+                        };
+                        rules.default_mode = rules.default_mode.or(default_mode);
+                    }
                     if let Some(mode) = partial.default_mode {
-                        rules.default_mode = mode;
+                        rules.default_mode = Some(mode);
                     }
                     if let Some(paths) = partial.paths {
-                        rules.paths = paths;
+                        rules.paths.extend(paths);
+                    }
+                    if let Some(skip_umount) = partial.skip_umount {
+                        rules.skip_umount = skip_umount;
+                    }
+                    if let Some(stage) = partial.stage {
+                        rules.stage = Some(stage);
+                    }
+                    if let Some(companions) = partial.companions {
+                        rules.companions.extend(companions);
                     }
                 }
                 Err(e) => {
@@ -53,8 +97,31 @@ fn load_module_rules(module_dir: &Path, module_id: &str, cfg: &config::Config) -
     }
 
     if let Some(global_rules) = cfg.rules.get(module_id) {
-        rules.default_mode = global_rules.default_mode.clone();
+        let inherited = global_rules
+            .extends
+            .as_deref()
+            .and_then(|name| template(cfg, name, module_id))
+            .map(|base| global_rules.inherit(base));
+        let global_rules = inherited.as_ref().unwrap_or(global_rules);
+
+        rules.extends = global_rules.extends.clone();
+        if global_rules.default_mode.is_some() {
+            rules.default_mode = global_rules.default_mode.clone();
+        }
         rules.paths.extend(global_rules.paths.clone());
+        rules.companions.extend(global_rules.companions.clone());
+        rules.skip_umount |= global_rules.skip_umount;
+        if global_rules.stage.is_some() {
+            rules.stage = global_rules.stage;
+        }
+    }
+
+    if let Some(mode) = overrides.get(module_id) {
+        rules.default_mode = Some(mode.clone());
+    }
+
+    if module_dir.join(defs::SKIP_UMOUNT_FILE_NAME).exists() {
+        rules.skip_umount = true;
     }
 
     rules
@@ -64,27 +131,209 @@ fn load_module_rules(module_dir: &Path, module_id: &str, cfg: &config::Config) -
 pub struct Module {
     pub id: String,
     pub source_path: PathBuf,
+    pub source_dir: PathBuf,
     pub rules: ModuleRules,
+    pub sepolicy_rule: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanErrorKind {
+    Unreadable,
+    TooLarge,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanError {
+    pub id: String,
+    pub kind: ScanErrorKind,
+    pub path: PathBuf,
+    pub error: String,
+}
+
+impl ScanError {
+    fn new(id: impl Into<String>, path: &Path, err: &io::Error) -> Self {
+        let error = match err.raw_os_error() {
+            Some(code) => format!("{:?}", nix::errno::Errno::from_raw(code)),
+            None => err.to_string(),
+        };
+        Self {
+            id: id.into(),
+            kind: ScanErrorKind::Unreadable,
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+}
+
+// Prefixes up to this depth are tracked to name the directory responsible for blowing a cap.
+const CULPRIT_DEPTH: usize = 4;
+
+// Walks a module until it crosses a cap, so a runaway tree costs at most `max_entries` stats.
+fn check_limits(
+    module_dir: &Path,
+    id: &str,
+    max_entries: usize,
+    max_size: u64,
+) -> Option<ScanError> {
+    let mut entries = 0;
+    let mut size = 0;
+    let mut prefixes: HashMap<PathBuf, usize> = HashMap::new();
+
+    for entry in WalkDir::new(module_dir).min_depth(1).into_iter().flatten() {
+        entries += 1;
+        if entry.file_type().is_file() {
+            size += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        }
+        if let Ok(rel) = entry.path().strip_prefix(module_dir) {
+            let mut prefix = PathBuf::new();
+            for component in rel
+                .parent()
+                .into_iter()
+                .flat_map(Path::components)
+                .take(CULPRIT_DEPTH)
+            {
+                prefix.push(component);
+                *prefixes.entry(prefix.clone()).or_default() += 1;
+            }
+        }
+
+        let error = if entries > max_entries {
+            format!("more than {} entries", max_entries)
+        } else if size > max_size {
+            format!("more than {} bytes", max_size)
+        } else {
+            continue;
+        };
+
+        // The deepest directory holding at least half of what was seen.
+        let culprit = prefixes
+            .iter()
+            .filter(|(_, count)| **count * 2 >= entries)
+            .max_by_key(|(p, _)| p.components().count())
+            .map(|(p, _)| module_dir.join(p))
+            .unwrap_or_else(|| module_dir.to_path_buf());
+
+        return Some(ScanError {
+            id: id.to_string(),
+            kind: ScanErrorKind::TooLarge,
+            path: culprit,
+            error,
+        });
+    }
+
+    None
 }
 
-pub fn scan(source_dir: &Path, cfg: &config::Config) -> Result<Vec<Module>> {
+pub fn scan(source_dirs: &[PathBuf], cfg: &config::Config) -> Result<Vec<Module>> {
+    let (modules, errors) = scan_with_errors(source_dirs, cfg)?;
+    for e in &errors {
+        match e.kind {
+            ScanErrorKind::Unreadable => log::warn!(
+                "Module {} unreadable: {} ({})",
+                e.id,
+                e.error,
+                e.path.display()
+            ),
+            ScanErrorKind::TooLarge => log::error!(
+                "Module {} skipped: {} under {}",
+                e.id,
+                e.error,
+                e.path.display()
+            ),
+        }
+    }
+    Ok(modules)
+}
+
+// Earlier directories take precedence; managed modules come last. A module that cannot be read
+// is reported instead of failing the whole scan.
+pub fn scan_with_errors(
+    source_dirs: &[PathBuf],
+    cfg: &config::Config,
+) -> Result<(Vec<Module>, Vec<ScanError>)> {
+    let mut modules: Vec<Module> = Vec::new();
+    let mut errors = Vec::new();
+
+    let overrides = config::mode_overrides();
+    let managed_dir = Path::new(defs::MANAGED_MODULES_DIR);
+    let dirs = source_dirs
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(std::iter::once(managed_dir));
+
+    let mut seen_dirs = Vec::new();
+    for dir in dirs {
+        if seen_dirs.contains(&dir) {
+            continue;
+        }
+        seen_dirs.push(dir);
+
+        let (found, failed) = scan_dir(dir, cfg, &overrides);
+        errors.extend(failed);
+        for module in found {
+            if let Some(existing) = modules.iter().find(|m| m.id == module.id) {
+                log::debug!(
+                    "Module {} in {} is overridden by {}",
+                    module.id,
+                    dir.display(),
+                    existing.source_dir.display()
+                );
+                continue;
+            }
+            modules.push(module);
+        }
+    }
+
+    modules.sort_by(|a, b| b.id.cmp(&a.id));
+    errors.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok((modules, errors))
+}
+
+fn scan_dir(
+    source_dir: &Path,
+    cfg: &config::Config,
+    overrides: &HashMap<String, MountMode>,
+) -> (Vec<Module>, Vec<ScanError>) {
     if !source_dir.exists() {
-        return Ok(Vec::new());
+        return (Vec::new(), Vec::new());
     }
 
-    let dir_entries = fs::read_dir(source_dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    let max_size = match cfg.limits.max_size.as_deref().map(storage::parse_size) {
+        Some(Ok(size)) => size,
+        Some(Err(e)) => {
+            log::warn!("Ignoring invalid limits.max_size: {}", e);
+            u64::MAX
+        }
+        None => u64::MAX,
+    };
 
-    let mut modules: Vec<Module> = dir_entries
+    let dir_entries: Vec<_> = match fs::read_dir(source_dir) {
+        Ok(entries) => entries.collect(),
+        Err(e) => {
+            let id = source_dir.to_string_lossy().to_string();
+            return (Vec::new(), vec![ScanError::new(id, source_dir, &e)]);
+        }
+    };
+
+    let results: Vec<Result<Module, ScanError>> = dir_entries
         .into_par_iter()
         .filter_map(|entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(ScanError::new("?", source_dir, &e))),
+            };
             let path = entry.path();
+            let id = entry.file_name().to_string_lossy().to_string();
 
-            if !path.is_dir() {
-                return None;
+            match fs::metadata(&path) {
+                Ok(metadata) if metadata.is_dir() => {}
+                Ok(_) => return None,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+                Err(e) => return Some(Err(ScanError::new(id, &path, &e))),
             }
 
-            let id = entry.file_name().to_string_lossy().to_string();
-
             if matches!(
                 id.as_str(),
                 "meta-hybrid" | "lost+found" | ".git" | ".idea" | ".vscode"
@@ -92,6 +341,11 @@ pub fn scan(source_dir: &Path, cfg: &config::Config) -> Result<Vec<Module>> {
                 return None;
             }
 
+            // Anything we cannot list would otherwise just look empty and vanish from the plan.
+            if let Err(e) = fs::read_dir(&path) {
+                return Some(Err(ScanError::new(id, &path, &e)));
+            }
+
             if path.join(defs::DISABLE_FILE_NAME).exists()
                 || path.join(defs::REMOVE_FILE_NAME).exists()
                 || path.join(defs::SKIP_MOUNT_FILE_NAME).exists()
@@ -99,17 +353,30 @@ pub fn scan(source_dir: &Path, cfg: &config::Config) -> Result<Vec<Module>> {
                 return None;
             }
 
-            let rules = load_module_rules(&path, &id, cfg);
+            if let Some(e) = check_limits(&path, &id, cfg.limits.max_entries, max_size) {
+                return Some(Err(e));
+            }
+
+            let rules = load_module_rules(&path, &id, cfg, overrides);
+            let sepolicy_rule = Some(path.join("sepolicy.rule")).filter(|p| p.is_file());
 
-            Some(Module {
+            Some(Ok(Module {
                 id,
                 source_path: path,
+                source_dir: source_dir.to_path_buf(),
                 rules,
-            })
+                sepolicy_rule,
+            }))
         })
         .collect();
 
-    modules.sort_by(|a, b| b.id.cmp(&a.id));
-
-    Ok(modules)
+    let mut modules = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(module) => modules.push(module),
+            Err(e) => errors.push(e),
+        }
+    }
+    (modules, errors)
 }
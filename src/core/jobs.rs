@@ -0,0 +1,225 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::Config,
+    core::{inventory, ops::planner},
+    defs, utils,
+};
+
+const STATUS_FILE: &str = "status.json";
+const RESULT_FILE: &str = "result.json";
+const CANCEL_FILE: &str = "cancel";
+const MAX_KEPT_JOBS: usize = 8;
+const STATUS_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub kind: String,
+    pub state: JobState,
+    pub pid: u32,
+    pub done: usize,
+    pub total: usize,
+    pub started_at: u64,
+    pub elapsed_ms: u64,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn job_dir(id: &str) -> Result<PathBuf> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        bail!("Invalid job id: {}", id);
+    }
+    Ok(Path::new(defs::JOBS_DIR).join(id))
+}
+
+fn write_status(status: &JobStatus) -> Result<()> {
+    let json = serde_json::to_string(status)?;
+    utils::atomic_write(job_dir(&status.id)?.join(STATUS_FILE), json)
+}
+
+fn prune_old_jobs() {
+    let Ok(entries) = fs::read_dir(defs::JOBS_DIR) else {
+        return;
+    };
+
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    dirs.sort();
+
+    let excess = dirs.len().saturating_sub(MAX_KEPT_JOBS);
+    for dir in dirs.into_iter().take(excess) {
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            log::debug!("Failed to prune job {}: {}", dir.display(), e);
+        }
+    }
+}
+
+pub fn create(kind: &str) -> Result<JobStatus> {
+    prune_old_jobs();
+
+    let started_at = now_millis();
+    let id = format!("{:012x}-{}", started_at, std::process::id());
+    let dir = job_dir(&id)?;
+    utils::ensure_dir_exists(&dir)
+        .with_context(|| format!("Failed to create job directory {}", dir.display()))?;
+
+    let status = JobStatus {
+        id,
+        kind: kind.to_string(),
+        state: JobState::Running,
+        pid: 0,
+        done: 0,
+        total: 0,
+        started_at,
+        elapsed_ms: 0,
+        error: None,
+    };
+    write_status(&status)?;
+    Ok(status)
+}
+
+// For a job whose process never got to run it, which would otherwise stay Running with pid 0.
+pub fn fail(mut status: JobStatus, error: &anyhow::Error) -> Result<()> {
+    status.state = JobState::Failed;
+    status.error = Some(format!("{:#}", error));
+    write_status(&status)
+}
+
+pub fn status(id: &str) -> Result<JobStatus> {
+    let path = job_dir(id)?.join(STATUS_FILE);
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Job {} does not exist", id))?;
+    let mut status: JobStatus = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse status of job {}", id))?;
+
+    if status.state == JobState::Running
+        && status.pid != 0
+        && !Path::new(&format!("/proc/{}", status.pid)).exists()
+    {
+        status.state = JobState::Failed;
+        status.error = Some("Job process exited unexpectedly".to_string());
+    }
+
+    Ok(status)
+}
+
+pub fn result(id: &str) -> Result<String> {
+    let status = status(id)?;
+    if status.state != JobState::Done {
+        bail!("Job {} has no result (state: {:?})", id, status.state);
+    }
+
+    fs::read_to_string(job_dir(id)?.join(RESULT_FILE))
+        .with_context(|| format!("Failed to read result of job {}", id))
+}
+
+pub fn cancel(id: &str) -> Result<JobStatus> {
+    let status = status(id)?;
+    if status.state == JobState::Running {
+        fs::write(job_dir(id)?.join(CANCEL_FILE), "")
+            .with_context(|| format!("Failed to cancel job {}", id))?;
+    }
+    Ok(status)
+}
+
+pub fn run_conflicts(id: &str, config: &Config) -> Result<()> {
+    let dir = job_dir(id)?;
+    let cancel_marker = dir.join(CANCEL_FILE);
+    let started = Instant::now();
+
+    let mut status = self::status(id)?;
+    status.pid = std::process::id();
+    write_status(&status)?;
+
+    let cancelled = AtomicBool::new(false);
+    let tracker = Mutex::new((status.clone(), Instant::now()));
+
+    let outcome = (|| -> Result<Option<String>> {
+        let module_list = inventory::scan(&config.moduledir, config)
+            .context("Failed to scan modules for conflict analysis")?;
+        let plan = planner::generate(config, &module_list, config.primary_moduledir())
+            .context("Failed to generate plan for conflict analysis")?;
+
+        let report = plan.analyze_with(&|| cancelled.load(Ordering::Relaxed), &|done, total| {
+            let Ok(mut guard) = tracker.lock() else {
+                return;
+            };
+            let (status, last_write) = &mut *guard;
+            status.done = status.done.max(done);
+            status.total = total;
+
+            if last_write.elapsed() >= STATUS_INTERVAL || done == total {
+                *last_write = Instant::now();
+                status.elapsed_ms = started.elapsed().as_millis() as u64;
+                if cancel_marker.exists() {
+                    cancelled.store(true, Ordering::Relaxed);
+                }
+                if let Err(e) = write_status(status) {
+                    log::debug!("Failed to update job status: {:#}", e);
+                }
+            }
+        });
+
+        match report {
+            Some(report) if !cancel_marker.exists() => Ok(Some(
+                serde_json::to_string(&report.conflicts)
+                    .context("Failed to serialize conflict report")?,
+            )),
+            _ => Ok(None),
+        }
+    })();
+
+    let mut status = tracker
+        .into_inner()
+        .map(|(status, _)| status)
+        .unwrap_or(status);
+    status.elapsed_ms = started.elapsed().as_millis() as u64;
+
+    match outcome {
+        Ok(Some(json)) => {
+            utils::atomic_write(dir.join(RESULT_FILE), json)?;
+            status.state = JobState::Done;
+        }
+        Ok(None) => status.state = JobState::Cancelled,
+        Err(e) => {
+            status.state = JobState::Failed;
+            status.error = Some(format!("{:#}", e));
+        }
+    }
+
+    write_status(&status)
+}
@@ -0,0 +1,137 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, path::Path};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::Config,
+    core::{
+        inventory::lifecycle::{self, Action, ModuleChange},
+        recovery, safe_mode,
+        state::RuntimeState,
+    },
+    defs,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleVersion {
+    pub id: String,
+    pub version: String,
+    pub version_code: String,
+}
+
+impl ModuleVersion {
+    fn read(id: &str, dir: &Path) -> Self {
+        let content = fs::read_to_string(dir.join("module.prop")).unwrap_or_default();
+        let value = |key: &str| {
+            content
+                .lines()
+                .find_map(|l| l.trim().strip_prefix(key)?.strip_prefix('='))
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+        Self {
+            id: id.to_string(),
+            version: value("version"),
+            version_code: value("versionCode"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Updated,
+}
+
+impl Change {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Updated => "updated",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rollback {
+    pub module: String,
+    pub change: Change,
+    // The version the last completed boot ran, None for a module it did not have.
+    pub known_good: Option<String>,
+    pub current: String,
+    // None on a dry run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub applied: Option<ModuleChange>,
+}
+
+// Each boot's state starts from the previous one's set, which is how it carries across boots.
+pub fn last() -> Option<Vec<ModuleVersion>> {
+    RuntimeState::load().ok()?.known_good_modules
+}
+
+fn current(config: &Config) -> Vec<ModuleVersion> {
+    recovery::enabled_modules(config)
+        .into_iter()
+        .filter_map(|id| {
+            let dir = lifecycle::locate(config, &id).ok()?;
+            Some(ModuleVersion::read(&id, &dir))
+        })
+        .collect()
+}
+
+// Only a boot that ran the module set as installed proves it: safe mode mounts nothing and a
+// bisection step leaves part of it disabled.
+pub fn boot_completed(config: &Config) -> Result<Option<usize>> {
+    if safe_mode::active() || recovery::bisection().is_some() {
+        return Ok(None);
+    }
+
+    // Without a state this boot did not mount anything of ours either.
+    if !Path::new(defs::STATE_FILE).exists() {
+        return Ok(None);
+    }
+
+    let modules = current(config);
+    let count = modules.len();
+    let mut state = RuntimeState::load()?;
+    state.known_good_modules = Some(modules);
+    state.save()?;
+    Ok(Some(count))
+}
+
+// Disables the enabled modules that are new or carry another version than on the last completed
+// boot; a dry run only lists them.
+pub fn rollback(config: &Config, live: bool, dry_run: bool) -> Result<Vec<Rollback>> {
+    let Some(known) = RuntimeState::load()?.known_good_modules else {
+        bail!("No boot has completed since the module set was first tracked");
+    };
+
+    let mut rollbacks = Vec::new();
+    for module in current(config) {
+        let previous = known.iter().find(|k| k.id == module.id);
+        let change = match previous {
+            None => Change::Added,
+            Some(p) if *p != module => Change::Updated,
+            Some(_) => continue,
+        };
+        let applied = if dry_run {
+            None
+        } else {
+            Some(lifecycle::apply(config, &module.id, Action::Disable, live)?)
+        };
+        rollbacks.push(Rollback {
+            module: module.id,
+            change,
+            known_good: previous.map(|p| p.version.clone()),
+            current: module.version,
+            applied,
+        });
+    }
+    Ok(rollbacks)
+}
@@ -0,0 +1,145 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+use rayon::prelude::*;
+use regex_lite::Regex;
+
+use crate::{
+    core::{
+        diagnostics::{DiagnosticIssue, DiagnosticLevel, IssueCode},
+        inventory::Module,
+    },
+    utils,
+};
+
+const MODULE_SCRIPTS: &[&str] = &[
+    "post-fs-data.sh",
+    "post-mount.sh",
+    "service.sh",
+    "boot-completed.sh",
+    "uninstall.sh",
+];
+
+struct DangerRule {
+    regex: Regex,
+    level: DiagnosticLevel,
+    what: &'static str,
+}
+
+static DANGER_RULES: OnceLock<Vec<DangerRule>> = OnceLock::new();
+
+fn danger_rules() -> &'static [DangerRule] {
+    DANGER_RULES.get_or_init(|| {
+        [
+            (
+                r#"\brm\s+(-[a-zA-Z]+\s+)*-[a-zA-Z]*[rR][a-zA-Z]*\s+(--\s+)?["']?/(data|sdcard|storage(/emulated(/0)?)?|system|vendor|product)?/?\*?["']?\s*($|[;&|])"#,
+                DiagnosticLevel::Critical,
+                "recursively deletes a system or user data root",
+            ),
+            (
+                r"\bdd\b.*\bof=/dev/block/",
+                DiagnosticLevel::Critical,
+                "writes raw data to a block device",
+            ),
+            (
+                r"\bmkfs(\.\w+)?\b.*/dev/block/",
+                DiagnosticLevel::Critical,
+                "formats a block device",
+            ),
+            (
+                r"\b(curl|wget)\b[^|]*\|\s*(ba|z|mk)?sh\b",
+                DiagnosticLevel::Warning,
+                "pipes a download straight into a shell",
+            ),
+            (
+                r"\bsetenforce\s+0\b",
+                DiagnosticLevel::Warning,
+                "switches SELinux to permissive",
+            ),
+        ]
+        .into_iter()
+        .map(|(pattern, level, what)| DangerRule {
+            regex: Regex::new(pattern).expect("Failed to compile script lint regex"),
+            level,
+            what,
+        })
+        .collect()
+    })
+}
+
+fn check_syntax(module_id: &str, script: &Path) -> Option<DiagnosticIssue> {
+    let shell = utils::find_in_path("sh")?;
+    let output = Command::new(shell)
+        .arg("-n")
+        .arg(script)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        return None;
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Some(DiagnosticIssue {
+        code: IssueCode::ScriptSyntax,
+        level: DiagnosticLevel::Warning,
+        context: module_id.to_string(),
+        message: format!(
+            "{} has a syntax error: {}",
+            script.file_name().unwrap_or_default().to_string_lossy(),
+            stderr.lines().next().unwrap_or("sh -n failed").trim()
+        ),
+        path: Some(script.to_string_lossy().to_string()),
+    })
+}
+
+fn check_dangerous(module_id: &str, script: &Path) -> Vec<DiagnosticIssue> {
+    let Ok(content) = fs::read_to_string(script) else {
+        return Vec::new();
+    };
+    let name = script.file_name().unwrap_or_default().to_string_lossy();
+
+    let mut issues = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        for rule in danger_rules().iter().filter(|r| r.regex.is_match(line)) {
+            issues.push(DiagnosticIssue {
+                code: IssueCode::ScriptDangerous,
+                level: rule.level,
+                context: module_id.to_string(),
+                message: format!("{}:{} {}: {}", name, idx + 1, rule.what, line),
+                path: Some(script.to_string_lossy().to_string()),
+            });
+        }
+    }
+    issues
+}
+
+pub fn check_scripts(modules: &[Module]) -> Vec<DiagnosticIssue> {
+    modules
+        .par_iter()
+        .flat_map_iter(|module| {
+            MODULE_SCRIPTS
+                .iter()
+                .map(|name| module.source_path.join(name))
+                .filter(|script| script.is_file())
+                .flat_map(|script| {
+                    let mut issues = check_dangerous(&module.id, &script);
+                    issues.extend(check_syntax(&module.id, &script));
+                    issues
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
@@ -0,0 +1,188 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{collections::BTreeSet, path::Path};
+
+use anyhow::{Context, Result, bail};
+use rustix::mount::{MountPropagationFlags, UnmountFlags, mount_change, unmount};
+
+use crate::{
+    conf::config::Config,
+    core::{
+        inventory::{self, Module},
+        ops::{executor, planner, sync, verify},
+        state::{MountKind, MountRecord, RuntimeState},
+    },
+    mount::umount_mgr,
+    sys::mount::{is_mounted, mount_tmpfs},
+};
+
+fn detach(target: &str) {
+    if let Err(e) = unmount(target, UnmountFlags::DETACH) {
+        log::warn!("Failed to detach {}: {}", target, e);
+    }
+}
+
+fn refresh_module_lists(state: &mut RuntimeState) {
+    let overlay: BTreeSet<&String> = state
+        .mounts
+        .iter()
+        .filter(|r| r.kind != MountKind::Magic)
+        .flat_map(|r| r.modules.iter())
+        .collect();
+    state.overlay_modules = overlay.into_iter().cloned().collect();
+}
+
+fn with_staging<T>(config: &Config, f: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let staging = Path::new(&config.hybrid_mnt_dir);
+    if is_mounted(staging) {
+        bail!(
+            "{} is still mounted; another mount operation may be running",
+            staging.display()
+        );
+    }
+
+    mount_tmpfs(staging, &config.mountsource)?;
+    if let Err(e) = mount_change(staging, MountPropagationFlags::PRIVATE) {
+        log::warn!("Failed to make staging private: {}", e);
+    }
+
+    let result = f(staging);
+
+    // Overlays keep their own references to the layers, so the staging tmpfs can go.
+    if let Err(e) = unmount(staging, UnmountFlags::DETACH) {
+        log::warn!("Failed to unmount staging {}: {}", staging.display(), e);
+    }
+
+    result
+}
+
+// Rebuilds every non-magic mount the module touches. With `keep` false the module is left out,
+// so shared overlays come back with the remaining layers and its own mounts are dropped.
+fn rebuild_module(config: &Config, module_id: &str, keep: bool) -> Result<Vec<String>> {
+    let mut state = RuntimeState::load()?;
+    if state.mounts.is_empty() {
+        bail!("No mount records from the last boot; reboot once before using live mount changes");
+    }
+    if state.magic_modules.iter().any(|m| m == module_id) {
+        bail!(
+            "Module {} is magic-mounted; live mount changes only support overlay modules",
+            module_id
+        );
+    }
+
+    let mut modules = inventory::scan(&config.moduledir, config)?;
+    if keep {
+        if !modules.iter().any(|m| m.id == module_id) {
+            bail!("Module {} not found or disabled", module_id);
+        }
+    } else {
+        modules.retain(|m| m.id != module_id);
+    }
+
+    let recorded: Vec<MountRecord> = state
+        .mounts
+        .iter()
+        .filter(|r| r.kind != MountKind::Magic && r.modules.iter().any(|m| m == module_id))
+        .cloned()
+        .collect();
+    if !keep && recorded.is_empty() {
+        bail!("Module {} has no recorded mounts", module_id);
+    }
+
+    let touched = with_staging(config, |staging| {
+        let draft = planner::generate(config, &modules, staging)?;
+
+        let mut targets: BTreeSet<String> = recorded.iter().map(|r| r.target.clone()).collect();
+        let mut involved: BTreeSet<String> = BTreeSet::new();
+        for op in &draft.overlay_ops {
+            let op_modules = executor::overlay_op_modules(op);
+            if op_modules.iter().any(|m| m == module_id) || targets.contains(&op.target) {
+                targets.insert(op.target.clone());
+                involved.extend(op_modules);
+            }
+        }
+        if keep {
+            involved.insert(module_id.to_string());
+        }
+
+        let to_sync: Vec<Module> = modules
+            .iter()
+            .filter(|m| involved.contains(&m.id))
+            .cloned()
+            .collect();
+        sync::perform_sync(&to_sync, staging, config).context("Failed to stage modules")?;
+
+        let plan = planner::generate(config, &to_sync, staging)?;
+
+        for target in &targets {
+            let had_overlay = state
+                .mounts
+                .iter()
+                .any(|r| r.kind == MountKind::Overlay && r.target == *target);
+            if had_overlay {
+                detach(target);
+            }
+            state.mounts.retain(|r| r.target != *target);
+            state.fingerprints.retain(|f| f.target != *target);
+
+            let Some(op) = plan.overlay_ops.iter().find(|op| op.target == *target) else {
+                continue;
+            };
+            let op_modules: Vec<String> = executor::overlay_op_modules(op)
+                .into_iter()
+                .filter(|m| involved.contains(m))
+                .collect();
+            let umount = !config.disable_umount
+                && !op_modules.iter().any(|m| plan.skip_umount_ids.contains(m));
+            executor::mount_overlay_op(op, config, umount)
+                .with_context(|| format!("Failed to remount overlay on {}", target))?;
+            state
+                .mounts
+                .push(executor::overlay_record(target, op_modules, config));
+            state
+                .fingerprints
+                .push(verify::fingerprint(target, &op.lowerdirs));
+        }
+
+        for record in recorded.iter().filter(|r| r.kind == MountKind::Rootdir) {
+            detach(&record.target);
+            state.mounts.retain(|r| r.target != record.target);
+        }
+        for op in plan
+            .rootdir_ops
+            .iter()
+            .filter(|op| keep && op.module_id == module_id)
+        {
+            executor::mount_rootdir_op(
+                op,
+                !config.disable_umount && !plan.skip_umount_ids.contains(module_id),
+            )?;
+            targets.insert(op.target.to_string_lossy().to_string());
+            state.mounts.push(executor::rootdir_record(op));
+        }
+
+        executor::release_images(&plan);
+        Ok(targets.into_iter().collect::<Vec<String>>())
+    })?;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if !config.disable_umount
+        && let Err(e) = umount_mgr::commit()
+    {
+        log::warn!("try_umount commit failed: {}", e);
+    }
+
+    refresh_module_lists(&mut state);
+    state.save()?;
+
+    Ok(touched)
+}
+
+pub fn remount_module(config: &Config, module_id: &str) -> Result<Vec<String>> {
+    rebuild_module(config, module_id, true)
+}
+
+pub fn unmount_module(config: &Config, module_id: &str) -> Result<Vec<String>> {
+    rebuild_module(config, module_id, false)
+}
@@ -0,0 +1,186 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::{Config, MaintenanceConfig},
+    core::{artifacts, granary, inventory, ops::sync, state::RuntimeState},
+    defs, utils,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    Done,
+    Skipped,
+    Failed,
+}
+
+impl TaskStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Done => "done",
+            Self::Skipped => "skipped",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskOutcome {
+    pub task: String,
+    pub status: TaskStatus,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceSummary {
+    pub started: u64,
+    pub millis: u64,
+    pub tasks: Vec<TaskOutcome>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+pub fn last() -> Option<MaintenanceSummary> {
+    let content = fs::read_to_string(defs::MAINTENANCE_FILE).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// A device without a battery counts as plugged in.
+fn is_charging() -> bool {
+    let Ok(entries) = fs::read_dir(POWER_SUPPLY_DIR) else {
+        return true;
+    };
+    let batteries: Vec<String> = entries
+        .flatten()
+        .filter(|e| fs::read_to_string(e.path().join("type")).is_ok_and(|t| t.trim() == "Battery"))
+        .filter_map(|e| fs::read_to_string(e.path().join("status")).ok())
+        .collect();
+    batteries.is_empty()
+        || batteries
+            .iter()
+            .any(|s| matches!(s.trim(), "Charging" | "Full"))
+}
+
+fn is_idle(max_load: f64) -> bool {
+    fs::read_to_string("/proc/loadavg")
+        .ok()
+        .and_then(|s| s.split_whitespace().next()?.parse::<f64>().ok())
+        .is_some_and(|load| load < max_load)
+}
+
+fn outcome(task: &str, result: Result<(TaskStatus, String)>) -> TaskOutcome {
+    let (status, detail) = result.unwrap_or_else(|e| (TaskStatus::Failed, format!("{:#}", e)));
+    log::info!("Maintenance: {} {}: {}", task, status.as_str(), detail);
+    TaskOutcome {
+        task: task.to_string(),
+        status,
+        detail,
+    }
+}
+
+fn verify_manifests(config: &Config) -> Result<(TaskStatus, String)> {
+    let modules = inventory::scan(&config.moduledir, config).context("Failed to scan modules")?;
+    let changed = sync::changed_since_sync(&modules);
+    if changed.is_empty() {
+        return Ok((
+            TaskStatus::Done,
+            format!("{} module(s) match their last sync", modules.len()),
+        ));
+    }
+    Ok((
+        TaskStatus::Done,
+        format!("changed since last sync: {}", changed.join(", ")),
+    ))
+}
+
+pub fn run(config: &Config) -> MaintenanceSummary {
+    let started = now();
+    let clock = Instant::now();
+    log::info!(">> Maintenance started");
+
+    let tasks = vec![
+        outcome(
+            "snapshots",
+            Ok((
+                TaskStatus::Done,
+                format!("evicted {}", granary::prune(&config.backup, None)),
+            )),
+        ),
+        outcome(
+            "artifacts",
+            artifacts::prune(&config.artifacts)
+                .map(|n| (TaskStatus::Done, format!("removed {}", n))),
+        ),
+        outcome("manifests", verify_manifests(config)),
+        outcome(
+            "logs",
+            Ok((
+                TaskStatus::Done,
+                format!(
+                    "removed {} stale rotated log(s)",
+                    utils::prune_rotated_logs()
+                ),
+            )),
+        ),
+    ];
+
+    let summary = MaintenanceSummary {
+        started,
+        millis: clock.elapsed().as_millis() as u64,
+        tasks,
+    };
+
+    let saved = serde_json::to_string_pretty(&summary)
+        .map_err(Into::into)
+        .and_then(|json| utils::atomic_write(defs::MAINTENANCE_FILE, json));
+    if let Err(e) = saved {
+        log::warn!("Failed to save maintenance summary: {:#}", e);
+    }
+    if Path::new(defs::STATE_FILE).exists()
+        && let Ok(mut state) = RuntimeState::load()
+    {
+        state.maintenance = Some(summary.clone());
+        if let Err(e) = state.save() {
+            log::warn!("Failed to record maintenance in state: {:#}", e);
+        }
+    }
+
+    summary
+}
+
+// Runs for the lifetime of the daemon. A due run waits until the device is idle and, unless
+// configured otherwise, charging.
+pub fn watch(config: Config, settings: MaintenanceConfig) {
+    let interval = settings.interval_days.max(1) * 24 * 60 * 60;
+    log::info!(
+        ">> Maintenance scheduled every {} day(s)",
+        interval / (24 * 60 * 60)
+    );
+
+    loop {
+        let due = last().is_none_or(|s| now().saturating_sub(s.started) >= interval);
+        if due && (!settings.require_charging || is_charging()) && is_idle(settings.max_load) {
+            run(&config);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -3,16 +3,21 @@
 
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{Result, ensure};
 
 use crate::{
-    conf::config::Config,
+    conf::config::{Config, MountStage},
     core::{
-        inventory,
+        capacity, coexist, companion, defer, diagnostics, inventory,
         inventory::model as modules,
-        ops::{executor, planner, sync},
-        state, storage,
+        metrics, namespaces, notify,
+        ops::{executor, plan_diff::PlanRecord, planner, preflight, sync, verify},
+        progress::{self, Phase},
+        report,
+        scripts::{self, ModuleScript},
+        sepolicy, state, storage,
         storage::StorageHandle,
+        visibility,
     },
 };
 
@@ -25,28 +30,36 @@ pub struct StorageReady {
 pub struct ModulesReady {
     pub handle: StorageHandle,
     pub modules: Vec<inventory::Module>,
+    // Earlier-stage modules whose APEX overrides were deferred to this pass.
+    pub apex_modules: Vec<inventory::Module>,
+    pub scripts: Vec<ModuleScript>,
 }
 
 pub struct Planned {
     pub handle: StorageHandle,
     pub plan: planner::MountPlan,
+    pub scripts: Vec<ModuleScript>,
 }
 
 pub struct Executed {
     pub handle: StorageHandle,
     pub plan: planner::MountPlan,
     pub result: executor::ExecutionResult,
+    pub scripts: Vec<ModuleScript>,
 }
 
 pub struct MountController<S> {
     config: Config,
+    // None runs every module in a single pass.
+    stage: Option<MountStage>,
     state: S,
 }
 
 impl MountController<Init> {
-    pub fn new(config: Config) -> Self {
+    pub fn new(config: Config, stage: Option<MountStage>) -> Self {
         Self {
             config,
+            stage,
             state: Init,
         }
     }
@@ -56,18 +69,22 @@ impl MountController<Init> {
         mnt_base: &Path,
         img_path: &Path,
     ) -> Result<MountController<StorageReady>> {
+        progress::report(Phase::Storage);
+
+        if self.stage == Some(MountStage::Service) {
+            return self.resume_storage();
+        }
+
         let handle = storage::setup(
             mnt_base,
             img_path,
             &self.config.moduledir,
+            storage::ImageSpec::from_config(&self.config),
             matches!(
                 self.config.overlay_mode,
                 crate::conf::config::OverlayMode::Ext4
             ),
-            matches!(
-                self.config.overlay_mode,
-                crate::conf::config::OverlayMode::Erofs
-            ),
+            storage::resolve_packed_fs(&self.config.overlay_mode),
             &self.config.mountsource,
             self.config.disable_umount,
         )?;
@@ -76,6 +93,34 @@ impl MountController<Init> {
 
         Ok(MountController {
             config: self.config,
+            stage: self.stage,
+            state: StorageReady { handle },
+        })
+    }
+
+    // The service stage mounts from the storage post-fs-data already prepared and synced.
+    fn resume_storage(self) -> Result<MountController<StorageReady>> {
+        let previous = state::RuntimeState::load()?;
+        ensure!(
+            previous.mount_point.is_dir(),
+            "No storage from the post-fs-data stage to resume"
+        );
+
+        let handle = StorageHandle {
+            mount_point: previous.mount_point,
+            mode: previous.storage_mode,
+            backing_image: None,
+            final_target: None,
+        };
+
+        log::info!(
+            ">> Storage Backend: [{}] (resumed)",
+            handle.mode.to_uppercase()
+        );
+
+        Ok(MountController {
+            config: self.config,
+            stage: self.stage,
             state: StorageReady { handle },
         })
     }
@@ -83,18 +128,77 @@ impl MountController<Init> {
 
 impl MountController<StorageReady> {
     pub fn scan_and_sync(mut self) -> Result<MountController<ModulesReady>> {
-        let modules = inventory::scan(&self.config.moduledir, &self.config)?;
+        progress::report(Phase::Scan);
+        let mut modules = inventory::scan(&self.config.moduledir, &self.config)?;
 
         log::info!(
             ">> Inventory Scan: Found {} enabled modules.",
             modules.len()
         );
 
-        sync::perform_sync(&modules, &self.state.handle.mount_point)?;
+        defer::apply(
+            &self.config,
+            &mut modules,
+            self.stage == Some(MountStage::Service),
+        );
+
+        // Every enabled module's scripts run, whichever stage or backend mounts its files.
+        let scripts = if self.config.scripts.enabled {
+            scripts::discover(&self.config, &modules, self.stage)
+        } else {
+            Vec::new()
+        };
+
+        // Before coexistence drops modules another manager mounts, which still own their files.
+        if self.stage != Some(MountStage::Service)
+            && let Err(e) = companion::apply(&self.config, &modules)
+        {
+            log::warn!("Failed to apply module companion files: {:#}", e);
+        }
+
+        coexist::apply(&self.config, &mut modules)?;
+
+        if self.config.sepolicy.enabled
+            && self.stage != Some(MountStage::Service)
+            && let Err(e) = sepolicy::apply(&self.config, &modules)
+        {
+            log::warn!("Failed to apply module sepolicy rules: {:#}", e);
+        }
+
+        if self.stage == Some(MountStage::Service) {
+            let deferred_apex = state::RuntimeState::load()
+                .map(|s| s.deferred_apex)
+                .unwrap_or_default();
+            visibility::retain_global(&self.config, &mut modules);
+            let (modules, apex_modules): (Vec<_>, Vec<_>) = modules
+                .into_iter()
+                .filter(|m| m.rules.stage() == MountStage::Service || deferred_apex.contains(&m.id))
+                .partition(|m| m.rules.stage() == MountStage::Service);
+            log::info!(">> Stage [service]: {} module(s)", modules.len());
+
+            return Ok(MountController {
+                config: self.config,
+                stage: self.stage,
+                state: ModulesReady {
+                    handle: self.state.handle,
+                    modules,
+                    apex_modules,
+                    scripts,
+                },
+            });
+        }
+
+        progress::report(Phase::Sync);
+        sync::perform_sync(&modules, &self.state.handle.mount_point, &self.config)?;
 
-        if self.state.handle.mode == "erofs_staging" {
+        // Only the ext4 image is rebuilt on /data; the other backends live in memory.
+        if self.state.handle.mode == "ext4" {
+            capacity::track(&self.config, &self.state.handle.mount_point);
+        }
+
+        if self.state.handle.mode.ends_with("_staging") {
             let needs_magic = modules.iter().any(|m| {
-                m.rules.default_mode == inventory::MountMode::Magic
+                m.rules.mode() == inventory::MountMode::Magic
                     || m.rules
                         .paths
                         .values()
@@ -111,11 +215,21 @@ impl MountController<StorageReady> {
 
         self.state.handle.commit(self.config.disable_umount)?;
 
+        // Service-stage modules are synced now too, so the storage is complete before it is sealed.
+        if let Some(stage) = self.stage {
+            modules.retain(|m| m.rules.stage() == stage);
+            log::info!(">> Stage [post-fs-data]: {} module(s)", modules.len());
+        }
+        visibility::retain_global(&self.config, &mut modules);
+
         Ok(MountController {
             config: self.config,
+            stage: self.stage,
             state: ModulesReady {
                 handle: self.state.handle,
                 modules,
+                apex_modules: Vec::new(),
+                scripts,
             },
         })
     }
@@ -123,17 +237,45 @@ impl MountController<StorageReady> {
 
 impl MountController<ModulesReady> {
     pub fn generate_plan(self) -> Result<MountController<Planned>> {
-        let plan = planner::generate(
+        progress::report(Phase::Plan);
+        let mut plan = planner::generate(
             &self.config,
             &self.state.modules,
             &self.state.handle.mount_point,
         )?;
+        planner::plan_deferred_apex(
+            &self.config,
+            &mut plan,
+            &self.state.apex_modules,
+            &self.state.handle.mount_point,
+        );
+
+        let mut issues = preflight::check_plan(
+            &plan,
+            &self.state.modules,
+            &self.config,
+            &self.state.handle.mount_point,
+        );
+        let suppressed = diagnostics::suppress(&mut issues, &self.config.suppressed_issues);
+        if suppressed > 0 {
+            log::info!("Preflight: {} suppressed issue(s)", suppressed);
+        }
+        for issue in issues {
+            log::error!(
+                "Preflight {} [{}]: {}",
+                issue.code.as_str(),
+                issue.context,
+                issue.message
+            );
+        }
 
         Ok(MountController {
             config: self.config,
+            stage: self.stage,
             state: Planned {
                 handle: self.state.handle,
                 plan,
+                scripts: self.state.scripts,
             },
         })
     }
@@ -142,15 +284,18 @@ impl MountController<ModulesReady> {
 impl MountController<Planned> {
     pub fn execute(self) -> Result<MountController<Executed>> {
         log::info!(">> Link Start! Executing mount plan...");
+        progress::report(Phase::Mount);
 
         let result = executor::execute(&self.state.plan, &self.config)?;
 
         Ok(MountController {
             config: self.config,
+            stage: self.stage,
             state: Executed {
                 handle: self.state.handle,
                 plan: self.state.plan,
                 result,
+                scripts: self.state.scripts,
             },
         })
     }
@@ -158,11 +303,15 @@ impl MountController<Planned> {
 
 impl MountController<Executed> {
     pub fn finalize(self) -> Result<()> {
-        modules::update_description(
-            &self.state.handle.mode,
-            self.state.result.overlay_module_ids.len(),
-            self.state.result.magic_module_ids.len(),
-        );
+        let resumed = self.stage == Some(MountStage::Service);
+
+        let mut record = PlanRecord::from_plan(&self.state.plan, &self.state.handle.mount_point);
+        if resumed && let Ok(earlier) = PlanRecord::load() {
+            record.merge(earlier);
+        }
+        if let Err(e) = record.save() {
+            log::warn!("Failed to record mount plan: {:#}", e);
+        }
 
         let mut active_mounts: Vec<String> = self
             .state
@@ -175,19 +324,80 @@ impl MountController<Executed> {
         active_mounts.sort();
         active_mounts.dedup();
 
-        let state = state::RuntimeState::new(
+        if !self.state.result.mount_failures.is_empty() {
+            notify::queue(
+                &self.config.notify,
+                "mount_failures",
+                &format!(
+                    "{} mount(s) failed during boot",
+                    self.state.result.mount_failures.len()
+                ),
+                serde_json::json!({ "failures": &self.state.result.mount_failures }),
+            );
+        }
+
+        let unverified = verify::verify(&self.state.result.mounts);
+        let fingerprints: Vec<verify::Fingerprint> = self
+            .state
+            .plan
+            .overlay_ops
+            .iter()
+            .filter(|op| {
+                self.state
+                    .result
+                    .mounts
+                    .iter()
+                    .any(|m| m.target == op.target)
+                    && !unverified.iter().any(|u| u.target == op.target)
+            })
+            .map(|op| verify::fingerprint(&op.target, &op.lowerdirs))
+            .collect();
+        let script_failures = scripts::run(&self.config, &self.state.scripts);
+
+        let mut state = state::RuntimeState::new(
             self.state.handle.mode,
             self.state.handle.mount_point,
             self.state.result.overlay_module_ids,
             self.state.result.magic_module_ids,
             active_mounts,
+            self.state.result.mount_failures,
+            self.state.result.mounts,
+        );
+        state.script_failures = script_failures;
+        state.unverified_mounts = unverified;
+        state.fingerprints = fingerprints;
+        state.metrics = metrics::collect();
+        state.deferred_apex = self.state.plan.deferred_apex.clone();
+        let stage_report = report::stage_report(&self.config, self.stage, &self.state.plan, &state);
+        if resumed && let Ok(earlier) = state::RuntimeState::load() {
+            state.merge(earlier);
+        }
+
+        modules::update_description(
+            &state.storage_mode,
+            state.overlay_modules.len(),
+            state.magic_modules.len(),
         );
 
         if let Err(e) = state.save() {
             log::error!("Failed to save runtime state: {:#}", e);
         }
 
+        if !self.config.early_namespaces.is_empty()
+            && let Err(e) = namespaces::replay(&self.config)
+        {
+            log::warn!("Failed to replay mounts into early namespaces: {:#}", e);
+        }
+
+        if let Err(e) = report::save(stage_report) {
+            log::warn!("Failed to write boot report: {:#}", e);
+        }
+
         log::info!(">> System operational. Mount sequence complete.");
+        // service.sh always follows post-fs-data with the service pass, which finishes the boot.
+        if self.stage != Some(MountStage::PostFsData) {
+            progress::report(Phase::Complete);
+        }
 
         Ok(())
     }
@@ -0,0 +1,81 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    progress::{self, PhaseTiming},
+    trace,
+};
+
+static MODULES: Mutex<BTreeMap<String, ModuleTiming>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModuleTiming {
+    pub id: String,
+    pub sync_ms: u64,
+    // Every mount the module took part in counts in full, shared overlays included.
+    pub mount_ms: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Metrics {
+    pub phases: Vec<PhaseTiming>,
+    pub modules: Vec<ModuleTiming>,
+}
+
+impl Metrics {
+    // Phases stay in the order they ran; module times add up across stages.
+    pub fn merge(&mut self, earlier: Self) {
+        let mut phases = earlier.phases;
+        phases.append(&mut self.phases);
+        self.phases = phases;
+
+        for timing in earlier.modules {
+            match self.modules.iter_mut().find(|m| m.id == timing.id) {
+                Some(current) => {
+                    current.sync_ms += timing.sync_ms;
+                    current.mount_ms += timing.mount_ms;
+                }
+                None => self.modules.push(timing),
+            }
+        }
+        self.modules.sort_by(|a, b| a.id.cmp(&b.id));
+    }
+}
+
+fn update(id: &str, apply: impl FnOnce(&mut ModuleTiming)) {
+    if let Ok(mut modules) = MODULES.lock() {
+        let timing = modules
+            .entry(id.to_string())
+            .or_insert_with(|| ModuleTiming {
+                id: id.to_string(),
+                ..Default::default()
+            });
+        apply(timing);
+    }
+}
+
+pub fn record_sync(id: &str, elapsed: Duration) {
+    update(id, |t| t.sync_ms += elapsed.as_millis() as u64);
+    trace::complete(format!("sync {}", id), "sync", elapsed);
+}
+
+pub fn record_mount(ids: &[String], elapsed: Duration) {
+    for id in ids {
+        update(id, |t| t.mount_ms += elapsed.as_millis() as u64);
+    }
+    trace::complete(format!("mount {}", ids.join(",")), "mount", elapsed);
+}
+
+pub fn collect() -> Metrics {
+    Metrics {
+        phases: progress::timings(),
+        modules: MODULES
+            .lock()
+            .map(|m| m.values().cloned().collect())
+            .unwrap_or_default(),
+    }
+}
@@ -1,10 +1,50 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod artifacts;
+pub mod capacity;
+pub mod capture;
+pub mod coexist;
+pub mod companion;
+pub mod compat;
+pub mod daemon;
+pub mod debloat;
+pub mod defer;
+pub mod denylist;
+pub mod diagnostics;
+pub mod doctor;
+pub mod granary;
+pub mod info;
+pub mod installer;
+pub mod integrity;
 pub mod inventory;
+pub mod jobs;
+pub mod known_good;
+pub mod lint;
+pub mod live;
+pub mod maintenance;
 pub mod manager;
+pub mod metrics;
+pub mod namespaces;
+pub mod notify;
 pub mod ops;
+pub mod packager;
+pub mod privapp;
+pub mod profile;
+pub mod progress;
+pub mod recovery;
+pub mod report;
+pub mod rw;
+pub mod safe_mode;
+pub mod scripts;
+pub mod sepolicy;
 pub mod state;
+pub mod stealth;
 pub mod storage;
+pub mod trace;
+pub mod uninstall;
+pub mod visibility;
+pub mod watchdog;
+pub mod webui;
 
 pub use manager::MountController;
@@ -0,0 +1,186 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::CString,
+    fs::{self, File},
+    io,
+    os::{
+        fd::{AsFd, AsRawFd},
+        unix::process::CommandExt,
+    },
+    process::{Command, Stdio},
+};
+
+use anyhow::{Result, bail};
+use procfs::process::all_processes;
+use rustix::{
+    fs::CWD,
+    mount::{MoveMountFlags, OpenTreeFlags, move_mount, open_tree},
+};
+use serde::Serialize;
+
+use crate::{
+    conf::config::Config,
+    core::{
+        state::{MountKind, RuntimeState},
+        stealth,
+    },
+};
+
+const PER_USER_RANGE: u32 = 100_000;
+const FIRST_APP_UID: u32 = 10_000;
+
+// A private mount namespace of a service that was started before our mounts existed, so they never
+// propagated into it. App namespaces are left out: missing mounts there are the point of umount.
+#[derive(Debug, Serialize)]
+pub struct EarlyNamespace {
+    pub namespace: String,
+    pub pid: i32,
+    pub name: String,
+    pub processes: usize,
+    pub missing: Vec<String>,
+    pub allowlisted: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub replayed: Vec<String>,
+}
+
+fn our_targets(state: &RuntimeState) -> HashSet<String> {
+    state
+        .mounts
+        .iter()
+        .filter(|m| m.kind != MountKind::Magic)
+        .map(|m| m.target.clone())
+        .collect()
+}
+
+fn global_namespace() -> String {
+    fs::read_link("/proc/1/ns/mnt")
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+pub fn scan(config: &Config) -> Vec<EarlyNamespace> {
+    let state = RuntimeState::load().unwrap_or_default();
+    let targets = our_targets(&state);
+    if targets.is_empty() {
+        return Vec::new();
+    }
+    let global = global_namespace();
+
+    let mut namespaces: BTreeMap<String, EarlyNamespace> = BTreeMap::new();
+    for process in all_processes().into_iter().flatten().flatten() {
+        if process
+            .uid()
+            .is_ok_and(|uid| uid % PER_USER_RANGE >= FIRST_APP_UID)
+        {
+            continue;
+        }
+        let Ok(namespace) = fs::read_link(format!("/proc/{}/ns/mnt", process.pid)) else {
+            continue;
+        };
+        let namespace = namespace.to_string_lossy().to_string();
+        if namespace == global {
+            continue;
+        }
+        if let Some(known) = namespaces.get_mut(&namespace) {
+            known.processes += 1;
+            continue;
+        }
+
+        let visible: HashSet<String> = stealth::visible_targets(process.pid, config, &targets)
+            .into_iter()
+            .collect();
+        let mut missing: Vec<String> = targets.difference(&visible).cloned().collect();
+        if missing.is_empty() {
+            continue;
+        }
+        missing.sort();
+        let name = process.stat().map(|s| s.comm).unwrap_or_default();
+        namespaces.insert(
+            namespace.clone(),
+            EarlyNamespace {
+                namespace,
+                pid: process.pid,
+                allowlisted: config.early_namespaces.contains(&name),
+                name,
+                processes: 1,
+                missing,
+                replayed: Vec::new(),
+            },
+        );
+    }
+    namespaces.into_values().collect()
+}
+
+// The mount is cloned here, in the global namespace, and a forked child joins the target namespace
+// to attach it; a multithreaded process cannot change its own mount namespace.
+fn replay_mount(pid: i32, target: &str) -> Result<()> {
+    let tree = open_tree(
+        CWD,
+        target,
+        OpenTreeFlags::OPEN_TREE_CLONE | OpenTreeFlags::OPEN_TREE_CLOEXEC,
+    )?;
+    let namespace = File::open(format!("/proc/{}/ns/mnt", pid))?;
+    let target = CString::new(target)?;
+    let ns_fd = namespace.as_raw_fd();
+
+    let mut command = Command::new("true");
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setns(ns_fd, libc::CLONE_NEWNS) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            move_mount(
+                tree.as_fd(),
+                c"",
+                CWD,
+                target.as_c_str(),
+                MoveMountFlags::MOVE_MOUNT_F_EMPTY_PATH,
+            )?;
+            Ok(())
+        });
+    }
+    command.status()?;
+    Ok(())
+}
+
+// Replays the missing mounts into every allowlisted namespace, parents before children.
+pub fn replay(config: &Config) -> Result<Vec<EarlyNamespace>> {
+    let own = fs::read_link("/proc/self/ns/mnt")
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if own != global_namespace() {
+        bail!("Replaying mounts has to run in the global mount namespace");
+    }
+
+    let mut namespaces = scan(config);
+    for ns in namespaces.iter_mut().filter(|ns| ns.allowlisted) {
+        ns.missing.sort_by_key(|t| t.matches('/').count());
+        for target in &ns.missing {
+            match replay_mount(ns.pid, target) {
+                Ok(()) => ns.replayed.push(target.clone()),
+                Err(e) => log::warn!(
+                    "Failed to replay {} into {} (pid {}): {:#}",
+                    target,
+                    ns.name,
+                    ns.pid,
+                    e
+                ),
+            }
+        }
+        log::info!(
+            "Replayed {} of {} mount(s) into {} (pid {})",
+            ns.replayed.len(),
+            ns.missing.len(),
+            ns.name,
+            ns.pid
+        );
+    }
+    Ok(namespaces)
+}
@@ -0,0 +1,166 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{conf::config::NotifyConfig, defs, utils};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub event: String,
+    pub timestamp: u64,
+    pub message: String,
+    #[serde(default)]
+    pub details: Value,
+}
+
+impl Event {
+    fn new(event: &str, message: &str, details: Value) -> Self {
+        Self {
+            event: event.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            message: message.to_string(),
+            details,
+        }
+    }
+}
+
+// Nothing can be delivered during post-fs-data (no am, usually no network), so events are
+// queued and sent once boot-completed runs.
+pub fn queue(config: &NotifyConfig, event: &str, message: &str, details: Value) {
+    if !config.is_enabled() {
+        return;
+    }
+
+    let event = Event::new(event, message, details);
+
+    let result = serde_json::to_string(&event)
+        .map_err(anyhow::Error::from)
+        .and_then(|line| {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(defs::NOTIFY_QUEUE_FILE)?;
+            writeln!(file, "{}", line)?;
+            Ok(())
+        });
+
+    if let Err(e) = result {
+        log::warn!("Notify: failed to queue {} event: {:#}", event.event, e);
+    }
+}
+
+fn run(command: &mut Command, payload: &str) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(payload.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn post(url: &str, payload: &str) -> Result<()> {
+    if let Some(curl) = utils::find_in_path("curl") {
+        return run(
+            Command::new(curl)
+                .args(["-fsS", "-m", "15", "-H", "Title: Hybrid Mount"])
+                .args(["--data-binary", "@-", url]),
+            payload,
+        );
+    }
+
+    let wget = utils::find_in_path("wget").context("Neither curl nor wget is available")?;
+    run(
+        Command::new(wget)
+            .args(["-q", "-O", "/dev/null", "-T", "15"])
+            .arg(format!("--post-data={}", payload))
+            .arg(url),
+        "",
+    )
+}
+
+pub fn deliver(config: &NotifyConfig, event: &Event) {
+    let Ok(payload) = serde_json::to_string(event) else {
+        return;
+    };
+
+    if let Some(action) = &config.broadcast {
+        let result = run(
+            Command::new("am")
+                .args(["broadcast", "-a", action])
+                .args(["--es", "event", &event.event])
+                .args(["--es", "payload", &payload]),
+            "",
+        );
+        if let Err(e) = result {
+            log::warn!("Notify: am broadcast failed: {:#}", e);
+        }
+    }
+
+    if let Some(url) = &config.ntfy_url
+        && let Err(e) = post(url, &payload)
+    {
+        log::warn!("Notify: failed to post to {}: {:#}", url, e);
+    }
+
+    if let Some(script) = &config.script {
+        let result = run(
+            Command::new("sh")
+                .arg(script)
+                .env("HYBRID_MOUNT_EVENT", &event.event),
+            &payload,
+        );
+        if let Err(e) = result {
+            log::warn!("Notify: script {} failed: {:#}", script.display(), e);
+        }
+    }
+}
+
+// For events raised once the system is up, e.g. by the watchdog.
+pub fn send(config: &NotifyConfig, event: &str, message: &str, details: Value) {
+    if config.is_enabled() {
+        deliver(config, &Event::new(event, message, details));
+    }
+}
+
+pub fn flush(config: &NotifyConfig) -> Result<usize> {
+    let queue = Path::new(defs::NOTIFY_QUEUE_FILE);
+    let Ok(content) = fs::read_to_string(queue) else {
+        return Ok(0);
+    };
+    fs::remove_file(queue).context("Failed to clear notification queue")?;
+
+    let events: Vec<Event> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    for event in &events {
+        deliver(config, event);
+    }
+    Ok(events.len())
+}
@@ -2,94 +2,327 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
     path::{Path, PathBuf},
+    time::Instant,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use rustix::mount::{MountFlags, UnmountFlags, mount_remount, unmount};
 
 use crate::{
     conf::config,
-    core::ops::planner::MountPlan,
+    core::{
+        metrics,
+        ops::{
+            planner::{ImageLayer, MountPlan, OverlayOperation, RootdirOperation},
+            verify,
+        },
+        rw,
+        state::{MountKind, MountRecord},
+        storage,
+    },
     defs,
     mount::{
+        explain::{self, MountFailure, MountOp},
         magic_mount,
         overlayfs::{self, utils::umount_dir},
         umount_mgr,
     },
+    sys::mount::bind_mount,
     utils,
 };
 
 pub struct ExecutionResult {
     pub overlay_module_ids: Vec<String>,
     pub magic_module_ids: Vec<String>,
+    pub mount_failures: Vec<MountFailure>,
+    pub mounts: Vec<MountRecord>,
+}
+
+pub fn mount_rootdir_op(op: &RootdirOperation, umount: bool) -> Result<()> {
+    bind_mount(&op.source, &op.target, false)
+        .with_context(|| format!("bind {} -> {}", op.source.display(), op.target.display()))?;
+
+    if let Err(e) = mount_remount(&op.target, MountFlags::RDONLY | MountFlags::BIND, "") {
+        log::warn!("make file {} ro: {e:#?}", op.target.display());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if umount && let Err(e) = umount_mgr::send_umountable(&op.target) {
+        log::warn!(
+            "Failed to schedule unmount for {}: {}",
+            op.target.display(),
+            e
+        );
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let _ = umount;
+
+    Ok(())
+}
+
+// Pinned files go into a layer of their own above every module. Directories on the way mirror the
+// real target so the merged view keeps their mode and context.
+fn build_pin_layer(op: &OverlayOperation) -> Result<PathBuf> {
+    let target = Path::new(&op.target);
+    let layer = Path::new(defs::PINS_DIR).join(op.target.trim_start_matches('/').replace('/', "_"));
+    if layer.exists() {
+        fs::remove_dir_all(&layer)?;
+    }
+    utils::ensure_dir_exists(&layer)?;
+    utils::copy_entry(target, &layer)?;
+
+    for pin in &op.pinned {
+        let mut prefix = PathBuf::new();
+        if let Some(parent) = pin.relative.parent() {
+            for component in parent.components() {
+                prefix.push(component);
+                let dst = layer.join(&prefix);
+                if dst.is_dir() {
+                    continue;
+                }
+                let src = target.join(&prefix);
+                if src.is_dir() {
+                    utils::copy_entry(&src, &dst)?;
+                } else {
+                    utils::ensure_dir_exists(&dst)?;
+                }
+            }
+        }
+        utils::copy_entry(&pin.source, &layer.join(&pin.relative)).with_context(|| {
+            format!(
+                "Failed to pin {} from {}",
+                pin.relative.display(),
+                pin.module_id
+            )
+        })?;
+    }
+
+    Ok(layer)
+}
+
+// The planner never mounts images, and another pass may have released the ones it used.
+fn mount_image(image: &ImageLayer) -> Result<()> {
+    if image.is_mounted() {
+        return Ok(());
+    }
+    storage::mount_packed_image(&image.image, &image.mountpoint, image.fs_type)
+        .with_context(|| format!("Failed to mount image of {}", image.module_id))
+}
+
+fn mount_images(op: &OverlayOperation) -> Result<()> {
+    op.images.iter().try_for_each(mount_image)
+}
+
+// Magic mount reads image-backed modules from their mounted image; one that fails to mount falls
+// back to its synced copy.
+fn magic_image_roots(plan: &MountPlan, magic_queue: &[String]) -> HashMap<String, PathBuf> {
+    plan.images
+        .iter()
+        .filter(|image| magic_queue.contains(&image.module_id))
+        .filter_map(|image| match mount_image(image) {
+            Ok(()) => Some((image.module_id.clone(), image.mountpoint.clone())),
+            Err(e) => {
+                log::warn!("{:#}, magic mount uses its synced copy", e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Overlays hold their own references to the image filesystems, so the mountpoints can go once
+// everything is mounted.
+pub fn release_images(plan: &MountPlan) {
+    for image in plan.images.iter().filter(|image| image.is_mounted()) {
+        if let Err(e) = unmount(&image.mountpoint, UnmountFlags::DETACH) {
+            log::warn!(
+                "Failed to release image mount {}: {}",
+                image.mountpoint.display(),
+                e
+            );
+        }
+    }
+}
+
+pub fn mount_overlay_op(
+    op: &OverlayOperation,
+    config: &config::Config,
+    umount: bool,
+) -> Result<()> {
+    mount_images(op)?;
+    let mut lowerdir_strings: Vec<String> = op
+        .lowerdirs
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect();
+
+    if !op.pinned.is_empty() {
+        let layer = build_pin_layer(op)?;
+        lowerdir_strings.insert(0, layer.display().to_string());
+    }
+
+    let (upper_opt, work_opt) = rw::layer_dirs(&op.partition_name, Path::new(&op.target)).unzip();
+
+    log::info!(
+        "Mounting {} [OVERLAY] (Layers: {})",
+        op.target,
+        lowerdir_strings.len()
+    );
+
+    overlayfs::overlayfs::mount_overlay(
+        &op.target,
+        &lowerdir_strings,
+        work_opt,
+        upper_opt,
+        &config.mountsource,
+    )?;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if umount && let Err(e) = umount_mgr::send_umountable(&op.target) {
+        log::warn!(
+            "Failed to schedule unmount for {}(kernel): {}",
+            op.target,
+            e
+        );
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let _ = umount;
+
+    Ok(())
+}
+
+pub fn overlay_record(target: &str, modules: Vec<String>, config: &config::Config) -> MountRecord {
+    MountRecord {
+        kind: MountKind::Overlay,
+        target: target.to_string(),
+        modules,
+        fs_type: "overlay".to_string(),
+        source: config.mountsource.clone(),
+    }
+}
+
+pub fn rootdir_record(op: &RootdirOperation) -> MountRecord {
+    let (fs_type, source) = verify::backing(&op.source, &verify::current_mountinfo());
+    MountRecord {
+        kind: MountKind::Rootdir,
+        target: op.target.to_string_lossy().to_string(),
+        modules: vec![op.module_id.clone()],
+        fs_type,
+        source,
+    }
+}
+
+fn magic_records(module_dir: &Path, config: &config::Config) -> Vec<MountRecord> {
+    let mountinfo = verify::current_mountinfo();
+    magic_mount::take_mounted_roots()
+        .into_iter()
+        .map(|root| {
+            let modules: BTreeSet<String> = root
+                .module_paths
+                .iter()
+                .filter_map(|p| p.strip_prefix(module_dir).ok()?.components().next())
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            let (fs_type, source) = match &root.source {
+                Some(file) => verify::backing(file, &mountinfo),
+                None => ("tmpfs".to_string(), config.mountsource.clone()),
+            };
+            MountRecord {
+                kind: MountKind::Magic,
+                target: root.target.to_string_lossy().to_string(),
+                modules: modules.into_iter().collect(),
+                fs_type,
+                source,
+            }
+        })
+        .collect()
+}
+
+pub fn overlay_op_modules(op: &OverlayOperation) -> Vec<String> {
+    op.lowerdirs
+        .iter()
+        .filter_map(|p| {
+            match op
+                .images
+                .iter()
+                .find(|image| p.starts_with(&image.mountpoint))
+            {
+                Some(image) => Some(image.module_id.clone()),
+                None => utils::extract_module_id(p),
+            }
+        })
+        .collect()
 }
 
 pub fn execute(plan: &MountPlan, config: &config::Config) -> Result<ExecutionResult> {
     let mut final_magic_ids: HashSet<String> = plan.magic_module_ids.iter().cloned().collect();
     let mut final_overlay_ids: HashSet<String> = HashSet::new();
+    let mut mount_failures = Vec::new();
+    let mut mounts = Vec::new();
+
+    if !plan.rootdir_ops.is_empty() {
+        log::info!(">> Phase 0: Rootdir bind mounts...");
+    }
+
+    for op in &plan.rootdir_ops {
+        log::info!(
+            "Mounting {} [ROOTDIR] from {}",
+            op.target.display(),
+            op.module_id
+        );
+
+        let umount = !config.disable_umount && !plan.skip_umount_ids.contains(&op.module_id);
+
+        let started = Instant::now();
+        let mounted = mount_rootdir_op(op, umount);
+        metrics::record_mount(std::slice::from_ref(&op.module_id), started.elapsed());
+
+        match mounted {
+            Ok(_) => mounts.push(rootdir_record(op)),
+            Err(e) => {
+                log::warn!(
+                    "Rootdir mount failed for {}: {}",
+                    op.target.display(),
+                    explain::explain(MountOp::Bind, &e)
+                );
+                mount_failures.push(MountFailure::new(
+                    MountOp::Bind,
+                    op.target.to_string_lossy(),
+                    &e,
+                ));
+            }
+        }
+    }
 
     log::info!(">> Phase 1: OverlayFS Execution...");
 
     for op in &plan.overlay_ops {
-        let involved_modules: Vec<String> = op
-            .lowerdirs
-            .iter()
-            .filter_map(|p| utils::extract_module_id(p))
-            .collect();
-
-        let lowerdir_strings: Vec<String> = op
-            .lowerdirs
-            .iter()
-            .map(|p| p.display().to_string())
-            .collect();
-
-        let rw_root = Path::new(defs::SYSTEM_RW_DIR);
-        let part_rw = rw_root.join(&op.partition_name);
-        let upper = part_rw.join("upperdir");
-        let work = part_rw.join("workdir");
-
-        let (upper_opt, work_opt) = if upper.exists() && work.exists() {
-            (Some(upper), Some(work))
-        } else {
-            (None, None)
-        };
+        let involved_modules = overlay_op_modules(op);
+        let umount = !config.disable_umount
+            && !involved_modules
+                .iter()
+                .any(|id| plan.skip_umount_ids.contains(id));
 
-        log::info!(
-            "Mounting {} [OVERLAY] (Layers: {})",
-            op.target,
-            lowerdir_strings.len()
-        );
+        let started = Instant::now();
+        let mounted = mount_overlay_op(op, config, umount);
+        metrics::record_mount(&involved_modules, started.elapsed());
 
-        match overlayfs::overlayfs::mount_overlay(
-            &op.target,
-            &lowerdir_strings,
-            work_opt,
-            upper_opt,
-            &config.mountsource,
-        ) {
+        match mounted {
             Ok(_) => {
-                for id in involved_modules {
-                    final_overlay_ids.insert(id);
-                }
-
-                #[cfg(any(target_os = "linux", target_os = "android"))]
-                if !config.disable_umount
-                    && let Err(e) = umount_mgr::send_umountable(&op.target)
-                {
-                    log::warn!(
-                        "Failed to schedule unmount for {}(kernel): {}",
-                        op.target,
-                        e
-                    );
-                }
+                final_overlay_ids.extend(involved_modules.iter().cloned());
+                mounts.push(overlay_record(&op.target, involved_modules, config));
             }
             Err(e) => {
                 log::warn!(
                     "OverlayFS failed for {}: {}. Fallback to Magic Mount.",
                     op.target,
-                    e
+                    explain::explain(MountOp::Overlay, &e)
                 );
+                mount_failures.push(MountFailure::new(MountOp::Overlay, &op.target, &e));
                 for id in involved_modules {
                     final_magic_ids.insert(id);
                 }
@@ -100,7 +333,12 @@ pub fn execute(plan: &MountPlan, config: &config::Config) -> Result<ExecutionRes
     final_overlay_ids.retain(|id| !final_magic_ids.contains(id));
 
     let mut magic_queue: Vec<String> = final_magic_ids.iter().cloned().collect();
-    magic_queue.sort();
+    magic_queue.sort_by(|a, b| {
+        config
+            .module_priority(b)
+            .cmp(&config.module_priority(a))
+            .then_with(|| a.cmp(b))
+    });
 
     if !magic_queue.is_empty() {
         let tempdir = PathBuf::from(&config.hybrid_mnt_dir).join("magic_workspace");
@@ -111,7 +349,10 @@ pub fn execute(plan: &MountPlan, config: &config::Config) -> Result<ExecutionRes
             tempdir.display()
         );
 
-        if matches!(config.overlay_mode, config::OverlayMode::Erofs) {
+        if matches!(
+            config.overlay_mode,
+            config::OverlayMode::Erofs | config::OverlayMode::Squashfs
+        ) {
             if tempdir.exists() {
                 crate::sys::mount::mount_tmpfs(&tempdir, "magic_ws")?;
                 #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -119,33 +360,63 @@ pub fn execute(plan: &MountPlan, config: &config::Config) -> Result<ExecutionRes
                     log::warn!("Failed to schedule unmount for magic_ws: {}", e);
                 }
             } else {
-                log::error!("Magic Mount anchor missing in EROFS image!");
+                log::error!("Magic Mount anchor missing in packed image!");
             }
         } else if !tempdir.exists() {
             std::fs::create_dir_all(&tempdir)?;
         }
 
         let module_dir = Path::new(&config.hybrid_mnt_dir);
-        let magic_need_ids: HashSet<String> = magic_queue.iter().cloned().collect();
-
-        if let Err(e) = magic_mount::magic_mount(
+        let image_roots = magic_image_roots(plan, &magic_queue);
+        let started = Instant::now();
+        let mounted = magic_mount::magic_mount(
             &tempdir,
             module_dir,
+            &image_roots,
             &config.mountsource,
-            &config.partitions,
-            magic_need_ids,
+            &config.extra_partitions(),
+            &magic_queue,
+            &plan.skip_umount_ids,
+            &config.resolve,
+            &config.effective_protected_paths(),
             !config.disable_umount,
-        ) {
-            log::error!("Magic Mount critical failure: {:#}", e);
-            final_magic_ids.clear();
+        );
+        metrics::record_mount(&magic_queue, started.elapsed());
+
+        match mounted {
+            Err(e) => {
+                log::error!(
+                    "Magic Mount critical failure: {}",
+                    explain::explain(MountOp::Bind, &e)
+                );
+                mount_failures.push(MountFailure::new(MountOp::Bind, "/", &e));
+                final_magic_ids.clear();
+            }
+            Ok(failures) => {
+                for (target, e) in &failures {
+                    log::error!(
+                        "Magic Mount failed for {}: {}",
+                        target.display(),
+                        explain::explain(MountOp::Bind, e)
+                    );
+                    mount_failures.push(MountFailure::new(
+                        MountOp::Bind,
+                        target.to_string_lossy(),
+                        e,
+                    ));
+                }
+                mounts.extend(magic_records(module_dir, config));
+            }
         }
     }
 
+    release_images(plan);
+
     if let Err(e) = umount_dir(&config.hybrid_mnt_dir) {
         log::warn!(
             "Failed to schedule unmount for {}: {}",
             config.hybrid_mnt_dir,
-            e
+            explain::explain(MountOp::Unmount, &e)
         );
     }
 
@@ -168,5 +439,7 @@ pub fn execute(plan: &MountPlan, config: &config::Config) -> Result<ExecutionRes
     Ok(ExecutionResult {
         overlay_module_ids: result_overlay,
         magic_module_ids: result_magic,
+        mount_failures,
+        mounts,
     })
 }
@@ -0,0 +1,206 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{core::ops::manifest, defs, utils};
+
+const STAGING_PREFIX: &str = ".tmp_";
+const BACKUP_PREFIX: &str = ".backup_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    // The staging copy is being filled; the live copy is untouched.
+    Staging,
+    // The staging copy is complete and is replacing the live copy.
+    Swapping,
+    // The live copy is being patched in place.
+    Patching,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    module: String,
+    phase: Phase,
+}
+
+// One module's sync in flight. Dropping it without `commit` leaves the record for `recover`.
+pub struct Journal {
+    path: PathBuf,
+    module: String,
+}
+
+pub fn staging_dir(target_base: &Path, module_id: &str) -> PathBuf {
+    target_base.join(format!("{}{}", STAGING_PREFIX, module_id))
+}
+
+pub fn backup_dir(target_base: &Path, module_id: &str) -> PathBuf {
+    target_base.join(format!("{}{}", BACKUP_PREFIX, module_id))
+}
+
+// Kept on /data rather than in the storage, which is rebuilt every boot and would take the
+// records of an interrupted sync with it.
+fn journal_path(module_id: &str) -> PathBuf {
+    Path::new(defs::SYNC_JOURNAL_DIR).join(format!("{}.json", module_id))
+}
+
+impl Journal {
+    pub fn begin(module_id: &str, phase: Phase) -> Result<Self> {
+        utils::ensure_dir_exists(defs::SYNC_JOURNAL_DIR)?;
+        let journal = Self {
+            path: journal_path(module_id),
+            module: module_id.to_string(),
+        };
+        journal.advance(phase)?;
+        Ok(journal)
+    }
+
+    pub fn advance(&self, phase: Phase) -> Result<()> {
+        let record = Record {
+            module: self.module.clone(),
+            phase,
+        };
+        utils::atomic_write(&self.path, serde_json::to_string(&record)?)
+    }
+
+    pub fn commit(self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("Failed to close sync journal for {}: {}", self.module, e);
+        }
+    }
+}
+
+fn remove_tree(path: &Path) {
+    let removed = match fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(_) => return,
+    };
+    if let Err(e) = removed {
+        log::warn!("Failed to remove {}: {}", path.display(), e);
+    }
+}
+
+// Staging is rolled back, a swap is rolled forward (or back when the staging copy is gone), and an
+// in-place patch drops the manifest so the next sync falls back to a full copy.
+fn replay(target_base: &Path, record: &Record) -> Result<&'static str> {
+    let id = &record.module;
+    let dst = target_base.join(id);
+    let staging = staging_dir(target_base, id);
+    let backup = backup_dir(target_base, id);
+
+    match record.phase {
+        Phase::Staging => {
+            remove_tree(&staging);
+            if !dst.exists() && backup.exists() {
+                fs::rename(&backup, &dst)?;
+            }
+            Ok("rolled back")
+        }
+        Phase::Swapping if staging.exists() => {
+            if dst.exists() {
+                if backup.exists() {
+                    remove_tree(&dst);
+                } else {
+                    fs::rename(&dst, &backup)?;
+                }
+            }
+            fs::rename(&staging, &dst)?;
+            remove_tree(&backup);
+            Ok("rolled forward")
+        }
+        Phase::Swapping => {
+            if dst.exists() {
+                remove_tree(&backup);
+                Ok("rolled forward")
+            } else {
+                if backup.exists() {
+                    fs::rename(&backup, &dst)?;
+                }
+                let _ = fs::remove_file(manifest::manifest_path(id));
+                Ok("rolled back")
+            }
+        }
+        Phase::Patching => {
+            let _ = fs::remove_file(manifest::manifest_path(id));
+            Ok("marked for a full copy")
+        }
+    }
+}
+
+// Runs before a sync touches the storage, so nothing is ever mounted from a half-written module.
+pub fn recover(target_base: &Path) {
+    let records: Vec<PathBuf> = fs::read_dir(defs::SYNC_JOURNAL_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+
+    let mut journaled = Vec::new();
+    for path in records {
+        let record = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Record>(&s).ok());
+        // A torn record means the sync had not started changing anything.
+        let Some(record) = record else {
+            let _ = fs::remove_file(&path);
+            continue;
+        };
+
+        match replay(target_base, &record) {
+            Ok(action) => log::warn!(
+                "Interrupted sync of {} {} ({:?})",
+                record.module,
+                action,
+                record.phase
+            ),
+            Err(e) => log::error!(
+                "Failed to recover interrupted sync of {}: {}",
+                record.module,
+                e
+            ),
+        }
+        let _ = fs::remove_file(&path);
+        journaled.push(record.module);
+    }
+
+    let Ok(entries) = fs::read_dir(target_base) else {
+        return;
+    };
+    let names: Vec<String> = entries
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+
+    // Leftovers no journal accounts for: staging copies, backups and atomic_write temporaries.
+    for name in &names {
+        let path = target_base.join(name);
+        if fs::symlink_metadata(&path).is_err() {
+            continue;
+        }
+        if let Some(id) = name.strip_prefix(STAGING_PREFIX) {
+            if !journaled.iter().any(|m| m == id) {
+                log::warn!("Removing stale staging copy of {}", id);
+                remove_tree(&path);
+            }
+        } else if let Some(id) = name.strip_prefix(BACKUP_PREFIX) {
+            let dst = target_base.join(id);
+            if dst.exists() {
+                remove_tree(&path);
+            } else if let Err(e) = fs::rename(&path, &dst) {
+                log::error!("Failed to restore backup of {}: {}", id, e);
+            }
+        } else if name.starts_with('.') && name.ends_with(".tmp") {
+            remove_tree(&path);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Read,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use flate2::Crc;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{defs, utils};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub mode: u32,
+    pub size: u64,
+    pub mtime: i64,
+    // Changes with xattrs and SELinux labels too, which leave the mtime alone.
+    #[serde(default)]
+    pub ctime: i64,
+    pub hash: u32,
+    #[serde(default)]
+    pub xattrs: u32,
+}
+
+impl Entry {
+    fn same_content(&self, other: &Self) -> bool {
+        self.mode == other.mode
+            && self.xattrs == other.xattrs
+            && (self.is_dir() || (self.size == other.size && self.hash == other.hash))
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & libc::S_IFMT == libc::S_IFDIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.mode & libc::S_IFMT == libc::S_IFREG
+    }
+}
+
+pub type Manifest = BTreeMap<String, Entry>;
+
+#[derive(Debug, Default)]
+pub struct Delta {
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+// Kept on /data rather than next to the synced copy, which is rebuilt every boot, so a boot only
+// rehashes the files that changed since the last one.
+pub fn manifest_path(module_id: &str) -> PathBuf {
+    Path::new(defs::MANIFESTS_DIR).join(format!("{}.json", module_id))
+}
+
+// Manifests of modules that are no longer installed anywhere.
+pub fn prune(installed: impl Fn(&str) -> bool) {
+    let Ok(entries) = fs::read_dir(defs::MANIFESTS_DIR) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(id) = name.strip_suffix(".json")
+            && !installed(id)
+        {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Option<Manifest> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn save(path: &Path, manifest: &Manifest) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        utils::ensure_dir_exists(parent)?;
+    }
+    utils::atomic_write(path, serde_json::to_string(manifest)?)
+}
+
+pub fn hash_file(path: &Path) -> Result<u32> {
+    let mut file = File::open(path)?;
+    let mut crc = Crc::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        crc.update(&buf[..n]);
+    }
+    Ok(crc.sum())
+}
+
+fn hash_entry(path: &Path, metadata: &fs::Metadata) -> Result<u32> {
+    let ft = metadata.file_type();
+    if ft.is_file() {
+        return hash_file(path);
+    }
+
+    let mut crc = Crc::new();
+    if ft.is_symlink() {
+        crc.update(fs::read_link(path)?.as_os_str().as_encoded_bytes());
+    } else if !ft.is_dir() {
+        crc.update(&metadata.rdev().to_le_bytes());
+    }
+    Ok(crc.sum())
+}
+
+fn hash_xattrs(path: &Path) -> u32 {
+    let mut crc = Crc::new();
+    for (name, value) in utils::list_xattrs(path) {
+        crc.update(&name);
+        crc.update(&[0]);
+        crc.update(&(value.len() as u32).to_le_bytes());
+        crc.update(&value);
+    }
+    crc.sum()
+}
+
+// Entries whose size, mtime and ctime match the previous manifest keep their recorded hashes unread.
+pub fn build(root: &Path, previous: Option<&Manifest>) -> Result<Manifest> {
+    let mut manifest = Manifest::new();
+
+    for entry in WalkDir::new(root).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let Ok(rel) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        let rel = rel.to_string_lossy().to_string();
+        let metadata = entry.metadata()?;
+        let mtime = metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec();
+        let ctime = metadata.ctime() * 1_000_000_000 + metadata.ctime_nsec();
+
+        let cached = previous.and_then(|p| p.get(&rel)).filter(|old| {
+            old.mode == metadata.mode()
+                && old.size == metadata.len()
+                && old.mtime == mtime
+                && old.ctime == ctime
+        });
+        let (hash, xattrs) = match cached {
+            Some(old) => (old.hash, old.xattrs),
+            None => (
+                hash_entry(entry.path(), &metadata)?,
+                hash_xattrs(entry.path()),
+            ),
+        };
+
+        manifest.insert(
+            rel,
+            Entry {
+                mode: metadata.mode(),
+                size: metadata.len(),
+                mtime,
+                ctime,
+                hash,
+                xattrs,
+            },
+        );
+    }
+
+    Ok(manifest)
+}
+
+// Content digest of a whole tree; mtimes are left out so a touched but unchanged file still matches.
+pub fn tree_hash(manifest: &Manifest) -> u32 {
+    let mut crc = Crc::new();
+    for (path, entry) in manifest {
+        crc.update(path.as_bytes());
+        crc.update(&entry.mode.to_le_bytes());
+        crc.update(&entry.size.to_le_bytes());
+        crc.update(&entry.hash.to_le_bytes());
+        crc.update(&entry.xattrs.to_le_bytes());
+    }
+    crc.sum()
+}
+
+pub fn diff(old: &Manifest, new: &Manifest) -> Delta {
+    let changed = new
+        .iter()
+        .filter(|(path, entry)| old.get(*path).is_none_or(|o| !o.same_content(entry)))
+        .map(|(path, _)| path.clone())
+        .collect();
+    let removed = old
+        .keys()
+        .filter(|path| !new.contains_key(*path))
+        .cloned()
+        .collect();
+
+    Delta { changed, removed }
+}
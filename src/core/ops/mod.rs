@@ -2,5 +2,12 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 pub mod executor;
+pub mod journal;
+pub mod manifest;
+pub mod plan_diff;
 pub mod planner;
+pub mod preflight;
+pub mod scoped;
 pub mod sync;
+pub mod verify;
+pub mod winnow;
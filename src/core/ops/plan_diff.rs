@@ -0,0 +1,207 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{core::ops::planner::MountPlan, defs, utils};
+
+// What a plan mounts, keyed by target, with overlay modules in lowerdir order.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlanRecord {
+    pub timestamp: u64,
+    pub overlay: BTreeMap<String, Vec<String>>,
+    pub magic: Vec<String>,
+    pub rootdir: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TargetDiff {
+    pub target: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub reordered: bool,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModeChange {
+    pub module: String,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanDiff {
+    pub baseline: u64,
+    pub targets: Vec<TargetDiff>,
+    pub mode_changes: Vec<ModeChange>,
+}
+
+impl PlanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty() && self.mode_changes.is_empty()
+    }
+}
+
+fn layer_module_id(layer: &Path, storage_root: &Path) -> Option<String> {
+    match layer.strip_prefix(storage_root) {
+        Ok(rel) => rel.iter().next().map(|id| id.to_string_lossy().to_string()),
+        Err(_) => utils::extract_module_id(layer),
+    }
+}
+
+impl PlanRecord {
+    // Keeps what an earlier boot stage mounted alongside this stage's plan.
+    pub fn merge(&mut self, earlier: Self) {
+        for (target, ids) in earlier.overlay {
+            self.overlay.entry(target).or_insert(ids);
+        }
+        for (target, id) in earlier.rootdir {
+            self.rootdir.entry(target).or_insert(id);
+        }
+        for id in earlier.magic {
+            if !self.magic.contains(&id) {
+                self.magic.push(id);
+            }
+        }
+        self.magic.sort();
+    }
+
+    pub fn from_plan(plan: &MountPlan, storage_root: &Path) -> Self {
+        let overlay = plan
+            .overlay_ops
+            .iter()
+            .map(|op| {
+                let mut ids: Vec<String> = Vec::new();
+                for id in op
+                    .lowerdirs
+                    .iter()
+                    .filter_map(|l| layer_module_id(l, storage_root))
+                {
+                    if !ids.contains(&id) {
+                        ids.push(id);
+                    }
+                }
+                (op.target.clone(), ids)
+            })
+            .collect();
+
+        let rootdir = plan
+            .rootdir_ops
+            .iter()
+            .map(|op| {
+                (
+                    op.target.to_string_lossy().to_string(),
+                    op.module_id.clone(),
+                )
+            })
+            .collect();
+
+        Self {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            overlay,
+            magic: plan.magic_module_ids.clone(),
+            rootdir,
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        utils::atomic_write(defs::LAST_PLAN_FILE, serde_json::to_string(self)?)
+    }
+
+    pub fn load() -> Result<Self> {
+        let content = fs::read_to_string(defs::LAST_PLAN_FILE)
+            .context("No plan recorded yet; boot once with this version first")?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn modules(&self) -> BTreeSet<&String> {
+        self.overlay
+            .values()
+            .flatten()
+            .chain(&self.magic)
+            .chain(self.rootdir.values())
+            .collect()
+    }
+
+    fn mode_of(&self, id: &str) -> &'static str {
+        let overlay = self.overlay.values().any(|ids| ids.iter().any(|m| m == id));
+        let magic = self.magic.iter().any(|m| m == id);
+        match (overlay, magic) {
+            (true, true) => "overlay+magic",
+            (true, false) => "overlay",
+            (false, true) => "magic",
+            (false, false) if self.rootdir.values().any(|m| m == id) => "rootdir",
+            (false, false) => "none",
+        }
+    }
+}
+
+pub fn diff(old: &PlanRecord, new: &PlanRecord) -> PlanDiff {
+    let empty = Vec::new();
+    let targets: BTreeSet<&String> = old.overlay.keys().chain(new.overlay.keys()).collect();
+
+    let targets = targets
+        .into_iter()
+        .filter_map(|target| {
+            let before = old.overlay.get(target).unwrap_or(&empty);
+            let after = new.overlay.get(target).unwrap_or(&empty);
+            if before == after {
+                return None;
+            }
+
+            let added: Vec<String> = after
+                .iter()
+                .filter(|id| !before.contains(id))
+                .cloned()
+                .collect();
+            let removed: Vec<String> = before
+                .iter()
+                .filter(|id| !after.contains(id))
+                .cloned()
+                .collect();
+            // Same surviving modules in a different stacking order.
+            let kept_before: Vec<&String> = before.iter().filter(|id| after.contains(id)).collect();
+            let kept_after: Vec<&String> = after.iter().filter(|id| before.contains(id)).collect();
+
+            Some(TargetDiff {
+                target: target.clone(),
+                added,
+                removed,
+                reordered: kept_before != kept_after,
+                before: before.clone(),
+                after: after.clone(),
+            })
+        })
+        .collect();
+
+    let modules: BTreeSet<&String> = old.modules().into_iter().chain(new.modules()).collect();
+    let mode_changes = modules
+        .into_iter()
+        .filter_map(|id| {
+            let (before, after) = (old.mode_of(id), new.mode_of(id));
+            (before != after).then(|| ModeChange {
+                module: id.clone(),
+                before: before.to_string(),
+                after: after.to_string(),
+            })
+        })
+        .collect();
+
+    PlanDiff {
+        baseline: old.timestamp,
+        targets,
+        mode_changes,
+    }
+}
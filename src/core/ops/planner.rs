@@ -5,17 +5,29 @@ use std::{
     collections::{HashMap, HashSet, VecDeque},
     fs,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use anyhow::Result;
+use procfs::process::{MountInfo, Process};
 use rayon::prelude::*;
 use serde::Serialize;
 use walkdir::WalkDir;
 
 use crate::{
     conf::config,
-    core::inventory::{Module, MountMode},
-    defs, utils,
+    core::{
+        diagnostics::{DiagnosticIssue, DiagnosticLevel, IssueCode},
+        inventory::{Module, MountMode},
+        rw,
+    },
+    defs,
+    mount::overlayfs::{limit, overlayfs::MAX_LOWERDIR_COUNT},
+    sys::{
+        kmod,
+        mount::{is_dlkm_partition, is_mounted, resolve_dlkm_root},
+    },
+    utils,
 };
 
 #[derive(Debug, Clone)]
@@ -23,13 +35,71 @@ pub struct OverlayOperation {
     pub partition_name: String,
     pub target: String,
     pub lowerdirs: Vec<PathBuf>,
+    pub pinned: Vec<PinnedFile>,
+    // Images some of the lowerdirs live in; they have to be mounted before the overlay is.
+    pub images: Vec<ImageLayer>,
+}
+
+// A module packed into its own read-only image. Its layers are directories under `mountpoint`, so
+// they stack with plain directory layers in the same overlay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageLayer {
+    pub module_id: String,
+    pub image: PathBuf,
+    pub fs_type: &'static str,
+    pub mountpoint: PathBuf,
+}
+
+impl ImageLayer {
+    pub fn is_mounted(&self) -> bool {
+        is_mounted(&self.mountpoint)
+    }
+}
+
+// A file whose winner was pinned with `conflicts resolve` but that another layer would shadow.
+#[derive(Debug, Clone)]
+pub struct PinnedFile {
+    pub relative: PathBuf,
+    pub module_id: String,
+    pub source: PathBuf,
+}
+
+// An overlay the kernel would refuse for stacking too deep, routed to magic mount instead.
+#[derive(Debug, Clone)]
+pub struct DepthFallback {
+    pub target: String,
+    pub depth: usize,
+    pub modules: Vec<String>,
+}
+
+// Module content aimed at a protected path: removed from the module's storage copy, or the whole
+// module left out of the plan when that copy cannot be changed.
+#[derive(Debug, Clone)]
+pub struct ProtectedHit {
+    pub path: PathBuf,
+    pub module: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RootdirOperation {
+    pub module_id: String,
+    pub source: PathBuf,
+    pub target: PathBuf,
 }
 
 #[derive(Debug, Default)]
 pub struct MountPlan {
+    pub rootdir_ops: Vec<RootdirOperation>,
     pub overlay_ops: Vec<OverlayOperation>,
     pub overlay_module_ids: Vec<String>,
     pub magic_module_ids: Vec<String>,
+    pub skip_umount_ids: HashSet<String>,
+    pub depth_fallbacks: Vec<DepthFallback>,
+    pub protected_hits: Vec<ProtectedHit>,
+    // Every image the plan reads from, including ones only magic mount uses.
+    pub images: Vec<ImageLayer>,
+    // Modules whose APEX overrides wait for a later pass because apexd had not activated yet.
+    pub deferred_apex: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -37,19 +107,7 @@ pub struct ConflictEntry {
     pub partition: String,
     pub relative_path: String,
     pub contending_modules: Vec<String>,
-}
-
-#[derive(Debug, Clone, Serialize)]
-pub enum DiagnosticLevel {
-    Warning,
-    Critical,
-}
-
-#[derive(Debug, Clone, Serialize)]
-pub struct DiagnosticIssue {
-    pub level: DiagnosticLevel,
-    pub context: String,
-    pub message: String,
+    pub winner: String,
 }
 
 #[derive(Debug, Default)]
@@ -60,19 +118,42 @@ pub struct AnalysisReport {
 
 impl MountPlan {
     pub fn analyze(&self) -> AnalysisReport {
+        self.analyze_with(&|| false, &|_, _| {}).unwrap_or_default()
+    }
+
+    pub fn analyze_with(
+        &self,
+        cancelled: &(dyn Fn() -> bool + Sync),
+        progress: &(dyn Fn(usize, usize) + Sync),
+    ) -> Option<AnalysisReport> {
+        let total = self.overlay_ops.len();
+        let done = AtomicUsize::new(0);
+
         let results: Vec<(Vec<ConflictEntry>, Vec<DiagnosticIssue>)> = self
             .overlay_ops
             .par_iter()
             .map(|op| {
+                if cancelled() {
+                    return None;
+                }
+
                 let mut local_conflicts = Vec::new();
                 let mut local_diagnostics = Vec::new();
                 let mut file_map: HashMap<String, Vec<String>> = HashMap::new();
+                let mut kernel_objects: HashMap<String, usize> = HashMap::new();
+                let ko_enforcement = kmod::signature_enforcement().filter(|_| {
+                    Path::new(&op.target)
+                        .iter()
+                        .any(|c| is_dlkm_partition(&c.to_string_lossy()))
+                });
 
                 if !Path::new(&op.target).exists() {
                     local_diagnostics.push(DiagnosticIssue {
+                        code: IssueCode::TargetMissing,
                         level: DiagnosticLevel::Critical,
                         context: op.partition_name.clone(),
                         message: format!("Target mount point does not exist: {}", op.target),
+                        path: Some(op.target.clone()),
                     });
                 }
 
@@ -85,12 +166,17 @@ impl MountPlan {
                         utils::extract_module_id(layer_path).unwrap_or_else(|| "UNKNOWN".into());
 
                     for entry in WalkDir::new(layer_path).min_depth(1).into_iter().flatten() {
+                        if cancelled() {
+                            return None;
+                        }
+
                         if entry.path_is_symlink()
                             && let Ok(target) = std::fs::read_link(entry.path())
                             && target.is_absolute()
                             && !target.exists()
                         {
                             local_diagnostics.push(DiagnosticIssue {
+                                code: IssueCode::DeadSymlink,
                                 level: DiagnosticLevel::Warning,
                                 context: module_id.clone(),
                                 message: format!(
@@ -98,6 +184,7 @@ impl MountPlan {
                                     entry.path().display(),
                                     target.display()
                                 ),
+                                path: Some(entry.path().to_string_lossy().to_string()),
                             });
                         }
 
@@ -105,6 +192,10 @@ impl MountPlan {
                             continue;
                         }
 
+                        if ko_enforcement.is_some() && kmod::is_kernel_object(entry.path()) {
+                            *kernel_objects.entry(module_id.clone()).or_default() += 1;
+                        }
+
                         if let Ok(rel) = entry.path().strip_prefix(layer_path) {
                             let rel_str = rel.to_string_lossy().to_string();
                             file_map.entry(rel_str).or_default().push(module_id.clone());
@@ -112,19 +203,45 @@ impl MountPlan {
                     }
                 }
 
+                for (module_id, count) in kernel_objects {
+                    local_diagnostics.push(DiagnosticIssue {
+                        code: IssueCode::KernelObjectUnsigned,
+                        level: DiagnosticLevel::Warning,
+                        context: module_id,
+                        message: format!(
+                            "Replaces {} kernel object(s) under {}, but the kernel enforces \
+                             module signatures ({}); unsigned .ko files will refuse to load",
+                            count,
+                            op.target,
+                            ko_enforcement.unwrap_or_default()
+                        ),
+                        path: Some(op.target.clone()),
+                    });
+                }
+
                 for (rel_path, modules) in file_map {
                     if modules.len() > 1 {
+                        // The first lowerdir is the top layer, unless a pinned layer goes above it.
+                        let winner = op
+                            .pinned
+                            .iter()
+                            .find(|p| p.relative == Path::new(&rel_path))
+                            .map(|p| p.module_id.clone())
+                            .unwrap_or_else(|| modules[0].clone());
                         local_conflicts.push(ConflictEntry {
                             partition: op.partition_name.clone(),
                             relative_path: rel_path,
+                            winner,
                             contending_modules: modules,
                         });
                     }
                 }
 
-                (local_conflicts, local_diagnostics)
+                progress(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+
+                Some((local_conflicts, local_diagnostics))
             })
-            .collect();
+            .collect::<Option<Vec<_>>>()?;
 
         let mut report = AnalysisReport::default();
         for (c, d) in results {
@@ -138,8 +255,315 @@ impl MountPlan {
                 .then_with(|| a.relative_path.cmp(&b.relative_path))
         });
 
-        report
+        Some(report)
+    }
+}
+
+fn collect_rootdir_ops(plan: &mut MountPlan, module: &Module, content_path: &Path) {
+    let rootdir = content_path.join(defs::ROOTDIR_NAME);
+    if !rootdir.is_dir() {
+        return;
+    }
+
+    for entry in WalkDir::new(&rootdir).min_depth(1).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(rel) = entry.path().strip_prefix(&rootdir) else {
+            continue;
+        };
+        let target = Path::new("/").join(rel);
+        let target_str = target.to_string_lossy();
+
+        if !defs::ROOTDIR_ALLOWLIST.contains(&target_str.as_ref()) {
+            log::warn!(
+                "Module {} ships rootdir file {} outside the allowlist, ignoring",
+                module.id,
+                target.display()
+            );
+            continue;
+        }
+
+        if !fs::symlink_metadata(&target).is_ok_and(|m| m.file_type().is_file()) {
+            log::warn!(
+                "Rootdir target {} is not a regular file on this device, ignoring",
+                target.display()
+            );
+            continue;
+        }
+
+        if let Some(existing) = plan.rootdir_ops.iter().find(|op| op.target == target) {
+            log::warn!(
+                "Rootdir target {} already provided by {}, ignoring {}",
+                target.display(),
+                existing.module_id,
+                module.id
+            );
+            continue;
+        }
+
+        plan.rootdir_ops.push(RootdirOperation {
+            module_id: module.id.clone(),
+            source: entry.path().to_path_buf(),
+            target,
+        });
+    }
+}
+
+// APEX payloads are read-only loop mounts (or bind mounts of /system/apex/<name> when flattened),
+// so overlays stacked on them are lost whenever apexd remounts. Files are bound one by one over
+// the active /apex/<name> instead, which also means an APEX can only have files replaced.
+fn apex_root(name: &str) -> Option<PathBuf> {
+    let active = Path::new("/apex").join(name);
+    if active.is_dir() {
+        return Some(active);
+    }
+    let flattened = Path::new(defs::FLATTENED_APEX_DIR).join(name);
+    flattened.is_dir().then_some(flattened)
+}
+
+// apexd mounts the active APEXes during post-fs-data; binding before it is done would land under
+// its mounts or on a path that does not exist yet.
+fn apexd_ready() -> bool {
+    matches!(
+        utils::get_property("apexd.status").as_deref(),
+        Some("activated" | "ready")
+    )
+}
+
+fn has_apex_overrides(module: &Module, content_path: &Path) -> bool {
+    content_path.join(defs::APEX_DIR_NAME).is_dir()
+        && module.rules.get_mode(defs::APEX_DIR_NAME) != MountMode::Ignore
+}
+
+fn collect_apex_ops(
+    plan: &mut MountPlan,
+    module: &Module,
+    content_path: &Path,
+    protected: &[PathBuf],
+) {
+    if !has_apex_overrides(module, content_path) {
+        return;
     }
+    let apex_dir = content_path.join(defs::APEX_DIR_NAME);
+
+    for apex in fs::read_dir(&apex_dir).into_iter().flatten().flatten() {
+        let name = apex.file_name().to_string_lossy().to_string();
+        let Some(root) = apex_root(&name) else {
+            log::warn!(
+                "Module {} overrides APEX {} which is not active on this device, ignoring",
+                module.id,
+                name
+            );
+            continue;
+        };
+
+        for entry in WalkDir::new(apex.path()).min_depth(1).into_iter().flatten() {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(rel) = entry.path().strip_prefix(apex.path()) else {
+                continue;
+            };
+            let target = root.join(rel);
+
+            if let Some(path) = exposes(&target, protected) {
+                log::warn!(
+                    "{}: skipping APEX file {}, which would replace protected path {}",
+                    module.id,
+                    target.display(),
+                    path.display()
+                );
+                plan.protected_hits.push(ProtectedHit {
+                    path: path.clone(),
+                    module: module.id.clone(),
+                });
+                continue;
+            }
+
+            if !fs::symlink_metadata(&target).is_ok_and(|m| m.file_type().is_file()) {
+                log::warn!(
+                    "Module {}: {} does not exist in the APEX image, only existing files can be \
+                     replaced",
+                    module.id,
+                    target.display()
+                );
+                continue;
+            }
+
+            if let Some(existing) = plan.rootdir_ops.iter().find(|op| op.target == target) {
+                log::warn!(
+                    "APEX file {} already provided by {}, ignoring {}",
+                    target.display(),
+                    existing.module_id,
+                    module.id
+                );
+                continue;
+            }
+
+            plan.rootdir_ops.push(RootdirOperation {
+                module_id: module.id.clone(),
+                source: entry.path().to_path_buf(),
+                target,
+            });
+        }
+    }
+}
+
+// The image takes the place of the synced copy. Nothing is mounted here: planning reads the synced
+// copy, and the executor mounts the image before anything is built from it.
+fn module_image(module: &Module) -> Option<ImageLayer> {
+    let (image, fs_type) = ["erofs", "squashfs"].into_iter().find_map(|fs_type| {
+        let image = Path::new(defs::MODULE_IMAGES_DIR).join(format!("{}.{}", module.id, fs_type));
+        image.is_file().then_some((image, fs_type))
+    })?;
+    Some(ImageLayer {
+        module_id: module.id.clone(),
+        image,
+        fs_type,
+        mountpoint: Path::new(defs::LAYER_MNT_DIR).join(&module.id),
+    })
+}
+
+// Partition names may be nested (`mnt/vendor/persist`), so look each one up instead of listing
+// the module's top level.
+fn partition_dirs(content_path: &Path, partitions: &[String]) -> Vec<(String, PathBuf)> {
+    partitions
+        .iter()
+        .filter(|p| p.as_str() != defs::APEX_DIR_NAME)
+        .map(|p| (p.clone(), content_path.join(p)))
+        .filter(|(_, path)| path.is_dir())
+        .collect()
+}
+
+// A layer holding nothing but plain directories changes nothing in the merged view. Opaque
+// directories hide what is below them, so they count as content.
+fn is_effectively_empty(layer: &Path) -> bool {
+    WalkDir::new(layer).into_iter().all(|entry| {
+        entry.is_ok_and(|e| e.file_type().is_dir() && !utils::is_overlay_opaque(e.path()))
+    })
+}
+
+// The same module can reach a target through more than one path (storage copy and source copy,
+// or symlinked directories), and every lowerdir counts against the mount option length limit.
+fn normalize_layers(target: &Path, layers: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    let mut normalized = Vec::with_capacity(layers.len());
+
+    for layer in layers {
+        let canonical = layer.canonicalize().unwrap_or_else(|_| layer.clone());
+        if !seen.insert(canonical) {
+            log::debug!(
+                "{}: dropping duplicate lowerdir {}",
+                target.display(),
+                layer.display()
+            );
+            continue;
+        }
+        if is_effectively_empty(&layer) {
+            log::debug!(
+                "{}: dropping empty lowerdir {}",
+                target.display(),
+                layer.display()
+            );
+            continue;
+        }
+        normalized.push(layer);
+    }
+
+    normalized
+}
+
+fn collect_pins(config: &config::Config, target: &Path, layers: &[PathBuf]) -> Vec<PinnedFile> {
+    let mut pinned = Vec::new();
+
+    for (path, module_id) in &config.resolve {
+        let Ok(relative) = Path::new(path).strip_prefix(target) else {
+            continue;
+        };
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+
+        let Some(owner) = layers.iter().find(|l| {
+            utils::extract_module_id(l).as_deref() == Some(module_id.as_str())
+                && l.join(relative).is_file()
+        }) else {
+            log::warn!(
+                "Pinned {} -> {}: module does not provide this file under {}",
+                path,
+                module_id,
+                target.display()
+            );
+            continue;
+        };
+
+        let top = layers
+            .iter()
+            .find(|l| fs::symlink_metadata(l.join(relative)).is_ok());
+        if top == Some(owner) {
+            continue;
+        }
+
+        log::info!("Pinned {} -> {}: adding override layer", path, module_id);
+        pinned.push(PinnedFile {
+            relative: relative.to_path_buf(),
+            module_id: module_id.clone(),
+            source: owner.join(relative),
+        });
+    }
+
+    pinned.sort_by(|a, b| a.relative.cmp(&b.relative));
+    pinned
+}
+
+// Where a module would carry `path`: under its own partition directory, or under system/ for
+// partitions that also appear as /system/<partition>.
+fn protected_candidates(content_path: &Path, path: &Path) -> Vec<PathBuf> {
+    let Ok(rel) = path.strip_prefix("/") else {
+        return Vec::new();
+    };
+    let mut candidates = vec![content_path.join(rel)];
+    if !rel.starts_with("system") {
+        candidates.push(content_path.join("system").join(rel));
+    }
+    candidates
+}
+
+// The protected paths a module ships content for. Its copy is never touched: the mounts that
+// would put that content in place are skipped instead.
+fn guard_protected(
+    plan: &mut MountPlan,
+    module: &Module,
+    content_path: &Path,
+    protected: &[PathBuf],
+) -> Vec<PathBuf> {
+    let mut shipped = Vec::new();
+    for path in protected {
+        if protected_candidates(content_path, path)
+            .iter()
+            .any(|c| fs::symlink_metadata(c).is_ok())
+        {
+            log::warn!(
+                "{}: ships content for protected path {}",
+                module.id,
+                path.display()
+            );
+            plan.protected_hits.push(ProtectedHit {
+                path: path.clone(),
+                module: module.id.clone(),
+            });
+            shipped.push(path.clone());
+        }
+    }
+    shipped
+}
+
+// A mount on `target` replaces everything at or below it.
+fn exposes<'a>(target: &Path, paths: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    paths
+        .iter()
+        .find(|p| p.starts_with(target) || target.starts_with(p))
 }
 
 struct ProcessingItem {
@@ -148,6 +572,84 @@ struct ProcessingItem {
     partition_label: String,
 }
 
+// APEX overrides an earlier pass deferred, planned on their own: the rest of these modules is
+// already mounted.
+pub fn plan_deferred_apex(
+    config: &config::Config,
+    plan: &mut MountPlan,
+    modules: &[Module],
+    storage_root: &Path,
+) {
+    if modules.is_empty() {
+        return;
+    }
+    if !apexd_ready() {
+        log::warn!(
+            "apexd has still not activated, skipping APEX overrides of {} module(s)",
+            modules.len()
+        );
+        return;
+    }
+    let protected = config.effective_protected_paths();
+    for module in modules {
+        let mut content_path = storage_root.join(&module.id);
+        if !content_path.exists() {
+            content_path = module.source_path.clone();
+        }
+        collect_apex_ops(plan, module, &content_path, &protected);
+    }
+}
+
+// A read-write partition needs its overlay even when no module touches it: the stock tree is the
+// only lower layer then. Split partitions get one per top-level directory, like module layers.
+fn plan_rw_partitions(
+    config: &config::Config,
+    plan: &mut MountPlan,
+    sensitive_partitions: &HashSet<&str>,
+) {
+    for partition in rw::enabled(config) {
+        let root = Path::new("/").join(&partition);
+        let Ok(root) = root.canonicalize() else {
+            continue;
+        };
+        let name = root
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let targets: Vec<PathBuf> =
+            if sensitive_partitions.contains(name.as_str()) || name == "system" {
+                fs::read_dir(&root)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+                    .map(|e| e.path())
+                    .collect()
+            } else {
+                vec![root]
+            };
+
+        for target in targets {
+            let covered = plan.overlay_ops.iter().any(|op| {
+                let planned = Path::new(&op.target);
+                planned.starts_with(&target) || target.starts_with(planned)
+            });
+            if covered {
+                continue;
+            }
+            log::info!("{}: read-write with no module layers", target.display());
+            plan.overlay_ops.push(OverlayOperation {
+                partition_name: partition.clone(),
+                target: target.to_string_lossy().to_string(),
+                lowerdirs: Vec::new(),
+                pinned: Vec::new(),
+                images: Vec::new(),
+            });
+        }
+    }
+}
+
 pub fn generate(
     config: &config::Config,
     modules: &[Module],
@@ -155,14 +657,23 @@ pub fn generate(
 ) -> Result<MountPlan> {
     let mut plan = MountPlan::default();
 
-    let mut overlay_groups: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    let mut overlay_groups: HashMap<PathBuf, Vec<(i32, PathBuf)>> = HashMap::new();
 
     let mut overlay_ids = HashSet::new();
     let mut magic_ids = HashSet::new();
 
     let sensitive_partitions: HashSet<&str> = defs::SENSITIVE_PARTITIONS.iter().cloned().collect();
+    let partitions = config.effective_partitions();
+    let protected = config.effective_protected_paths();
+    let apex_ready = apexd_ready();
+    // Where each image-backed module was planned from, so its layers can be moved into the image.
+    let mut image_roots: Vec<(PathBuf, ImageLayer)> = Vec::new();
 
     for module in modules {
+        if module.rules.skip_umount {
+            plan.skip_umount_ids.insert(module.id.clone());
+        }
+
         let mut content_path = storage_root.join(&module.id);
         if !content_path.exists() {
             content_path = module.source_path.clone();
@@ -170,116 +681,223 @@ pub fn generate(
         if !content_path.exists() {
             continue;
         }
+        if let Some(image) = module_image(module) {
+            image_roots.push((content_path.clone(), image.clone()));
+            plan.images.push(image);
+        }
+        let shipped = guard_protected(&mut plan, module, &content_path, &protected);
+
+        collect_rootdir_ops(&mut plan, module, &content_path);
+        if !apex_ready && has_apex_overrides(module, &content_path) {
+            log::info!(
+                "{}: apexd has not activated yet, APEX overrides wait for the service stage",
+                module.id
+            );
+            plan.deferred_apex.push(module.id.clone());
+        } else {
+            collect_apex_ops(&mut plan, module, &content_path, &protected);
+        }
 
-        if let Ok(entries) = fs::read_dir(&content_path) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if !path.is_dir() {
-                    continue;
+        for (dir_name, path) in partition_dirs(&content_path, &partitions) {
+            let partition_root = if is_dlkm_partition(&dir_name) {
+                match resolve_dlkm_root(&dir_name) {
+                    Some(root) => root,
+                    None => continue,
                 }
+            } else {
+                PathBuf::from("/").join(&dir_name)
+            };
+
+            let mode = module.rules.get_mode(&dir_name);
+            if matches!(mode, MountMode::Magic) {
+                magic_ids.insert(module.id.clone());
+                continue;
+            }
+            if matches!(mode, MountMode::Ignore) {
+                continue;
+            }
 
-                let dir_name = entry.file_name().to_string_lossy().to_string();
-
-                if !defs::BUILTIN_PARTITIONS.contains(&dir_name.as_str())
-                    && !config.partitions.contains(&dir_name)
-                {
+            let mut groups = Vec::new();
+            let mut split_whiteouts = Vec::new();
+            let mut queue = VecDeque::new();
+            queue.push_back(ProcessingItem {
+                module_source: path.clone(),
+                system_target: partition_root,
+                partition_label: dir_name.clone(),
+            });
+
+            while let Some(item) = queue.pop_front() {
+                let ProcessingItem {
+                    module_source,
+                    system_target,
+                    partition_label,
+                } = item;
+
+                if !system_target.exists() {
                     continue;
                 }
 
-                let mode = module.rules.get_mode(&dir_name);
-                if matches!(mode, MountMode::Magic) {
-                    magic_ids.insert(module.id.clone());
-                    continue;
-                }
-                if matches!(mode, MountMode::Ignore) {
-                    continue;
-                }
-
-                overlay_ids.insert(module.id.clone());
-
-                let mut queue = VecDeque::new();
-                queue.push_back(ProcessingItem {
-                    module_source: path.clone(),
-                    system_target: PathBuf::from("/").join(&dir_name),
-                    partition_label: dir_name.clone(),
-                });
-
-                while let Some(item) = queue.pop_front() {
-                    let ProcessingItem {
-                        module_source,
-                        system_target,
-                        partition_label,
-                    } = item;
-
-                    if !system_target.exists() {
-                        continue;
-                    }
-
-                    let resolved_target = match fs::read_link(&system_target) {
-                        Ok(target) => {
-                            if target.is_absolute() {
-                                target
-                            } else {
-                                system_target
-                                    .parent()
-                                    .unwrap_or(Path::new("/"))
-                                    .join(target)
-                            }
+                let resolved_target = match fs::read_link(&system_target) {
+                    Ok(target) => {
+                        if target.is_absolute() {
+                            target
+                        } else {
+                            system_target
+                                .parent()
+                                .unwrap_or(Path::new("/"))
+                                .join(target)
                         }
-                        Err(_) => system_target.clone(),
-                    };
+                    }
+                    Err(_) => system_target.clone(),
+                };
 
-                    let canonical_target = if resolved_target.exists() {
-                        match resolved_target.canonicalize() {
-                            Ok(p) => p,
-                            Err(_) => resolved_target,
-                        }
-                    } else {
-                        resolved_target
-                    };
-
-                    let target_name = canonical_target
-                        .file_name()
-                        .map(|s| s.to_string_lossy())
-                        .unwrap_or_default();
-
-                    let should_split = sensitive_partitions.contains(target_name.as_ref())
-                        || target_name == "system";
-
-                    if should_split {
-                        if let Ok(sub_entries) = fs::read_dir(&module_source) {
-                            for sub_entry in sub_entries.flatten() {
-                                let sub_path = sub_entry.path();
-                                if !sub_path.is_dir() {
-                                    continue;
+                let canonical_target = if resolved_target.exists() {
+                    match resolved_target.canonicalize() {
+                        Ok(p) => p,
+                        Err(_) => resolved_target,
+                    }
+                } else {
+                    resolved_target
+                };
+
+                let target_name = canonical_target
+                    .file_name()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or_default();
+
+                let should_split =
+                    sensitive_partitions.contains(&*target_name) || target_name == "system";
+
+                if should_split {
+                    if let Ok(sub_entries) = fs::read_dir(&module_source) {
+                        for sub_entry in sub_entries.flatten() {
+                            let sub_path = sub_entry.path();
+                            if !sub_path.is_dir() {
+                                if utils::is_whiteout(&sub_path)
+                                    || utils::is_xattr_whiteout(&sub_path)
+                                {
+                                    split_whiteouts
+                                        .push(canonical_target.join(sub_entry.file_name()));
                                 }
-                                let sub_name = sub_entry.file_name();
-
-                                queue.push_back(ProcessingItem {
-                                    module_source: sub_path,
-                                    system_target: canonical_target.join(sub_name),
-                                    partition_label: partition_label.clone(),
-                                });
+                                continue;
                             }
+                            let sub_name = sub_entry.file_name();
+
+                            queue.push_back(ProcessingItem {
+                                module_source: sub_path,
+                                system_target: canonical_target.join(sub_name),
+                                partition_label: partition_label.clone(),
+                            });
                         }
-                    } else {
-                        overlay_groups
-                            .entry(canonical_target)
-                            .or_default()
-                            .push(module_source);
                     }
+                } else {
+                    groups.push((canonical_target, module_source));
+                }
+            }
+
+            // A whiteout directly under a split partition root has no overlay to live in.
+            if !split_whiteouts.is_empty() {
+                log::warn!(
+                    "{}: cannot express whiteout(s) {:?} with overlayfs, magic mounting {}",
+                    module.id,
+                    split_whiteouts,
+                    dir_name
+                );
+                magic_ids.insert(module.id.clone());
+                continue;
+            }
+
+            overlay_ids.insert(module.id.clone());
+            let priority = config.module_priority(&module.id);
+            for (target, source) in groups {
+                if let Some(path) = exposes(&target, &shipped) {
+                    log::warn!(
+                        "{}: skipping its layer on {}, which would replace protected path {}",
+                        module.id,
+                        target.display(),
+                        path.display()
+                    );
+                    continue;
                 }
+                overlay_groups
+                    .entry(target)
+                    .or_default()
+                    .push((priority, source));
             }
         }
     }
 
-    for (target_path, layers) in overlay_groups {
+    let mountinfo: Vec<MountInfo> = Process::myself()
+        .and_then(|p| p.mountinfo())
+        .map(|m| m.into_iter().collect())
+        .unwrap_or_default();
+
+    for (target_path, mut layers) in overlay_groups {
+        layers.sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+        let layers = layers.into_iter().map(|(_, source)| source).collect();
         let target_str = target_path.to_string_lossy().to_string();
 
         if !target_path.is_dir() {
             continue;
         }
 
+        let layers = normalize_layers(&target_path, layers);
+        if layers.is_empty() {
+            log::info!(
+                "{}: no layers left after normalization, skipping",
+                target_str
+            );
+            continue;
+        }
+
+        // Staging puts one more overlay between the module layers and the final mount.
+        let staged = usize::from(layers.len() + 1 > MAX_LOWERDIR_COUNT);
+        let layer_depth = layers
+            .iter()
+            .map(|l| limit::stack_depth(l, &mountinfo))
+            .max()
+            .unwrap_or(0);
+        let depth = 1 + limit::stack_depth(&target_path, &mountinfo).max(layer_depth + staged);
+        if depth > limit::MAX_STACK_DEPTH {
+            let modules: Vec<String> = layers
+                .iter()
+                .filter_map(|l| utils::extract_module_id(l))
+                .collect();
+            log::warn!(
+                "{}: overlay would stack {} deep (limit {}), magic mounting {:?}",
+                target_str,
+                depth,
+                limit::MAX_STACK_DEPTH,
+                modules
+            );
+            magic_ids.extend(modules.iter().cloned());
+            plan.depth_fallbacks.push(DepthFallback {
+                target: target_str,
+                depth,
+                modules,
+            });
+            continue;
+        }
+
+        let pinned = collect_pins(config, &target_path, &layers);
+        let mut images: Vec<ImageLayer> = Vec::new();
+        let layers = layers
+            .into_iter()
+            .map(|layer| {
+                let Some((root, image)) =
+                    image_roots.iter().find(|(root, _)| layer.starts_with(root))
+                else {
+                    return layer;
+                };
+                if !images.contains(image) {
+                    images.push(image.clone());
+                }
+                image
+                    .mountpoint
+                    .join(layer.strip_prefix(root).unwrap_or(&layer))
+            })
+            .collect();
+
         let partition_name = target_path
             .iter()
             .nth(1)
@@ -290,9 +908,14 @@ pub fn generate(
             partition_name,
             target: target_str,
             lowerdirs: layers,
+            pinned,
+            images,
         });
     }
 
+    plan_rw_partitions(config, &mut plan, &sensitive_partitions);
+
+    overlay_ids.retain(|id| !magic_ids.contains(id));
     plan.overlay_module_ids = overlay_ids.into_iter().collect();
     plan.magic_module_ids = magic_ids.into_iter().collect();
     plan.overlay_module_ids.sort();
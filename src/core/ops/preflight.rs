@@ -0,0 +1,253 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use procfs::{Current, Meminfo};
+use rustix::fs::statvfs;
+use walkdir::WalkDir;
+
+use crate::{
+    conf::config::{Config, OverlayMode},
+    core::{
+        diagnostics::{DiagnosticIssue, DiagnosticLevel, IssueCode},
+        inventory::Module,
+        ops::planner::MountPlan,
+    },
+    defs,
+    mount::overlayfs::{limit, overlayfs::MAX_LOWERDIR_COUNT},
+};
+
+// Rough kernel memory held by one tmpfs inode plus its dentry.
+const TMPFS_INODE_COST: u64 = 1024;
+const EXT4_MIN_IMAGE_SIZE: u64 = 64 * 1024 * 1024;
+
+fn mib(bytes: u64) -> u64 {
+    bytes.div_ceil(1024 * 1024)
+}
+
+fn available_memory() -> Option<u64> {
+    Meminfo::current().ok().and_then(|m| m.mem_available)
+}
+
+fn available_space(path: &Path) -> Option<u64> {
+    statvfs(path).ok().map(|s| s.f_bavail * s.f_frsize)
+}
+
+fn content_root(module: &Module, storage_root: &Path) -> PathBuf {
+    let synced = storage_root.join(&module.id);
+    if synced.exists() {
+        synced
+    } else {
+        module.source_path.clone()
+    }
+}
+
+fn content_size(modules: &[Module], partitions: &[String]) -> u64 {
+    modules
+        .iter()
+        .flat_map(|m| {
+            partitions
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once(defs::ROOTDIR_NAME))
+                .map(move |p| m.source_path.join(p))
+        })
+        .filter(|p| p.is_dir())
+        .flat_map(|p| WalkDir::new(p).into_iter().flatten())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn magic_mirror_inodes(plan: &MountPlan, modules: &[Module], storage_root: &Path) -> u64 {
+    let mut inodes = 0;
+
+    for module in modules
+        .iter()
+        .filter(|m| plan.magic_module_ids.contains(&m.id))
+    {
+        let root = content_root(module, storage_root);
+        for entry in WalkDir::new(&root).min_depth(1).into_iter().flatten() {
+            inodes += 1;
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+            if let Ok(rel) = entry.path().strip_prefix(&root)
+                && let Ok(stock) = fs::read_dir(Path::new("/").join(rel))
+            {
+                inodes += stock.count() as u64;
+            }
+        }
+    }
+
+    inodes
+}
+
+pub fn check_storage(modules: &[Module], config: &Config) -> Vec<DiagnosticIssue> {
+    let mut issues = Vec::new();
+    let size = content_size(modules, &config.effective_partitions());
+
+    let needs_memory = matches!(
+        config.overlay_mode,
+        OverlayMode::Tmpfs | OverlayMode::Erofs | OverlayMode::Squashfs
+    );
+    if needs_memory
+        && let Some(memory) = available_memory()
+        && size > memory
+    {
+        issues.push(DiagnosticIssue {
+            code: IssueCode::StorageMemory,
+            level: DiagnosticLevel::Critical,
+            context: "storage".to_string(),
+            message: format!(
+                "Modules need {} MiB in tmpfs but only {} MiB of memory is available",
+                mib(size),
+                mib(memory)
+            ),
+            path: None,
+        });
+    }
+
+    let image_size = match config.overlay_mode {
+        OverlayMode::Tmpfs => None,
+        OverlayMode::Ext4 => Some(((size as f64 * 1.2) as u64).max(EXT4_MIN_IMAGE_SIZE)),
+        OverlayMode::Erofs | OverlayMode::Squashfs => Some(size),
+    };
+    let image_dir = Path::new(defs::MODULES_IMG_FILE)
+        .parent()
+        .unwrap_or(Path::new("/data"));
+
+    if let Some(image_size) = image_size
+        && let Some(space) = available_space(image_dir)
+        && image_size > space
+    {
+        issues.push(DiagnosticIssue {
+            code: IssueCode::StorageSpace,
+            level: DiagnosticLevel::Critical,
+            context: "storage".to_string(),
+            message: format!(
+                "Modules image needs up to {} MiB but {} only has {} MiB free",
+                mib(image_size),
+                image_dir.display(),
+                mib(space)
+            ),
+            path: None,
+        });
+    }
+
+    issues
+}
+
+pub fn check_plan(
+    plan: &MountPlan,
+    modules: &[Module],
+    config: &Config,
+    storage_root: &Path,
+) -> Vec<DiagnosticIssue> {
+    let mut issues = Vec::new();
+    let mount_root = Path::new(&config.hybrid_mnt_dir);
+    let arg_limit = limit::cached_arg_limit();
+
+    for op in &plan.overlay_ops {
+        let layers = op.lowerdirs.len() + 1;
+        if layers > MAX_LOWERDIR_COUNT {
+            issues.push(DiagnosticIssue {
+                code: IssueCode::OverlayTooManyLayers,
+                level: DiagnosticLevel::Warning,
+                context: op.partition_name.clone(),
+                message: format!(
+                    "Overlay on {} stacks {} layers, above the limit of {}; they will be merged \
+                     in staging overlays, which drops their whiteouts against the stock files",
+                    op.target, layers, MAX_LOWERDIR_COUNT
+                ),
+                path: Some(op.target.clone()),
+            });
+        }
+
+        // Lowerdirs are mounted from the storage mount point, not from where the plan found them.
+        let arg_len = op
+            .lowerdirs
+            .iter()
+            .map(|l| match l.strip_prefix(storage_root) {
+                Ok(rel) => mount_root.join(rel).as_os_str().len(),
+                Err(_) => l.as_os_str().len(),
+            })
+            .sum::<usize>()
+            + op.target.len()
+            + op.lowerdirs.len();
+        if arg_len > arg_limit {
+            issues.push(DiagnosticIssue {
+                code: IssueCode::OverlayArgTooLong,
+                level: DiagnosticLevel::Warning,
+                context: op.partition_name.clone(),
+                message: format!(
+                    "Overlay lowerdir option for {} is {} bytes, above the limit of {}; layers \
+                     will be merged in staging overlays, which drops their whiteouts against \
+                     the stock files",
+                    op.target, arg_len, arg_limit
+                ),
+                path: Some(op.target.clone()),
+            });
+        }
+    }
+
+    for fallback in &plan.depth_fallbacks {
+        issues.push(DiagnosticIssue {
+            code: IssueCode::OverlayStackDepth,
+            level: DiagnosticLevel::Warning,
+            context: fallback.modules.join(","),
+            message: format!(
+                "Overlay on {} would be stacked {} filesystems deep, above the kernel limit of \
+                 {} (the target is already an overlay, e.g. after adb remount); {} will be magic \
+                 mounted instead",
+                fallback.target,
+                fallback.depth,
+                limit::MAX_STACK_DEPTH,
+                fallback.modules.join(", ")
+            ),
+            path: Some(fallback.target.clone()),
+        });
+    }
+
+    for hit in &plan.protected_hits {
+        issues.push(DiagnosticIssue {
+            code: IssueCode::ProtectedPath,
+            level: DiagnosticLevel::Warning,
+            context: hit.module.clone(),
+            message: format!(
+                "{} ships content for protected path {}; the mounts that would put it in place \
+                 are skipped",
+                hit.module,
+                hit.path.display()
+            ),
+            path: Some(hit.path.to_string_lossy().to_string()),
+        });
+    }
+
+    let inodes = magic_mirror_inodes(plan, modules, storage_root);
+    if inodes > 0
+        && let Some(memory) = available_memory()
+        && inodes * TMPFS_INODE_COST > memory
+    {
+        issues.push(DiagnosticIssue {
+            code: IssueCode::MagicTmpfsMemory,
+            level: DiagnosticLevel::Critical,
+            context: "magic".to_string(),
+            message: format!(
+                "Magic mount mirrors about {} entries ({} MiB of tmpfs) but only {} MiB of \
+                 memory is available",
+                inodes,
+                mib(inodes * TMPFS_INODE_COST),
+                mib(memory)
+            ),
+            path: None,
+        });
+    }
+
+    issues
+}
@@ -0,0 +1,116 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use walkdir::WalkDir;
+
+use crate::{
+    conf::config::Config,
+    core::{
+        inventory::{self, Module, lifecycle},
+        ops::{manifest, planner::ConflictEntry},
+        state::RuntimeState,
+    },
+};
+
+pub fn walk_files(root: &Path) -> Vec<String> {
+    WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(root)
+                .ok()
+                .map(|rel| rel.to_string_lossy().to_string())
+        })
+        .collect()
+}
+
+// The manifest written at the last sync stands in for walking the synced copy.
+fn listing(storage: &Path, module: &Module) -> Vec<String> {
+    if !storage.is_absolute() {
+        return walk_files(&module.source_path);
+    }
+    if let Some(cached) = manifest::load(&manifest::manifest_path(&module.id)) {
+        return cached
+            .into_iter()
+            .filter(|(_, entry)| entry.is_file())
+            .map(|(path, _)| path)
+            .collect();
+    }
+    let synced = storage.join(&module.id);
+    walk_files(if synced.is_dir() {
+        &synced
+    } else {
+        &module.source_path
+    })
+}
+
+pub fn split<'a>(path: &'a str, partitions: &[String]) -> Option<(&'a str, &'a str)> {
+    let (partition, rest) = path.split_once('/')?;
+    partitions
+        .iter()
+        .any(|p| p == partition)
+        .then_some((partition, rest))
+}
+
+// Only the module's own files are walked, so it also works for a module that is still disabled.
+// Winners follow the planner's layer order and `conflicts resolve` pins, but per partition path
+// rather than per overlay target.
+pub fn check(config: &Config, id: &str) -> Result<Vec<ConflictEntry>> {
+    let source = lifecycle::locate(config, id)?;
+    let partitions = config.effective_partitions();
+    let own: BTreeSet<String> = walk_files(&source)
+        .into_iter()
+        .filter(|path| split(path, &partitions).is_some())
+        .collect();
+
+    let storage = RuntimeState::load().unwrap_or_default().mount_point;
+    let modules = inventory::scan(&config.moduledir, config).context("Failed to scan modules")?;
+
+    let mut contenders: BTreeMap<&str, Vec<(i32, &str)>> = own
+        .iter()
+        .map(|path| (path.as_str(), vec![(config.module_priority(id), id)]))
+        .collect();
+    for module in modules.iter().filter(|m| m.id != id) {
+        let priority = config.module_priority(&module.id);
+        for path in listing(&storage, module) {
+            if let Some(entry) = contenders.get_mut(path.as_str()) {
+                entry.push((priority, &module.id));
+            }
+        }
+    }
+
+    let mut conflicts = Vec::new();
+    for (path, mut modules) in contenders {
+        let Some((partition, relative)) = split(path, &partitions) else {
+            continue;
+        };
+        if modules.len() < 2 {
+            continue;
+        }
+        modules.sort_by_key(|&(priority, id)| (Reverse(priority), Reverse(id)));
+        let ids: Vec<String> = modules.iter().map(|(_, id)| id.to_string()).collect();
+        let winner = config
+            .resolve
+            .get(&format!("/{}", path))
+            .filter(|pinned| ids.contains(pinned))
+            .unwrap_or(&ids[0])
+            .clone();
+        conflicts.push(ConflictEntry {
+            partition: partition.to_string(),
+            relative_path: relative.to_string(),
+            winner,
+            contending_modules: ids,
+        });
+    }
+    Ok(conflicts)
+}
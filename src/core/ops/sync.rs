@@ -1,86 +1,306 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::{collections::HashSet, fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+    time::Instant,
+};
 
 use anyhow::Result;
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
-use crate::{core::inventory::Module, defs, utils};
-
-pub fn perform_sync(modules: &[Module], target_base: &Path) -> Result<()> {
+use crate::{
+    conf::config::{Config, KernelObjectPolicy},
+    core::{
+        inventory::{Module, lifecycle},
+        metrics,
+        ops::{
+            journal::{self, Journal, Phase},
+            manifest,
+        },
+    },
+    defs,
+    mount::explain::{self, MountOp},
+    sys::kmod,
+    utils,
+};
+
+pub fn perform_sync(modules: &[Module], target_base: &Path, config: &Config) -> Result<()> {
     log::info!("Starting smart module sync to {}", target_base.display());
 
+    let partitions = config.effective_partitions();
+    let strip_kernel_objects = config.kernel_object_policy == KernelObjectPolicy::Skip
+        && kmod::signature_enforcement().is_some();
+
+    journal::recover(target_base);
     prune_orphaned_modules(modules, target_base)?;
 
-    modules.par_iter().for_each(|module| {
-        let dst = target_base.join(&module.id);
-        let dst_backup = target_base.join(format!(".backup_{}", module.id));
+    let hashes: Vec<(String, Option<u32>)> = modules
+        .par_iter()
+        .map(|module| {
+            let started = Instant::now();
+            let dst = target_base.join(&module.id);
+
+            let has_content = partitions
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once(defs::ROOTDIR_NAME))
+                .any(|p| {
+                    let part_path = module.source_path.join(p);
+
+                    part_path.exists() && has_files_recursive(&part_path)
+                });
+
+            if !has_content {
+                log::debug!("Skipping module: {}", module.id);
+                return (module.id.clone(), None);
+            }
 
-        let has_content = defs::BUILTIN_PARTITIONS.iter().any(|p| {
-            let part_path = module.source_path.join(p);
+            let manifest_path = manifest::manifest_path(&module.id);
+            let previous = manifest::load(&manifest_path);
+            let current = match manifest::build(&module.source_path, previous.as_ref()) {
+                Ok(current) => Some(current),
+                Err(e) => {
+                    log::warn!("Failed to index module {}: {}", module.id, e);
+                    None
+                }
+            };
 
-            part_path.exists() && has_files_recursive(&part_path)
-        });
+            // The storage is rebuilt every boot, so only a copy made earlier in this boot (by
+            // post-fs-data, for the service stage) is still there to patch.
+            let delta = match (&previous, &current) {
+                (Some(previous), Some(current)) if dst.exists() => {
+                    Some(manifest::diff(previous, current))
+                }
+                _ => None,
+            };
 
-        if has_content && should_sync(&module.source_path, &dst) {
-            log::info!("Syncing module: {} (Updated/New)", module.id);
+            let synced = match delta {
+                Some(delta) if delta.is_empty() => {
+                    log::debug!("Skipping module: {} (unchanged)", module.id);
+                    true
+                }
+                Some(delta) => {
+                    log::info!(
+                        "Syncing module: {} ({} changed, {} removed)",
+                        module.id,
+                        delta.changed.len(),
+                        delta.removed.len()
+                    );
+                    match apply_delta(module, &dst, &delta, strip_kernel_objects) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            log::warn!(
+                                "Incremental sync failed for {}, falling back to a full copy: {:#}",
+                                module.id,
+                                e
+                            );
+                            full_sync(module, target_base, strip_kernel_objects)
+                        }
+                    }
+                }
+                None => {
+                    log::info!("Syncing module: {} (Updated/New)", module.id);
+                    full_sync(module, target_base, strip_kernel_objects)
+                }
+            };
+
+            let hash = current.as_ref().filter(|_| synced).map(manifest::tree_hash);
+            let saved = match current {
+                Some(current) if synced => manifest::save(&manifest_path, &current),
+                _ => fs::remove_file(&manifest_path).or(Ok(())),
+            };
+            if let Err(e) = saved {
+                log::warn!("Failed to update sync manifest for {}: {}", module.id, e);
+            }
+            metrics::record_sync(&module.id, started.elapsed());
+            (module.id.clone(), hash)
+        })
+        .collect();
 
-            let tmp_dst = target_base.join(format!(".tmp_{}", module.id));
+    if let Err(e) = record_source_hashes(hashes) {
+        log::warn!("Failed to record module source hashes: {}", e);
+    }
+    manifest::prune(|id| lifecycle::locate(config, id).is_ok());
 
-            if tmp_dst.exists() {
-                let _ = fs::remove_dir_all(&tmp_dst);
-            }
+    Ok(())
+}
 
-            if let Err(e) = utils::sync_dir(&module.source_path, &tmp_dst, true) {
-                log::error!("Failed to sync module {}: {}", module.id, e);
-                let _ = fs::remove_dir_all(&tmp_dst);
-                return;
-            }
+fn load_source_hashes() -> BTreeMap<String, u32> {
+    fs::read_to_string(defs::SOURCE_HASHES_FILE)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
 
-            if let Err(e) = utils::prune_empty_dirs(&tmp_dst) {
-                log::warn!("Failed to prune empty dirs for {}: {}", module.id, e);
-            }
+fn record_source_hashes(hashes: Vec<(String, Option<u32>)>) -> Result<()> {
+    let mut recorded = load_source_hashes();
+    for (id, hash) in hashes {
+        match hash {
+            Some(hash) => recorded.insert(id, hash),
+            None => recorded.remove(&id),
+        };
+    }
+    utils::atomic_write(defs::SOURCE_HASHES_FILE, serde_json::to_string(&recorded)?)
+}
 
-            if let Err(e) = apply_overlay_opaque_flags(&tmp_dst) {
-                log::warn!(
-                    "Failed to apply overlay opaque xattrs for {}: {}",
-                    module.id,
-                    e
-                );
-            }
+// Modules whose source tree no longer matches the copy made at the last sync. The sync manifests
+// let unchanged files skip rehashing.
+pub fn changed_since_sync(modules: &[Module]) -> Vec<String> {
+    let recorded = load_source_hashes();
+
+    let mut changed: Vec<String> = modules
+        .par_iter()
+        .filter_map(|module| {
+            let synced = *recorded.get(&module.id)?;
+            let previous = manifest::load(&manifest::manifest_path(&module.id));
+            let current = manifest::build(&module.source_path, previous.as_ref()).ok()?;
+            (manifest::tree_hash(&current) != synced).then(|| module.id.clone())
+        })
+        .collect();
+
+    changed.sort();
+    changed
+}
 
-            let mut backup_created = false;
-            if dst.exists() {
-                if let Err(e) = fs::rename(&dst, &dst_backup) {
-                    log::error!("Failed to backup existing module {}: {}", module.id, e);
-                    let _ = fs::remove_dir_all(&tmp_dst);
-                    return;
-                }
-                backup_created = true;
-            }
+fn finish_tree(module_id: &str, root: &Path, strip_kernel_objects: bool) {
+    if strip_kernel_objects {
+        strip_dlkm_kernel_objects(module_id, root);
+    }
 
-            if let Err(e) = fs::rename(&tmp_dst, &dst) {
-                log::error!("Failed to commit atomic sync for {}: {}", module.id, e);
-                if backup_created {
-                    let _ = fs::rename(&dst_backup, &dst);
-                }
-                let _ = fs::remove_dir_all(&tmp_dst);
-                return;
-            }
+    if let Err(e) = utils::prune_empty_dirs(root) {
+        log::warn!("Failed to prune empty dirs for {}: {}", module_id, e);
+    }
 
-            if backup_created && let Err(e) = fs::remove_dir_all(&dst_backup) {
-                log::warn!("Failed to clean up backup for {}: {}", module.id, e);
-            }
-        } else {
-            log::debug!("Skipping module: {}", module.id);
+    if let Err(e) = apply_overlay_opaque_flags(root) {
+        log::warn!(
+            "Failed to apply overlay opaque xattrs for {}: {}",
+            module_id,
+            e
+        );
+    }
+}
+
+fn apply_delta(
+    module: &Module,
+    dst: &Path,
+    delta: &manifest::Delta,
+    strip_kernel_objects: bool,
+) -> Result<()> {
+    let journal = Journal::begin(&module.id, Phase::Patching)?;
+
+    for rel in delta.removed.iter().rev() {
+        let path = dst.join(rel);
+        match fs::symlink_metadata(&path) {
+            Ok(m) if m.is_dir() => fs::remove_dir_all(&path)?,
+            Ok(_) => fs::remove_file(&path)?,
+            Err(_) => {}
         }
-    });
+    }
+
+    for rel in &delta.changed {
+        let path = dst.join(rel);
+        // Parents may have been pruned as empty on an earlier sync.
+        if let Some(parent) = path.parent() {
+            utils::ensure_dir_exists(parent)?;
+        }
+        utils::copy_entry(&module.source_path.join(rel), &path)?;
+    }
 
+    finish_tree(&module.id, dst, strip_kernel_objects);
+    journal.commit();
     Ok(())
 }
 
+fn full_sync(module: &Module, target_base: &Path, strip_kernel_objects: bool) -> bool {
+    let dst = target_base.join(&module.id);
+    let dst_backup = journal::backup_dir(target_base, &module.id);
+    let tmp_dst = journal::staging_dir(target_base, &module.id);
+
+    if tmp_dst.exists() {
+        let _ = fs::remove_dir_all(&tmp_dst);
+    }
+
+    let journal = match Journal::begin(&module.id, Phase::Staging) {
+        Ok(journal) => journal,
+        Err(e) => {
+            log::error!("Failed to journal sync of {}: {}", module.id, e);
+            return false;
+        }
+    };
+
+    if let Err(e) = utils::sync_dir(&module.source_path, &tmp_dst, true) {
+        log::error!(
+            "Failed to sync module {}: {}",
+            module.id,
+            explain::explain(MountOp::Sync, &e)
+        );
+        let _ = fs::remove_dir_all(&tmp_dst);
+        journal.commit();
+        return false;
+    }
+
+    finish_tree(&module.id, &tmp_dst, strip_kernel_objects);
+
+    if let Err(e) = journal.advance(Phase::Swapping) {
+        log::error!("Failed to journal sync of {}: {}", module.id, e);
+        let _ = fs::remove_dir_all(&tmp_dst);
+        journal.commit();
+        return false;
+    }
+
+    let mut backup_created = false;
+    if dst.exists() {
+        if let Err(e) = fs::rename(&dst, &dst_backup) {
+            log::error!("Failed to backup existing module {}: {}", module.id, e);
+            let _ = fs::remove_dir_all(&tmp_dst);
+            journal.commit();
+            return false;
+        }
+        backup_created = true;
+    }
+
+    if let Err(e) = fs::rename(&tmp_dst, &dst) {
+        log::error!("Failed to commit atomic sync for {}: {}", module.id, e);
+        if backup_created {
+            let _ = fs::rename(&dst_backup, &dst);
+        }
+        let _ = fs::remove_dir_all(&tmp_dst);
+        journal.commit();
+        return false;
+    }
+
+    if backup_created && let Err(e) = fs::remove_dir_all(&dst_backup) {
+        log::warn!("Failed to clean up backup for {}: {}", module.id, e);
+    }
+    journal.commit();
+
+    true
+}
+
+fn strip_dlkm_kernel_objects(module_id: &str, root: &Path) {
+    for (partition, _) in defs::DLKM_PARTITIONS {
+        for entry in WalkDir::new(root.join(partition)).into_iter().flatten() {
+            if !entry.file_type().is_file() || !kmod::is_kernel_object(entry.path()) {
+                continue;
+            }
+
+            log::warn!(
+                "Skipping kernel object {} from {} ({})",
+                entry.path().display(),
+                module_id,
+                kmod::signature_enforcement().unwrap_or("signature enforced")
+            );
+            if let Err(e) = fs::remove_file(entry.path()) {
+                log::warn!("Failed to drop {}: {}", entry.path().display(), e);
+            }
+        }
+    }
+}
+
 fn apply_overlay_opaque_flags(root: &Path) -> Result<()> {
     for entry in WalkDir::new(root).min_depth(1).into_iter().flatten() {
         if entry.file_type().is_file()
@@ -113,7 +333,7 @@ fn prune_orphaned_modules(modules: &[Module], target_base: &Path) -> Result<()>
         if name != "lost+found"
             && name != "meta-hybrid"
             && !name.starts_with('.')
-            && !active_ids.contains(name.as_ref())
+            && !active_ids.contains(&*name)
         {
             log::info!("Pruning orphaned module storage: {}", name);
 
@@ -130,25 +350,6 @@ fn prune_orphaned_modules(modules: &[Module], target_base: &Path) -> Result<()>
     Ok(())
 }
 
-fn should_sync(src: &Path, dst: &Path) -> bool {
-    if !dst.exists() {
-        return true;
-    }
-
-    let src_prop = src.join("module.prop");
-
-    let dst_prop = dst.join("module.prop");
-
-    if !src_prop.exists() || !dst_prop.exists() {
-        return true;
-    }
-
-    match (fs::read(&src_prop), fs::read(&dst_prop)) {
-        (Ok(s), Ok(d)) => s != d,
-        _ => true,
-    }
-}
-
 fn has_files_recursive(path: &Path) -> bool {
     if let Ok(entries) = fs::read_dir(path) {
         for entry in entries.flatten() {
@@ -0,0 +1,203 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+};
+
+use procfs::process::{MountInfo, Process};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::{
+    core::state::{MountKind, MountRecord},
+    defs, utils,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnverifiedMount {
+    pub kind: MountKind,
+    pub target: String,
+    pub modules: Vec<String>,
+    pub reason: String,
+}
+
+// The shape of the part of a merged view the modules provide, taken right after mounting. The
+// layers are usually detached by the time of a recheck, so the path list goes to a file of its
+// own; the digest covers the paths too, so an edited list shows up as drift.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub target: String,
+    pub digest: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FingerprintDrift {
+    pub target: String,
+    pub expected: String,
+    pub found: String,
+}
+
+pub fn current_mountinfo() -> Vec<MountInfo> {
+    Process::myself()
+        .and_then(|p| p.mountinfo())
+        .map(|m| m.into_iter().collect())
+        .unwrap_or_default()
+}
+
+// A bind mount shows up with the filesystem and source of the mount its source path lives on.
+pub fn backing(path: &Path, mountinfo: &[MountInfo]) -> (String, String) {
+    mountinfo
+        .iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .max_by_key(|m| m.mount_point.as_os_str().len())
+        .map(|m| {
+            (
+                m.fs_type.clone(),
+                m.mount_source.clone().unwrap_or_default(),
+            )
+        })
+        .unwrap_or_default()
+}
+
+// Re-reads mountinfo after execution: a mount call that returned Ok can still be missing (torn
+// down by someone else) or hidden under a later mount on the same path. Each record names the
+// filesystem and source it left on top, and anything else there counts as covered.
+pub fn verify(mounts: &[MountRecord]) -> Vec<UnverifiedMount> {
+    let Ok(mountinfo) = Process::myself().and_then(|p| p.mountinfo()) else {
+        log::warn!("Failed to read mountinfo, skipping mount verification");
+        return Vec::new();
+    };
+
+    let mut unverified = Vec::new();
+    for record in mounts {
+        // mountinfo lists mounts in order, so the last one on a path is what is visible.
+        let top = mountinfo
+            .iter()
+            .rfind(|m| m.mount_point.to_string_lossy() == record.target.trim_end_matches('/'));
+
+        let reason = match top {
+            None => "not mounted".to_string(),
+            Some(m)
+                if (!record.fs_type.is_empty() && m.fs_type != record.fs_type)
+                    || (!record.source.is_empty()
+                        && m.mount_source.as_deref() != Some(record.source.as_str())) =>
+            {
+                format!(
+                    "covered by {} mount from {}",
+                    m.fs_type,
+                    m.mount_source.as_deref().unwrap_or("none")
+                )
+            }
+            Some(_) => continue,
+        };
+
+        log::error!("Mount verification: {} {}", record.target, reason);
+        unverified.push(UnverifiedMount {
+            kind: record.kind,
+            target: record.target.clone(),
+            modules: record.modules.clone(),
+            reason,
+        });
+    }
+
+    unverified
+}
+
+fn type_tag(file_type: fs::FileType) -> u8 {
+    if file_type.is_dir() {
+        b'd'
+    } else if file_type.is_symlink() {
+        b'l'
+    } else if file_type.is_char_device() {
+        b'c'
+    } else if file_type.is_file() {
+        b'f'
+    } else {
+        b'o'
+    }
+}
+
+// Sizes and types only, no content: cheap enough to repeat from the watchdog. Whiteouts in the
+// layers show up as missing entries in the merged view, which is part of the shape too.
+fn structure(target: &str, paths: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for rel in paths {
+        hasher.update(rel.as_bytes());
+        hasher.update([0]);
+        match fs::symlink_metadata(Path::new(target).join(rel)) {
+            Ok(meta) => {
+                hasher.update([type_tag(meta.file_type())]);
+                hasher.update(meta.len().to_le_bytes());
+            }
+            Err(_) => hasher.update(b"-"),
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn paths_file(target: &str) -> PathBuf {
+    let name = format!("{:x}", Sha256::digest(target.as_bytes()));
+    Path::new(defs::FINGERPRINTS_DIR).join(&name[..16])
+}
+
+// A missing or unreadable list reads as empty, which no longer matches the digest.
+fn load_paths(target: &str) -> Vec<String> {
+    fs::read_to_string(paths_file(target))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_paths(target: &str, paths: &[String]) {
+    let result = utils::ensure_dir_exists(defs::FINGERPRINTS_DIR)
+        .and_then(|_| Ok(serde_json::to_string(paths)?))
+        .and_then(|json| utils::atomic_write(paths_file(target), json));
+    if let Err(e) = result {
+        log::warn!("Failed to save fingerprint paths of {}: {:#}", target, e);
+    }
+}
+
+pub fn fingerprint(target: &str, layers: &[PathBuf]) -> Fingerprint {
+    let paths: BTreeSet<String> = layers
+        .iter()
+        .flat_map(|layer| {
+            WalkDir::new(layer)
+                .min_depth(1)
+                .into_iter()
+                .flatten()
+                .filter(|e| !e.file_type().is_dir())
+                .filter_map(move |e| {
+                    e.path()
+                        .strip_prefix(layer)
+                        .ok()
+                        .map(|rel| rel.to_string_lossy().to_string())
+                })
+        })
+        .collect();
+    let paths: Vec<String> = paths.into_iter().collect();
+    save_paths(target, &paths);
+
+    Fingerprint {
+        target: target.to_string(),
+        digest: structure(target, &paths),
+    }
+}
+
+pub fn recheck(fingerprints: &[Fingerprint]) -> Vec<FingerprintDrift> {
+    fingerprints
+        .iter()
+        .filter_map(|f| {
+            let found = structure(&f.target, &load_paths(&f.target));
+            (found != f.digest).then(|| FingerprintDrift {
+                target: f.target.clone(),
+                expected: f.digest.clone(),
+                found,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,87 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{conf::config::Config, core::ops::planner::MountPlan, utils};
+
+// How a `conflicts resolve` rule plays out against the current plan.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuleReview {
+    pub path: String,
+    pub module: String,
+    pub contenders: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub natural_winner: Option<String>,
+    pub forced: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale: Option<String>,
+}
+
+fn contenders(path: &Path, plan: &MountPlan, storage_root: &Path) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+
+    // Overlay layers are already in winning order.
+    for op in &plan.overlay_ops {
+        let Ok(rel) = path.strip_prefix(&op.target) else {
+            continue;
+        };
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+        for layer in &op.lowerdirs {
+            if layer.join(rel).is_file()
+                && let Some(id) = utils::extract_module_id(layer)
+                && !ids.contains(&id)
+            {
+                ids.push(id);
+            }
+        }
+    }
+
+    if let Ok(rel) = path.strip_prefix("/") {
+        for id in &plan.magic_module_ids {
+            let module = storage_root.join(id);
+            if (module.join(rel).is_file() || module.join("system").join(rel).is_file())
+                && !ids.contains(id)
+            {
+                ids.push(id.clone());
+            }
+        }
+    }
+
+    ids
+}
+
+pub fn review(config: &Config, plan: &MountPlan, storage_root: &Path) -> Vec<RuleReview> {
+    let mut rules: Vec<(&String, &String)> = config.resolve.iter().collect();
+    rules.sort();
+
+    rules
+        .into_iter()
+        .map(|(path, module)| {
+            let contenders = contenders(Path::new(path), plan, storage_root);
+            let natural_winner = contenders.first().cloned();
+
+            let stale = if !contenders.contains(module) {
+                Some("module no longer provides this file".to_string())
+            } else if contenders.len() < 2 {
+                Some("no conflict at this path".to_string())
+            } else {
+                None
+            };
+            let forced = stale.is_none() && natural_winner.as_ref() != Some(module);
+
+            RuleReview {
+                path: path.clone(),
+                module: module.clone(),
+                contenders,
+                natural_winner,
+                forced,
+                stale,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,208 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs::{self, File},
+    io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use walkdir::WalkDir;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    conf::config::Config,
+    core::{inventory::files, rw},
+    utils,
+};
+
+const RW_MODULE_ID: &str = "meta-hybrid-rw";
+
+// The stock Magisk installer stub; KernelSU and APatch install the zip without running it.
+const UPDATE_BINARY: &str = r#"#!/sbin/sh
+umask 022
+ui_print() { echo "$1"; }
+OUTFD=$2
+ZIPFILE=$3
+mount /data 2>/dev/null
+if [ ! -f /data/adb/magisk/util_functions.sh ]; then
+  ui_print "Install this module from the Magisk, KernelSU or APatch app"
+  exit 1
+fi
+. /data/adb/magisk/util_functions.sh
+install_module
+exit 0
+"#;
+
+pub enum Source {
+    Rw,
+    Module(String),
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PackageSummary {
+    pub id: String,
+    pub output: PathBuf,
+    pub files: usize,
+    pub removed: Vec<String>,
+    pub replaced: Vec<String>,
+}
+
+// (directory to read, path it takes inside the zip)
+fn roots(config: &Config, source: &Source) -> Result<Vec<(PathBuf, String)>> {
+    match source {
+        // Magisk-style modules keep every partition below system/.
+        Source::Rw => {
+            let roots: Vec<(PathBuf, String)> = rw::upper_dirs(config)
+                .into_iter()
+                .map(|(partition, upper)| {
+                    let prefix = if partition == "system" {
+                        partition
+                    } else {
+                        format!("system/{}", partition)
+                    };
+                    (upper, prefix)
+                })
+                .collect();
+            if roots.is_empty() {
+                bail!("No partition has read-write changes; see `meta-hybrid rw enable`");
+            }
+            Ok(roots)
+        }
+        Source::Module(id) => {
+            let content = files::content_root(config, id)?;
+            Ok(config
+                .effective_partitions()
+                .into_iter()
+                .map(|p| (content.join(&p), p))
+                .filter(|(dir, _)| dir.is_dir())
+                .collect())
+        }
+    }
+}
+
+fn add_root(
+    zip: &mut ZipWriter<File>,
+    root: &Path,
+    prefix: &str,
+    summary: &mut PackageSummary,
+) -> Result<()> {
+    for entry in WalkDir::new(root).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        let rel = path.strip_prefix(root)?;
+        let name = Path::new(prefix).join(rel).to_string_lossy().to_string();
+        let metadata = entry.metadata()?;
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(metadata.permissions().mode() & 0o7777);
+
+        // Whiteouts and opaque directories cannot be stored in a zip; the installer recreates
+        // them from REMOVE and REPLACE.
+        if utils::is_whiteout(path) || utils::is_xattr_whiteout(path) {
+            summary.removed.push(format!("/{}", name));
+        } else if entry.file_type().is_dir() {
+            if utils::is_overlay_opaque(path) {
+                summary.replaced.push(format!("/{}", name));
+            }
+            zip.add_directory(name, options)?;
+        } else if entry.file_type().is_symlink() {
+            let target = fs::read_link(path)?;
+            zip.add_symlink(name, target.to_string_lossy(), options)?;
+            summary.files += 1;
+        } else if entry.file_type().is_file() {
+            zip.start_file(name, options)?;
+            io::copy(&mut File::open(path)?, zip)?;
+            summary.files += 1;
+        } else {
+            log::warn!("Export: skipping special file {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn add_text(zip: &mut ZipWriter<File>, name: &str, content: &str, mode: u32) -> Result<()> {
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(mode);
+    zip.start_file(name, options)?;
+    io::Write::write_all(zip, content.as_bytes())?;
+    Ok(())
+}
+
+pub fn export(
+    config: &Config,
+    source: &Source,
+    id: Option<&str>,
+    name: Option<&str>,
+    output: &Path,
+) -> Result<PackageSummary> {
+    let id = match (id, source) {
+        (Some(id), _) => id.to_string(),
+        (None, Source::Rw) => RW_MODULE_ID.to_string(),
+        (None, Source::Module(module)) => format!("{}-export", module),
+    };
+    utils::validate_module_id(&id)?;
+    let description = match source {
+        Source::Rw => "Changes made to read-write partitions".to_string(),
+        Source::Module(module) => format!("Exported from {}", module),
+    };
+
+    let roots = roots(config, source)?;
+    if let Some(parent) = output.parent() {
+        utils::ensure_dir_exists(parent)?;
+    }
+    let file =
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let mut summary = PackageSummary {
+        id: id.clone(),
+        output: output.to_path_buf(),
+        ..Default::default()
+    };
+
+    for (root, prefix) in &roots {
+        add_root(&mut zip, root, prefix, &mut summary)
+            .with_context(|| format!("Failed to pack {}", root.display()))?;
+    }
+
+    add_text(
+        &mut zip,
+        "module.prop",
+        &format!(
+            "id={}\nname={}\nversion=1\nversionCode=1\nauthor=meta-hybrid\ndescription={}\n",
+            id,
+            name.unwrap_or(&id),
+            description
+        ),
+        0o644,
+    )?;
+    add_text(
+        &mut zip,
+        "customize.sh",
+        &format!(
+            "REMOVE=\"\n{}\n\"\nREPLACE=\"\n{}\n\"\n",
+            summary.removed.join("\n"),
+            summary.replaced.join("\n")
+        ),
+        0o644,
+    )?;
+    add_text(
+        &mut zip,
+        "META-INF/com/google/android/update-binary",
+        UPDATE_BINARY,
+        0o755,
+    )?;
+    add_text(
+        &mut zip,
+        "META-INF/com/google/android/updater-script",
+        "#MAGISK\n",
+        0o644,
+    )?;
+    zip.finish().context("Failed to finish module zip")?;
+
+    Ok(summary)
+}
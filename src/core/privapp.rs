@@ -0,0 +1,200 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{Context, Result};
+use regex_lite::Regex;
+use walkdir::WalkDir;
+
+use crate::{
+    core::{
+        diagnostics::{DiagnosticIssue, DiagnosticLevel, IssueCode},
+        inventory::Module,
+    },
+    defs,
+    utils::{self, apk},
+};
+
+pub const MANAGED_MODULE_ID: &str = "meta-hybrid.privapp";
+const STOCK_PERMISSION_DIRS: &[&str] = &[
+    "/system/etc/permissions",
+    "/system_ext/etc/permissions",
+    "/product/etc/permissions",
+    "/vendor/etc/permissions",
+    "/odm/etc/permissions",
+];
+
+static PRIVAPP_REGEX: OnceLock<Regex> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct UnlistedPrivApp {
+    pub module_id: String,
+    pub partition: String,
+    pub apk: PathBuf,
+    pub manifest: apk::ApkManifest,
+}
+
+fn collect_whitelisted(dir: &Path, packages: &mut HashSet<String>) {
+    let re = PRIVAPP_REGEX.get_or_init(|| {
+        Regex::new(r#"<privapp-permissions\s+package\s*=\s*"([^"]+)""#)
+            .expect("Failed to compile privapp regex")
+    });
+
+    for entry in WalkDir::new(dir).max_depth(6).into_iter().flatten() {
+        let path = entry.path();
+        if !entry.file_type().is_file()
+            || path.extension().is_none_or(|e| e != "xml")
+            || !path
+                .parent()
+                .is_some_and(|p| p.ends_with("etc/permissions"))
+        {
+            continue;
+        }
+
+        if let Ok(content) = fs::read_to_string(path) {
+            packages.extend(re.captures_iter(&content).map(|c| c[1].to_string()));
+        }
+    }
+}
+
+// Whitelists are read per partition; an app under system/product belongs to product.
+fn whitelist_partition(rel: &Path) -> String {
+    let components: Vec<String> = rel
+        .iter()
+        .map(|c| c.to_string_lossy().to_string())
+        .collect();
+
+    components
+        .iter()
+        .position(|c| c == "priv-app")
+        .and_then(|i| i.checked_sub(1))
+        .map(|i| components[i].clone())
+        .unwrap_or_else(|| "system".to_string())
+}
+
+fn content_root(module: &Module, storage_root: &Path) -> PathBuf {
+    let synced = storage_root.join(&module.id);
+    if synced.exists() {
+        synced
+    } else {
+        module.source_path.clone()
+    }
+}
+
+pub fn find_unlisted(modules: &[Module], storage_root: &Path) -> Vec<UnlistedPrivApp> {
+    let mut whitelisted = HashSet::new();
+    for dir in STOCK_PERMISSION_DIRS {
+        collect_whitelisted(Path::new(dir), &mut whitelisted);
+    }
+    for module in modules {
+        collect_whitelisted(&content_root(module, storage_root), &mut whitelisted);
+    }
+
+    let mut unlisted = Vec::new();
+
+    for module in modules {
+        let root = content_root(module, storage_root);
+
+        for entry in WalkDir::new(&root).max_depth(6).into_iter().flatten() {
+            let path = entry.path();
+            if !entry.file_type().is_file() || path.extension().is_none_or(|e| e != "apk") {
+                continue;
+            }
+
+            let Ok(rel) = path.strip_prefix(&root) else {
+                continue;
+            };
+            let Some(app_dir) = rel.parent() else {
+                continue;
+            };
+            if app_dir.parent().and_then(|p| p.file_name()) != Some("priv-app".as_ref()) {
+                continue;
+            }
+
+            if Path::new("/").join(app_dir).exists() {
+                continue;
+            }
+
+            match apk::read_manifest(path) {
+                Ok(manifest) => {
+                    if manifest.permissions.is_empty() || whitelisted.contains(&manifest.package) {
+                        continue;
+                    }
+                    unlisted.push(UnlistedPrivApp {
+                        module_id: module.id.clone(),
+                        partition: whitelist_partition(rel),
+                        apk: rel.to_path_buf(),
+                        manifest,
+                    });
+                }
+                Err(e) => log::debug!("Skipping privapp check for {}: {:#}", path.display(), e),
+            }
+        }
+    }
+
+    unlisted
+}
+
+pub fn to_diagnostics(apps: &[UnlistedPrivApp]) -> Vec<DiagnosticIssue> {
+    apps.iter()
+        .map(|app| DiagnosticIssue {
+            code: IssueCode::PrivappUnlisted,
+            level: DiagnosticLevel::Warning,
+            context: app.module_id.clone(),
+            message: format!(
+                "New priv-app {} (/{}) has no privapp-permissions entry in any layer; ROMs \
+                 enforcing privapp permissions will bootloop",
+                app.manifest.package,
+                app.apk.display()
+            ),
+            path: Some(Path::new("/").join(&app.apk).to_string_lossy().to_string()),
+        })
+        .collect()
+}
+
+fn render_whitelist(app: &UnlistedPrivApp) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!-- Generated by meta-hybrid -->\n<permissions>\n",
+    );
+
+    xml.push_str(&format!(
+        "    <privapp-permissions package=\"{}\">\n",
+        app.manifest.package
+    ));
+    for permission in &app.manifest.permissions {
+        xml.push_str(&format!("        <permission name=\"{}\"/>\n", permission));
+    }
+    xml.push_str("    </privapp-permissions>\n</permissions>\n");
+    xml
+}
+
+pub fn generate_managed_module(apps: &[UnlistedPrivApp]) -> Result<PathBuf> {
+    let module_dir = Path::new(defs::MANAGED_MODULES_DIR).join(MANAGED_MODULE_ID);
+
+    for app in apps {
+        let dir = module_dir.join(&app.partition).join("etc/permissions");
+        utils::ensure_dir_exists(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        utils::atomic_write(
+            dir.join(format!("privapp-permissions-{}.xml", app.manifest.package)),
+            render_whitelist(app),
+        )?;
+    }
+
+    utils::atomic_write(
+        module_dir.join("module.prop"),
+        format!(
+            "id={}\nname=Hybrid Mount privapp permissions\nversion=1\nversionCode=1\n\
+             author=meta-hybrid\ndescription=Generated privapp-permissions whitelist\n",
+            MANAGED_MODULE_ID
+        ),
+    )?;
+
+    Ok(module_dir)
+}
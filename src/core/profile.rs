@@ -0,0 +1,215 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::{self, Config},
+    core::{granary, integrity, state::KvStore},
+    defs, utils,
+};
+
+const PROFILE_EXT: &str = "json";
+const TOML_EXT: &str = "toml";
+pub const ACTIVE_KEY: &str = "active_profile";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileFormat {
+    // Written by `profile save`: config plus module states.
+    #[default]
+    Snapshot,
+    // A plain config file dropped into the profiles dir by hand; module states are left alone.
+    Toml,
+}
+
+impl ProfileFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Snapshot => "snapshot",
+            Self::Toml => "toml",
+        }
+    }
+}
+
+// A named setup to switch between: the whole config (winnow `resolve` rules included) plus which
+// modules are disabled.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub created: u64,
+    pub config: String,
+    #[serde(default)]
+    pub disabled_modules: Vec<String>,
+    #[serde(skip)]
+    pub format: ProfileFormat,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileEntry {
+    pub name: String,
+    pub format: ProfileFormat,
+    pub created: u64,
+    pub disabled_modules: usize,
+    pub active: bool,
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        bail!("Invalid profile name: {:?}", name);
+    }
+    Ok(())
+}
+
+fn profile_path(name: &str) -> Result<PathBuf> {
+    validate_name(name)?;
+    Ok(Path::new(defs::PROFILES_DIR).join(format!("{}.{}", name, PROFILE_EXT)))
+}
+
+fn toml_path(name: &str) -> Result<PathBuf> {
+    validate_name(name)?;
+    Ok(Path::new(defs::PROFILES_DIR).join(format!("{}.{}", name, TOML_EXT)))
+}
+
+// It becomes the config root reads at boot, so it gets the same writer check as the config itself.
+fn load_toml(name: &str, path: &Path) -> Result<Profile> {
+    config::check_writers(path)?;
+    let content = fs::read_to_string(path).with_context(|| format!("No profile named {}", name))?;
+    let created = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    Ok(Profile {
+        name: name.to_string(),
+        created,
+        config: content,
+        disabled_modules: Vec::new(),
+        format: ProfileFormat::Toml,
+    })
+}
+
+// A saved snapshot wins over a hand-written TOML file of the same name.
+pub fn load(name: &str) -> Result<Profile> {
+    let path = profile_path(name)?;
+    if !path.exists() {
+        let toml = toml_path(name)?;
+        if toml.exists() {
+            return load_toml(name, &toml);
+        }
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("No profile named {}", name))?;
+    serde_json::from_str(&content).with_context(|| format!("Profile {} is corrupt", name))
+}
+
+pub fn active() -> Option<String> {
+    KvStore::get::<String>(ACTIVE_KEY).filter(|s| !s.is_empty())
+}
+
+pub fn list() -> Vec<ProfileEntry> {
+    let Ok(entries) = fs::read_dir(defs::PROFILES_DIR) else {
+        return Vec::new();
+    };
+    let active = active();
+
+    let names: BTreeSet<String> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == PROFILE_EXT || ext == TOML_EXT)
+        })
+        .filter_map(|p| Some(p.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+
+    names
+        .iter()
+        .filter_map(|name| load(name).ok())
+        .map(|p| ProfileEntry {
+            active: active.as_deref() == Some(p.name.as_str()),
+            name: p.name,
+            format: p.format,
+            created: p.created,
+            disabled_modules: p.disabled_modules.len(),
+        })
+        .collect()
+}
+
+pub fn save(name: &str, config: &Config) -> Result<Profile> {
+    let path = profile_path(name)?;
+    utils::ensure_dir_exists(defs::PROFILES_DIR)?;
+
+    let profile = Profile {
+        name: name.to_string(),
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        config: toml::to_string(config).context("Failed to serialize config")?,
+        disabled_modules: granary::disabled_modules(config),
+        format: ProfileFormat::Snapshot,
+    };
+    utils::atomic_write(&path, serde_json::to_string_pretty(&profile)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(profile)
+}
+
+pub fn delete(name: &str) -> Result<()> {
+    let removed = [profile_path(name)?, toml_path(name)?]
+        .iter()
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count();
+    if removed == 0 {
+        bail!("No profile named {}", name);
+    }
+    if active().as_deref() == Some(name) {
+        let _ = KvStore::remove(ACTIVE_KEY);
+    }
+    Ok(())
+}
+
+// Mounts are only built at boot, so the profile's config and module states are written now and
+// take effect on the next one.
+pub fn switch(name: &str, config_path: &Path) -> Result<Profile> {
+    let profile = load(name)?;
+    let config: Config = toml::from_str(&profile.config)
+        .with_context(|| format!("Profile {} holds an invalid config", name))?;
+
+    integrity::guard_config(config_path)?;
+    utils::atomic_write(config_path, &profile.config)
+        .with_context(|| format!("Failed to write {}", config_path.display()))?;
+    integrity::sign_config(config_path, config.integrity)?;
+    if profile.format == ProfileFormat::Snapshot {
+        granary::apply_module_states(&config, &profile.disabled_modules);
+    }
+    KvStore::set(ACTIVE_KEY, &name)?;
+
+    log::info!("Profile: switched to {}, mounts follow on next boot", name);
+    Ok(profile)
+}
+
+// The parts of a new config that never change what is mounted, so a running daemon can adopt
+// them without waiting for a reboot.
+pub fn apply_live(current: &mut Config, next: Config) {
+    current.logging = next.logging;
+    current.log_format = next.log_format;
+    current.suppressed_issues = next.suppressed_issues;
+    current.notify = next.notify;
+    current.backup = next.backup;
+    current.artifacts = next.artifacts;
+    current.apply_log_settings();
+}
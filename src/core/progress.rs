@@ -0,0 +1,119 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    os::unix::fs::{FileTypeExt, OpenOptionsExt},
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use nix::{fcntl::OFlag, sys::stat::Mode, unistd::mkfifo};
+use serde::{Deserialize, Serialize};
+
+use crate::{core::trace, defs, utils};
+
+pub const PROGRESS_PROPERTY: &str = "meta.hybrid.stage";
+
+static STARTED: Mutex<Vec<(Phase, Instant)>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub millis: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Storage,
+    Scan,
+    Sync,
+    Plan,
+    Mount,
+    Complete,
+    Failed,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Storage => "storage",
+            Self::Scan => "scan",
+            Self::Sync => "sync",
+            Self::Plan => "plan",
+            Self::Mount => "mount",
+            Self::Complete => "complete",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn percent(self) -> u8 {
+        match self {
+            Self::Storage => 10,
+            Self::Scan => 25,
+            Self::Sync => 40,
+            Self::Plan => 60,
+            Self::Mount => 75,
+            Self::Complete | Self::Failed => 100,
+        }
+    }
+}
+
+// Created up front so a script can open it for reading before the first phase is reported.
+pub fn init() {
+    let fifo = Path::new(defs::PROGRESS_FIFO);
+    if fifo.metadata().is_ok_and(|m| m.file_type().is_fifo()) {
+        return;
+    }
+    let _ = std::fs::remove_file(fifo);
+    if let Err(e) = utils::ensure_dir_exists(defs::RUN_DIR)
+        .and_then(|_| mkfifo(fifo, Mode::from_bits_truncate(0o644)).map_err(Into::into))
+    {
+        log::debug!("Failed to create progress pipe: {:#}", e);
+    }
+}
+
+// Best effort on both channels: boot never waits on whoever is (or is not) listening.
+pub fn report(phase: Phase) {
+    if let Ok(mut started) = STARTED.lock() {
+        started.push((phase, Instant::now()));
+    }
+    trace::phase((!matches!(phase, Phase::Complete | Phase::Failed)).then(|| phase.as_str()));
+
+    let value = format!("{}:{}", phase.as_str(), phase.percent());
+
+    if let Err(e) = utils::set_property(PROGRESS_PROPERTY, &value) {
+        log::debug!("Failed to publish progress property: {:#}", e);
+    }
+
+    // Opening a FIFO for writing without blocking fails with ENXIO when nobody is reading.
+    if let Ok(mut pipe) = OpenOptions::new()
+        .write(true)
+        .custom_flags(OFlag::O_NONBLOCK.bits())
+        .open(defs::PROGRESS_FIFO)
+    {
+        let _ = writeln!(pipe, "{}", value);
+    }
+}
+
+// Each phase lasts until the next one is reported; the last one still running is timed up to now.
+pub fn timings() -> Vec<PhaseTiming> {
+    let Ok(started) = STARTED.lock() else {
+        return Vec::new();
+    };
+    let now = Instant::now();
+    started
+        .iter()
+        .enumerate()
+        .filter(|(_, (phase, _))| !matches!(phase, Phase::Complete | Phase::Failed))
+        .map(|(i, (phase, at))| {
+            let end = started.get(i + 1).map_or(now, |(_, next)| *next);
+            PhaseTiming {
+                phase: phase.as_str().to_string(),
+                millis: end.duration_since(*at).as_millis() as u64,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,358 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, path::Path, process::Command};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::Config,
+    core::{granary, notify, state::KvStore},
+    defs,
+};
+
+// Consecutive boots that never reached boot-completed before we roll back.
+const MAX_FAILED_BOOTS: u32 = 3;
+
+pub const COUNTER_KEY: &str = "failed_boots";
+pub const BOOT_SNAPSHOT_KEY: &str = "boot_snapshot";
+const BISECT_KEY: &str = "bisect";
+// Set once the last-good snapshot has been tried, so a bootloop it does not fix moves on to
+// bisection instead of restoring it again.
+const RESTORED_KEY: &str = "restored_last_good";
+
+// Recovery journal for narrowing a bootloop down to one module. Of the modules enabled when it
+// started, those outside `suspects` have booted fine; `testing` is the half of the suspects left
+// enabled for the current boot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bisection {
+    pub modules: Vec<String>,
+    pub suspects: Vec<String>,
+    pub testing: Vec<String>,
+}
+
+impl Bisection {
+    fn new(modules: Vec<String>) -> Self {
+        let mut bisection = Self {
+            suspects: modules.clone(),
+            modules,
+            testing: Vec::new(),
+        };
+        bisection.split();
+        bisection
+    }
+
+    fn split(&mut self) {
+        self.testing = self.suspects[..self.suspects.len().div_ceil(2)].to_vec();
+    }
+
+    // A failed boot puts the culprit among the modules tested; a completed one clears them.
+    fn narrow(&mut self, failed: bool) {
+        if failed {
+            self.suspects = std::mem::take(&mut self.testing);
+        } else {
+            self.suspects.retain(|m| !self.testing.contains(m));
+        }
+        self.split();
+    }
+
+    fn culprit(&self) -> Option<&str> {
+        match self.suspects.as_slice() {
+            [culprit] => Some(culprit),
+            _ => None,
+        }
+    }
+
+    fn is_disabled(&self, module: &str) -> bool {
+        match self.culprit() {
+            Some(culprit) => module == culprit,
+            None => {
+                self.suspects.iter().any(|m| m == module)
+                    && !self.testing.iter().any(|m| m == module)
+            }
+        }
+    }
+}
+
+fn read_counter() -> u32 {
+    KvStore::get(COUNTER_KEY).unwrap_or(0)
+}
+
+pub fn disable_all_modules(config: &Config) -> Result<usize> {
+    let mut disabled = 0;
+    for dir in &config.moduledir {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten().filter(|e| e.path().is_dir()) {
+            let marker = entry.path().join(defs::DISABLE_FILE_NAME);
+            if entry.file_name() == "meta-hybrid" || marker.exists() {
+                continue;
+            }
+            fs::write(&marker, "").with_context(|| {
+                format!("Failed to disable {}", entry.file_name().to_string_lossy())
+            })?;
+            disabled += 1;
+        }
+    }
+    Ok(disabled)
+}
+
+pub fn enabled_modules(config: &Config) -> Vec<String> {
+    let mut modules: Vec<String> = config
+        .moduledir
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter(|e| e.path().is_dir() && !e.path().join(defs::DISABLE_FILE_NAME).exists())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|id| id != "meta-hybrid")
+        .collect();
+    modules.sort();
+    modules.dedup();
+    modules
+}
+
+// Only the modules the bisection started with are touched; anything disabled before stays so.
+fn apply_bisection(config: &Config, bisection: &Bisection) {
+    for module in &bisection.modules {
+        let Some(dir) = config
+            .moduledir
+            .iter()
+            .map(|dir| dir.join(module))
+            .find(|path| path.is_dir())
+        else {
+            continue;
+        };
+        let marker = dir.join(defs::DISABLE_FILE_NAME);
+        let result = if bisection.is_disabled(module) {
+            fs::write(&marker, "")
+        } else if marker.exists() {
+            fs::remove_file(&marker)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            log::warn!("Recovery: failed to set state of {}: {}", module, e);
+        }
+    }
+}
+
+// Applies the bisection for the next boot and records it, or ends it once one module is left.
+fn advance(config: &Config, bisection: Bisection) -> Result<String> {
+    apply_bisection(config, &bisection);
+
+    if let Some(culprit) = bisection.culprit() {
+        KvStore::remove(BISECT_KEY)?;
+        let message = format!(
+            "bisection narrowed the bootloop down to {}, disabled it and re-enabled the other {} \
+             module(s)",
+            culprit,
+            bisection.modules.len() - 1
+        );
+        notify::queue(
+            &config.notify,
+            "bootloop_culprit",
+            &message,
+            serde_json::json!({ "module": culprit }),
+        );
+        return Ok(message);
+    }
+    KvStore::set(BISECT_KEY, &bisection)?;
+    Ok(format!(
+        "bisection: booting with {} of {} suspect module(s) enabled ({} cleared)",
+        bisection.testing.len(),
+        bisection.suspects.len(),
+        bisection.modules.len() - bisection.suspects.len()
+    ))
+}
+
+fn start_bisection(config: &Config) -> Result<String> {
+    let modules = enabled_modules(config);
+    if modules.is_empty() {
+        return Ok("no enabled modules to bisect".to_string());
+    }
+    advance(config, Bisection::new(modules))
+}
+
+pub fn bisection() -> Option<Bisection> {
+    KvStore::get(BISECT_KEY)
+}
+
+pub fn recover(config: &Config, config_path: &Path) -> Result<String> {
+    if let Some(mut bisection) = bisection() {
+        bisection.narrow(true);
+        return advance(config, bisection);
+    }
+
+    if let Some(snapshot) = granary::last_good()
+        && !KvStore::get::<bool>(RESTORED_KEY).unwrap_or(false)
+    {
+        granary::restore(&snapshot.id, config_path, false)?;
+        KvStore::set(RESTORED_KEY, &true)?;
+        return Ok(format!("restored last-good snapshot {}", snapshot.id));
+    }
+
+    start_bisection(config)
+}
+
+// Recovery images often leave /data unmounted; their fstab knows how to mount it.
+pub fn ensure_data_mounted() -> Result<Option<String>> {
+    if Path::new(defs::MODULES_DIR).is_dir() {
+        return Ok(None);
+    }
+
+    let status = Command::new("mount")
+        .arg("/data")
+        .status()
+        .context("Failed to run mount")?;
+    if !status.success() || !Path::new(defs::MODULES_DIR).is_dir() {
+        bail!(
+            "/data is not mounted and `mount /data` failed ({}); decrypt or mount it from the \
+             recovery menu first",
+            status
+        );
+    }
+    Ok(Some("mounted /data".to_string()))
+}
+
+fn clear_run_dir(steps: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(defs::RUN_DIR) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => steps.push(format!("removed {}", path.display())),
+            Err(e) => steps.push(format!("could not remove {}: {}", path.display(), e)),
+        }
+    }
+}
+
+// Half-written config files left by an interrupted atomic write.
+fn clear_pending_configs(config_path: &Path, steps: &mut Vec<String>) {
+    let Some(dir) = config_path.parent() else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') && name.ends_with(".tmp") && fs::remove_file(entry.path()).is_ok()
+        {
+            steps.push(format!("removed pending {}", entry.path().display()));
+        }
+    }
+}
+
+// Everything `meta-hybrid rescue` does, one line per action, in order.
+pub fn rescue(config: &Config, config_path: &Path, disable_all: bool) -> Result<Vec<String>> {
+    let mut steps = Vec::new();
+
+    if disable_all {
+        KvStore::remove(BISECT_KEY)?;
+        let disabled = disable_all_modules(config)?;
+        steps.push(format!("disabled {} module(s)", disabled));
+    } else {
+        steps.push(recover(config, config_path)?);
+    }
+
+    clear_run_dir(&mut steps);
+    clear_pending_configs(config_path, &mut steps);
+
+    Ok(steps)
+}
+
+// Returns true when the boot counter tripped and the config on disk was rolled back. While a
+// bisection runs, a single boot that did not complete counts as failed.
+pub fn guard_boot(config: &Config, config_path: &Path) -> bool {
+    let count = read_counter() + 1;
+    let limit = if bisection().is_some() {
+        2
+    } else {
+        MAX_FAILED_BOOTS
+    };
+
+    if count < limit {
+        if let Err(e) = KvStore::set(COUNTER_KEY, &count) {
+            log::warn!("Recovery: failed to update boot counter: {:#}", e);
+        }
+        return false;
+    }
+
+    log::error!(
+        "!! {} boots in a row did not complete, starting recovery",
+        count
+    );
+    let _ = KvStore::remove(COUNTER_KEY);
+
+    let recovered = recover(config, config_path);
+    // The boot that tests a bisection step has to be counted.
+    if bisection().is_some()
+        && let Err(e) = KvStore::set(COUNTER_KEY, &1)
+    {
+        log::warn!("Recovery: failed to update boot counter: {:#}", e);
+    }
+    let (message, recovered) = match recovered {
+        Ok(action) => {
+            log::warn!("Recovery: {}", action);
+            (action, true)
+        }
+        Err(e) => {
+            log::error!("Recovery failed: {:#}", e);
+            (format!("recovery failed: {:#}", e), false)
+        }
+    };
+    notify::queue(
+        &config.notify,
+        "bootloop_recovery",
+        &message,
+        serde_json::json!({ "failed_boots": count, "recovered": recovered }),
+    );
+    recovered
+}
+
+pub fn set_boot_snapshot(id: &str) {
+    if let Err(e) = KvStore::set(BOOT_SNAPSHOT_KEY, &id) {
+        log::warn!("Recovery: failed to record boot snapshot: {:#}", e);
+    }
+}
+
+// A completed boot clears the modules it tested and sets up the next step.
+pub fn bisect_completed(config: &Config) -> Result<Option<String>> {
+    let Some(mut bisection) = bisection() else {
+        return Ok(None);
+    };
+    bisection.narrow(false);
+    let message = advance(config, bisection)?;
+    log::info!("Recovery: {}", message);
+    Ok(Some(message))
+}
+
+pub fn boot_completed() -> Result<Option<String>> {
+    let _ = KvStore::remove(COUNTER_KEY);
+    let _ = KvStore::remove(RESTORED_KEY);
+
+    let Some(id) = KvStore::get::<String>(BOOT_SNAPSHOT_KEY) else {
+        return Ok(None);
+    };
+    // The snapshot may have been pruned or deleted since; the rest of boot-completed still runs.
+    let promoted = granary::mark_last_good(&id);
+    KvStore::remove(BOOT_SNAPSHOT_KEY)?;
+    if let Err(e) = promoted {
+        log::warn!(
+            "Recovery: failed to mark snapshot {} last-good: {:#}",
+            id,
+            e
+        );
+        return Ok(None);
+    }
+    Ok(Some(id))
+}
@@ -0,0 +1,161 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use rustix::fs::statvfs;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::{Config, MountStage},
+    core::{
+        ops::{
+            planner::MountPlan,
+            winnow::{self, RuleReview},
+        },
+        progress::{self, PhaseTiming},
+        state::RuntimeState,
+    },
+    defs, utils,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Overlay,
+    Magic,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModuleOutcome {
+    pub id: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub mode: String,
+    pub mount_point: String,
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StageReport {
+    pub stage: String,
+    pub phases: Vec<PhaseTiming>,
+    pub modules: Vec<ModuleOutcome>,
+    pub conflicts_resolved: Vec<RuleReview>,
+    pub storage: StorageStats,
+    pub mount_failures: usize,
+    pub unverified_mounts: usize,
+    pub script_failures: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BootReport {
+    pub timestamp: u64,
+    pub stages: Vec<StageReport>,
+}
+
+fn stage_name(stage: Option<MountStage>) -> &'static str {
+    match stage {
+        None => "all",
+        Some(MountStage::PostFsData) => "post-fs-data",
+        Some(MountStage::Service) => "service",
+    }
+}
+
+fn storage_stats(state: &RuntimeState) -> StorageStats {
+    let (total_bytes, free_bytes) = statvfs(&state.mount_point)
+        .map(|s| (s.f_blocks * s.f_frsize, s.f_bavail * s.f_frsize))
+        .unwrap_or_default();
+    StorageStats {
+        mode: state.storage_mode.clone(),
+        mount_point: state.mount_point.to_string_lossy().to_string(),
+        total_bytes,
+        free_bytes,
+        image_bytes: fs::metadata(defs::MODULES_IMG_FILE).ok().map(|m| m.len()),
+    }
+}
+
+// A module the plan placed that the executor reported under neither backend did not mount.
+fn outcomes(plan: &MountPlan, state: &RuntimeState) -> Vec<ModuleOutcome> {
+    let mut ids: Vec<&String> = plan
+        .overlay_module_ids
+        .iter()
+        .chain(&plan.magic_module_ids)
+        .chain(&state.overlay_modules)
+        .chain(&state.magic_modules)
+        .collect();
+    ids.sort();
+    ids.dedup();
+
+    ids.into_iter()
+        .map(|id| {
+            let outcome = if state.overlay_modules.contains(id) {
+                Outcome::Overlay
+            } else if state.magic_modules.contains(id) {
+                Outcome::Magic
+            } else {
+                Outcome::Failed
+            };
+            ModuleOutcome {
+                id: id.clone(),
+                outcome,
+            }
+        })
+        .collect()
+}
+
+pub fn stage_report(
+    config: &Config,
+    stage: Option<MountStage>,
+    plan: &MountPlan,
+    state: &RuntimeState,
+) -> StageReport {
+    let mut conflicts_resolved = winnow::review(config, plan, &state.mount_point);
+    conflicts_resolved.retain(|r| r.stale.is_none());
+
+    StageReport {
+        stage: stage_name(stage).to_string(),
+        phases: progress::timings(),
+        modules: outcomes(plan, state),
+        conflicts_resolved,
+        storage: storage_stats(state),
+        mount_failures: state.mount_failures.len(),
+        unverified_mounts: state.unverified_mounts.len(),
+        script_failures: state.script_failures.len(),
+    }
+}
+
+// The service stage adds its report to the one post-fs-data left; any other stage starts over.
+pub fn save(report: StageReport) -> Result<()> {
+    let path = Path::new(defs::BOOT_REPORT_FILE);
+    let mut boot = if report.stage == stage_name(Some(MountStage::Service)) {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<BootReport>(&content).ok())
+            .unwrap_or_default()
+    } else {
+        BootReport::default()
+    };
+
+    boot.stages.retain(|s| s.stage != report.stage);
+    boot.stages.push(report);
+    boot.timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    utils::atomic_write(path, serde_json::to_string_pretty(&boot)?)?;
+    Ok(())
+}
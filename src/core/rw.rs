@@ -0,0 +1,189 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::{conf::config::Config, defs, utils};
+
+const UPPER_DIR: &str = "upperdir";
+const WORK_DIR: &str = "workdir";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Changed,
+    Deleted,
+    Opaque,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Changed => "changed",
+            Self::Deleted => "deleted",
+            Self::Opaque => "opaque",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+fn partition_dir(config: &Config, partition: &str) -> Result<PathBuf> {
+    if partition.contains('/') || !config.effective_partitions().iter().any(|p| p == partition) {
+        bail!(
+            "{} is not a managed top-level partition ({})",
+            partition,
+            config.effective_partitions().join(", ")
+        );
+    }
+    Ok(Path::new(defs::SYSTEM_RW_DIR).join(partition))
+}
+
+// The upper tree mirrors the partition, so a split partition gives each of its overlays the
+// matching subdirectory instead of one shared upperdir.
+pub fn layer_dirs(partition: &str, target: &Path) -> Option<(PathBuf, PathBuf)> {
+    let dir = Path::new(defs::SYSTEM_RW_DIR).join(partition);
+    let (upper, work) = (dir.join(UPPER_DIR), dir.join(WORK_DIR));
+    if !upper.is_dir() || !work.is_dir() {
+        return None;
+    }
+
+    let rel = target.strip_prefix(Path::new("/").join(partition)).ok()?;
+    let (upper, work) = (upper.join(rel), work.join(rel));
+    let created = utils::ensure_dir_exists(&upper).and_then(|_| utils::ensure_dir_exists(&work));
+    if let Err(e) = created {
+        log::warn!("RW: {} stays read-only: {:#}", target.display(), e);
+        return None;
+    }
+    Some((upper, work))
+}
+
+// Partitions that mount read-write this boot.
+pub fn enabled(config: &Config) -> Vec<String> {
+    config
+        .effective_partitions()
+        .into_iter()
+        .filter(|p| !p.contains('/'))
+        .filter(|p| {
+            let dir = Path::new(defs::SYSTEM_RW_DIR).join(p);
+            dir.join(UPPER_DIR).is_dir() && dir.join(WORK_DIR).is_dir()
+        })
+        .collect()
+}
+
+// Partitions holding read-write changes, including ones disabled with their changes kept.
+pub fn upper_dirs(config: &Config) -> Vec<(String, PathBuf)> {
+    config
+        .effective_partitions()
+        .into_iter()
+        .filter(|p| !p.contains('/'))
+        .map(|p| {
+            let upper = Path::new(defs::SYSTEM_RW_DIR).join(&p).join(UPPER_DIR);
+            (p, upper)
+        })
+        .filter(|(_, upper)| upper.is_dir())
+        .collect()
+}
+
+pub fn enable(config: &Config, partition: &str) -> Result<PathBuf> {
+    let dir = partition_dir(config, partition)?;
+    for sub in [UPPER_DIR, WORK_DIR] {
+        utils::ensure_dir_exists(dir.join(sub))
+            .with_context(|| format!("Failed to create {}", dir.join(sub).display()))?;
+    }
+    Ok(dir.join(UPPER_DIR))
+}
+
+// Without a workdir the next boot mounts the partition read-only again; the upperdir is kept so
+// enabling again picks the changes back up.
+pub fn disable(config: &Config, partition: &str, purge: bool) -> Result<bool> {
+    let dir = partition_dir(config, partition)?;
+    if !dir.exists() {
+        return Ok(false);
+    }
+
+    let victim = if purge { dir } else { dir.join(WORK_DIR) };
+    if victim.exists() {
+        fs::remove_dir_all(&victim)
+            .with_context(|| format!("Failed to remove {}", victim.display()))?;
+    }
+    Ok(true)
+}
+
+// Copy-up leaves the parents of every touched file behind as plain directories, so only opaque
+// and empty directories count as changes of their own.
+pub fn diff(config: &Config, partition: &str) -> Result<Vec<Change>> {
+    let upper = partition_dir(config, partition)?.join(UPPER_DIR);
+    if !upper.is_dir() {
+        bail!(
+            "{} is not read-write; run `meta-hybrid rw enable {}`",
+            partition,
+            partition
+        );
+    }
+
+    let mut changes = Vec::new();
+    for entry in WalkDir::new(&upper).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        let kind = if utils::is_whiteout(path) || utils::is_xattr_whiteout(path) {
+            ChangeKind::Deleted
+        } else if entry.file_type().is_dir() {
+            if utils::is_overlay_opaque(path) {
+                ChangeKind::Opaque
+            } else if fs::read_dir(path).is_ok_and(|mut d| d.next().is_none()) {
+                ChangeKind::Changed
+            } else {
+                continue;
+            }
+        } else {
+            ChangeKind::Changed
+        };
+
+        let rel = path.strip_prefix(&upper)?;
+        changes.push(Change {
+            path: Path::new("/")
+                .join(partition)
+                .join(rel)
+                .to_string_lossy()
+                .to_string(),
+            kind,
+        });
+    }
+    Ok(changes)
+}
+
+pub fn export(config: &Config, partition: &str, module_id: &str) -> Result<PathBuf> {
+    utils::validate_module_id(module_id)?;
+    let upper = partition_dir(config, partition)?.join(UPPER_DIR);
+    if !upper.is_dir() {
+        bail!("{} has no read-write changes to export", partition);
+    }
+
+    let module = Path::new(defs::MANAGED_MODULES_DIR).join(module_id);
+    if module.exists() {
+        bail!("{} already exists", module.display());
+    }
+
+    utils::sync_dir(&upper, &module.join(partition), false)?;
+    utils::atomic_write(
+        module.join("module.prop"),
+        format!(
+            "id={}\nname={}\nversion=1\nversionCode=1\nauthor=meta-hybrid\n\
+             description=Changes made to /{} while it was read-write\n",
+            module_id, module_id, partition
+        ),
+    )?;
+    Ok(module)
+}
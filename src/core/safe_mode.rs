@@ -0,0 +1,125 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs::{self, OpenOptions},
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::Config,
+    core::{inventory::model as modules, notify, state::RuntimeState},
+    defs,
+};
+
+const INPUT_DIR: &str = "/dev/input";
+const KEY_VOLUMEDOWN: usize = 114;
+// (KEY_MAX + 1) / 8 bytes of key state bits.
+const KEY_BITS_LEN: usize = 96;
+
+// _IOC(_IOC_READ, 'E', 0x18, len) from linux/input.h.
+const fn eviocgkey(len: usize) -> u32 {
+    (2 << 30) | ((len as u32) << 16) | ((b'E' as u32) << 8) | 0x18
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    Marker,
+    VolumeKey,
+}
+
+impl Trigger {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Marker => "marker",
+            Self::VolumeKey => "volume_key",
+        }
+    }
+}
+
+// Asks every input device for its current key state rather than waiting for events, so only a key
+// held at this moment counts.
+fn volume_down_held() -> bool {
+    let Ok(entries) = fs::read_dir(INPUT_DIR) else {
+        return false;
+    };
+    let devices: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with("event"))
+        })
+        .collect();
+
+    devices.iter().any(|device| {
+        let Ok(file) = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(device)
+        else {
+            return false;
+        };
+        let mut bits = [0u8; KEY_BITS_LEN];
+        let read = unsafe {
+            libc::ioctl(
+                file.as_raw_fd(),
+                eviocgkey(KEY_BITS_LEN) as _,
+                bits.as_mut_ptr(),
+            )
+        };
+        read > 0 && bits[KEY_VOLUMEDOWN / 8] & (1 << (KEY_VOLUMEDOWN % 8)) != 0
+    })
+}
+
+pub fn detect() -> Option<Trigger> {
+    if Path::new(defs::SAFE_MODE_FILE).exists() {
+        return Some(Trigger::Marker);
+    }
+    volume_down_held().then_some(Trigger::VolumeKey)
+}
+
+// Set by this boot's post-fs-data stage, so the service stage stays out too once the key is let go.
+pub fn active() -> bool {
+    RuntimeState::load().is_ok_and(|state| state.safe_mode.is_some())
+}
+
+// Nothing is mounted; the state is reset so nothing refers to the mounts of an earlier boot.
+pub fn enter(config: &Config, trigger: Trigger) -> Result<()> {
+    log::warn!(
+        "!! Safe mode ({}): skipping all mounts for this boot",
+        trigger.as_str()
+    );
+    if trigger == Trigger::Marker {
+        log::warn!("!! Remove {} to leave safe mode", defs::SAFE_MODE_FILE);
+    }
+
+    let mut state = RuntimeState::new(
+        String::new(),
+        PathBuf::new(),
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+    );
+    state.safe_mode = Some(trigger);
+    state.save()?;
+
+    modules::set_description(&format!(
+        "🛟 安全模式 / Safe mode ({}): no modules mounted",
+        trigger.as_str()
+    ));
+    notify::queue(
+        &config.notify,
+        "safe_mode",
+        "Safe mode: no modules were mounted this boot",
+        serde_json::json!({ "trigger": trigger }),
+    );
+    Ok(())
+}
@@ -0,0 +1,188 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conf::config::{Config, MountStage},
+    core::inventory::Module,
+    defs, utils,
+};
+
+const BUSYBOX_CANDIDATES: &[&str] = &[
+    "/data/adb/ksu/bin/busybox",
+    "/data/adb/ap/bin/busybox",
+    "/data/adb/magisk/busybox",
+];
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone)]
+pub struct ModuleScript {
+    pub module_id: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptFailure {
+    pub module: String,
+    pub script: String,
+    pub reason: String,
+}
+
+pub fn script_name(stage: Option<MountStage>) -> &'static str {
+    match stage {
+        Some(MountStage::Service) => "service.sh",
+        _ => "post-fs-data.sh",
+    }
+}
+
+// Highest priority first, then moved behind whatever `scripts.after` says must run earlier.
+pub fn discover(
+    config: &Config,
+    modules: &[Module],
+    stage: Option<MountStage>,
+) -> Vec<ModuleScript> {
+    let name = script_name(stage);
+    let mut pending: Vec<ModuleScript> = modules
+        .iter()
+        .map(|m| ModuleScript {
+            module_id: m.id.clone(),
+            path: m.source_path.join(name),
+        })
+        .filter(|s| s.path.is_file())
+        .collect();
+    pending.sort_by(|a, b| {
+        config
+            .module_priority(&b.module_id)
+            .cmp(&config.module_priority(&a.module_id))
+            .then_with(|| a.module_id.cmp(&b.module_id))
+    });
+
+    let present: HashSet<String> = pending.iter().map(|s| s.module_id.clone()).collect();
+    let mut done: HashSet<String> = HashSet::new();
+    let mut ordered = Vec::with_capacity(pending.len());
+
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|s| {
+            config.scripts.after.get(&s.module_id).is_none_or(|deps| {
+                deps.iter()
+                    .all(|dep| done.contains(dep) || !present.contains(dep))
+            })
+        });
+        let index = ready.unwrap_or_else(|| {
+            log::warn!(
+                "Script ordering cycle involving {}, running it anyway",
+                pending[0].module_id
+            );
+            0
+        });
+        let script = pending.remove(index);
+        done.insert(script.module_id.clone());
+        ordered.push(script);
+    }
+
+    ordered
+}
+
+fn shell() -> Command {
+    match BUSYBOX_CANDIDATES
+        .iter()
+        .map(Path::new)
+        .find(|p| p.exists())
+    {
+        Some(busybox) => {
+            let mut command = Command::new(busybox);
+            command.arg("sh").env("ASH_STANDALONE", "1");
+            command
+        }
+        None => Command::new("sh"),
+    }
+}
+
+// Output goes to a file rather than a pipe: scripts often leave background jobs behind, and those
+// must not die of SIGPIPE once we exit.
+fn run_one(script: &ModuleScript, timeout: Duration) -> Result<Option<String>> {
+    let log_dir = Path::new(defs::SCRIPT_LOG_DIR);
+    utils::ensure_dir_exists(log_dir)?;
+    let name = script
+        .path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let log_path = log_dir.join(format!("{}.{}.log", script.module_id, name));
+    let log_file = File::create(&log_path)?;
+
+    let mut child = shell()
+        .arg(&script.path)
+        .current_dir(script.path.parent().unwrap_or(Path::new("/")))
+        .stdin(Stdio::null())
+        .stdout(log_file.try_clone()?)
+        .stderr(log_file)
+        .spawn()
+        .with_context(|| format!("Failed to start {}", script.path.display()))?;
+
+    let start = Instant::now();
+    let failure = loop {
+        if let Some(status) = child.try_wait()? {
+            break (!status.success()).then(|| format!("exited with {}", status));
+        }
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            break Some(format!("timed out after {}s", timeout.as_secs()));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    if let Ok(output) = fs::read_to_string(&log_path) {
+        for line in output.lines().filter(|l| !l.trim().is_empty()) {
+            log::info!("[{}/{}] {}", script.module_id, name, line);
+        }
+    }
+
+    Ok(failure)
+}
+
+pub fn run(config: &Config, scripts: &[ModuleScript]) -> Vec<ScriptFailure> {
+    let mut failures = Vec::new();
+
+    for script in scripts {
+        let seconds = config
+            .scripts
+            .timeouts
+            .get(&script.module_id)
+            .copied()
+            .unwrap_or(config.scripts.timeout);
+        let name = script
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        log::info!("Running {} of {}", name, script.module_id);
+        let reason = match run_one(script, Duration::from_secs(seconds)) {
+            Ok(None) => continue,
+            Ok(Some(reason)) => reason,
+            Err(e) => format!("{:#}", e),
+        };
+
+        log::warn!("{} of {} {}", name, script.module_id, reason);
+        failures.push(ScriptFailure {
+            module: script.module_id.clone(),
+            script: name,
+            reason,
+        });
+    }
+
+    failures
+}
@@ -0,0 +1,117 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    conf::config::{Config, SepolicyConfig},
+    core::inventory::Module,
+    defs, utils,
+};
+
+const TOOL_CANDIDATES: &[&str] = &[
+    defs::KSUD_PATH,
+    "/data/adb/ap/bin/magiskpolicy",
+    "/data/adb/magisk/magiskpolicy",
+];
+
+// Every module's sepolicy.rule, one commented section per module, blank lines and comments dropped.
+pub fn combined(modules: &[Module]) -> String {
+    let mut policy = String::new();
+
+    for module in modules {
+        let Some(rule_file) = &module.sepolicy_rule else {
+            continue;
+        };
+        let content = match fs::read_to_string(rule_file) {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("Failed to read sepolicy.rule of {}: {}", module.id, e);
+                continue;
+            }
+        };
+
+        let rules: Vec<&str> = content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect();
+        if rules.is_empty() {
+            continue;
+        }
+
+        let _ = writeln!(policy, "# {}", module.id);
+        for rule in rules {
+            let _ = writeln!(policy, "{}", rule);
+        }
+    }
+
+    policy
+}
+
+fn resolve_tool(config: &SepolicyConfig) -> Option<PathBuf> {
+    if let Some(tool) = &config.tool {
+        return Some(tool.clone());
+    }
+    TOOL_CANDIDATES
+        .iter()
+        .map(PathBuf::from)
+        .find(|p| p.exists())
+}
+
+fn apply_file(tool: &Path, rule_file: &Path) -> Result<()> {
+    let is_magiskpolicy = tool
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().contains("magiskpolicy"));
+
+    let mut command = Command::new(tool);
+    if is_magiskpolicy {
+        command.arg("--live").arg("--apply").arg(rule_file);
+    } else {
+        command.args(["sepolicy", "apply"]).arg(rule_file);
+    }
+
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {}", tool.display()))?;
+    if !output.status.success() {
+        bail!(
+            "{} exited with {}: {}",
+            tool.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+// Returns how many modules contributed rules.
+pub fn apply(config: &Config, modules: &[Module]) -> Result<usize> {
+    let policy = combined(modules);
+    let count = policy.lines().filter(|l| l.starts_with('#')).count();
+    if count == 0 {
+        return Ok(0);
+    }
+
+    let Some(tool) = resolve_tool(&config.sepolicy) else {
+        bail!("No sepolicy tool found (ksud or magiskpolicy)");
+    };
+
+    let rule_file = Path::new(defs::SEPOLICY_RULE_FILE);
+    utils::atomic_write(rule_file, &policy)?;
+    apply_file(&tool, rule_file)?;
+
+    log::info!(
+        "Applied sepolicy rules from {} module(s) via {}",
+        count,
+        tool.display()
+    );
+    Ok(count)
+}
@@ -2,15 +2,68 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
-    fs,
-    path::PathBuf,
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf},
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use rustix::fs::{FlockOperation, flock};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
 
-use crate::{defs, utils::fs::xattr};
+use crate::{
+    conf::config,
+    core::{
+        capture,
+        companion::{self, CompanionFile},
+        defer::{self, DeferredModule},
+        integrity::{self, Verdict},
+        inventory::lifecycle::PendingChange,
+        known_good::{self, ModuleVersion},
+        maintenance::{self, MaintenanceSummary},
+        metrics::Metrics,
+        ops::verify::{Fingerprint, UnverifiedMount},
+        profile, recovery,
+        safe_mode::Trigger,
+        scripts::ScriptFailure,
+    },
+    defs,
+    mount::explain::MountFailure,
+    utils::{self, fs::xattr},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountKind {
+    Overlay,
+    Rootdir,
+    Magic,
+}
+
+impl MountKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Overlay => "overlay",
+            Self::Rootdir => "rootdir",
+            Self::Magic => "magic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MountRecord {
+    pub kind: MountKind,
+    pub target: String,
+    pub modules: Vec<String>,
+    // What mountinfo should show on top of the target; empty in states written before they were
+    // recorded.
+    #[serde(default)]
+    pub fs_type: String,
+    #[serde(default)]
+    pub source: String,
+}
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct RuntimeState {
@@ -26,6 +79,34 @@ pub struct RuntimeState {
     pub zygisksu_enforce: bool,
     #[serde(default)]
     pub tmpfs_xattr_supported: bool,
+    #[serde(default)]
+    pub mount_failures: Vec<MountFailure>,
+    #[serde(default)]
+    pub mounts: Vec<MountRecord>,
+    #[serde(default)]
+    pub script_failures: Vec<ScriptFailure>,
+    #[serde(default)]
+    pub unverified_mounts: Vec<UnverifiedMount>,
+    #[serde(default)]
+    pub fingerprints: Vec<Fingerprint>,
+    #[serde(default)]
+    pub metrics: Metrics,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<MaintenanceSummary>,
+    #[serde(default)]
+    pub companions: Vec<CompanionFile>,
+    #[serde(default)]
+    pub pending_changes: Vec<PendingChange>,
+    #[serde(default)]
+    pub deferred_modules: Vec<DeferredModule>,
+    // The enabled modules and their versions on the last boot that completed; None until one has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub known_good_modules: Option<Vec<ModuleVersion>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safe_mode: Option<Trigger>,
+    // Modules whose APEX overrides the post-fs-data pass left to the service stage.
+    #[serde(default)]
+    pub deferred_apex: Vec<String>,
 }
 
 impl RuntimeState {
@@ -36,6 +117,8 @@ impl RuntimeState {
         overlay_modules: Vec<String>,
         magic_modules: Vec<String>,
         active_mounts: Vec<String>,
+        mount_failures: Vec<MountFailure>,
+        mounts: Vec<MountRecord>,
     ) -> Self {
         let start = SystemTime::now();
 
@@ -59,19 +142,86 @@ impl RuntimeState {
             active_mounts,
             zygisksu_enforce,
             tmpfs_xattr_supported,
+            mount_failures,
+            mounts,
+            script_failures: Vec::new(),
+            unverified_mounts: Vec::new(),
+            fingerprints: Vec::new(),
+            metrics: Metrics::default(),
+            maintenance: maintenance::last(),
+            companions: companion::tracked(),
+            pending_changes: Vec::new(),
+            deferred_modules: defer::last(),
+            known_good_modules: known_good::last(),
+            safe_mode: None,
+            deferred_apex: Vec::new(),
         }
     }
 
+    // Folds in the state an earlier boot stage left behind.
+    pub fn merge(&mut self, earlier: Self) {
+        let extend = |current: &mut Vec<String>, earlier: Vec<String>| {
+            for item in earlier {
+                if !current.contains(&item) {
+                    current.push(item);
+                }
+            }
+            current.sort();
+        };
+        extend(&mut self.overlay_modules, earlier.overlay_modules);
+        extend(&mut self.magic_modules, earlier.magic_modules);
+        extend(&mut self.active_mounts, earlier.active_mounts);
+
+        let mut mounts = earlier.mounts;
+        mounts.retain(|m| !self.mounts.iter().any(|r| r.target == m.target));
+        mounts.append(&mut self.mounts);
+        self.mounts = mounts;
+
+        let mut failures = earlier.mount_failures;
+        failures.append(&mut self.mount_failures);
+        self.mount_failures = failures;
+
+        let mut script_failures = earlier.script_failures;
+        script_failures.append(&mut self.script_failures);
+        self.script_failures = script_failures;
+
+        let mut unverified = earlier.unverified_mounts;
+        unverified.retain(|u| !self.mounts.iter().any(|m| m.target == u.target));
+        unverified.append(&mut self.unverified_mounts);
+        self.unverified_mounts = unverified;
+
+        let mut fingerprints = earlier.fingerprints;
+        fingerprints.retain(|f| !self.fingerprints.iter().any(|g| g.target == f.target));
+        fingerprints.append(&mut self.fingerprints);
+        self.fingerprints = fingerprints;
+
+        self.metrics.merge(earlier.metrics);
+        self.pending_changes = earlier.pending_changes;
+    }
+
     pub fn save(&self) -> Result<()> {
         let json = serde_json::to_string_pretty(self)?;
 
         fs::write(defs::STATE_FILE, json)?;
+        config::harden_file(Path::new(defs::STATE_FILE));
+        integrity::sign(Path::new(defs::STATE_FILE))?;
 
         Ok(())
     }
 
+    // The state file survives reboots; only one written since the last boot describes live mounts.
+    pub fn is_this_boot(&self) -> bool {
+        procfs::boot_time_secs()
+            .map(|boot| self.timestamp >= boot)
+            .unwrap_or(false)
+    }
+
     pub fn load() -> Result<Self> {
-        if !std::path::Path::new(defs::STATE_FILE).exists() {
+        if !Path::new(defs::STATE_FILE).exists() {
+            return Ok(Self::default());
+        }
+        if integrity::verify(Path::new(defs::STATE_FILE)) == Verdict::Tampered {
+            log::warn!("Runtime state failed its integrity check, ignoring it");
             return Ok(Self::default());
         }
 
@@ -82,3 +232,135 @@ impl RuntimeState {
         Ok(state)
     }
 }
+
+// Small values that outlive a boot (counters, pending actions, lists), kept in one file instead of a
+// marker file each. Markers a user creates by hand, like `.safe_mode`, stay files.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KvStore {
+    entries: BTreeMap<String, Value>,
+}
+
+// How a file the store replaced held its value.
+#[derive(Clone, Copy)]
+enum Legacy {
+    Count,
+    Text,
+    Marker,
+    Json,
+}
+
+impl Legacy {
+    fn parse(self, content: &str) -> Option<Value> {
+        let content = content.trim();
+        match self {
+            Self::Count => content.parse::<u32>().ok().map(Value::from),
+            Self::Text => (!content.is_empty()).then(|| Value::from(content)),
+            Self::Marker => Some(Value::Bool(true)),
+            Self::Json => serde_json::from_str(content).ok(),
+        }
+    }
+}
+
+const LEGACY_FILES: &[(&str, &str, Legacy)] = &[
+    (
+        "/data/adb/meta-hybrid/run/boot_counter",
+        recovery::COUNTER_KEY,
+        Legacy::Count,
+    ),
+    (
+        "/data/adb/meta-hybrid/run/boot_snapshot",
+        recovery::BOOT_SNAPSHOT_KEY,
+        Legacy::Text,
+    ),
+    (
+        "/data/adb/meta-hybrid/run/capture_next_boot",
+        capture::ARMED_KEY,
+        Legacy::Marker,
+    ),
+    (
+        "/data/adb/meta-hybrid/profiles/active",
+        profile::ACTIVE_KEY,
+        Legacy::Text,
+    ),
+    (
+        "/data/adb/meta-hybrid/run/deferred.json",
+        defer::DEFERRED_KEY,
+        Legacy::Json,
+    ),
+];
+
+impl KvStore {
+    // Held for the whole read-modify-write, so the boot stages, the daemon and the CLI cannot drop
+    // each other's keys. The store file itself is replaced on every save and cannot carry the lock.
+    fn load() -> Result<(File, Self)> {
+        utils::ensure_dir_exists(defs::BASE_DIR)?;
+        let lock = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(defs::KV_LOCK_FILE)?;
+        flock(&lock, FlockOperation::LockExclusive)?;
+
+        let mut store: Self = fs::read_to_string(defs::KV_STORE_FILE)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        let moved = store.migrate();
+        if !moved.is_empty() {
+            store.save()?;
+            for path in moved {
+                let _ = fs::remove_file(path);
+            }
+        }
+        Ok((lock, store))
+    }
+
+    // A legacy file only fills a key the store has no value for yet. Returns the files read, which
+    // go once the store holding their values is saved.
+    fn migrate(&mut self) -> Vec<&'static str> {
+        let mut moved = Vec::new();
+        for &(path, key, legacy) in LEGACY_FILES {
+            let Ok(content) = fs::read_to_string(path) else {
+                continue;
+            };
+            if !self.entries.contains_key(key)
+                && let Some(value) = legacy.parse(&content)
+            {
+                self.entries.insert(key.to_string(), value);
+            }
+            moved.push(path);
+        }
+        moved
+    }
+
+    fn save(&self) -> Result<()> {
+        utils::atomic_write(defs::KV_STORE_FILE, serde_json::to_string_pretty(self)?)?;
+        config::harden_file(Path::new(defs::KV_STORE_FILE));
+        Ok(())
+    }
+
+    // A value of another shape than `T` reads as missing.
+    pub fn get<T: DeserializeOwned>(key: &str) -> Option<T> {
+        let (_lock, mut store) = Self::load().ok()?;
+        let value = store.entries.remove(key)?;
+        serde_json::from_value(value).ok()
+    }
+
+    pub fn set<T: Serialize>(key: &str, value: &T) -> Result<()> {
+        let (_lock, mut store) = Self::load()?;
+        store
+            .entries
+            .insert(key.to_string(), serde_json::to_value(value)?);
+        store.save()
+    }
+
+    // Returns the value that was there, if any; a missing key writes nothing.
+    pub fn remove(key: &str) -> Result<Option<Value>> {
+        let (_lock, mut store) = Self::load()?;
+        let previous = store.entries.remove(key);
+        if previous.is_some() {
+            store.save()?;
+        }
+        Ok(previous)
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    process::{Command, Stdio},
+};
+
+use procfs::process::{Process, all_processes};
+use serde::Serialize;
+
+use crate::{
+    conf::config::Config,
+    core::{denylist, state::RuntimeState},
+};
+
+const PER_USER_RANGE: u32 = 100_000;
+const FIRST_APP_UID: u32 = 10_000;
+
+struct AppProcess {
+    pid: i32,
+    user: u32,
+    namespace: String,
+}
+
+// How well our mounts are hidden from one Android user's (or work profile's) denylisted apps.
+#[derive(Debug, Serialize)]
+pub struct UserCoverage {
+    pub user: u32,
+    pub processes: usize,
+    pub hidden: usize,
+    pub leaking: Vec<i32>,
+}
+
+// try_umount only acts on processes zygote forks for the user it knows about, so every user's
+// app processes are walked here, not just user 0's. Only apps on the denylist are meant not to see
+// the modules; the rest legitimately keep them.
+fn app_processes(packages: &HashSet<String>) -> Vec<AppProcess> {
+    let Ok(processes) = all_processes() else {
+        return Vec::new();
+    };
+
+    processes
+        .flatten()
+        .filter_map(|p| {
+            let uid = p.uid().ok()?;
+            if uid % PER_USER_RANGE < FIRST_APP_UID
+                || !denylist::package_of(&p).is_some_and(|pkg| packages.contains(&pkg))
+            {
+                return None;
+            }
+            let namespace = fs::read_link(format!("/proc/{}/ns/mnt", p.pid))
+                .ok()?
+                .to_string_lossy()
+                .to_string();
+            Some(AppProcess {
+                pid: p.pid,
+                user: uid / PER_USER_RANGE,
+                namespace,
+            })
+        })
+        .collect()
+}
+
+fn our_targets(config: &Config, state: &RuntimeState) -> HashSet<String> {
+    let partitions = config.effective_partitions();
+    let mut targets: HashSet<String> = state.mounts.iter().map(|m| m.target.clone()).collect();
+    targets.extend(
+        state
+            .active_mounts
+            .iter()
+            .filter(|p| partitions.contains(p))
+            .map(|p| format!("/{}", p)),
+    );
+    targets
+}
+
+pub fn visible_targets(pid: i32, config: &Config, targets: &HashSet<String>) -> Vec<String> {
+    let Ok(mountinfo) = Process::new(pid).and_then(|p| p.mountinfo()) else {
+        return Vec::new();
+    };
+
+    mountinfo
+        .into_iter()
+        .filter(|m| m.mount_source.as_deref() == Some(config.mountsource.as_str()))
+        .map(|m| m.mount_point.to_string_lossy().to_string())
+        .filter(|p| targets.contains(p))
+        .collect()
+}
+
+// Lazily detaches our mounts inside one namespace; a child process does the setns so this
+// process never leaves its own namespace.
+pub fn sweep_namespace(pid: i32, mounts: &[String]) -> bool {
+    let mut mounts = mounts.to_vec();
+    // Children before parents, or the parent detach takes them along unreported.
+    mounts.sort_by_key(|m| std::cmp::Reverse(m.len()));
+
+    mounts.iter().all(|target| {
+        Command::new("nsenter")
+            .arg(format!("--mount=/proc/{}/ns/mnt", pid))
+            .args(["--", "umount", "-l", target])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    })
+}
+
+pub fn self_test(config: &Config, sweep: bool) -> Vec<UserCoverage> {
+    let state = RuntimeState::load().unwrap_or_default();
+    let targets = our_targets(config, &state);
+    let global = fs::read_link("/proc/1/ns/mnt")
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let packages = denylist::packages();
+    if packages.is_empty() {
+        log::warn!("The denylist is empty, so no app is expected to have the modules hidden");
+    }
+
+    let mut users: BTreeMap<u32, UserCoverage> = BTreeMap::new();
+    let mut swept: HashSet<String> = HashSet::new();
+
+    for process in app_processes(&packages) {
+        let coverage = users.entry(process.user).or_insert(UserCoverage {
+            user: process.user,
+            processes: 0,
+            hidden: 0,
+            leaking: Vec::new(),
+        });
+        coverage.processes += 1;
+
+        let mut visible = visible_targets(process.pid, config, &targets);
+        // Never touch the global namespace: that would unmount the modules for everyone.
+        if sweep
+            && !visible.is_empty()
+            && process.namespace != global
+            && swept.insert(process.namespace.clone())
+        {
+            if sweep_namespace(process.pid, &visible) {
+                log::info!(
+                    "Swept {} mount(s) from pid {} (user {})",
+                    visible.len(),
+                    process.pid,
+                    process.user
+                );
+            }
+            visible = visible_targets(process.pid, config, &targets);
+        }
+
+        if visible.is_empty() {
+            coverage.hidden += 1;
+        } else {
+            coverage.leaking.push(process.pid);
+        }
+    }
+
+    users.into_values().collect()
+}
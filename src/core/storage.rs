@@ -15,13 +15,70 @@ use rustix::mount::{MountPropagationFlags, UnmountFlags, mount_change, unmount a
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use crate::mount::umount_mgr::send_umountable;
 use crate::{
+    conf::config::{Config, ImageFs, OverlayMode},
     defs,
     mount::overlayfs::utils as overlay_utils,
-    sys::{mount::is_mounted, nuke},
+    sys::{
+        mount::{is_filesystem_supported, is_mounted},
+        nuke, tools,
+    },
     utils::{self, ensure_dir_exists, lsetfilecon},
 };
 
 const DEFAULT_SELINUX_CONTEXT: &str = "u:object_r:system_file:s0";
+const MIN_IMAGE_SIZE: u64 = 64 * 1024 * 1024;
+const IMAGE_BLOCK_SIZE: u64 = 1024;
+const INODE_SIZE: u64 = 256;
+// Spare inodes on top of the content's, for the directories the sync and mounts add.
+const SPARE_INODES: u64 = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageSpec {
+    pub fs: ImageFs,
+    pub min_size: u64,
+}
+
+impl ImageSpec {
+    pub fn from_config(config: &Config) -> Self {
+        let min_size = match config.image_size.as_deref().map(parse_size) {
+            Some(Ok(size)) => size,
+            Some(Err(e)) => {
+                log::warn!("Ignoring image_size: {:#}", e);
+                0
+            }
+            None => 0,
+        };
+
+        Self {
+            fs: config.image_fs,
+            min_size,
+        }
+    }
+}
+
+pub fn parse_size(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let upper = trimmed.to_ascii_uppercase();
+    let upper = upper
+        .strip_suffix("IB")
+        .or_else(|| upper.strip_suffix('B'))
+        .unwrap_or(&upper);
+
+    let (number, shift) = match upper.chars().last() {
+        Some('K') => (&upper[..upper.len() - 1], 10),
+        Some('M') => (&upper[..upper.len() - 1], 20),
+        Some('G') => (&upper[..upper.len() - 1], 30),
+        _ => (upper, 0),
+    };
+
+    let value: u64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid size: {}", trimmed))?;
+    value
+        .checked_mul(1 << shift)
+        .with_context(|| format!("Size too large: {}", trimmed))
+}
 
 pub struct StorageHandle {
     pub mount_point: PathBuf,
@@ -32,19 +89,24 @@ pub struct StorageHandle {
 
 impl StorageHandle {
     pub fn commit(&mut self, disable_umount: bool) -> Result<()> {
-        if self.mode == "erofs_staging" {
+        if let Some(fs_type) = self.mode.strip_suffix("_staging") {
+            let fs_type = fs_type.to_string();
             let image_path = self
                 .backing_image
                 .as_ref()
-                .context("EROFS backing image path missing")?;
+                .context("Packed backing image path missing")?;
 
             let final_target = self
                 .final_target
                 .as_ref()
-                .context("EROFS final target missing")?;
+                .context("Packed image final target missing")?;
 
-            create_erofs_image(&self.mount_point, image_path)
-                .context("Failed to pack EROFS image")?;
+            match fs_type.as_str() {
+                "squashfs" => create_squashfs_image(&self.mount_point, image_path)
+                    .context("Failed to pack squashfs image")?,
+                _ => create_erofs_image(&self.mount_point, image_path)
+                    .context("Failed to pack EROFS image")?,
+            }
 
             if let Err(e) = umount(&self.mount_point, UnmountFlags::DETACH) {
                 log::warn!("Failed to unmount staging tmpfs: {}", e);
@@ -56,13 +118,13 @@ impl StorageHandle {
 
             ensure_dir_exists(final_target)?;
 
-            mount_erofs_image(image_path, final_target)
-                .context("Failed to mount finalized EROFS image")?;
+            mount_packed_image(image_path, final_target, &fs_type)
+                .with_context(|| format!("Failed to mount finalized {} image", fs_type))?;
 
             nuke::nuke_path(image_path);
 
             if let Err(e) = mount_change(final_target, MountPropagationFlags::PRIVATE) {
-                log::warn!("Failed to make EROFS storage private: {}", e);
+                log::warn!("Failed to make {} storage private: {}", fs_type, e);
             }
 
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -71,7 +133,7 @@ impl StorageHandle {
             }
 
             self.mount_point = final_target.clone();
-            self.mode = "erofs".to_string();
+            self.mode = fs_type;
             self.final_target = None;
         }
 
@@ -79,20 +141,30 @@ impl StorageHandle {
     }
 }
 
-fn calculate_total_size(path: &Path) -> Result<u64> {
-    let mut total_size = 0;
-    if path.is_dir() {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let file_type = entry.file_type()?;
-            if file_type.is_file() {
-                total_size += entry.metadata()?.len();
-            } else if file_type.is_dir() {
-                total_size += calculate_total_size(&entry.path())?;
-            }
+#[derive(Debug, Default)]
+struct ContentSize {
+    bytes: u64,
+    entries: u64,
+}
+
+// What the content costs on the image: every file rounded up to whole blocks, plus an inode and
+// a directory entry for everything, symlinks and directories included.
+fn measure_content(path: &Path, size: &mut ContentSize) -> Result<()> {
+    if !path.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        size.entries += 1;
+        if file_type.is_file() {
+            size.bytes += entry.metadata()?.len().div_ceil(IMAGE_BLOCK_SIZE) * IMAGE_BLOCK_SIZE;
+        } else if file_type.is_dir() {
+            size.bytes += IMAGE_BLOCK_SIZE;
+            measure_content(&entry.path(), size)?;
         }
     }
-    Ok(total_size)
+    Ok(())
 }
 
 fn check_image<P>(img: P) -> Result<()>
@@ -101,7 +173,7 @@ where
 {
     let path = img.as_ref();
     let path_str = path.to_str().context("Invalid path string")?;
-    let result = Command::new("e2fsck")
+    let result = tools::command("e2fsck")
         .args(["-yf", path_str])
         .status()
         .with_context(|| format!("Failed to exec e2fsck {}", path.display()))?;
@@ -111,12 +183,14 @@ where
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn setup(
     mnt_base: &Path,
     img_path: &Path,
-    moduledir: &Path,
+    moduledirs: &[PathBuf],
+    image: ImageSpec,
     force_ext4: bool,
-    use_erofs: bool,
+    packed_fs: Option<&str>,
     mount_source: &str,
     disable_umount: bool,
 ) -> Result<StorageHandle> {
@@ -140,9 +214,9 @@ pub fn setup(
         }
     };
 
-    if use_erofs && is_erofs_supported() {
-        let erofs_path = img_path.with_extension("erofs");
-        let staging_dir = Path::new(defs::RUN_DIR).join("erofs_staging");
+    if let Some(fs_type) = packed_fs {
+        let packed_path = img_path.with_extension(fs_type);
+        let staging_dir = Path::new(defs::RUN_DIR).join(format!("{}_staging", fs_type));
 
         if is_mounted(&staging_dir) {
             let _ = umount(&staging_dir, UnmountFlags::DETACH);
@@ -159,8 +233,8 @@ pub fn setup(
 
         return Ok(StorageHandle {
             mount_point: staging_dir,
-            mode: "erofs_staging".to_string(),
-            backing_image: Some(erofs_path),
+            mode: format!("{}_staging", fs_type),
+            backing_image: Some(packed_path),
             final_target: Some(mnt_base.to_path_buf()),
         });
     }
@@ -170,10 +244,10 @@ pub fn setup(
 
         try_hide(mnt_base);
 
-        let erofs_path = img_path.with_extension("erofs");
-
-        if erofs_path.exists() {
-            let _ = fs::remove_file(erofs_path);
+        for stale in ["erofs", "squashfs"].map(|ext| img_path.with_extension(ext)) {
+            if stale.exists() {
+                let _ = fs::remove_file(stale);
+            }
         }
 
         return Ok(StorageHandle {
@@ -184,7 +258,7 @@ pub fn setup(
         });
     }
 
-    let handle = setup_ext4_image(mnt_base, img_path, moduledir)?;
+    let handle = setup_image(mnt_base, img_path, moduledirs, image)?;
 
     make_private(mnt_base);
 
@@ -206,47 +280,100 @@ fn try_setup_tmpfs(target: &Path, mount_source: &str) -> Result<bool> {
     Ok(false)
 }
 
-fn setup_ext4_image(target: &Path, img_path: &Path, moduledir: &Path) -> Result<StorageHandle> {
-    if img_path.exists()
-        && let Err(e) = fs::remove_file(img_path)
-    {
-        log::warn!("Failed to remove old image: {}", e);
+fn resolve_image_fs(requested: ImageFs) -> &'static str {
+    match requested {
+        ImageFs::Ext4 => "ext4",
+        ImageFs::F2fs | ImageFs::Auto if is_f2fs_supported() => "f2fs",
+        ImageFs::F2fs => {
+            log::warn!("F2FS is not supported by this kernel or mkfs.f2fs is missing, using ext4");
+            "ext4"
+        }
+        ImageFs::Auto => "ext4",
     }
+}
 
-    let total_size = calculate_total_size(moduledir)?;
-    let min_size = 64 * 1024 * 1024;
-    let grow_size = std::cmp::max((total_size as f64 * 1.2) as u64, min_size);
-
-    fs::File::create(img_path)
-        .context("Failed to create ext4 image file")?
-        .set_len(grow_size)
-        .context("Failed to extend ext4 image")?;
+fn format_image(img_path: &Path, fs_type: &str, inodes: u64) -> Result<()> {
+    let mut cmd = match fs_type {
+        "f2fs" => {
+            let mut cmd = tools::command("mkfs.f2fs");
+            cmd.arg("-f");
+            cmd
+        }
+        _ => {
+            let mut cmd = tools::command("mkfs.ext4");
+            cmd.arg("-b")
+                .arg(IMAGE_BLOCK_SIZE.to_string())
+                .arg("-N")
+                .arg(inodes.to_string());
+            cmd
+        }
+    };
 
-    let result = Command::new("mkfs.ext4")
-        .arg("-b")
-        .arg("1024")
+    let result = cmd
         .arg(img_path)
         .stdout(std::process::Stdio::piped())
         .output()?;
 
     ensure!(
         result.status.success(),
-        "Failed to format ext4 image: {}",
+        "Failed to format {} image: {}",
+        fs_type,
         String::from_utf8(result.stderr)?
     );
+    Ok(())
+}
 
-    check_image(img_path)?;
+fn setup_image(
+    target: &Path,
+    img_path: &Path,
+    moduledirs: &[PathBuf],
+    image: ImageSpec,
+) -> Result<StorageHandle> {
+    let fs_type = resolve_image_fs(image.fs);
+
+    if img_path.exists()
+        && let Err(e) = fs::remove_file(img_path)
+    {
+        log::warn!("Failed to remove old image: {}", e);
+    }
+
+    // The image is rebuilt every boot, so it only has to fit what is installed now, with some room
+    // for block groups, journal and the odd file a module adds at runtime.
+    let mut content = ContentSize::default();
+    for dir in moduledirs {
+        measure_content(dir, &mut content)?;
+    }
+    let required = content.bytes + content.entries * INODE_SIZE;
+    let grow_size = (required + required / 5)
+        .max(MIN_IMAGE_SIZE)
+        .max(image.min_size);
+    let inodes = content.entries + content.entries / 5 + SPARE_INODES;
+
+    // set_len leaves the image sparse, so only written blocks take space on /data.
+    fs::File::create(img_path)
+        .context("Failed to create modules image file")?
+        .set_len(grow_size)
+        .context("Failed to extend modules image")?;
+
+    format_image(img_path, fs_type, inodes)?;
 
     utils::lsetfilecon(img_path, "u:object_r:ksu_file:s0").ok();
 
     ensure_dir_exists(target)?;
-    if overlay_utils::AutoMountExt4::try_new(img_path, target, false).is_err() {
-        if crate::sys::mount::repair_image(img_path).is_ok() {
-            overlay_utils::AutoMountExt4::try_new(img_path, target, false)
-                .context("Failed to mount modules.img after repair")
-                .map(|_| ())?;
-        } else {
-            bail!("Failed to repair modules.img");
+    if fs_type == "f2fs" {
+        overlay_utils::mount_image(img_path, target, fs_type)
+            .context("Failed to mount F2FS modules image")?;
+    } else {
+        check_image(img_path)?;
+
+        if overlay_utils::AutoMountExt4::try_new(img_path, target, false).is_err() {
+            if crate::sys::mount::repair_image(img_path).is_ok() {
+                overlay_utils::AutoMountExt4::try_new(img_path, target, false)
+                    .context("Failed to mount modules.img after repair")
+                    .map(|_| ())?;
+            } else {
+                bail!("Failed to repair modules.img");
+            }
         }
     }
 
@@ -260,31 +387,43 @@ fn setup_ext4_image(target: &Path, img_path: &Path, moduledir: &Path) -> Result<
 
     Ok(StorageHandle {
         mount_point: target.to_path_buf(),
-        mode: "ext4".to_string(),
+        mode: fs_type.to_string(),
         backing_image: Some(img_path.to_path_buf()),
         final_target: None,
     })
 }
 
-fn is_erofs_supported() -> bool {
-    fs::read_to_string("/proc/filesystems")
-        .map(|content| content.contains("erofs"))
-        .unwrap_or(false)
+pub fn is_erofs_supported() -> bool {
+    is_filesystem_supported("erofs")
 }
 
-fn create_erofs_image(src_dir: &Path, image_path: &Path) -> Result<()> {
-    let mkfs_bin = Path::new(defs::MKFS_EROFS_PATH);
-    let cmd_name = if mkfs_bin.exists() {
-        mkfs_bin.as_os_str()
-    } else {
-        std::ffi::OsStr::new("mkfs.erofs")
-    };
+pub fn is_squashfs_supported() -> bool {
+    is_filesystem_supported("squashfs") && tools::find("mksquashfs").is_some()
+}
+
+// Squashfs stands in for EROFS on kernels that lack it.
+pub fn resolve_packed_fs(mode: &OverlayMode) -> Option<&'static str> {
+    match mode {
+        OverlayMode::Erofs if is_erofs_supported() => Some("erofs"),
+        OverlayMode::Erofs if is_squashfs_supported() => {
+            log::warn!("EROFS is not supported by this kernel, packing modules as squashfs");
+            Some("squashfs")
+        }
+        OverlayMode::Squashfs if is_squashfs_supported() => Some("squashfs"),
+        _ => None,
+    }
+}
+
+pub fn is_f2fs_supported() -> bool {
+    is_filesystem_supported("f2fs") && tools::find("mkfs.f2fs").is_some()
+}
 
+fn create_erofs_image(src_dir: &Path, image_path: &Path) -> Result<()> {
     if image_path.exists() {
         let _ = fs::remove_file(image_path);
     }
 
-    let output = Command::new(cmd_name)
+    let output = tools::command("mkfs.erofs")
         .arg("-z")
         .arg("lz4hc")
         .arg("-x")
@@ -305,22 +444,52 @@ fn create_erofs_image(src_dir: &Path, image_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn mount_erofs_image(image_path: &Path, target: &Path) -> Result<()> {
+fn create_squashfs_image(src_dir: &Path, image_path: &Path) -> Result<()> {
+    if image_path.exists() {
+        let _ = fs::remove_file(image_path);
+    }
+
+    // gzip is the one compressor every squashfs-enabled kernel can read.
+    let output = tools::command("mksquashfs")
+        .arg(src_dir)
+        .arg(image_path)
+        .args(["-comp", "gzip", "-noappend", "-no-progress"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .context("Failed to execute mksquashfs")?;
+
+    if !output.status.success() {
+        bail!(
+            "Failed to create squashfs image: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let _ = fs::set_permissions(image_path, fs::Permissions::from_mode(0o644));
+    lsetfilecon(image_path, "u:object_r:ksu_file:s0")?;
+    Ok(())
+}
+
+pub fn mount_packed_image(image_path: &Path, target: &Path, fs_type: &str) -> Result<()> {
     ensure_dir_exists(target)?;
     lsetfilecon(image_path, "u:object_r:ksu_file:s0").ok();
     let status = Command::new("mount")
-        .args(["-t", "erofs", "-o", "loop,ro,nodev,noatime"])
+        .args(["-t", fs_type, "-o", "loop,ro,nodev,noatime"])
         .arg(image_path)
         .arg(target)
         .status()
-        .context("Failed to execute mount command for EROFS")?;
+        .with_context(|| format!("Failed to execute mount command for {}", fs_type))?;
 
     if !status.success() {
-        bail!("EROFS Mount command failed");
+        bail!("{} mount command failed", fs_type);
     }
 
     if fs::read_dir(target)?.next().is_none() {
-        bail!("EROFS mount success but directory is empty (Loop device failure?)");
+        bail!(
+            "{} mount success but directory is empty (Loop device failure?)",
+            fs_type
+        );
     }
 
     Ok(())
@@ -0,0 +1,97 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, sync::Mutex, time::Duration};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{conf::config::MountStage, defs, utils};
+
+static EVENTS: Mutex<Vec<TraceEvent>> = Mutex::new(Vec::new());
+static OPEN_PHASE: Mutex<Option<(String, u64)>> = Mutex::new(None);
+
+// A Chrome trace "complete" event, which Perfetto imports as a slice. Timestamps are
+// CLOCK_BOOTTIME microseconds, the clock platform traces use, so both line up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceEvent {
+    pub name: String,
+    pub cat: String,
+    pub ph: String,
+    pub ts: u64,
+    pub dur: u64,
+    pub pid: u32,
+    pub tid: i32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TraceFile {
+    #[serde(rename = "traceEvents")]
+    trace_events: Vec<TraceEvent>,
+    #[serde(rename = "displayTimeUnit")]
+    display_time_unit: String,
+}
+
+fn boottime_us() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
+fn push(name: String, cat: &str, ts: u64, dur: u64) {
+    let event = TraceEvent {
+        name,
+        cat: cat.to_string(),
+        ph: "X".to_string(),
+        ts,
+        dur,
+        pid: std::process::id(),
+        tid: unsafe { libc::gettid() },
+    };
+    if let Ok(mut events) = EVENTS.lock() {
+        events.push(event);
+    }
+}
+
+// A span that ends now and lasted `elapsed`.
+pub fn complete(name: String, cat: &str, elapsed: Duration) {
+    let end = boottime_us();
+    let dur = elapsed.as_micros() as u64;
+    push(name, cat, end.saturating_sub(dur), dur);
+}
+
+// Closes the running phase span and, unless `name` is None, opens the next one.
+pub fn phase(name: Option<&str>) {
+    let now = boottime_us();
+    let Ok(mut open) = OPEN_PHASE.lock() else {
+        return;
+    };
+    if let Some((previous, started)) = open.take() {
+        push(previous, "phase", started, now - started);
+    }
+    *open = name.map(|n| (n.to_string(), now));
+}
+
+// The service stage appends to the trace post-fs-data left; any other stage starts over.
+pub fn save(stage: Option<MountStage>) -> Result<()> {
+    phase(None);
+
+    let mut trace = if stage == Some(MountStage::Service) {
+        fs::read_to_string(defs::BOOT_TRACE_FILE)
+            .ok()
+            .and_then(|content| serde_json::from_str::<TraceFile>(&content).ok())
+            .unwrap_or_default()
+    } else {
+        TraceFile::default()
+    };
+    trace.display_time_unit = "ms".to_string();
+    if let Ok(mut events) = EVENTS.lock() {
+        trace.trace_events.append(&mut events);
+    }
+
+    utils::atomic_write(defs::BOOT_TRACE_FILE, serde_json::to_string(&trace)?)?;
+    Ok(())
+}
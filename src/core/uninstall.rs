@@ -0,0 +1,133 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use procfs::process::Process;
+use rustix::mount::{UnmountFlags, unmount};
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::{conf::config::Config, core::state::RuntimeState, defs};
+
+#[derive(Debug, Serialize)]
+pub struct Removal {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+fn disk_usage(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+// Without purge only what a reinstall rebuilds goes: the modules image, its mount point and
+// runtime state. Purge also drops config, rules, snapshots, exports and logs.
+pub fn removals(purge: bool) -> Vec<Removal> {
+    let paths: Vec<PathBuf> = if purge {
+        vec![PathBuf::from(defs::BASE_DIR)]
+    } else {
+        [defs::MODULES_IMG_FILE, defs::RUN_DIR, defs::IMAGE_MNT_DIR]
+            .iter()
+            .map(PathBuf::from)
+            .collect()
+    };
+
+    paths
+        .into_iter()
+        .filter(|p| fs::symlink_metadata(p).is_ok())
+        .map(|path| Removal {
+            size: disk_usage(&path),
+            path,
+        })
+        .collect()
+}
+
+// Only mounts mountinfo shows as ours right now: a recorded target whose top mount carries our
+// mount source, from a state written this boot, then whatever sits inside our own directories.
+// Anything else on a recorded path, like the real partition, is left alone. One unmount per
+// record: DETACH only takes the top mount.
+fn mount_targets(config: &Config) -> Vec<PathBuf> {
+    let Ok(mountinfo) = Process::myself().and_then(|p| p.mountinfo()) else {
+        return Vec::new();
+    };
+    let state = RuntimeState::load()
+        .ok()
+        .filter(|s| s.is_this_boot())
+        .unwrap_or_default();
+
+    // Older states record magic mount as a single "/" entry.
+    let mut targets: Vec<PathBuf> = state
+        .mounts
+        .iter()
+        .rev()
+        .map(|m| PathBuf::from(&m.target))
+        .filter(|t| t != Path::new("/"))
+        .filter(|t| {
+            mountinfo
+                .iter()
+                .rfind(|m| m.mount_point == *t)
+                .is_some_and(|m| m.mount_source.as_deref() == Some(config.mountsource.as_str()))
+        })
+        .collect();
+
+    let mut ours: Vec<PathBuf> = mountinfo
+        .iter()
+        .map(|m| m.mount_point.clone())
+        .filter(|m| {
+            (*m == state.mount_point && !m.as_os_str().is_empty())
+                || m.starts_with(defs::BASE_DIR)
+                || m.starts_with(defs::POACEAE_MOUNT_POINT)
+        })
+        .collect();
+    ours.sort_by_key(|m| std::cmp::Reverse(m.as_os_str().len()));
+    ours.dedup();
+    targets.extend(ours);
+    targets
+}
+
+// One line per action, in order.
+pub fn run(config: &Config, purge: bool) -> Vec<String> {
+    let mut steps = Vec::new();
+
+    let targets = mount_targets(config);
+    if targets.is_empty() {
+        steps.push("no mounts of ours found, skipped unmounting".to_string());
+    }
+    for target in targets {
+        match unmount(&target, UnmountFlags::DETACH) {
+            Ok(()) => steps.push(format!("unmounted {}", target.display())),
+            Err(e) => steps.push(format!("could not unmount {}: {}", target.display(), e)),
+        }
+    }
+
+    for removal in removals(purge) {
+        let result = if removal.path.is_dir() {
+            fs::remove_dir_all(&removal.path)
+        } else {
+            fs::remove_file(&removal.path)
+        };
+        match result {
+            Ok(()) => steps.push(format!(
+                "removed {} ({} bytes)",
+                removal.path.display(),
+                removal.size
+            )),
+            Err(e) => steps.push(format!(
+                "could not remove {}: {}",
+                removal.path.display(),
+                e
+            )),
+        }
+    }
+
+    steps
+}
@@ -0,0 +1,233 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use procfs::process::{Process, all_processes};
+use walkdir::WalkDir;
+
+use crate::{
+    conf::config::Config,
+    core::{inventory::Module, state::RuntimeState},
+    sys::proc_events::{ProcEvent, ProcEvents},
+};
+
+// One overlay per target directory, stacking every scoped module of the app over its own view.
+struct ScopedMount {
+    lowers: Vec<PathBuf>,
+    target: PathBuf,
+}
+
+// Scoped modules are synced like any other but never mounted globally.
+pub fn retain_global(config: &Config, modules: &mut Vec<Module>) {
+    let before = modules.len();
+    modules.retain(|m| !config.visibility.contains_key(&m.id));
+    if modules.len() < before {
+        log::info!(
+            ">> Visibility: {} module(s) scoped to specific apps",
+            before - modules.len()
+        );
+    }
+}
+
+// The directories under a module's partitions that directly hold files, relative to its root.
+fn file_dirs(config: &Config, module_root: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    for partition in config.effective_partitions() {
+        let root = module_root.join(&partition);
+        if !root.is_dir() {
+            continue;
+        }
+        for entry in WalkDir::new(&root).into_iter().flatten() {
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+            let has_files = fs::read_dir(entry.path())
+                .map(|mut d| d.any(|e| e.is_ok_and(|e| e.file_type().is_ok_and(|t| !t.is_dir()))))
+                .unwrap_or(false);
+            if has_files && let Ok(rel) = entry.path().strip_prefix(module_root) {
+                dirs.push(rel.to_path_buf());
+            }
+        }
+    }
+    dirs
+}
+
+// An overlay merges whole subtrees, so only the outermost directories need a mount, and modules
+// touching the same directory share it.
+fn scoped_mounts(config: &Config, storage_root: &Path, modules: &[String]) -> Vec<ScopedMount> {
+    let roots: Vec<PathBuf> = modules.iter().map(|m| storage_root.join(m)).collect();
+    let dirs: BTreeSet<PathBuf> = roots
+        .iter()
+        .flat_map(|root| file_dirs(config, root))
+        .filter(|rel| Path::new("/").join(rel).is_dir())
+        .collect();
+
+    dirs.iter()
+        .filter(|rel| !rel.ancestors().skip(1).any(|a| dirs.contains(a)))
+        .map(|rel| ScopedMount {
+            lowers: roots
+                .iter()
+                .map(|root| root.join(rel))
+                .filter(|l| l.is_dir())
+                .collect(),
+            target: Path::new("/").join(rel),
+        })
+        .collect()
+}
+
+fn mounted(pid: i32, mount: &ScopedMount) -> bool {
+    let Some(first) = mount
+        .lowers
+        .first()
+        .map(|l| l.to_string_lossy().to_string())
+    else {
+        return true;
+    };
+    Process::new(pid)
+        .and_then(|p| p.mountinfo())
+        .map(|mountinfo| {
+            mountinfo.into_iter().any(|m| {
+                m.mount_point == mount.target
+                    && m.fs_type == "overlay"
+                    && m.super_options
+                        .get("lowerdir")
+                        .cloned()
+                        .flatten()
+                        .is_some_and(|l| l.split(':').any(|l| l == first))
+            })
+        })
+        .unwrap_or(false)
+}
+
+fn mount_in_namespace(pid: i32, mount: &ScopedMount) -> bool {
+    let mut options = String::from("lowerdir=");
+    for lower in &mount.lowers {
+        options.push_str(&lower.to_string_lossy());
+        options.push(':');
+    }
+    options.push_str(&mount.target.to_string_lossy());
+    Command::new("nsenter")
+        .arg(format!("--mount=/proc/{}/ns/mnt", pid))
+        .args(["--", "mount", "-t", "overlay", "overlay", "-o"])
+        .arg(options)
+        .arg(&mount.target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+fn package_of(pid: i32) -> Option<String> {
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let name = cmdline.split(|b| *b == 0).next()?;
+    let name = String::from_utf8_lossy(name);
+    // Secondary processes run as `package:name`.
+    let package = name.split(':').next()?.trim();
+    (!package.is_empty()).then(|| package.to_string())
+}
+
+fn namespace_of(pid: i32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{}/ns/mnt", pid)).ok()
+}
+
+// Every process of an app shares its namespace, so the mountinfo check makes repeated calls for
+// the same app no-ops.
+fn apply(pid: i32, by_package: &HashMap<String, Vec<ScopedMount>>, global: Option<&PathBuf>) {
+    let Some((package, mounts)) = package_of(pid).and_then(|p| by_package.get_key_value(&p)) else {
+        return;
+    };
+    // Never the global namespace: that would show the modules to every app.
+    let Some(namespace) = namespace_of(pid) else {
+        return;
+    };
+    if global == Some(&namespace) {
+        return;
+    }
+
+    let pending: Vec<&ScopedMount> = mounts.iter().filter(|m| !mounted(pid, m)).collect();
+    if pending.is_empty() {
+        return;
+    }
+    let done = pending
+        .iter()
+        .filter(|m| mount_in_namespace(pid, m))
+        .count();
+    log::info!(
+        "Visibility: {} of {} mount(s) for {} in pid {}",
+        done,
+        pending.len(),
+        package,
+        pid
+    );
+}
+
+fn scan(by_package: &HashMap<String, Vec<ScopedMount>>, global: Option<&PathBuf>) {
+    for process in all_processes().into_iter().flatten().flatten() {
+        apply(process.pid, by_package, global);
+    }
+}
+
+// Mounts each app's scoped modules as the app starts, woken by the kernel's process events. Runs
+// for the lifetime of the daemon.
+pub fn watch(config: Config) {
+    let mut modules_of: HashMap<String, Vec<String>> = HashMap::new();
+    for (module, packages) in &config.visibility {
+        for package in packages {
+            modules_of
+                .entry(package.clone())
+                .or_default()
+                .push(module.clone());
+        }
+    }
+    if modules_of.is_empty() {
+        return;
+    }
+
+    let storage_root = RuntimeState::load().unwrap_or_default().mount_point;
+    let by_package: HashMap<String, Vec<ScopedMount>> = modules_of
+        .into_iter()
+        .map(|(package, modules)| {
+            let mounts = scoped_mounts(&config, &storage_root, &modules);
+            (package, mounts)
+        })
+        .collect();
+    let global = namespace_of(1);
+
+    // Subscribed before the first scan, so an app starting in between is not missed.
+    let events = match ProcEvents::subscribe() {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!("Visibility watcher disabled, no process events: {:#}", e);
+            return;
+        }
+    };
+    log::info!(
+        ">> Visibility watcher started for {} package(s)",
+        by_package.len()
+    );
+    scan(&by_package, global.as_ref());
+
+    loop {
+        let batch = match events.read() {
+            Ok(batch) => batch,
+            Err(e) => {
+                log::error!("Visibility watcher stopped: {:#}", e);
+                return;
+            }
+        };
+        for event in batch {
+            match event {
+                ProcEvent::Named(pid) => apply(pid, &by_package, global.as_ref()),
+                ProcEvent::Lost => scan(&by_package, global.as_ref()),
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    os::fd::AsRawFd,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    conf::config::Config,
+    core::{
+        live, notify,
+        ops::verify,
+        state::{MountKind, RuntimeState},
+    },
+};
+
+// Upper bound between checks when the kernel does not signal mount table changes.
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+// A module whose mounts keep vanishing is left alone after this many repairs.
+const MAX_REPAIRS: u32 = 5;
+// Merged views are re-fingerprinted this often even when the mount table stays quiet, since files
+// can be swapped under a mount without touching it.
+const FINGERPRINT_INTERVAL: Duration = Duration::from_secs(60);
+
+// /proc/self/mounts raises POLLPRI whenever the mount table of our namespace changes.
+fn wait_for_change(mounts: &File) {
+    let mut fd = libc::pollfd {
+        fd: mounts.as_raw_fd(),
+        events: libc::POLLPRI | libc::POLLERR,
+        revents: 0,
+    };
+    unsafe {
+        libc::poll(&mut fd, 1, POLL_TIMEOUT.as_millis() as libc::c_int);
+    }
+}
+
+fn check_fingerprints(
+    config: &Config,
+    state: &RuntimeState,
+    drifted: &mut HashMap<String, String>,
+) {
+    let drifts = verify::recheck(&state.fingerprints);
+    drifted.retain(|target, _| drifts.iter().any(|d| d.target == *target));
+
+    for drift in drifts {
+        if drifted.get(&drift.target) == Some(&drift.found) {
+            continue;
+        }
+        drifted.insert(drift.target.clone(), drift.found.clone());
+
+        let message = format!(
+            "Merged view of {} changed since it was mounted (fingerprint {:.12}, now {:.12})",
+            drift.target, drift.expected, drift.found
+        );
+        log::error!("Watchdog: {}", message);
+        notify::send(
+            &config.notify,
+            "mount_tampered",
+            &message,
+            serde_json::json!({ "drift": drift }),
+        );
+    }
+}
+
+pub fn watch(config: &Config) -> Result<()> {
+    let mounts = File::open("/proc/self/mounts").context("Failed to open /proc/self/mounts")?;
+    let mut repairs: HashMap<String, u32> = HashMap::new();
+    // Drifted targets already reported, with the hash they were reported at.
+    let mut drifted: HashMap<String, String> = HashMap::new();
+    let mut last_fingerprint = Instant::now();
+
+    log::info!(">> Watchdog: watching for dropped mounts");
+
+    loop {
+        wait_for_change(&mounts);
+
+        let mut state = RuntimeState::load().context("Failed to load runtime state")?;
+        if last_fingerprint.elapsed() >= FINGERPRINT_INTERVAL {
+            last_fingerprint = Instant::now();
+            check_fingerprints(config, &state, &mut drifted);
+        }
+        // Magic mounts cannot be redone live; the boot-time check reports them.
+        state.mounts.retain(|m| {
+            m.kind != MountKind::Magic
+                && m.modules
+                    .iter()
+                    .any(|id| repairs.get(id).copied().unwrap_or(0) < MAX_REPAIRS)
+        });
+
+        let lost = verify::verify(&state.mounts);
+        let modules: BTreeSet<String> = lost.iter().flat_map(|l| l.modules.clone()).collect();
+
+        for module in modules {
+            let count = repairs.entry(module.clone()).or_default();
+            if *count >= MAX_REPAIRS {
+                continue;
+            }
+            *count += 1;
+
+            let targets: Vec<&str> = lost
+                .iter()
+                .filter(|l| l.modules.contains(&module))
+                .map(|l| l.target.as_str())
+                .collect();
+            match live::remount_module(config, &module) {
+                Ok(touched) => log::warn!(
+                    "Watchdog: {} lost {:?}, remounted {} target(s) (repair {}/{})",
+                    module,
+                    targets,
+                    touched.len(),
+                    count,
+                    MAX_REPAIRS
+                ),
+                Err(e) => log::error!(
+                    "Watchdog: {} lost {:?}, remount failed: {:#}",
+                    module,
+                    targets,
+                    e
+                ),
+            }
+            if *count == MAX_REPAIRS {
+                log::error!(
+                    "Watchdog: giving up on {} after {} repairs",
+                    module,
+                    MAX_REPAIRS
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::Path;
+
+#[cfg(not(feature = "embed-webui"))]
+use anyhow::bail;
+use anyhow::{Context, Result};
+
+use crate::defs;
+#[cfg(feature = "embed-webui")]
+use crate::utils;
+
+// Only ever extracted, never served: the WebUI needs the root manager's exec bridge to do anything.
+#[cfg(feature = "embed-webui")]
+static WEBROOT: include_dir::Dir<'_> =
+    include_dir::include_dir!("$CARGO_MANIFEST_DIR/module/webroot");
+
+pub fn is_embedded() -> bool {
+    cfg!(feature = "embed-webui")
+}
+
+#[cfg(feature = "embed-webui")]
+fn unpack(dest: &Path) -> Result<()> {
+    utils::ensure_dir_exists(dest)?;
+    WEBROOT.extract(dest)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "embed-webui"))]
+fn unpack(_dest: &Path) -> Result<()> {
+    bail!("This build carries no WebUI; build it with `cargo xtask build --embed-webui`")
+}
+
+pub fn extract(dest: &Path) -> Result<()> {
+    unpack(dest).with_context(|| format!("Failed to extract the WebUI to {}", dest.display()))
+}
+
+// The module's webroot can go missing (a partial update, a cleaner app); the copy in the binary
+// puts it back so the root manager has something to serve.
+pub fn restore_missing() -> Option<&'static str> {
+    let webroot = Path::new(defs::WEBROOT_DIR);
+    if !is_embedded() || webroot.join("index.html").exists() {
+        return None;
+    }
+    match extract(webroot) {
+        Ok(()) => {
+            log::info!("Restored the WebUI to {}", webroot.display());
+            Some(defs::WEBROOT_DIR)
+        }
+        Err(e) => {
+            log::warn!("{:#}", e);
+            None
+        }
+    }
+}
@@ -2,20 +2,68 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 pub const DEFAULT_HYBRID_MNT_DIR: &str = "/debug_ramdisk";
+pub const BASE_DIR: &str = "/data/adb/meta-hybrid";
+pub const IMAGE_MNT_DIR: &str = "/data/adb/meta-hybrid/mnt";
 pub const MODULES_IMG_FILE: &str = "/data/adb/meta-hybrid/modules.img";
 pub const RUN_DIR: &str = "/data/adb/meta-hybrid/run/";
 pub const STATE_FILE: &str = "/data/adb/meta-hybrid/run/daemon_state.json";
+pub const JOBS_DIR: &str = "/data/adb/meta-hybrid/run/jobs";
+pub const BOOT_REPORT_FILE: &str = "/data/adb/meta-hybrid/run/boot_report.json";
+pub const BOOT_TRACE_FILE: &str = "/data/adb/meta-hybrid/run/boot_trace.json";
+pub const LAST_PLAN_FILE: &str = "/data/adb/meta-hybrid/run/last_plan.json";
+pub const SOURCE_HASHES_FILE: &str = "/data/adb/meta-hybrid/run/source_hashes.json";
+pub const OVERLAY_LIMIT_FILE: &str = "/data/adb/meta-hybrid/run/overlay_limit.json";
+pub const OVERLAY_STAGE_DIR: &str = "/data/adb/meta-hybrid/run/overlay_stage";
+pub const SEPOLICY_RULE_FILE: &str = "/data/adb/meta-hybrid/run/sepolicy.rule";
+pub const PROGRESS_FIFO: &str = "/data/adb/meta-hybrid/run/progress";
+pub const SCRIPT_LOG_DIR: &str = "/data/adb/meta-hybrid/run/scripts";
+pub const UMOUNT_TARGETS_FILE: &str = "/data/adb/meta-hybrid/run/umount_targets.json";
+pub const DENYLIST_CACHE_FILE: &str = "/data/adb/meta-hybrid/run/denylist.json";
+pub const PINS_DIR: &str = "/data/adb/meta-hybrid/run/pins";
+pub const FINGERPRINTS_DIR: &str = "/data/adb/meta-hybrid/run/fingerprints";
+pub const NOTIFY_QUEUE_FILE: &str = "/data/adb/meta-hybrid/run/notify_queue.jsonl";
+pub const LAYER_MNT_DIR: &str = "/data/adb/meta-hybrid/run/layers";
+pub const INSTALL_STAGE_DIR: &str = "/data/adb/meta-hybrid/run/install";
+pub const MANIFESTS_DIR: &str = "/data/adb/meta-hybrid/manifests";
+pub const SYNC_JOURNAL_DIR: &str = "/data/adb/meta-hybrid/journal";
+pub const MODULE_IMAGES_DIR: &str = "/data/adb/meta-hybrid/images";
+pub const GRANARY_DIR: &str = "/data/adb/meta-hybrid/granary";
+pub const PROFILES_DIR: &str = "/data/adb/meta-hybrid/profiles";
+pub const CAPTURES_DIR: &str = "/data/adb/meta-hybrid/captures";
+pub const SAFE_MODE_FILE: &str = "/data/adb/meta-hybrid/.safe_mode";
+pub const INTEGRITY_KEY_FILE: &str = "/data/adb/meta-hybrid/.integrity_key";
+pub const REDACT_KEY_FILE: &str = "/data/adb/meta-hybrid/.redact_key";
+pub const COMPANIONS_FILE: &str = "/data/adb/meta-hybrid/companions.json";
+pub const COMPANION_BACKUP_DIR: &str = "/data/adb/meta-hybrid/companions";
+pub const STORAGE_HISTORY_FILE: &str = "/data/adb/meta-hybrid/storage_history.jsonl";
+pub const MAINTENANCE_FILE: &str = "/data/adb/meta-hybrid/maintenance.json";
+pub const EXPORTS_DIR: &str = "/data/adb/meta-hybrid/exports";
+pub const EXPORTS_INDEX_NAME: &str = ".exports.jsonl";
+pub const CTL_SOCKET: &str = "/data/adb/meta-hybrid/run/ctl.sock";
 pub const DISABLE_FILE_NAME: &str = "disable";
 pub const REMOVE_FILE_NAME: &str = "remove";
 pub const SKIP_MOUNT_FILE_NAME: &str = "skip_mount";
+pub const SKIP_UMOUNT_FILE_NAME: &str = "skip_umount";
 pub const SYSTEM_RW_DIR: &str = "/data/adb/meta-hybrid/rw";
 pub const MODULE_PROP_FILE: &str = "/data/adb/modules/meta-hybrid/module.prop";
+pub const WEBROOT_DIR: &str = "/data/adb/modules/meta-hybrid/webroot";
 pub const MODULES_DIR: &str = "/data/adb/modules";
+// Root managers move modules staged here into place on the next boot.
+pub const MODULES_UPDATE_DIR: &str = "/data/adb/modules_update";
+pub const UPDATE_FLAG_FILE_NAME: &str = "update";
+pub const KSUD_PATH: &str = "/data/adb/ksud";
+pub const MANAGED_MODULES_DIR: &str = "/data/adb/meta-hybrid/managed";
+pub const DAEMON_LOG_FILE: &str = "/data/adb/meta-hybrid/daemon.log";
+pub const KV_STORE_FILE: &str = "/data/adb/meta-hybrid/kv.json";
+pub const KV_LOCK_FILE: &str = "/data/adb/meta-hybrid/kv.lock";
 pub const CONFIG_FILE: &str = "/data/adb/meta-hybrid/config.toml";
-pub const MKFS_EROFS_PATH: &str = "/data/adb/metamodule/tools/mkfs.erofs";
+pub const MODE_CONFIG_FILE: &str = "/data/adb/meta-hybrid/module_mode.conf";
+pub const DENYLIST_FILE: &str = "/data/adb/meta-hybrid/denylist";
+pub const TOOLS_DIR: &str = "/data/adb/metamodule/tools";
 pub const POACEAE_MOUNT_POINT: &str = "/data/adb/poaceaefs_mount";
 pub const ZYGISKSU_DENYLIST_FILE: &str = "/data/adb/zygisksu/denylist_enforce";
 
+// Fallback for `builtin_partitions` when the config does not override it.
 pub const BUILTIN_PARTITIONS: &[&str] = &[
     "system",
     "vendor",
@@ -38,6 +86,25 @@ pub const BUILTIN_PARTITIONS: &[&str] = &[
     "my_stock",
     "optics",
     "prism",
+    "vendor_dlkm",
+    "odm_dlkm",
+    "system_dlkm",
+];
+
+// Root directories that auto-discovery never treats as a partition, even when block-backed.
+pub const NON_PARTITION_DIRS: &[&str] = &[
+    "data",
+    "metadata",
+    "cache",
+    "mnt",
+    "storage",
+    "sdcard",
+    "apex",
+    "debug_ramdisk",
+    "linkerconfig",
+    "second_stage_resources",
+    "postinstall",
+    "lost+found",
 ];
 
 pub const SENSITIVE_PARTITIONS: &[&str] = &[
@@ -61,6 +128,43 @@ pub const SENSITIVE_PARTITIONS: &[&str] = &[
     "my_stock",
     "optics",
     "prism",
+    "vendor_dlkm",
+    "odm_dlkm",
+    "system_dlkm",
+];
+
+// dlkm partitions are first-stage mounts at `/<name>`; older layouts nest them under a parent.
+pub const DLKM_PARTITIONS: &[(&str, &str)] = &[
+    ("vendor_dlkm", "/vendor"),
+    ("odm_dlkm", "/odm"),
+    ("system_dlkm", "/system"),
+];
+
+pub const ROOTDIR_NAME: &str = "rootdir";
+pub const APEX_DIR_NAME: &str = "apex";
+pub const FLATTENED_APEX_DIR: &str = "/system/apex";
+pub const ROOTDIR_ALLOWLIST: &[&str] = &[
+    "/init.environ.rc",
+    "/init.usb.rc",
+    "/init.usb.configfs.rc",
+    "/init.zygote32.rc",
+    "/init.zygote64.rc",
+    "/init.zygote64_32.rc",
+    "/ueventd.rc",
+];
+
+// Module content at or below these paths is never mounted: replacing them bricks the device or is
+// an obvious tampering signal.
+pub const BUILTIN_PROTECTED_PATHS: &[&str] = &[
+    "/system/bin/init",
+    "/system/bin/linker",
+    "/system/bin/linker64",
+    "/system/bin/keystore2",
+    "/system/bin/vold",
+    "/system/etc/selinux",
+    "/system/etc/init/hw",
+    "/vendor/etc/selinux",
+    "/system/apex/com.android.runtime.apex",
 ];
 
 pub const REPLACE_DIR_FILE_NAME: &str = ".replace";
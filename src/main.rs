@@ -8,17 +8,24 @@ mod mount;
 mod sys;
 mod utils;
 
-use core::MountController;
-use std::path::PathBuf;
+use core::{
+    MountController,
+    capture::Capture,
+    daemon, granary, integrity,
+    progress::{self, Phase},
+    recovery, safe_mode, trace, watchdog,
+};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
-use clap::Parser;
 use conf::{
     cli::{Cli, Commands},
     cli_handlers,
-    config::Config,
+    config::{Config, MountStage},
+    output,
 };
 use mimalloc::MiMalloc;
+use mount::umount_mgr;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -60,9 +67,33 @@ fn load_final_config(cli: &Cli) -> Result<Config> {
 }
 
 fn main() -> Result<()> {
-    // [Change] Create RUN_DIR immediately as it now hosts critical state files (boot_counter)
-    utils::ensure_dir_exists(defs::RUN_DIR)
-        .with_context(|| format!("Failed to create run directory: {}", defs::RUN_DIR))?;
+    let cli = match Cli::try_parse_compat() {
+        Ok(cli) => cli,
+        // A usage error comes before the flag is parsed, so it is looked for by hand.
+        Err(e) if e.use_stderr() && std::env::args().any(|a| a == "--json-errors") => {
+            output::print_error(&e.into());
+            std::process::exit(2);
+        }
+        Err(e) => e.exit(),
+    };
+
+    let json_errors = cli.json_errors;
+    match run(cli) {
+        Err(e) if json_errors => {
+            output::print_error(&e);
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    // [Change] Create RUN_DIR immediately as it now hosts critical state files (daemon_state.json)
+    // Rescue runs from recovery, where /data may not be mounted yet and run/ is what it clears.
+    if !matches!(cli.command, Some(Commands::Rescue { .. })) {
+        utils::ensure_dir_exists(defs::RUN_DIR)
+            .with_context(|| format!("Failed to create run directory: {}", defs::RUN_DIR))?;
+    }
 
     let threads = std::thread::available_parallelism()
         .map(|n| n.get())
@@ -72,26 +103,139 @@ fn main() -> Result<()> {
         .num_threads(threads)
         .build_global();
 
-    let cli = Cli::parse();
-
     if let Some(command) = &cli.command {
         match command {
-            Commands::GenConfig { output } => cli_handlers::handle_gen_config(output)?,
+            Commands::GenConfig { file } => cli_handlers::handle_gen_config(&cli, file)?,
+            Commands::ValidateConfig { path } => {
+                cli_handlers::handle_validate_config(&cli, path.as_deref())?
+            }
             Commands::ShowConfig => cli_handlers::handle_show_config(&cli)?,
             Commands::SaveConfig { payload } => cli_handlers::handle_save_config(payload)?,
             Commands::SaveModuleRules { module, payload } => {
                 cli_handlers::handle_save_module_rules(module, payload)?
             }
-            Commands::Modules => cli_handlers::handle_modules(&cli)?,
-            Commands::Conflicts => cli_handlers::handle_conflicts(&cli)?,
-            Commands::Diagnostics => cli_handlers::handle_diagnostics(&cli)?,
+            Commands::Modules { action } => cli_handlers::handle_modules(&cli, action.as_ref())?,
+            Commands::Conflicts { action, module } => {
+                cli_handlers::handle_conflicts(&cli, action.as_ref(), module.as_deref())?
+            }
+            Commands::Diagnostics {
+                fix_privapp,
+                summary,
+            } => cli_handlers::handle_diagnostics(&cli, *fix_privapp, *summary)?,
+            Commands::Lint { module } => cli_handlers::handle_lint(&cli, module.as_deref())?,
+            Commands::Suppress { rule, remove } => {
+                cli_handlers::handle_suppress(&cli, rule, *remove)?
+            }
+            Commands::Capabilities => cli_handlers::handle_capabilities(&cli)?,
+            Commands::CompatReport => cli_handlers::handle_compat_report(&cli)?,
+            Commands::Remount { module } => cli_handlers::handle_remount(&cli, module)?,
+            Commands::Unmount { module } => cli_handlers::handle_unmount(&cli, module)?,
+            Commands::BootCompleted => cli_handlers::handle_boot_completed(&cli)?,
+            Commands::CaptureNextBoot { cancel } => {
+                cli_handlers::handle_capture_next_boot(*cancel)?
+            }
+            Commands::Daemon => {
+                utils::init_logging().context("Failed to initialize logging")?;
+                let config = load_final_config(&cli)?;
+                config.apply_log_settings();
+                daemon::serve(&config)?
+            }
+            Commands::Watch => {
+                utils::init_logging().context("Failed to initialize logging")?;
+                let config = load_final_config(&cli)?;
+                config.apply_log_settings();
+                watchdog::watch(&config)?
+            }
+            Commands::Ctl { payload } => cli_handlers::handle_ctl(payload)?,
+            Commands::Plan { action } => cli_handlers::handle_plan(&cli, action)?,
+            Commands::Uninstall { purge, yes } => {
+                cli_handlers::handle_uninstall(&cli, *purge, *yes)?
+            }
+            Commands::Rescue { disable_all } => cli_handlers::handle_rescue(&cli, *disable_all)?,
+            Commands::RollbackModules { live, dry_run } => {
+                cli_handlers::handle_rollback_modules(&cli, *live, *dry_run)?
+            }
+            Commands::Metrics => cli_handlers::handle_metrics()?,
+            Commands::Maintenance => cli_handlers::handle_maintenance(&cli)?,
+            Commands::Doctor => cli_handlers::handle_doctor(&cli)?,
+            Commands::Info => cli_handlers::handle_info(&cli)?,
+            Commands::Logs { tail, level } => cli_handlers::handle_logs(&cli, *tail, *level),
+            Commands::StealthTest { sweep } => cli_handlers::handle_stealth_test(&cli, *sweep)?,
+            Commands::Namespaces { replay } => cli_handlers::handle_namespaces(&cli, *replay)?,
+            Commands::Setprop { name, value } => utils::set_property(name, value)?,
+            Commands::Sepolicy { dry_run } => cli_handlers::handle_sepolicy(&cli, *dry_run)?,
+            Commands::Winnow { action } => cli_handlers::handle_winnow(&cli, action)?,
+            Commands::Storage { action, history } => {
+                cli_handlers::handle_storage(&cli, action.as_ref(), *history)?
+            }
+            Commands::Snapshot { action } => cli_handlers::handle_snapshot(&cli, action)?,
+            Commands::Webui { action } => cli_handlers::handle_webui(action)?,
+            Commands::Profile { action } => cli_handlers::handle_profile(&cli, action)?,
+            Commands::Debloat { action } => cli_handlers::handle_debloat(&cli, action)?,
+            Commands::Rw { action } => cli_handlers::handle_rw(&cli, action)?,
+            Commands::ExportModule {
+                module,
+                file,
+                id,
+                name,
+                ..
+            } => cli_handlers::handle_export_module(
+                &cli,
+                module.as_deref(),
+                file,
+                id.as_deref(),
+                name.as_deref(),
+            )?,
+            Commands::Integrity { action } => {
+                cli_handlers::handle_integrity(&cli, action.as_ref())?
+            }
+            Commands::Harden => cli_handlers::handle_harden(),
+            Commands::Install {
+                file,
+                force,
+                dry_run,
+            } => cli_handlers::handle_install(&cli, file, *force, *dry_run)?,
+            Commands::Denylist { action } => cli_handlers::handle_denylist(action)?,
             Commands::Poaceae { target, action } => cli_handlers::handle_poaceae(target, action)?,
         }
 
         return Ok(());
     }
 
+    let capture = Capture::begin();
+    match &capture {
+        Some(capture) => {
+            utils::init_logging_with(core::capture::LOG_LEVEL, Some(&capture.log_path()))
+        }
+        None => utils::init_logging(),
+    }
+    .context("Failed to initialize logging")?;
+
     let mut config = load_final_config(&cli)?;
+    config.apply_log_settings();
+
+    let config_path = cli
+        .config
+        .as_deref()
+        .unwrap_or(Path::new(defs::CONFIG_FILE));
+    // The service stage is the second invocation of the same boot.
+    let first_stage = cli.stage != Some(MountStage::Service);
+
+    // Checked before the boot counter: a boot the user asked to keep clean is not a failed one.
+    if first_stage && let Some(trigger) = safe_mode::detect() {
+        return safe_mode::enter(&config, trigger);
+    }
+    if !first_stage && safe_mode::active() {
+        log::warn!("!! Safe mode: skipping service stage mounts");
+        return Ok(());
+    }
+
+    if first_stage && recovery::guard_boot(&config, config_path) {
+        config = load_final_config(&cli)?;
+    }
+    if first_stage {
+        integrity::init(config.integrity);
+    }
 
     if utils::check_zygisksu_enforce_status() {
         if config.allow_umount_coexistence {
@@ -105,8 +249,6 @@ fn main() -> Result<()> {
         }
     }
 
-    utils::init_logging().context("Failed to initialize logging")?;
-
     let camouflage_name = utils::random_kworker_name();
 
     if let Err(e) = utils::camouflage_process(&camouflage_name) {
@@ -130,12 +272,56 @@ fn main() -> Result<()> {
     let mnt_base = PathBuf::from(&config.hybrid_mnt_dir);
     let img_path = PathBuf::from(defs::MODULES_IMG_FILE);
 
-    /*if let Err(e) = granary::create_snapshot(&config, "Boot Backup", "Automatic Pre-Mount") {
-        log::warn!("Backup: Failed to create boot snapshot: {}", e);
-    }*/
+    let boot_snapshot = first_stage
+        .then(|| {
+            granary::create_snapshot(
+                &config,
+                "Boot Backup",
+                "Automatic Pre-Mount",
+                config.backup.deep,
+            )
+            .inspect_err(|e| log::warn!("Backup: Failed to create boot snapshot: {:#}", e))
+            .ok()
+        })
+        .flatten();
+
+    if first_stage {
+        umount_mgr::reset_registered();
+    }
+    progress::init();
+    let result = run_mount_sequence(config, cli.stage, &mnt_base, &img_path);
+    if result.is_err() {
+        progress::report(Phase::Failed);
+    }
+    if let Err(e) = trace::save(cli.stage) {
+        log::warn!("Failed to write boot trace: {:#}", e);
+    }
+
+    // Promoted to last-good once Android reports boot-completed.
+    if result.is_ok()
+        && let Some(meta) = &boot_snapshot
+    {
+        recovery::set_boot_snapshot(&meta.id);
+    }
+
+    if let Some(capture) = capture {
+        match capture.finish() {
+            Ok(bundle) => log::info!(">> Boot capture saved to {}", bundle.display()),
+            Err(e) => log::warn!("Failed to finish boot capture: {:#}", e),
+        }
+    }
+
+    result
+}
 
-    MountController::new(config)
-        .init_storage(&mnt_base, &img_path)
+fn run_mount_sequence(
+    config: Config,
+    stage: Option<MountStage>,
+    mnt_base: &Path,
+    img_path: &Path,
+) -> Result<()> {
+    MountController::new(config, stage)
+        .init_storage(mnt_base, img_path)
         .context("Failed to initialize storage")?
         .scan_and_sync()
         .context("Failed to scan and sync modules")?
@@ -0,0 +1,137 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountOp {
+    Overlay,
+    Bind,
+    Move,
+    Tmpfs,
+    Image,
+    Remount,
+    Unmount,
+    Sync,
+}
+
+impl fmt::Display for MountOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Overlay => "overlay",
+            Self::Bind => "bind",
+            Self::Move => "move",
+            Self::Tmpfs => "tmpfs",
+            Self::Image => "image",
+            Self::Remount => "remount",
+            Self::Unmount => "unmount",
+            Self::Sync => "sync",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MountFailure {
+    pub op: MountOp,
+    pub target: String,
+    pub message: String,
+    #[serde(default)]
+    pub hint: Option<String>,
+}
+
+impl MountFailure {
+    pub fn new(op: MountOp, target: impl Into<String>, err: &anyhow::Error) -> Self {
+        Self {
+            op,
+            target: target.into(),
+            message: format!("{:#}", err),
+            hint: hint_for(op, err).map(str::to_string),
+        }
+    }
+}
+
+pub fn errno_of(err: &anyhow::Error) -> Option<i32> {
+    err.chain().find_map(|cause| {
+        if let Some(errno) = cause.downcast_ref::<rustix::io::Errno>() {
+            return Some(errno.raw_os_error());
+        }
+        if let Some(errno) = cause.downcast_ref::<nix::errno::Errno>() {
+            return Some(*errno as i32);
+        }
+        cause
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io_err| io_err.raw_os_error())
+    })
+}
+
+pub fn hint(op: MountOp, errno: i32) -> Option<&'static str> {
+    let hint = match (op, errno) {
+        (MountOp::Overlay, libc::EINVAL) => {
+            "kernel rejected the overlay options (kernel lacks overlay redirect_dir/index \
+             support, or a lowerdir is already an overlay)"
+        }
+        (MountOp::Overlay, libc::ENOSPC) => "no space left for the overlay upperdir/workdir",
+        (MountOp::Overlay, libc::EROFS) => {
+            "overlay upperdir/workdir lives on a read-only filesystem"
+        }
+        (MountOp::Overlay, libc::ENOTDIR) => "a lowerdir or the mount target is not a directory",
+        (MountOp::Overlay, libc::EXDEV) => "overlay upperdir and workdir must share one filesystem",
+        (MountOp::Overlay, libc::ENODEV) => "kernel has no overlayfs support",
+        (MountOp::Overlay, libc::ENOENT) => "a lowerdir disappeared before mounting",
+        (MountOp::Bind | MountOp::Move, libc::EINVAL) => {
+            "target is not mountable (missing mount point or unsupported propagation)"
+        }
+        (MountOp::Bind | MountOp::Move, libc::ENOTDIR) => {
+            "file/directory type mismatch between module content and the stock path"
+        }
+        (MountOp::Bind | MountOp::Move, libc::EROFS) => {
+            "target filesystem is read-only; a tmpfs skeleton is required for new entries"
+        }
+        (MountOp::Bind | MountOp::Move, libc::EXDEV) => {
+            "cannot move a mount across mount namespaces or shared peer groups"
+        }
+        (MountOp::Bind | MountOp::Move, libc::ENOSPC) => {
+            "mount table is full (fs.mount-max reached)"
+        }
+        (MountOp::Tmpfs, libc::ENOSPC) => "tmpfs is out of memory-backed space",
+        (MountOp::Tmpfs, libc::EINVAL) => "kernel rejected the tmpfs mount options",
+        (MountOp::Tmpfs, libc::ENODEV) => "kernel has no tmpfs support",
+        (MountOp::Image, libc::ENOSPC) => {
+            "modules image is out of space — raise it with `meta-hybrid storage resize <size>`"
+        }
+        (MountOp::Image, libc::EINVAL) => {
+            "image is not a valid filesystem or loop device setup failed"
+        }
+        (MountOp::Image, libc::EROFS) => {
+            "image was mounted read-only (filesystem errors? run e2fsck)"
+        }
+        (MountOp::Image, libc::ENODEV) => "kernel does not support the image filesystem",
+        (MountOp::Remount, libc::EINVAL) => "target is not a mount point",
+        (MountOp::Remount, libc::EROFS) => "underlying filesystem cannot be made writable",
+        (MountOp::Unmount, libc::EINVAL) => "target is not a mount point",
+        (MountOp::Unmount, libc::EBUSY) => "target is still busy",
+        (MountOp::Sync, libc::ENOSPC) => "storage is out of space while syncing modules",
+        (MountOp::Sync, libc::EROFS) => "storage backend is mounted read-only",
+        (MountOp::Sync, libc::EXDEV) => {
+            "staging and storage directories are on different filesystems"
+        }
+        (_, libc::EPERM | libc::EACCES) => "permission denied (SELinux policy or missing root)",
+        _ => return None,
+    };
+    Some(hint)
+}
+
+pub fn hint_for(op: MountOp, err: &anyhow::Error) -> Option<&'static str> {
+    errno_of(err).and_then(|errno| hint(op, errno))
+}
+
+pub fn explain(op: MountOp, err: &anyhow::Error) -> String {
+    match hint_for(op, err) {
+        Some(hint) => format!("{:#} ({})", err, hint),
+        None => format!("{:#}", err),
+    }
+}
@@ -3,30 +3,86 @@
 mod utils;
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
-    sync::atomic::AtomicU32,
+    sync::{
+        Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
 };
 
 use anyhow::{Context, Result, bail};
+use rayon::prelude::*;
 use rustix::mount::{
-    MountFlags, MountPropagationFlags, UnmountFlags, mount, mount_bind, mount_change, mount_move,
-    mount_remount, unmount,
+    MountFlags, MountPropagationFlags, UnmountFlags, mount_change, mount_move, mount_remount,
+    unmount,
 };
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use crate::mount::umount_mgr::{self, send_umountable};
 use crate::{
     mount::{
-        magic_mount::utils::{clone_symlink, collect_module_files, mount_mirror},
+        magic_mount::utils::{
+            apply_pins, clone_symlink, collect_module_files, module_root, mount_mirror,
+            strip_protected,
+        },
         node::{Node, NodeFileType},
     },
+    sys::mount::{bind_mount, mount_tmpfs},
     utils::ensure_dir_exists,
 };
 
 static MOUNTED_FILES: AtomicU32 = AtomicU32::new(0);
 static MOUNTED_SYMBOLS_FILES: AtomicU32 = AtomicU32::new(0);
+static SKIP_UMOUNT_ROOTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static MOUNTED_ROOTS: Mutex<Vec<MountedRoot>> = Mutex::new(Vec::new());
+
+// One of the outermost mounts magic mount leaves behind, a tmpfs over a directory or a module file
+// bound straight over a stock one. Every other mount it makes sits below one of these.
+pub struct MountedRoot {
+    pub target: PathBuf,
+    // The bound module file; None for a tmpfs.
+    pub source: Option<PathBuf>,
+    pub module_paths: Vec<PathBuf>,
+}
+
+fn module_paths(node: &Node) -> Vec<PathBuf> {
+    fn collect(node: &Node, paths: &mut Vec<PathBuf>) {
+        paths.extend(node.module_path.clone());
+        for child in node.children.values() {
+            collect(child, paths);
+        }
+    }
+
+    let mut paths = Vec::new();
+    collect(node, &mut paths);
+    paths
+}
+
+fn record_root(target: &Path, source: Option<&Path>, module_paths: Vec<PathBuf>) {
+    if let Ok(mut roots) = MOUNTED_ROOTS.lock() {
+        roots.push(MountedRoot {
+            target: target.to_path_buf(),
+            source: source.map(Path::to_path_buf),
+            module_paths,
+        });
+    }
+}
+
+pub fn take_mounted_roots() -> Vec<MountedRoot> {
+    MOUNTED_ROOTS
+        .lock()
+        .map(|mut roots| std::mem::take(&mut *roots))
+        .unwrap_or_default()
+}
+
+fn keeps_mounted(node: &Node, roots: &[PathBuf]) -> bool {
+    node.module_path
+        .as_ref()
+        .is_some_and(|p| roots.iter().any(|r| p.starts_with(r)))
+        || node.children.values().any(|c| keeps_mounted(c, roots))
+}
 
 struct MagicMount {
     node: Node,
@@ -86,8 +142,7 @@ impl MagicMount {
                     self.work_dir_path.display(),
                 )
             })?;
-            let mounted = MOUNTED_SYMBOLS_FILES.load(std::sync::atomic::Ordering::Relaxed) + 1;
-            MOUNTED_SYMBOLS_FILES.store(mounted, std::sync::atomic::Ordering::Relaxed);
+            MOUNTED_SYMBOLS_FILES.fetch_add(1, Ordering::Relaxed);
             Ok(())
         } else {
             bail!("cannot mount root symlink {}!", self.path.display());
@@ -114,7 +169,7 @@ impl MagicMount {
             self.work_dir_path.display()
         );
 
-        mount_bind(module_path, target).with_context(|| {
+        bind_mount(module_path, target, false).with_context(|| {
             #[cfg(any(target_os = "linux", target_os = "android"))]
             if self.umount {
                 let _ = send_umountable(target);
@@ -129,9 +184,11 @@ impl MagicMount {
         if let Err(e) = mount_remount(target, MountFlags::RDONLY | MountFlags::BIND, "") {
             log::warn!("make file {} ro: {e:#?}", target.display());
         }
+        if !self.has_tmpfs {
+            record_root(&self.path, Some(module_path), vec![module_path.clone()]);
+        }
 
-        let mounted = MOUNTED_FILES.load(std::sync::atomic::Ordering::Relaxed) + 1;
-        MOUNTED_FILES.store(mounted, std::sync::atomic::Ordering::Relaxed);
+        MOUNTED_FILES.fetch_add(1, Ordering::Relaxed);
         Ok(())
     }
 
@@ -171,12 +228,24 @@ impl MagicMount {
         }
         let has_tmpfs = tmpfs || self.has_tmpfs;
 
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let keep_mounted = tmpfs
+            && SKIP_UMOUNT_ROOTS
+                .lock()
+                .is_ok_and(|roots| keeps_mounted(&self.node, &roots));
+
         if has_tmpfs {
             utils::tmpfs_skeleton(&self.path, &self.work_dir_path, &self.node)?;
         }
+        // Taken before mounting the children, which consumes them.
+        let root_modules = if tmpfs {
+            module_paths(&self.node)
+        } else {
+            Vec::new()
+        };
 
         if tmpfs {
-            mount_bind(&self.work_dir_path, &self.work_dir_path).with_context(|| {
+            bind_mount(&self.work_dir_path, &self.work_dir_path, false).with_context(|| {
                 format!(
                     "creating tmpfs for {} at {}",
                     self.path.display(),
@@ -250,9 +319,10 @@ impl MagicMount {
             if let Err(e) = mount_change(&self.path, MountPropagationFlags::PRIVATE) {
                 log::warn!("make dir {} private: {e:#?}", self.path.display());
             }
+            record_root(&self.path, None, root_modules);
 
             #[cfg(any(target_os = "linux", target_os = "android"))]
-            if self.umount {
+            if self.umount && !keep_mounted {
                 let _ = send_umountable(&self.path);
             }
         }
@@ -300,36 +370,90 @@ impl MagicMount {
     }
 }
 
+// Top-level partitions never share a mount point, so each subtree gets its own worker and a
+// failure in one of them leaves the others mounted.
+fn mount_partitions(
+    root: &Node,
+    tmp_dir: &Path,
+    #[cfg(any(target_os = "linux", target_os = "android"))] umount: bool,
+) -> Vec<(PathBuf, anyhow::Error)> {
+    root.children
+        .par_iter()
+        .filter(|(name, node)| {
+            let real_path = Path::new("/").join(name);
+            let mountable = !node.skip
+                && node.file_type == NodeFileType::Directory
+                && real_path.is_dir()
+                && !real_path.is_symlink();
+            if !mountable {
+                log::error!("cannot create tmpfs on /, ignore: {name}");
+            }
+            mountable
+        })
+        .filter_map(|(name, node)| {
+            let target = Path::new("/").join(name);
+            log::debug!("magic mount worker started for {}", target.display());
+            MagicMount::new(
+                node,
+                Path::new("/"),
+                tmp_dir,
+                false,
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                umount,
+            )
+            .do_mount()
+            .with_context(|| format!("magic mount {}", target.display()))
+            .err()
+            .map(|e| (target, e))
+        })
+        .collect()
+}
+
+// Returns the partitions whose subtree failed to mount.
+#[allow(clippy::too_many_arguments)]
 pub fn magic_mount<P>(
     tmp_path: P,
     module_dir: &Path,
+    image_roots: &HashMap<String, PathBuf>,
     mount_source: &str,
     extra_partitions: &[String],
-    need_id: HashSet<String>,
+    need_ids: &[String],
+    skip_umount_ids: &HashSet<String>,
+    pins: &HashMap<String, String>,
+    protected: &[PathBuf],
     #[cfg(any(target_os = "linux", target_os = "android"))] umount: bool,
     #[cfg(not(any(target_os = "linux", target_os = "android")))] _umount: bool,
-) -> Result<()>
+) -> Result<Vec<(PathBuf, anyhow::Error)>>
 where
     P: AsRef<Path>,
 {
-    if let Some(root) = collect_module_files(module_dir, extra_partitions, need_id)? {
+    take_mounted_roots();
+    if let Ok(mut roots) = SKIP_UMOUNT_ROOTS.lock() {
+        *roots = skip_umount_ids
+            .iter()
+            .map(|id| module_root(module_dir, image_roots, id))
+            .collect();
+    }
+
+    if let Some(mut root) =
+        collect_module_files(module_dir, image_roots, extra_partitions, need_ids)?
+    {
+        apply_pins(&mut root, module_dir, image_roots, pins);
+        strip_protected(&mut root, protected);
         log::debug!("collected: {root:?}");
         let tmp_root = tmp_path.as_ref();
         let tmp_dir = tmp_root.join("workdir");
         ensure_dir_exists(&tmp_dir)?;
 
-        mount(mount_source, &tmp_dir, "tmpfs", MountFlags::empty(), None).context("mount tmp")?;
+        mount_tmpfs(&tmp_dir, mount_source).context("mount tmp")?;
         mount_change(&tmp_dir, MountPropagationFlags::PRIVATE).context("make tmp private")?;
 
-        let ret = MagicMount::new(
+        let failures = mount_partitions(
             &root,
-            Path::new("/"),
-            tmp_dir.as_path(),
-            false,
+            &tmp_dir,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             umount,
-        )
-        .do_mount();
+        );
 
         if let Err(e) = unmount(&tmp_dir, UnmountFlags::DETACH) {
             log::error!("failed to unmount tmp {e}");
@@ -338,12 +462,12 @@ where
         umount_mgr::commit()?;
         fs::remove_dir(tmp_dir).ok();
 
-        let mounted_symbols = MOUNTED_SYMBOLS_FILES.load(std::sync::atomic::Ordering::Relaxed);
-        let mounted_files = MOUNTED_FILES.load(std::sync::atomic::Ordering::Relaxed);
+        let mounted_symbols = MOUNTED_SYMBOLS_FILES.load(Ordering::Relaxed);
+        let mounted_files = MOUNTED_FILES.load(Ordering::Relaxed);
         log::info!("mounted files: {mounted_files}, mounted symlinks: {mounted_symbols}");
-        ret
+        Ok(failures)
     } else {
         log::info!("no modules to mount, skipping!");
-        Ok(())
+        Ok(Vec::new())
     }
 }
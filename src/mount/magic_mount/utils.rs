@@ -1,21 +1,21 @@
 // Copyright 2026 https://github.com/Tools-cx-app/meta-magic_mount
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::{self, DirEntry, Metadata, create_dir, create_dir_all, read_link},
     os::unix::fs::{MetadataExt, symlink},
     path::{Path, PathBuf},
 };
 
 use anyhow::{Result, bail};
-use rustix::{
-    fs::{Gid, Mode, Uid, chmod, chown},
-    mount::mount_bind,
-};
+use rustix::fs::{Gid, Mode, Uid, chmod, chown};
 
 use crate::{
-    defs::{DISABLE_FILE_NAME, REMOVE_FILE_NAME, SKIP_MOUNT_FILE_NAME},
-    mount::node::Node,
+    defs::{
+        APEX_DIR_NAME, DISABLE_FILE_NAME, DLKM_PARTITIONS, REMOVE_FILE_NAME, SKIP_MOUNT_FILE_NAME,
+    },
+    mount::node::{Node, NodeFileType},
+    sys::mount::bind_mount,
     utils::{lgetfilecon, lsetfilecon, validate_module_id},
 };
 
@@ -59,78 +59,126 @@ where
     Ok(())
 }
 
+const MAX_MIRROR_DEPTH: usize = 64;
+const MAX_MIRROR_ENTRIES: usize = 200_000;
+
+// Iterative so crafted trees cannot exhaust the stack; a bind mount looping a directory back onto
+// an ancestor shows up as a repeated dev/inode pair.
 pub fn mount_mirror<P>(path: P, work_dir_path: P, entry: &DirEntry) -> Result<()>
 where
     P: AsRef<Path>,
 {
-    let path = path.as_ref().join(entry.file_name());
-    let work_dir_path = work_dir_path.as_ref().join(entry.file_name());
-    let file_type = entry.file_type()?;
-
-    if file_type.is_file() {
-        log::debug!(
-            "mount mirror file {} -> {}",
-            path.display(),
-            work_dir_path.display()
-        );
-        fs::File::create(&work_dir_path)?;
-        mount_bind(&path, &work_dir_path)?;
-    } else if file_type.is_dir() {
-        log::debug!(
-            "mount mirror dir {} -> {}",
-            path.display(),
-            work_dir_path.display()
-        );
-        create_dir(&work_dir_path)?;
-        let metadata = entry.metadata()?;
-        chmod(&work_dir_path, Mode::from_raw_mode(metadata.mode()))?;
-        chown(
-            &work_dir_path,
-            Some(Uid::from_raw(metadata.uid())),
-            Some(Gid::from_raw(metadata.gid())),
-        )?;
-        lsetfilecon(&work_dir_path, lgetfilecon(&path)?.as_str())?;
-        for entry in path.read_dir()?.flatten() {
-            mount_mirror(&path, &work_dir_path, &entry)?;
-        }
-    } else if file_type.is_symlink() {
-        log::debug!(
-            "create mirror symlink {} -> {}",
-            path.display(),
-            work_dir_path.display()
-        );
-        clone_symlink(&path, &work_dir_path)?;
+    let root = path.as_ref().join(entry.file_name());
+    let mut stack = vec![(
+        root.clone(),
+        work_dir_path.as_ref().join(entry.file_name()),
+        0,
+    )];
+    let mut visited = HashSet::new();
+    let mut entries = 0;
+
+    while let Some((path, work_dir_path, depth)) = stack.pop() {
+        entries += 1;
+        if entries > MAX_MIRROR_ENTRIES {
+            bail!(
+                "mirror of {} exceeds {} entries, giving up on this subtree",
+                root.display(),
+                MAX_MIRROR_ENTRIES
+            );
+        }
+
+        let metadata = fs::symlink_metadata(&path)?;
+        let file_type = metadata.file_type();
+
+        if file_type.is_file() {
+            log::debug!(
+                "mount mirror file {} -> {}",
+                path.display(),
+                work_dir_path.display()
+            );
+            fs::File::create(&work_dir_path)?;
+            bind_mount(&path, &work_dir_path, false)?;
+        } else if file_type.is_dir() {
+            if depth >= MAX_MIRROR_DEPTH {
+                bail!(
+                    "mirror of {} nests deeper than {} levels at {}",
+                    root.display(),
+                    MAX_MIRROR_DEPTH,
+                    path.display()
+                );
+            }
+            if !visited.insert((metadata.dev(), metadata.ino())) {
+                bail!(
+                    "directory cycle at {} while mirroring {}",
+                    path.display(),
+                    root.display()
+                );
+            }
+
+            log::debug!(
+                "mount mirror dir {} -> {}",
+                path.display(),
+                work_dir_path.display()
+            );
+            create_dir(&work_dir_path)?;
+            chmod(&work_dir_path, Mode::from_raw_mode(metadata.mode()))?;
+            chown(
+                &work_dir_path,
+                Some(Uid::from_raw(metadata.uid())),
+                Some(Gid::from_raw(metadata.gid())),
+            )?;
+            lsetfilecon(&work_dir_path, lgetfilecon(&path)?.as_str())?;
+            for child in path.read_dir()?.flatten() {
+                stack.push((
+                    child.path(),
+                    work_dir_path.join(child.file_name()),
+                    depth + 1,
+                ));
+            }
+        } else if file_type.is_symlink() {
+            log::debug!(
+                "create mirror symlink {} -> {}",
+                path.display(),
+                work_dir_path.display()
+            );
+            clone_symlink(&path, &work_dir_path)?;
+        }
     }
 
     Ok(())
 }
 
+// A module packed into an image is read from where the image is mounted.
+pub fn module_root(module_dir: &Path, image_roots: &HashMap<String, PathBuf>, id: &str) -> PathBuf {
+    image_roots
+        .get(id)
+        .cloned()
+        .unwrap_or_else(|| module_dir.join(id))
+}
+
 pub fn collect_module_files(
     module_dir: &Path,
+    image_roots: &HashMap<String, PathBuf>,
     extra_partitions: &[String],
-    need_id: HashSet<String>,
+    need_ids: &[String],
 ) -> Result<Option<Node>> {
     let mut root = Node::new_root("");
     let mut system = Node::new_root("system");
-    let module_root = module_dir;
+    let mut dlkm_nodes: HashMap<&str, Node> = HashMap::new();
     let mut has_file = HashSet::new();
 
-    log::debug!("begin collect module files: {}", module_root.display());
+    log::debug!("begin collect module files: {}", module_dir.display());
 
-    for entry in module_root.read_dir()?.flatten() {
-        if !entry.file_type()?.is_dir() {
+    // Earlier modules win file collisions, so need_ids arrives highest priority first.
+    for id in need_ids {
+        let module_path = module_root(module_dir, image_roots, id);
+        if !module_path.is_dir() {
             continue;
         }
 
-        let id = entry.file_name().to_str().unwrap().to_string();
         log::debug!("processing new module: {id}");
 
-        if !need_id.contains(&id) {
-            log::debug!("module {id} was blocked.");
-            continue;
-        }
-
-        let prop = entry.path().join("module.prop");
+        let prop = module_path.join("module.prop");
         if !prop.exists() {
             log::debug!("skipped module {id}, because not found module.prop");
             continue;
@@ -144,9 +192,9 @@ pub fn collect_module_files(
             }
         }
 
-        if entry.path().join(DISABLE_FILE_NAME).exists()
-            || entry.path().join(REMOVE_FILE_NAME).exists()
-            || entry.path().join(SKIP_MOUNT_FILE_NAME).exists()
+        if module_path.join(DISABLE_FILE_NAME).exists()
+            || module_path.join(REMOVE_FILE_NAME).exists()
+            || module_path.join(SKIP_MOUNT_FILE_NAME).exists()
         {
             log::debug!("skipped module {id}, due to disable/remove/skip_mount");
             continue;
@@ -155,10 +203,22 @@ pub fn collect_module_files(
         let mut modified = false;
         let mut partitions = HashSet::new();
         partitions.insert("system".to_string());
-        partitions.extend(extra_partitions.iter().cloned());
+        // APEX overrides are bound file by file by the planner, never magic mounted. Nested
+        // partitions such as mnt/vendor/persist have no place in the /system tree either.
+        partitions.extend(
+            extra_partitions
+                .iter()
+                .filter(|p| !DLKM_PARTITIONS.iter().any(|(d, _)| d == p))
+                .filter(|p| *p != APEX_DIR_NAME && !p.contains('/'))
+                .cloned(),
+        );
 
-        for p in &partitions {
-            if entry.path().join(p).is_dir() {
+        for p in partitions
+            .iter()
+            .map(String::as_str)
+            .chain(DLKM_PARTITIONS.iter().map(|(d, _)| *d))
+        {
+            if module_path.join(p).is_dir() {
                 modified = true;
                 break;
             }
@@ -169,14 +229,27 @@ pub fn collect_module_files(
             continue;
         }
 
-        log::debug!("collecting {}", entry.path().display());
+        log::debug!("collecting {}", module_path.display());
 
         for p in partitions {
-            if !entry.path().join(&p).exists() {
+            if !module_path.join(&p).exists() {
                 continue;
             }
 
-            has_file.insert(system.collect_module_files(entry.path().join(&p))?);
+            has_file.insert(system.collect_module_files(module_path.join(&p))?);
+        }
+
+        // dlkm partitions never live under /system, so they get their own root-level node.
+        for (partition, _) in DLKM_PARTITIONS {
+            let src = module_path.join(partition);
+            if !src.is_dir() {
+                continue;
+            }
+
+            let node = dlkm_nodes
+                .entry(partition)
+                .or_insert_with(|| Node::new_root(*partition));
+            has_file.insert(node.collect_module_files(&src)?);
         }
     }
 
@@ -220,6 +293,15 @@ pub fn collect_module_files(
             }
         }
 
+        for (partition, node) in dlkm_nodes {
+            if Path::new("/").join(partition).is_dir() {
+                log::debug!("attach dlkm partition '{partition}' to root");
+                root.children.insert(partition.to_string(), node);
+            } else {
+                log::warn!("/{partition} is not a root-level mount, skipping its magic mount");
+            }
+        }
+
         root.children.insert("system".to_string(), system);
         Ok(Some(root))
     } else {
@@ -227,6 +309,108 @@ pub fn collect_module_files(
     }
 }
 
+fn find_node_mut<'a>(root: &'a mut Node, path: &Path) -> Option<&'a mut Node> {
+    let mut node = root;
+    for component in path.components() {
+        node = node
+            .children
+            .get_mut(&*component.as_os_str().to_string_lossy())?;
+    }
+    Some(node)
+}
+
+// Drops the entries that would put module content at or over a protected path, so the stock files
+// stay visible: the path's own node, or an ancestor that replaces the directory holding it.
+pub fn strip_protected(root: &mut Node, protected: &[PathBuf]) {
+    for path in protected {
+        let Ok(relative) = path.strip_prefix("/") else {
+            continue;
+        };
+        let mut candidates = vec![relative.to_path_buf()];
+        if !relative.starts_with("system") {
+            candidates.push(Path::new("system").join(relative));
+        }
+        for relative in candidates {
+            let names: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            let mut node = &mut *root;
+            for (i, name) in names.iter().enumerate() {
+                let hides = match node.children.get(name) {
+                    None => break,
+                    Some(child) => {
+                        i + 1 == names.len()
+                            || child.replace
+                            || child.file_type != NodeFileType::Directory
+                    }
+                };
+                if hides {
+                    node.children.remove(name);
+                    log::warn!(
+                        "skipped module content for protected path {}",
+                        path.display()
+                    );
+                    break;
+                }
+                let Some(child) = node.children.get_mut(name) else {
+                    break;
+                };
+                node = child;
+            }
+        }
+    }
+}
+
+// Swaps the source of a collected file for the pinned module's copy of it.
+pub fn apply_pins(
+    root: &mut Node,
+    module_dir: &Path,
+    image_roots: &HashMap<String, PathBuf>,
+    pins: &HashMap<String, String>,
+) {
+    for (path, module_id) in pins {
+        let Ok(relative) = Path::new(path).strip_prefix("/") else {
+            continue;
+        };
+        let Some(node) = find_node_mut(root, relative) else {
+            continue;
+        };
+        if node.file_type != NodeFileType::RegularFile {
+            continue;
+        }
+        let Some(current) = &node.module_path else {
+            continue;
+        };
+        let owned = image_roots
+            .iter()
+            .find_map(|(id, image)| Some((id.clone(), current.strip_prefix(image).ok()?)))
+            .or_else(|| {
+                let mut components = current.strip_prefix(module_dir).ok()?.components();
+                let owner = components.next()?.as_os_str().to_string_lossy().to_string();
+                Some((owner, components.as_path()))
+            });
+        let Some((owner, rest)) = owned else {
+            continue;
+        };
+        if owner == *module_id {
+            continue;
+        }
+
+        let pinned = module_root(module_dir, image_roots, module_id).join(rest);
+        if pinned.is_file() {
+            log::info!("Pinned {} -> {}", path, module_id);
+            node.module_path = Some(pinned);
+        } else {
+            log::warn!(
+                "Pinned {} -> {}: module does not provide this file",
+                path,
+                module_id
+            );
+        }
+    }
+}
+
 pub fn clone_symlink<S>(src: S, dst: S) -> Result<()>
 where
     S: AsRef<Path>,
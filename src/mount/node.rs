@@ -12,7 +12,10 @@ use std::{
 use anyhow::Result;
 use extattr::lgetxattr;
 
-use crate::defs::{REPLACE_DIR_FILE_NAME, REPLACE_DIR_XATTR};
+use crate::{
+    defs::{REPLACE_DIR_FILE_NAME, REPLACE_DIR_XATTR},
+    utils::is_xattr_whiteout,
+};
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub enum NodeFileType {
@@ -116,7 +119,9 @@ impl Node {
     {
         if let Ok(metadata) = entry.metadata() {
             let path = entry.path();
-            let file_type = if metadata.file_type().is_char_device() && metadata.rdev() == 0 {
+            let file_type = if (metadata.file_type().is_char_device() && metadata.rdev() == 0)
+                || is_xattr_whiteout(&path)
+            {
                 Some(NodeFileType::Whiteout)
             } else {
                 Some(NodeFileType::from(metadata.file_type()))
@@ -0,0 +1,195 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    ffi::CString,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use procfs::process::MountInfo;
+use rustix::mount::{MountFlags, UnmountFlags, mount, unmount};
+use serde::{Deserialize, Serialize};
+
+use crate::{defs, utils};
+
+// FILESYSTEM_MAX_STACK_DEPTH: an overlay may only sit on filesystems stacked one level deep.
+pub const MAX_STACK_DEPTH: usize = 2;
+
+// Safe on every kernel, including the one-page data limit of the legacy mount(2) fallback.
+pub const FALLBACK_ARG_LENGTH: usize = 3000;
+const PROBE_CEILING: usize = 64 * 1024;
+const PROBE_STEP: usize = 256;
+const PROBE_NAME_LEN: usize = 200;
+// Bumped when the probe changes, so limits measured by an older one are probed again.
+const PROBE_REVISION: u32 = 2;
+
+static ARG_LIMIT: OnceLock<usize> = OnceLock::new();
+
+#[derive(Serialize, Deserialize)]
+struct CachedLimit {
+    kernel: String,
+    limit: usize,
+    #[serde(default)]
+    revision: u32,
+}
+
+fn kernel_release() -> String {
+    fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn load_cached() -> Option<usize> {
+    let content = fs::read_to_string(defs::OVERLAY_LIMIT_FILE).ok()?;
+    let cached: CachedLimit = serde_json::from_str(&content).ok()?;
+    (cached.kernel == kernel_release() && cached.revision == PROBE_REVISION).then_some(cached.limit)
+}
+
+// Mounts an overlay with a lowerdir value of about `len` bytes through mount(2). That is the path
+// long options take: fsconfig caps string values at 256 bytes, so mount_overlayfs falls back to
+// mount(2) for them. The data may be cut at a page rather than refused, so the marker of the
+// deepest named layer has to show through for the length to count.
+fn accepts(dirs: &[String], len: usize, target: &Path) -> bool {
+    let mut value = String::new();
+    let mut deepest = None;
+    for (i, dir) in dirs.iter().enumerate() {
+        if value.len() + dir.len() + 1 > len {
+            break;
+        }
+        if !value.is_empty() {
+            value.push(':');
+        }
+        value.push_str(dir);
+        deepest = Some(i);
+    }
+    let Some(deepest) = deepest else {
+        return false;
+    };
+
+    let Ok(data) = CString::new(format!("lowerdir={value}")) else {
+        return false;
+    };
+    if mount(
+        "overlay",
+        target,
+        "overlay",
+        MountFlags::RDONLY,
+        Some(data.as_c_str()),
+    )
+    .is_err()
+    {
+        return false;
+    }
+    let complete = target.join(deepest.to_string()).exists();
+    let _ = unmount(target, UnmountFlags::DETACH);
+    complete
+}
+
+fn probe(root: &Path) -> usize {
+    let target = root.join("target");
+    if utils::ensure_dir_exists(&target).is_err() {
+        return FALLBACK_ARG_LENGTH;
+    }
+    let name_len = root.as_os_str().len() + PROBE_NAME_LEN + 2;
+    let dirs: Vec<String> = (0..PROBE_CEILING / name_len + 1)
+        .map(|i| {
+            (
+                i,
+                root.join(format!("{:0>width$}", i, width = PROBE_NAME_LEN)),
+            )
+        })
+        .take_while(|(i, d)| {
+            utils::ensure_dir_exists(d).is_ok() && fs::write(d.join(i.to_string()), "").is_ok()
+        })
+        .map(|(_, d)| d.to_string_lossy().to_string())
+        .collect();
+
+    if !accepts(&dirs, FALLBACK_ARG_LENGTH, &target) {
+        return FALLBACK_ARG_LENGTH;
+    }
+    if accepts(&dirs, PROBE_CEILING, &target) {
+        return PROBE_CEILING;
+    }
+
+    let (mut lo, mut hi) = (FALLBACK_ARG_LENGTH, PROBE_CEILING);
+    while hi - lo > PROBE_STEP {
+        let mid = lo + (hi - lo) / 2;
+        if accepts(&dirs, mid, &target) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+// The longest lowerdir option this kernel takes, probed once per kernel release.
+pub fn arg_limit() -> usize {
+    *ARG_LIMIT.get_or_init(|| {
+        if let Some(limit) = load_cached() {
+            return limit;
+        }
+
+        let root = PathBuf::from(defs::RUN_DIR).join("overlay_probe");
+        let limit = probe(&root);
+        let _ = fs::remove_dir_all(&root);
+        log::info!("Overlay lowerdir option limit: {} bytes", limit);
+
+        let cached = CachedLimit {
+            kernel: kernel_release(),
+            limit,
+            revision: PROBE_REVISION,
+        };
+        if let Err(e) = serde_json::to_string(&cached)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| utils::atomic_write(defs::OVERLAY_LIMIT_FILE, json))
+        {
+            log::warn!("Failed to cache overlay option limit: {:#}", e);
+        }
+        limit
+    })
+}
+
+// How many stacked filesystems already sit under `path`, following overlay lowerdirs.
+pub fn stack_depth(path: &Path, mountinfo: &[MountInfo]) -> usize {
+    stack_depth_bounded(path, mountinfo, MAX_STACK_DEPTH + 1)
+}
+
+fn stack_depth_bounded(path: &Path, mountinfo: &[MountInfo], budget: usize) -> usize {
+    // Later entries shadow earlier ones on the same mount point.
+    let Some((index, mount)) = mountinfo
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| path.starts_with(&m.mount_point))
+        .max_by_key(|(_, m)| m.mount_point.as_os_str().len())
+    else {
+        return 0;
+    };
+    if mount.fs_type != "overlay" {
+        return 0;
+    }
+    if budget == 0 {
+        return 1;
+    }
+
+    let lowers = mount
+        .super_options
+        .get("lowerdir")
+        .cloned()
+        .flatten()
+        .unwrap_or_default();
+    // A lowerdir is resolved as it was when this overlay was mounted, often over itself.
+    1 + lowers
+        .split(':')
+        .filter(|l| !l.is_empty())
+        .map(|l| stack_depth_bounded(Path::new(l), &mountinfo[..index], budget - 1))
+        .max()
+        .unwrap_or(0)
+}
+
+// For reports outside the boot path: never mounts anything to find out.
+pub fn cached_arg_limit() -> usize {
+    load_cached().unwrap_or(FALLBACK_ARG_LENGTH)
+}
@@ -4,6 +4,7 @@ use std::{
     ffi::CString,
     os::fd::AsFd,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use anyhow::{Context, Result, bail};
@@ -16,10 +17,69 @@ use rustix::{
     },
 };
 
-use crate::mount::{overlayfs::utils::umount_dir, umount_mgr::send_umountable};
+use crate::{
+    defs,
+    mount::{
+        overlayfs::{limit, utils::umount_dir},
+        umount_mgr::send_umountable,
+    },
+    sys::mount::bind_mount,
+    utils::ensure_dir_exists,
+};
 
-const MAX_LOWERDIR_COUNT: usize = 128;
-const MAX_ARG_LENGTH: usize = 3000;
+pub const MAX_LOWERDIR_COUNT: usize = 128;
+
+static STAGE_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+fn fits(dirs: &[String], lowest: &str, arg_limit: usize) -> bool {
+    dirs.len() < MAX_LOWERDIR_COUNT
+        && dirs.iter().map(|d| d.len() + 1).sum::<usize>() + lowest.len() <= arg_limit
+}
+
+// Pre-merges module layers into read-only overlays of at most `arg_limit` bytes each, so the final
+// mount only has to name the merged views. Whiteouts are consumed inside each batch and no longer
+// reach the stock partition.
+fn stage_lower_dirs(
+    lower_dirs: &[String],
+    lowest: &str,
+    arg_limit: usize,
+    mount_source: &str,
+) -> Result<Vec<String>> {
+    let mut batches: Vec<Vec<String>> = vec![Vec::new()];
+    for dir in lower_dirs {
+        let batch = batches.last_mut().expect("batches is never empty");
+        let len = batch.iter().map(|d| d.len() + 1).sum::<usize>() + dir.len();
+        if !batch.is_empty() && (len > arg_limit || batch.len() + 1 >= MAX_LOWERDIR_COUNT) {
+            batches.push(vec![dir.clone()]);
+        } else {
+            batch.push(dir.clone());
+        }
+    }
+
+    let mut staged = Vec::with_capacity(batches.len());
+    for mut batch in batches {
+        if batch.len() == 1 {
+            staged.append(&mut batch);
+            continue;
+        }
+        let stage = Path::new(defs::OVERLAY_STAGE_DIR)
+            .join(STAGE_SEQ.fetch_add(1, Ordering::Relaxed).to_string());
+        ensure_dir_exists(&stage)?;
+        let bottom = batch.pop().expect("batch has at least two layers");
+        mount_overlayfs(&batch, &bottom, None, None, &stage, mount_source)
+            .with_context(|| format!("failed to stage {} layers", batch.len() + 1))?;
+        let _ = send_umountable(&stage);
+        staged.push(stage.display().to_string());
+    }
+
+    if !fits(&staged, lowest, arg_limit) {
+        bail!(
+            "{} layers still exceed the overlay limits after staging",
+            lower_dirs.len()
+        );
+    }
+    Ok(staged)
+}
 
 pub fn mount_overlayfs(
     lower_dirs: &[String],
@@ -29,33 +89,27 @@ pub fn mount_overlayfs(
     dest: impl AsRef<Path>,
     mount_source: &str,
 ) -> Result<()> {
-    let mut valid_lower_dirs: Vec<&str> = lower_dirs
+    let arg_limit = limit::arg_limit();
+    let staged;
+    let lower_dirs = if fits(lower_dirs, lowest, arg_limit) {
+        lower_dirs
+    } else {
+        log::warn!(
+            "{} overlay layers exceed the lowerdir limits ({} layers, {} bytes), staging them",
+            lower_dirs.len() + 1,
+            MAX_LOWERDIR_COUNT,
+            arg_limit
+        );
+        staged = stage_lower_dirs(lower_dirs, lowest, arg_limit, mount_source)?;
+        &staged[..]
+    };
+
+    let valid_lower_dirs: Vec<&str> = lower_dirs
         .iter()
         .map(|s| s.as_str())
         .chain(std::iter::once(lowest))
         .collect();
-
-    if valid_lower_dirs.len() > MAX_LOWERDIR_COUNT {
-        log::warn!(
-            "Too many overlay layers ({} > {}). Truncating to prevent failure. Some modules may not load.",
-            valid_lower_dirs.len(),
-            MAX_LOWERDIR_COUNT
-        );
-        valid_lower_dirs.truncate(MAX_LOWERDIR_COUNT);
-    }
-
-    let mut lowerdir_config = valid_lower_dirs.join(":");
-
-    if lowerdir_config.len() > MAX_ARG_LENGTH {
-        log::warn!(
-            "OverlayFS lowerdir argument too long ({} bytes). Truncating...",
-            lowerdir_config.len()
-        );
-        while lowerdir_config.len() > MAX_ARG_LENGTH && valid_lower_dirs.len() > 1 {
-            valid_lower_dirs.pop();
-            lowerdir_config = valid_lower_dirs.join(":");
-        }
-    }
+    let lowerdir_config = valid_lower_dirs.join(":");
 
     log::info!(
         "mount overlayfs on {:?}, layers={}, upperdir={:?}, workdir={:?}, source={}",
@@ -118,42 +172,6 @@ pub fn mount_overlayfs(
     Ok(())
 }
 
-pub fn bind_mount(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
-    log::info!(
-        "bind mount {} -> {}",
-        from.as_ref().display(),
-        to.as_ref().display()
-    );
-    use rustix::mount::{OpenTreeFlags, open_tree};
-    match open_tree(
-        CWD,
-        from.as_ref(),
-        OpenTreeFlags::OPEN_TREE_CLOEXEC
-            | OpenTreeFlags::OPEN_TREE_CLONE
-            | OpenTreeFlags::AT_RECURSIVE,
-    ) {
-        Result::Ok(tree) => {
-            move_mount(
-                tree.as_fd(),
-                "",
-                CWD,
-                to.as_ref(),
-                MoveMountFlags::MOVE_MOUNT_F_EMPTY_PATH,
-            )?;
-        }
-        _ => {
-            mount(
-                from.as_ref(),
-                to.as_ref(),
-                "",
-                MountFlags::BIND | MountFlags::REC,
-                None,
-            )?;
-        }
-    }
-    Ok(())
-}
-
 fn mount_overlay_child(
     mount_point: &str,
     relative: &String,
@@ -165,7 +183,7 @@ fn mount_overlay_child(
         .iter()
         .any(|lower| Path::new(&format!("{lower}{relative}")).exists())
     {
-        return bind_mount(stock_root, mount_point);
+        return bind_mount(Path::new(stock_root), Path::new(mount_point), true);
     }
     if !Path::new(&stock_root).is_dir() {
         return Ok(());
@@ -192,7 +210,7 @@ fn mount_overlay_child(
         mount_source,
     ) {
         log::warn!("failed: {:#}, fallback to bind mount", e);
-        bind_mount(stock_root, mount_point)?;
+        bind_mount(Path::new(stock_root), Path::new(mount_point), true)?;
     }
     let _ = send_umountable(mount_point);
     Ok(())
@@ -72,8 +72,17 @@ impl Drop for AutoMountExt4 {
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub fn mount_ext4(source: impl AsRef<Path>, target: impl AsRef<Path>) -> Result<()> {
+    mount_image(source, target, "ext4")
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn mount_image(
+    source: impl AsRef<Path>,
+    target: impl AsRef<Path>,
+    fs_type: &str,
+) -> Result<()> {
     let status = Command::new("mount")
-        .args(["-t", "ext4", "-o", "loop,rw,noatime"])
+        .args(["-t", fs_type, "-o", "loop,rw,noatime"])
         .arg(source.as_ref())
         .arg(target.as_ref())
         .status()
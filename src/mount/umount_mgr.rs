@@ -2,27 +2,36 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashSet},
+    fs,
     path::Path,
-    sync::{LazyLock, Mutex, OnceLock},
+    sync::{LazyLock, Mutex, OnceLock, atomic::Ordering},
 };
 
 use anyhow::Result;
 use ksu::TryUmount;
 
+use crate::{defs, utils};
+
 pub static TMPFS: OnceLock<String> = OnceLock::new();
 pub static LIST: LazyLock<Mutex<TryUmount>> = LazyLock::new(|| Mutex::new(TryUmount::new()));
 static HISTORY: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
-
+// Everything ever queued this boot, kept for the denylist watcher; HISTORY is cleared per commit.
+static REGISTERED: LazyLock<Mutex<BTreeSet<String>>> =
+    LazyLock::new(|| Mutex::new(BTreeSet::new()));
 pub fn send_umountable<P>(target: P) -> Result<()>
 where
     P: AsRef<Path>,
 {
-    if !crate::utils::KSU.load(std::sync::atomic::Ordering::Relaxed) {
+    let path_str = target.as_ref().to_string_lossy().to_string();
+    if let Ok(mut registered) = REGISTERED.lock() {
+        registered.insert(path_str.clone());
+    }
+
+    if !utils::KSU.load(Ordering::Relaxed) {
         return Ok(());
     }
 
-    let path_str = target.as_ref().to_string_lossy().to_string();
     let mut history = HISTORY
         .lock()
         .map_err(|_| anyhow::anyhow!("Failed to lock history mutex"))?;
@@ -39,8 +48,36 @@ where
     Ok(())
 }
 
+// Later passes (the service stage, live remounts) add to what earlier ones recorded.
+fn save_registered() {
+    let Ok(registered) = REGISTERED.lock() else {
+        return;
+    };
+    let mut all: BTreeSet<String> = registered_targets().into_iter().collect();
+    all.extend(registered.iter().cloned());
+    if let Err(e) = serde_json::to_string(&all)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| utils::atomic_write(defs::UMOUNT_TARGETS_FILE, json))
+    {
+        log::warn!("Failed to record umount targets: {:#}", e);
+    }
+}
+
+pub fn reset_registered() {
+    let _ = fs::remove_file(defs::UMOUNT_TARGETS_FILE);
+}
+
+pub fn registered_targets() -> Vec<String> {
+    fs::read_to_string(defs::UMOUNT_TARGETS_FILE)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
 pub fn commit() -> Result<()> {
-    if !crate::utils::KSU.load(std::sync::atomic::Ordering::Relaxed) {
+    save_registered();
+
+    if !utils::KSU.load(Ordering::Relaxed) {
         return Ok(());
     }
     let mut list = LIST
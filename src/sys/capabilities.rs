@@ -0,0 +1,92 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{
+    core::storage,
+    defs,
+    sys::{
+        kmod,
+        mount::{
+            detect_mount_source, has_new_mount_api, is_filesystem_supported, resolve_dlkm_root,
+        },
+        tools,
+    },
+    utils,
+};
+
+#[derive(Debug, Serialize)]
+pub struct Engines {
+    pub overlay: bool,
+    pub magic: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HidingBackends {
+    pub try_umount: bool,
+    pub poaceae: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Subsystems {
+    pub new_mount_api: bool,
+    pub rootdir: bool,
+    pub dlkm: bool,
+    pub module_sig_enforced: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub arch: &'static str,
+    pub root_manager: String,
+    pub engines: Engines,
+    pub storage_modes: Vec<&'static str>,
+    pub hiding: HidingBackends,
+    pub subsystems: Subsystems,
+}
+
+pub fn probe() -> Capabilities {
+    let mut storage_modes = Vec::new();
+    if utils::is_overlay_xattr_supported().unwrap_or(false) {
+        storage_modes.push("tmpfs");
+    }
+    if tools::find("mkfs.ext4").is_some() && is_filesystem_supported("ext4") {
+        storage_modes.push("ext4");
+    }
+    if storage::is_f2fs_supported() {
+        storage_modes.push("f2fs");
+    }
+    if storage::is_erofs_supported() && tools::find("mkfs.erofs").is_some() {
+        storage_modes.push("erofs");
+    }
+    if storage::is_squashfs_supported() {
+        storage_modes.push("squashfs");
+    }
+
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        arch: std::env::consts::ARCH,
+        root_manager: detect_mount_source(),
+        engines: Engines {
+            overlay: is_filesystem_supported("overlay"),
+            magic: true,
+        },
+        storage_modes,
+        hiding: HidingBackends {
+            try_umount: ksu::version().is_some(),
+            poaceae: Path::new(defs::POACEAE_MOUNT_POINT).exists(),
+        },
+        subsystems: Subsystems {
+            new_mount_api: has_new_mount_api(),
+            rootdir: true,
+            dlkm: defs::DLKM_PARTITIONS
+                .iter()
+                .any(|(p, _)| resolve_dlkm_root(p).is_some()),
+            module_sig_enforced: kmod::signature_enforcement().is_some(),
+        },
+    }
+}
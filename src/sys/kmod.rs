@@ -0,0 +1,42 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, path::Path, sync::OnceLock};
+
+const SIG_ENFORCE_PARAM: &str = "/sys/module/module/parameters/sig_enforce";
+const LOCKDOWN_FILE: &str = "/sys/kernel/security/lockdown";
+
+static SIG_ENFORCED: OnceLock<Option<String>> = OnceLock::new();
+
+fn probe_enforcement() -> Option<String> {
+    if fs::read_to_string(SIG_ENFORCE_PARAM).is_ok_and(|v| v.trim() == "Y") {
+        return Some("module.sig_enforce=Y".to_string());
+    }
+
+    if fs::read_to_string("/proc/cmdline")
+        .is_ok_and(|c| c.split_whitespace().any(|a| a == "module.sig_enforce=1"))
+    {
+        return Some("module.sig_enforce=1 on cmdline".to_string());
+    }
+
+    // Active mode is bracketed, e.g. "none [integrity] confidentiality".
+    if let Ok(content) = fs::read_to_string(LOCKDOWN_FILE)
+        && let Some(start) = content.find('[')
+        && let Some(end) = content[start..].find(']')
+    {
+        let mode = &content[start + 1..start + end];
+        if mode != "none" {
+            return Some(format!("lockdown={}", mode));
+        }
+    }
+
+    None
+}
+
+pub fn signature_enforcement() -> Option<&'static str> {
+    SIG_ENFORCED.get_or_init(probe_enforcement).as_deref()
+}
+
+pub fn is_kernel_object(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "ko")
+}
@@ -1,6 +1,10 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod capabilities;
+pub mod kmod;
 pub mod mount;
 pub mod nuke;
 pub mod poaceae;
+pub mod proc_events;
+pub mod tools;
@@ -1,13 +1,25 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::{fs, path::Path, process::Command};
+use std::{
+    fs,
+    os::fd::AsFd,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
 
 use anyhow::{Context, Result, bail};
 use procfs::process::Process;
-use rustix::mount::{MountFlags, mount};
+use rustix::{
+    fs::CWD,
+    mount::{
+        FsMountFlags, FsOpenFlags, MountAttrFlags, MountFlags, MoveMountFlags, OpenTreeFlags,
+        fsconfig_create, fsconfig_set_string, fsmount, fsopen, mount, mount_bind,
+        mount_bind_recursive, move_mount, open_tree,
+    },
+};
 
-use crate::utils::ensure_dir_exists;
+use crate::{defs, sys::tools, utils::ensure_dir_exists};
 
 pub fn detect_mount_source() -> String {
     if ksu::version().is_some() {
@@ -16,6 +28,93 @@ pub fn detect_mount_source() -> String {
     "APatch".to_string()
 }
 
+pub fn is_filesystem_supported(name: &str) -> bool {
+    fs::read_to_string("/proc/filesystems")
+        .map(|content| {
+            content
+                .lines()
+                .any(|line| line.split_whitespace().last() == Some(name))
+        })
+        .unwrap_or(false)
+}
+
+fn block_mount_points() -> Vec<PathBuf> {
+    let Ok(mountinfo) = Process::myself().and_then(|p| p.mountinfo()) else {
+        return Vec::new();
+    };
+    mountinfo
+        .into_iter()
+        .filter(|m| {
+            m.mount_source
+                .as_deref()
+                .is_some_and(|s| s.starts_with("/dev/block/"))
+                || matches!(m.fs_type.as_str(), "ext4" | "erofs" | "f2fs" | "squashfs")
+        })
+        .map(|m| m.mount_point)
+        .collect()
+}
+
+// Partitions beyond the configured ones that some module carries content for: block-backed mounts
+// at `/<name>`, or anywhere under /mnt (as `mnt/vendor/persist`). A root entry may also be a
+// symlink, as long as it resolves to such a mount or into /system like the built-ins do.
+pub fn discover_partitions(moduledirs: &[PathBuf], known: &[String]) -> Vec<String> {
+    let mounts = block_mount_points();
+    let mut candidates: Vec<String> = Vec::new();
+
+    for module in moduledirs
+        .iter()
+        .filter_map(|d| fs::read_dir(d).ok())
+        .flat_map(|entries| entries.flatten())
+    {
+        let Ok(dirs) = fs::read_dir(module.path()) else {
+            continue;
+        };
+        for dir in dirs.flatten().filter(|e| e.path().is_dir()) {
+            let name = dir.file_name().to_string_lossy().to_string();
+            if known.contains(&name)
+                || defs::NON_PARTITION_DIRS.contains(&name.as_str())
+                || candidates.contains(&name)
+            {
+                continue;
+            }
+            let Ok(canonical) = Path::new("/").join(&name).canonicalize() else {
+                continue;
+            };
+            if mounts.contains(&canonical) || canonical.starts_with("/system/") {
+                candidates.push(name);
+            }
+        }
+
+        for mount in mounts.iter().filter(|m| m.starts_with("/mnt")) {
+            let Ok(rel) = mount.strip_prefix("/") else {
+                continue;
+            };
+            let name = rel.to_string_lossy().to_string();
+            if !known.contains(&name)
+                && !candidates.contains(&name)
+                && module.path().join(rel).is_dir()
+            {
+                candidates.push(name);
+            }
+        }
+    }
+
+    candidates.sort();
+    candidates
+}
+
+pub fn is_dlkm_partition(name: &str) -> bool {
+    defs::DLKM_PARTITIONS.iter().any(|(p, _)| *p == name)
+}
+
+pub fn resolve_dlkm_root(name: &str) -> Option<PathBuf> {
+    let (_, parent) = defs::DLKM_PARTITIONS.iter().find(|(p, _)| *p == name)?;
+
+    [Path::new("/").join(name), Path::new(parent).join(name)]
+        .into_iter()
+        .find(|p| p.is_dir())
+}
+
 pub fn is_mounted<P: AsRef<Path>>(path: P) -> bool {
     let path_str = path.as_ref().to_string_lossy();
     let search = path_str.trim_end_matches('/');
@@ -39,8 +138,74 @@ pub fn is_mounted<P: AsRef<Path>>(path: P) -> bool {
     false
 }
 
+// Every tmpfs and bind mount goes through fsopen/open_tree + move_mount when the kernel has them,
+// so all our mounts look alike to audit hooks; mount(2) is the fallback.
+static NEW_MOUNT_API: LazyLock<bool> =
+    LazyLock::new(|| fsopen("tmpfs", FsOpenFlags::FSOPEN_CLOEXEC).is_ok());
+
+pub fn has_new_mount_api() -> bool {
+    *NEW_MOUNT_API
+}
+
+fn attach<Fd: AsFd>(mount: Fd, target: &Path) -> rustix::io::Result<()> {
+    move_mount(
+        mount.as_fd(),
+        "",
+        CWD,
+        target,
+        MoveMountFlags::MOVE_MOUNT_F_EMPTY_PATH,
+    )
+}
+
+pub fn bind_mount(from: &Path, to: &Path, recursive: bool) -> Result<()> {
+    if has_new_mount_api() {
+        let mut flags = OpenTreeFlags::OPEN_TREE_CLOEXEC | OpenTreeFlags::OPEN_TREE_CLONE;
+        if recursive {
+            flags |= OpenTreeFlags::AT_RECURSIVE;
+        }
+        match open_tree(CWD, from, flags).and_then(|tree| attach(tree, to)) {
+            Ok(()) => return Ok(()),
+            Err(e) => log::debug!(
+                "open_tree bind {} -> {} failed: {}, using mount(2)",
+                from.display(),
+                to.display(),
+                e
+            ),
+        }
+    }
+
+    if recursive {
+        mount_bind_recursive(from, to)?;
+    } else {
+        mount_bind(from, to)?;
+    }
+    Ok(())
+}
+
 pub fn mount_tmpfs(target: &Path, source: &str) -> Result<()> {
     ensure_dir_exists(target)?;
+    if has_new_mount_api() {
+        let result = fsopen("tmpfs", FsOpenFlags::FSOPEN_CLOEXEC).and_then(|fs| {
+            fsconfig_set_string(fs.as_fd(), "source", source)?;
+            fsconfig_set_string(fs.as_fd(), "mode", "0755")?;
+            fsconfig_create(fs.as_fd())?;
+            let mount = fsmount(
+                fs.as_fd(),
+                FsMountFlags::FSMOUNT_CLOEXEC,
+                MountAttrFlags::empty(),
+            )?;
+            attach(mount, target)
+        });
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => log::debug!(
+                "fsopen tmpfs at {} failed: {}, using mount(2)",
+                target.display(),
+                e
+            ),
+        }
+    }
+
     mount(
         source,
         target,
@@ -53,7 +218,7 @@ pub fn mount_tmpfs(target: &Path, source: &str) -> Result<()> {
 }
 
 pub fn repair_image(image_path: &Path) -> Result<()> {
-    let status = Command::new("e2fsck")
+    let status = tools::command("e2fsck")
         .args(["-y", "-f"])
         .arg(image_path)
         .status()
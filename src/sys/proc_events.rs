@@ -0,0 +1,144 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use anyhow::{Context, Result};
+
+// linux/connector.h and linux/cn_proc.h
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+const PROC_EVENT_COMM: u32 = 0x0000_0200;
+
+const NLMSG_HDR_LEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+// what, cpu and timestamp_ns come before the per-event data.
+const EVENT_DATA: usize = NLMSG_HDR_LEN + CN_MSG_LEN + 16;
+
+pub enum ProcEvent {
+    // A process got its final name: zygote children are renamed to their package, nothing execs.
+    Named(i32),
+    // The socket buffer overflowed and events were dropped.
+    Lost,
+}
+
+// The kernel's process event connector, so watchers wake up per process instead of polling /proc.
+pub struct ProcEvents {
+    fd: OwnedFd,
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+impl ProcEvents {
+    pub fn subscribe() -> Result<Self> {
+        let raw = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+        if raw < 0 {
+            return Err(io::Error::last_os_error()).context("Failed to open connector socket");
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = CN_IDX_PROC;
+        addr.nl_pid = std::process::id();
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if bound != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to join proc connector group");
+        }
+
+        // nlmsghdr, then cn_msg carrying a single u32 op.
+        let len = NLMSG_HDR_LEN + CN_MSG_LEN + 4;
+        let mut msg = Vec::with_capacity(len);
+        msg.extend_from_slice(&(len as u32).to_ne_bytes());
+        msg.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+        msg.extend_from_slice(&0u16.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&std::process::id().to_ne_bytes());
+        msg.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+        msg.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&4u16.to_ne_bytes());
+        msg.extend_from_slice(&0u16.to_ne_bytes());
+        msg.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+
+        let sent = unsafe {
+            libc::send(
+                fd.as_raw_fd(),
+                msg.as_ptr() as *const libc::c_void,
+                msg.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error())
+                .context("Failed to subscribe to process events");
+        }
+        Ok(Self { fd })
+    }
+
+    // Blocks until the next datagram; most carry events nobody here cares about, so the result is
+    // often empty.
+    pub fn read(&self) -> Result<Vec<ProcEvent>> {
+        let mut buf = [0u8; 4096];
+        let received = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if received < 0 {
+            let e = io::Error::last_os_error();
+            return match e.raw_os_error() {
+                Some(libc::ENOBUFS) => Ok(vec![ProcEvent::Lost]),
+                Some(libc::EINTR) => Ok(Vec::new()),
+                _ => Err(e).context("Failed to read process events"),
+            };
+        }
+
+        let buf = &buf[..received as usize];
+        let mut events = Vec::new();
+        let mut offset = 0;
+        while let Some(len) = u32_at(buf, offset).map(|l| l as usize) {
+            if len < NLMSG_HDR_LEN || offset + len > buf.len() {
+                break;
+            }
+            let msg = &buf[offset..offset + len];
+            if let (Some(what), Some(pid), Some(tgid)) = (
+                u32_at(msg, NLMSG_HDR_LEN + CN_MSG_LEN),
+                u32_at(msg, EVENT_DATA),
+                u32_at(msg, EVENT_DATA + 4),
+            ) && (what == PROC_EVENT_COMM || what == PROC_EVENT_EXEC)
+                // Threads renaming themselves are not new processes.
+                && pid == tgid
+            {
+                events.push(ProcEvent::Named(tgid as i32));
+            }
+            // Messages are padded to 4 bytes.
+            offset += (len + 3) & !3;
+        }
+        Ok(events)
+    }
+}
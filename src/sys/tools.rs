@@ -0,0 +1,111 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{LazyLock, Mutex},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{defs, utils};
+
+const CHECKSUMS_FILE: &str = "checksums.json";
+
+static RESOLVED: LazyLock<Mutex<HashMap<String, Option<PathBuf>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn abi() -> Option<&'static str> {
+    match env::consts::ARCH {
+        "aarch64" => Some("arm64-v8a"),
+        "arm" => Some("armeabi-v7a"),
+        "x86_64" => Some("x86_64"),
+        _ => None,
+    }
+}
+
+// The tools shipped next to our own binary, then the metamodule path older installs used.
+fn bundle_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|p| p.join("tools")))
+        .into_iter()
+        .collect();
+    let legacy = PathBuf::from(defs::TOOLS_DIR);
+    if !dirs.contains(&legacy) {
+        dirs.push(legacy);
+    }
+    dirs
+}
+
+// Catches truncated or mismatched extractions; the zip signature is what vouches for origin.
+fn verify(dir: &Path, name: &str) -> bool {
+    let expected = fs::read_to_string(dir.join(CHECKSUMS_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str::<HashMap<String, String>>(&content).ok())
+        .and_then(|mut sums| sums.remove(name));
+    let Some(expected) = expected else {
+        log::warn!("{}: no checksum recorded in {}", name, dir.display());
+        return false;
+    };
+    let Ok(mut file) = File::open(dir.join(name)) else {
+        return false;
+    };
+
+    let mut hasher = Sha256::new();
+    if io::copy(&mut file, &mut hasher).is_err() {
+        return false;
+    }
+    if !format!("{:x}", hasher.finalize()).eq_ignore_ascii_case(&expected) {
+        log::warn!(
+            "{}: checksum mismatch in {}, ignoring it",
+            name,
+            dir.display()
+        );
+        return false;
+    }
+    true
+}
+
+fn locate(name: &str) -> Option<PathBuf> {
+    for dir in bundle_dirs() {
+        if let Some(abi) = abi() {
+            let abi_dir = dir.join(abi);
+            if abi_dir.join(name).is_file() && verify(&abi_dir, name) {
+                return Some(abi_dir.join(name));
+            }
+        }
+        // Single-ABI bundles predate the per-ABI layout and carry no checksums.
+        if dir.join(name).is_file() {
+            return Some(dir.join(name));
+        }
+    }
+    utils::find_in_path(name)
+}
+
+// A bundled build for this ABI wins over the system's copy; None if neither exists.
+pub fn find(name: &str) -> Option<PathBuf> {
+    let Ok(mut resolved) = RESOLVED.lock() else {
+        return locate(name);
+    };
+    resolved
+        .entry(name.to_string())
+        .or_insert_with(|| {
+            let path = locate(name);
+            match &path {
+                Some(path) => log::debug!("Helper {}: {}", name, path.display()),
+                None => log::debug!("Helper {}: not found", name),
+            }
+            path
+        })
+        .clone()
+}
+
+pub fn command(name: &str) -> Command {
+    Command::new(find(name).unwrap_or_else(|| PathBuf::from(name)))
+}
@@ -0,0 +1,198 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{fs, io::Read, path::Path};
+
+use anyhow::{Context, Result, bail};
+use flate2::read::DeflateDecoder;
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+const MANIFEST_NAME: &[u8] = b"AndroidManifest.xml";
+
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const UTF8_FLAG: u32 = 1 << 8;
+
+#[derive(Debug, Default, Clone)]
+pub struct ApkManifest {
+    pub package: String,
+    pub permissions: Vec<String>,
+}
+
+fn u16_at(buf: &[u8], off: usize) -> Result<u16> {
+    buf.get(off..off + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .context("Truncated data")
+}
+
+fn u32_at(buf: &[u8], off: usize) -> Result<u32> {
+    buf.get(off..off + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .context("Truncated data")
+}
+
+fn utf8_len_at(buf: &[u8], pos: &mut usize) -> Result<usize> {
+    let first = *buf.get(*pos).context("Truncated string pool")? as usize;
+    *pos += 1;
+    if first & 0x80 == 0 {
+        return Ok(first);
+    }
+    let second = *buf.get(*pos).context("Truncated string pool")? as usize;
+    *pos += 1;
+    Ok(((first & 0x7f) << 8) | second)
+}
+
+fn read_zip_entry(zip: &[u8], wanted: &[u8]) -> Result<Vec<u8>> {
+    let eocd = (0..zip.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(zip, i).ok() == Some(EOCD_SIGNATURE))
+        .context("Not a zip archive")?;
+
+    let entries = u16_at(zip, eocd + 10)? as usize;
+    let mut off = u32_at(zip, eocd + 16)? as usize;
+
+    for _ in 0..entries {
+        if u32_at(zip, off)? != CENTRAL_SIGNATURE {
+            bail!("Corrupted zip central directory");
+        }
+        let method = u16_at(zip, off + 10)?;
+        let comp_size = u32_at(zip, off + 20)? as usize;
+        let name_len = u16_at(zip, off + 28)? as usize;
+        let extra_len = u16_at(zip, off + 30)? as usize;
+        let comment_len = u16_at(zip, off + 32)? as usize;
+        let local_off = u32_at(zip, off + 42)? as usize;
+        let name = zip
+            .get(off + 46..off + 46 + name_len)
+            .context("Truncated zip entry name")?;
+
+        if name == wanted {
+            if u32_at(zip, local_off)? != LOCAL_SIGNATURE {
+                bail!("Corrupted zip local header");
+            }
+            let data_off = local_off
+                + 30
+                + u16_at(zip, local_off + 26)? as usize
+                + u16_at(zip, local_off + 28)? as usize;
+            let data = zip
+                .get(data_off..data_off + comp_size)
+                .context("Truncated zip entry data")?;
+
+            return match method {
+                0 => Ok(data.to_vec()),
+                8 => {
+                    let mut out = Vec::new();
+                    DeflateDecoder::new(data).read_to_end(&mut out)?;
+                    Ok(out)
+                }
+                _ => bail!("Unsupported zip compression method {}", method),
+            };
+        }
+
+        off += 46 + name_len + extra_len + comment_len;
+    }
+
+    bail!("{} not found", String::from_utf8_lossy(wanted))
+}
+
+fn parse_string_pool(xml: &[u8], chunk: usize) -> Result<Vec<String>> {
+    let count = u32_at(xml, chunk + 8)? as usize;
+    let flags = u32_at(xml, chunk + 16)?;
+    let strings_start = chunk + u32_at(xml, chunk + 20)? as usize;
+    let offsets = chunk + u16_at(xml, chunk + 2)? as usize;
+    let utf8 = flags & UTF8_FLAG != 0;
+
+    let mut strings = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut pos = strings_start + u32_at(xml, offsets + i * 4)? as usize;
+
+        let value = if utf8 {
+            // UTF-16 length precedes the UTF-8 byte length.
+            let _ = utf8_len_at(xml, &mut pos)?;
+            let len = utf8_len_at(xml, &mut pos)?;
+            let bytes = xml.get(pos..pos + len).context("Truncated string")?;
+            String::from_utf8_lossy(bytes).into_owned()
+        } else {
+            let mut len = u16_at(xml, pos)? as usize;
+            pos += 2;
+            if len & 0x8000 != 0 {
+                len = ((len & 0x7fff) << 16) | u16_at(xml, pos)? as usize;
+                pos += 2;
+            }
+            let units = (0..len)
+                .map(|j| u16_at(xml, pos + j * 2))
+                .collect::<Result<Vec<u16>>>()?;
+            String::from_utf16_lossy(&units)
+        };
+
+        strings.push(value);
+    }
+
+    Ok(strings)
+}
+
+fn parse_binary_manifest(xml: &[u8]) -> Result<ApkManifest> {
+    let mut manifest = ApkManifest::default();
+    let mut strings: Vec<String> = Vec::new();
+    let mut pos = u16_at(xml, 2)? as usize;
+
+    while pos + 8 <= xml.len() {
+        let chunk_type = u16_at(xml, pos)?;
+        let header_size = u16_at(xml, pos + 2)? as usize;
+        let chunk_size = u32_at(xml, pos + 4)? as usize;
+        if chunk_size == 0 {
+            break;
+        }
+
+        if chunk_type == RES_STRING_POOL_TYPE {
+            strings = parse_string_pool(xml, pos)?;
+        } else if chunk_type == RES_XML_START_ELEMENT_TYPE {
+            let ext = pos + header_size;
+            let element = u32_at(xml, ext + 4)? as usize;
+            let attr_start = u16_at(xml, ext + 8)? as usize;
+            let attr_size = u16_at(xml, ext + 10)? as usize;
+            let attr_count = u16_at(xml, ext + 12)? as usize;
+
+            let attr = |wanted: &str| -> Result<Option<String>> {
+                for i in 0..attr_count {
+                    let a = ext + attr_start + i * attr_size;
+                    let name = u32_at(xml, a + 4)? as usize;
+                    let raw = u32_at(xml, a + 8)?;
+                    if strings.get(name).map(String::as_str) == Some(wanted) && raw != u32::MAX {
+                        return Ok(strings.get(raw as usize).cloned());
+                    }
+                }
+                Ok(None)
+            };
+
+            match strings.get(element).map(String::as_str) {
+                Some("manifest") => manifest.package = attr("package")?.unwrap_or_default(),
+                Some("uses-permission") => {
+                    if let Some(name) = attr("name")? {
+                        manifest.permissions.push(name);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        pos += chunk_size;
+    }
+
+    if manifest.package.is_empty() {
+        bail!("Manifest has no package attribute");
+    }
+
+    manifest.permissions.sort();
+    manifest.permissions.dedup();
+    Ok(manifest)
+}
+
+pub fn read_manifest(apk: &Path) -> Result<ApkManifest> {
+    let zip = fs::read(apk).with_context(|| format!("Failed to read {}", apk.display()))?;
+    let xml = read_zip_entry(&zip, MANIFEST_NAME)
+        .with_context(|| format!("Failed to extract manifest from {}", apk.display()))?;
+    parse_binary_manifest(&xml)
+        .with_context(|| format!("Failed to parse manifest of {}", apk.display()))
+}
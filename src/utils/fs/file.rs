@@ -15,7 +15,7 @@ use anyhow::{Context, Result, bail};
 use rustix::fs::ioctl_ficlone;
 use walkdir::WalkDir;
 
-use super::xattr::internal_copy_extended_attributes;
+use super::xattr::{create_xattr_whiteout, internal_copy_extended_attributes};
 
 pub fn atomic_write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, content: C) -> Result<()> {
     let path = path.as_ref();
@@ -81,6 +81,14 @@ fn make_device_node(path: &Path, mode: u32, rdev: u64) -> Result<()> {
     Ok(())
 }
 
+pub fn create_whiteout<P: AsRef<Path>>(path: P) -> Result<()> {
+    make_device_node(path.as_ref(), libc::S_IFCHR | 0o644, 0)
+}
+
+pub fn is_whiteout<P: AsRef<Path>>(path: P) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_char_device() && m.rdev() == 0)
+}
+
 fn native_cp_r(
     src: &Path,
     dst: &Path,
@@ -115,21 +123,8 @@ fn native_cp_r(
                 continue;
             }
             native_cp_r(&src_path, &dst_path, &next_relative, _repair, visited)?;
-        } else if ft.is_symlink() {
-            if dst_path.exists() {
-                fs::remove_file(&dst_path)?;
-            }
-            let link_target = fs::read_link(&src_path)?;
-            symlink(&link_target, &dst_path)?;
-        } else if ft.is_char_device() || ft.is_block_device() || ft.is_fifo() {
-            if dst_path.exists() {
-                fs::remove_file(&dst_path)?;
-            }
-            let mode = metadata.permissions().mode();
-            let rdev = metadata.rdev();
-            make_device_node(&dst_path, mode, rdev)?;
         } else {
-            reflink_or_copy(&src_path, &dst_path)?;
+            copy_node(&src_path, &dst_path, &metadata)?;
         }
 
         let _ = internal_copy_extended_attributes(&src_path, &dst_path);
@@ -137,6 +132,60 @@ fn native_cp_r(
     Ok(())
 }
 
+fn copy_node(src: &Path, dst: &Path, metadata: &fs::Metadata) -> Result<()> {
+    let ft = metadata.file_type();
+
+    if ft.is_symlink() {
+        if dst.exists() {
+            fs::remove_file(dst)?;
+        }
+        let link_target = fs::read_link(src)?;
+        symlink(&link_target, dst)?;
+    } else if ft.is_char_device() && metadata.rdev() == 0 {
+        if dst.exists() {
+            fs::remove_file(dst)?;
+        }
+        if let Err(e) = create_whiteout(dst) {
+            log::debug!("{:#}, falling back to an xattr whiteout", e);
+            create_xattr_whiteout(dst)?;
+        }
+    } else if ft.is_char_device() || ft.is_block_device() || ft.is_fifo() {
+        if dst.exists() {
+            fs::remove_file(dst)?;
+        }
+        let mode = metadata.permissions().mode();
+        let rdev = metadata.rdev();
+        make_device_node(dst, mode, rdev)?;
+    } else {
+        reflink_or_copy(src, dst)?;
+    }
+    Ok(())
+}
+
+// Copies a single entry (not its children), replacing whatever is at `dst`.
+pub fn copy_entry(src: &Path, dst: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(src)?;
+    let existing = fs::symlink_metadata(dst).ok();
+
+    if metadata.is_dir() {
+        if existing.as_ref().is_some_and(|m| !m.is_dir()) {
+            fs::remove_file(dst)?;
+        }
+        ensure_dir_exists(dst)?;
+        fs::set_permissions(dst, metadata.permissions())?;
+    } else {
+        match existing {
+            Some(m) if m.is_dir() => fs::remove_dir_all(dst)?,
+            Some(_) => fs::remove_file(dst)?,
+            None => {}
+        }
+        copy_node(src, dst, &metadata)?;
+    }
+
+    let _ = internal_copy_extended_attributes(src, dst);
+    Ok(())
+}
+
 pub fn sync_dir(src: &Path, dst: &Path, repair_context: bool) -> Result<()> {
     if !src.exists() {
         return Ok(());
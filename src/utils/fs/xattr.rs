@@ -11,6 +11,7 @@ use extattr::{Flags as XattrFlags, lgetxattr, llistxattr, lsetxattr};
 
 const SELINUX_XATTR: &str = "security.selinux";
 const OVERLAY_OPAQUE_XATTR: &str = "trusted.overlay.opaque";
+const OVERLAY_WHITEOUT_XATTR: &str = "trusted.overlay.whiteout";
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 fn copy_extended_attributes(src: &Path, dst: &Path) -> Result<()> {
@@ -42,6 +43,27 @@ fn copy_extended_attributes(_src: &Path, _dst: &Path) -> Result<()> {
     unimplemented!();
 }
 
+// Every extended attribute of `path` sorted by name, the SELinux label included.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn list_xattrs<P: AsRef<Path>>(path: P) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let path = path.as_ref();
+    let mut xattrs: Vec<(Vec<u8>, Vec<u8>)> = llistxattr(path)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|name| {
+            let value = lgetxattr(path, &name).ok()?;
+            Some((name.as_bytes().to_vec(), value))
+        })
+        .collect();
+    xattrs.sort();
+    xattrs
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn list_xattrs<P: AsRef<Path>>(_path: P) -> Vec<(Vec<u8>, Vec<u8>)> {
+    unimplemented!();
+}
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub fn set_overlay_opaque<P: AsRef<Path>>(path: P) -> Result<()> {
     lsetxattr(
@@ -58,6 +80,49 @@ pub fn set_overlay_opaque<P: AsRef<Path>>(_path: P) -> Result<()> {
     unimplemented!();
 }
 
+// Kernels from 6.7 also honour an empty file tagged overlay.whiteout in a lower layer, provided
+// its directory is marked opaque "x". Used where a 0:0 char device cannot be created.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn create_xattr_whiteout<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    std::fs::File::create(path)?;
+    lsetxattr(path, OVERLAY_WHITEOUT_XATTR, b"y", XattrFlags::empty())?;
+
+    if let Some(parent) = path.parent()
+        && lgetxattr(parent, OVERLAY_OPAQUE_XATTR).is_err()
+    {
+        lsetxattr(parent, OVERLAY_OPAQUE_XATTR, b"x", XattrFlags::empty())?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn create_xattr_whiteout<P: AsRef<Path>>(_path: P) -> Result<()> {
+    unimplemented!();
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn is_xattr_whiteout<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    std::fs::symlink_metadata(path).is_ok_and(|m| m.is_file() && m.len() == 0)
+        && lgetxattr(path, OVERLAY_WHITEOUT_XATTR).is_ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn is_xattr_whiteout<P: AsRef<Path>>(_path: P) -> bool {
+    unimplemented!();
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn is_overlay_opaque<P: AsRef<Path>>(path: P) -> bool {
+    lgetxattr(path.as_ref(), OVERLAY_OPAQUE_XATTR).is_ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn is_overlay_opaque<P: AsRef<Path>>(_path: P) -> bool {
+    unimplemented!();
+}
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub fn lsetfilecon<P: AsRef<Path>>(path: P, con: &str) -> Result<()> {
     if let Err(e) = lsetxattr(
@@ -1,22 +1,187 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use anyhow::Result;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Mutex,
+        atomic::{AtomicU8, AtomicU64, AtomicUsize, Ordering},
+    },
+};
 
-pub fn init_logging() -> Result<()> {
+use anyhow::{Context, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+use crate::{defs, utils::redact};
+
+pub const DEFAULT_LOG_MAX_SIZE: u64 = 1024 * 1024;
+pub const DEFAULT_LOG_KEEP: usize = 3;
+
+// What daemon.log holds. Logcat leaves it untouched, relying on the platform logger alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Plain,
+    Json,
+    Logcat,
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(LogFormat::Plain as u8);
+static MAX_SIZE: AtomicU64 = AtomicU64::new(DEFAULT_LOG_MAX_SIZE);
+static KEEP: AtomicUsize = AtomicUsize::new(DEFAULT_LOG_KEEP);
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+// daemon.log rolls over to daemon.log.1 once it would pass the size cap; daemon.log.<keep> is
+// the oldest kept.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let keep = KEEP.load(Ordering::Relaxed);
+        if keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            let _ = fs::remove_file(rotated_path(&self.path, keep));
+            for index in (1..keep).rev() {
+                let _ = fs::rename(
+                    rotated_path(&self.path, index),
+                    rotated_path(&self.path, index + 1),
+                );
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > MAX_SIZE.load(Ordering::Relaxed) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+struct TeeLogger {
+    inner: Box<dyn Log>,
+    daemon_log: Option<Mutex<RotatingFile>>,
+    tee: Option<Mutex<File>>,
+}
+
+fn format_record(record: &Record, message: &str, format: LogFormat) -> String {
+    let now = chrono::Local::now();
+    if format == LogFormat::Json {
+        let line = serde_json::json!({
+            "ts": now.to_rfc3339(),
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "msg": message,
+        });
+        return format!("{}\n", line);
+    }
+    format!(
+        "{} [{}] [{}] {}\n",
+        now.format("%m-%d %H:%M:%S%.3f"),
+        record.level(),
+        record.target(),
+        message
+    )
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let message = record.args().to_string();
+        let message = redact(&message);
+        self.inner.log(
+            &Record::builder()
+                .args(format_args!("{}", message))
+                .level(record.level())
+                .target(record.target())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+
+        let format = match FORMAT.load(Ordering::Relaxed) {
+            1 => LogFormat::Json,
+            2 => LogFormat::Logcat,
+            _ => LogFormat::Plain,
+        };
+        if format != LogFormat::Logcat
+            && let Some(Ok(mut file)) = self.daemon_log.as_ref().map(Mutex::lock)
+        {
+            let _ = file.write_all(format_record(record, &message, format).as_bytes());
+        }
+        // Captures are read by people, so they stay plain text.
+        if let Some(Ok(mut file)) = self.tee.as_ref().map(Mutex::lock) {
+            let _ = file.write_all(format_record(record, &message, LogFormat::Plain).as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        if let Some(Ok(mut file)) = self.daemon_log.as_ref().map(Mutex::lock) {
+            let _ = file.flush();
+        }
+        if let Some(Ok(mut file)) = self.tee.as_ref().map(Mutex::lock) {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn platform_logger(level: LevelFilter) -> Box<dyn Log> {
     #[cfg(target_os = "android")]
     {
-        android_logger::init_once(
+        Box::new(android_logger::AndroidLogger::new(
             android_logger::Config::default()
-                .with_max_level(log::LevelFilter::Debug)
+                .with_max_level(level)
                 .with_tag("mhm"),
-        );
+        ))
     }
 
     #[cfg(not(target_os = "android"))]
     {
-        use std::io::Write;
-
         let mut builder = env_logger::Builder::new();
 
         builder.format(|buf, record| {
@@ -28,7 +193,108 @@ pub fn init_logging() -> Result<()> {
                 record.args()
             )
         });
-        builder.filter_level(log::LevelFilter::Debug).init();
+        Box::new(builder.filter_level(level).build())
+    }
+}
+
+pub fn init_logging() -> Result<()> {
+    init_logging_with(LevelFilter::Debug, None)
+}
+
+// Every record also goes to daemon.log; with a tee path it is appended to that file too, e.g.
+// for boot captures.
+pub fn init_logging_with(level: LevelFilter, tee: Option<&Path>) -> Result<()> {
+    let inner = platform_logger(level);
+
+    let daemon_log = RotatingFile::open(Path::new(defs::DAEMON_LOG_FILE))
+        .inspect_err(|e| eprintln!("Failed to open {}: {}", defs::DAEMON_LOG_FILE, e))
+        .ok()
+        .map(Mutex::new);
+
+    let tee = tee
+        .map(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))
+        })
+        .transpose()?
+        .map(Mutex::new);
+
+    let logger = TeeLogger {
+        inner,
+        daemon_log,
+        tee,
+    };
+    if log::set_logger(Box::leak(Box::new(logger))).is_ok() {
+        log::set_max_level(level);
     }
     Ok(())
 }
+
+// Both take effect from the next record, so logging can start before the config is loaded.
+pub fn set_log_format(format: LogFormat) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+pub fn set_log_rotation(max_size: u64, keep: usize) {
+    MAX_SIZE.store(max_size.max(1), Ordering::Relaxed);
+    KEEP.store(keep, Ordering::Relaxed);
+}
+
+// Copies past `keep` are left behind when the setting is lowered, since rotation only ever drops
+// daemon.log.<keep>.
+pub fn prune_rotated_logs() -> usize {
+    let path = Path::new(defs::DAEMON_LOG_FILE);
+    (KEEP.load(Ordering::Relaxed) + 1..)
+        .map(|index| rotated_path(path, index))
+        .take_while(|p| p.exists())
+        .filter(|p| fs::remove_file(p).is_ok())
+        .count()
+}
+
+fn line_level(line: &str) -> Option<Level> {
+    if line.starts_with('{') {
+        let record: serde_json::Value = serde_json::from_str(line).ok()?;
+        return Level::from_str(record.get("level")?.as_str()?).ok();
+    }
+    line.split_whitespace()
+        .take(4)
+        .find_map(|w| w.strip_prefix('[')?.strip_suffix(']'))
+        .and_then(|w| Level::from_str(w).ok())
+}
+
+// Oldest first, across daemon.log and its rotated copies, whichever format wrote them. Lines
+// without a level (shell wrapper output, multi-line messages) count as the level of the record
+// before them.
+pub fn read_logs(min_level: Option<Level>, tail: Option<usize>) -> Vec<String> {
+    let path = Path::new(defs::DAEMON_LOG_FILE);
+    let mut files: Vec<PathBuf> = (1..)
+        .map(|index| rotated_path(path, index))
+        .take_while(|p| p.exists())
+        .collect();
+    files.reverse();
+    files.push(path.to_path_buf());
+
+    let mut lines = Vec::new();
+    for file in files {
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let mut level = Level::Info;
+        for line in content.lines() {
+            if let Some(parsed) = line_level(line) {
+                level = parsed;
+            }
+            if min_level.is_none_or(|min| level <= min) {
+                lines.push(redact(line).into_owned());
+            }
+        }
+    }
+
+    if let Some(tail) = tail {
+        lines.drain(..lines.len().saturating_sub(tail));
+    }
+    lines
+}
@@ -1,9 +1,12 @@
 // Copyright 2026 Hybrid Mount Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod apk;
 pub mod fs;
 pub mod log;
 pub mod process;
+pub mod redact;
+pub mod tar;
 pub mod validation;
 
-pub use self::{fs::*, log::*, process::*, validation::*};
+pub use self::{fs::*, log::*, process::*, redact::*, validation::*};
@@ -2,11 +2,20 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
+    env,
     ffi::CString,
-    time::{SystemTime, UNIX_EPOCH},
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail, ensure};
+
+const PROPERTY_SERVICE_SOCKET: &str = "/dev/socket/property_service";
+const PROP_MSG_SETPROP2: u32 = 0x0002_0001;
+const PROP_VALUE_MAX: usize = 92;
 
 pub fn camouflage_process(name: &str) -> Result<()> {
     let c_name = CString::new(name)?;
@@ -25,3 +34,54 @@ pub fn random_kworker_name() -> String {
     let y = (nanos >> 4) % 10;
     format!("kworker/u{}:{}", x, y)
 }
+
+// Empty or unset properties read as None.
+pub fn get_property(name: &str) -> Option<String> {
+    let output = Command::new("getprop").arg(name).output().ok()?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+// Same request bionic's __system_property_set sends, so no resetprop binary is needed. init still
+// enforces its own rules: ro.* properties can only be set once and SELinux must allow the caller.
+pub fn set_property(name: &str, value: &str) -> Result<()> {
+    ensure!(
+        !name.is_empty() && !name.contains(['\0', '=']),
+        "Invalid property name: {:?}",
+        name
+    );
+    ensure!(
+        value.len() < PROP_VALUE_MAX || name.starts_with("ro."),
+        "Value for {} is longer than {} bytes",
+        name,
+        PROP_VALUE_MAX - 1
+    );
+
+    let mut stream = UnixStream::connect(PROPERTY_SERVICE_SOCKET)
+        .with_context(|| format!("Failed to connect to {}", PROPERTY_SERVICE_SOCKET))?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+
+    let mut msg = Vec::with_capacity(12 + name.len() + value.len());
+    msg.extend_from_slice(&PROP_MSG_SETPROP2.to_ne_bytes());
+    for s in [name, value] {
+        msg.extend_from_slice(&(s.len() as u32).to_ne_bytes());
+        msg.extend_from_slice(s.as_bytes());
+    }
+    stream.write_all(&msg)?;
+
+    let mut result = [0u8; 4];
+    stream
+        .read_exact(&mut result)
+        .with_context(|| format!("No reply from init while setting {}", name))?;
+    match u32::from_ne_bytes(result) {
+        0 => Ok(()),
+        code => bail!("init refused to set {} (error 0x{:x})", name, code),
+    }
+}
+
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(binary))
+        .find(|candidate| candidate.is_file())
+}
@@ -0,0 +1,126 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    borrow::Cow,
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    sync::{LazyLock, RwLock},
+};
+
+use hmac::{Hmac, Mac};
+use regex_lite::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::defs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactAction {
+    #[default]
+    Hash,
+    Truncate,
+}
+
+// `pattern` must match a whole path component, e.g. `com\.bank\..*`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub action: RedactAction,
+}
+
+const TRUNCATE_KEEP: usize = 3;
+const KEY_LEN: usize = 32;
+// Bytes of the tag kept in a token.
+const TOKEN_LEN: usize = 8;
+
+static PATH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"/[^\s:;,'"()\[\]{}<>=]+"#).unwrap());
+static RULES: RwLock<Vec<(Regex, RedactAction)>> = RwLock::new(Vec::new());
+static KEY: LazyLock<Vec<u8>> = LazyLock::new(load_key);
+
+// Kept per device, so a token can't be reversed by hashing guessed package names elsewhere. Without
+// a stored key, tokens are only stable within this process. Runs inside the logger, so it must not
+// log itself.
+fn load_key() -> Vec<u8> {
+    if let Ok(key) = fs::read(defs::REDACT_KEY_FILE)
+        && key.len() == KEY_LEN
+    {
+        return key;
+    }
+    let mut key = vec![0u8; KEY_LEN];
+    let _ = File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut key));
+    let _ = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(defs::REDACT_KEY_FILE)
+        .and_then(|mut f| f.write_all(&key));
+    key
+}
+
+pub fn set_redact_rules(rules: &[RedactRule]) {
+    let compiled = rules
+        .iter()
+        .filter_map(
+            |rule| match Regex::new(&format!("^(?:{})$", rule.pattern)) {
+                Ok(re) => Some((re, rule.action)),
+                Err(e) => {
+                    log::warn!("Ignoring redaction pattern {:?}: {}", rule.pattern, e);
+                    None
+                }
+            },
+        )
+        .collect();
+    if let Ok(mut guard) = RULES.write() {
+        *guard = compiled;
+    }
+}
+
+// Tokens are stable per device, so the same component maps to the same token across lines and
+// bundles.
+fn redact_component(component: &str, action: RedactAction) -> String {
+    match action {
+        RedactAction::Hash => {
+            let mut mac =
+                Hmac::<Sha256>::new_from_slice(&KEY).expect("HMAC accepts keys of any length");
+            mac.update(component.as_bytes());
+            let tag = mac.finalize().into_bytes();
+            let hex: String = tag[..TOKEN_LEN]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            format!("#{}", hex)
+        }
+        RedactAction::Truncate => {
+            let kept: String = component.chars().take(TRUNCATE_KEEP).collect();
+            format!("{}*", kept)
+        }
+    }
+}
+
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let Ok(rules) = RULES.read() else {
+        return Cow::Borrowed(text);
+    };
+    if rules.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    PATH.replace_all(text, |caps: &regex_lite::Captures| {
+        caps[0]
+            .split('/')
+            .map(
+                |component| match rules.iter().find(|(re, _)| re.is_match(component)) {
+                    Some((_, action)) if !component.is_empty() => {
+                        redact_component(component, *action)
+                    }
+                    _ => component.to_string(),
+                },
+            )
+            .collect::<Vec<_>>()
+            .join("/")
+    })
+}
@@ -0,0 +1,383 @@
+// Copyright 2026 Hybrid Mount Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    ffi::OsStr,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::{
+        fd::OwnedFd,
+        unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+    },
+    path::{Component, Path},
+};
+
+use anyhow::{Context, Result, bail};
+use rustix::{
+    fs::{CWD, FileType, Mode, OFlags, fchmod, mkdirat, mknodat, openat, symlinkat},
+    io::Errno,
+};
+
+const BLOCK: usize = 512;
+const NAME_LEN: usize = 100;
+const PREFIX_LEN: usize = 155;
+
+// Just enough of ustar for module trees: no owners, no xattrs, and a whiteout is the only device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Whiteout,
+}
+
+impl EntryKind {
+    fn flag(self) -> u8 {
+        match self {
+            Self::File => b'0',
+            Self::Dir => b'5',
+            Self::Symlink => b'2',
+            Self::Whiteout => b'3',
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub path: String,
+    pub kind: EntryKind,
+    pub mode: u32,
+    pub link: String,
+    // Bytes of data that follow the header; only files have any.
+    pub size: u64,
+}
+
+impl Entry {
+    pub fn file(path: &str, mode: u32, size: u64) -> Self {
+        Self {
+            path: path.to_string(),
+            kind: EntryKind::File,
+            mode,
+            link: String::new(),
+            size,
+        }
+    }
+
+    fn of(path: &str, kind: EntryKind, mode: u32) -> Self {
+        Self {
+            kind,
+            ..Self::file(path, mode, 0)
+        }
+    }
+}
+
+fn put(header: &mut [u8; BLOCK], offset: usize, value: &[u8]) {
+    header[offset..offset + value.len()].copy_from_slice(value);
+}
+
+fn octal(value: u64, width: usize) -> Vec<u8> {
+    format!("{:0width$o}\0", value, width = width - 1).into_bytes()
+}
+
+// Long paths are split into ustar's prefix and name fields at a slash.
+fn split_path(path: &str) -> Result<(&str, &str)> {
+    if path.len() <= NAME_LEN {
+        return Ok(("", path));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(prefix, name)| prefix.len() <= PREFIX_LEN && name.len() <= NAME_LEN)
+        .with_context(|| format!("Path too long for a tar entry: {}", path))
+}
+
+fn header(entry: &Entry, mtime: u64) -> Result<[u8; BLOCK]> {
+    if entry.link.len() > NAME_LEN {
+        bail!("Symlink target too long for a tar entry: {}", entry.link);
+    }
+    let (prefix, name) = split_path(&entry.path)?;
+
+    let mut header = [0u8; BLOCK];
+    put(&mut header, 0, name.as_bytes());
+    put(&mut header, 100, &octal(entry.mode as u64, 8));
+    put(&mut header, 108, &octal(0, 8));
+    put(&mut header, 116, &octal(0, 8));
+    put(&mut header, 124, &octal(entry.size, 12));
+    put(&mut header, 136, &octal(mtime, 12));
+    put(&mut header, 148, b"        ");
+    put(&mut header, 156, &[entry.kind.flag()]);
+    put(&mut header, 157, entry.link.as_bytes());
+    put(&mut header, 257, b"ustar\0");
+    put(&mut header, 263, b"00");
+    put(&mut header, 329, &octal(0, 8));
+    put(&mut header, 337, &octal(0, 8));
+    put(&mut header, 345, prefix.as_bytes());
+
+    let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+    put(&mut header, 148, format!("{:06o}\0 ", checksum).as_bytes());
+    Ok(header)
+}
+
+fn padding(len: u64) -> u64 {
+    (BLOCK as u64 - len % BLOCK as u64) % BLOCK as u64
+}
+
+pub struct Writer<W: Write> {
+    inner: W,
+    mtime: u64,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W, mtime: u64) -> Self {
+        Self { inner, mtime }
+    }
+
+    // `data` has to yield exactly `entry.size` bytes.
+    pub fn append(&mut self, entry: &Entry, data: impl Read) -> Result<()> {
+        self.inner.write_all(&header(entry, self.mtime)?)?;
+        let written = io::copy(&mut data.take(entry.size), &mut self.inner)?;
+        if written != entry.size {
+            bail!("{} changed while it was archived", entry.path);
+        }
+        self.inner
+            .write_all(&vec![0u8; padding(entry.size) as usize])?;
+        Ok(())
+    }
+
+    pub fn append_data(&mut self, path: &str, mode: u32, data: &[u8]) -> Result<()> {
+        self.append(&Entry::file(path, mode, data.len() as u64), data)
+    }
+
+    // Adds `source` without following symlinks and streams file content instead of reading it
+    // whole. Sockets, fifos and other devices are left out and return false.
+    pub fn append_path(&mut self, path: &str, source: &Path) -> Result<bool> {
+        let meta = fs::symlink_metadata(source)?;
+        let file_type = meta.file_type();
+        let mode = meta.permissions().mode() & 0o7777;
+
+        if file_type.is_dir() {
+            self.append(&Entry::of(path, EntryKind::Dir, mode), io::empty())?;
+        } else if file_type.is_symlink() {
+            let mut entry = Entry::of(path, EntryKind::Symlink, mode);
+            entry.link = fs::read_link(source)?.to_string_lossy().to_string();
+            self.append(&entry, io::empty())?;
+        } else if file_type.is_char_device() && meta.rdev() == 0 {
+            self.append(&Entry::of(path, EntryKind::Whiteout, mode), io::empty())?;
+        } else if file_type.is_file() {
+            let file = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NOFOLLOW)
+                .open(source)?;
+            let size = file.metadata()?.len();
+            self.append(&Entry::file(path, mode, size), file)?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.write_all(&[0u8; 2 * BLOCK])?;
+        Ok(self.inner)
+    }
+}
+
+fn field(header: &[u8], offset: usize, len: usize) -> String {
+    let raw = &header[offset..offset + len];
+    let end = raw.iter().position(|b| *b == 0).unwrap_or(len);
+    String::from_utf8_lossy(&raw[..end]).to_string()
+}
+
+fn parse_octal(header: &[u8], offset: usize, len: usize) -> Result<u64> {
+    let text = field(header, offset, len);
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).with_context(|| format!("Bad octal field in tar header: {}", text))
+}
+
+pub struct Reader<R: Read> {
+    inner: R,
+    // What is left of the current entry's data, and the padding after it.
+    data_left: u64,
+    padding_left: u64,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            data_left: 0,
+            padding_left: 0,
+        }
+    }
+
+    fn skip_rest(&mut self) -> Result<()> {
+        let rest = self.data_left + self.padding_left;
+        let skipped = io::copy(&mut (&mut self.inner).take(rest), &mut io::sink())?;
+        if skipped != rest {
+            bail!("Truncated tar archive");
+        }
+        self.data_left = 0;
+        self.padding_left = 0;
+        Ok(())
+    }
+
+    // Copies the current entry's data to `out`; anything not read is skipped by the next call to
+    // `next_entry`.
+    pub fn read_data(&mut self, out: &mut impl Write) -> Result<u64> {
+        let expected = self.data_left;
+        let copied = io::copy(&mut (&mut self.inner).take(expected), out)?;
+        self.data_left = 0;
+        if copied != expected {
+            bail!("Truncated tar archive");
+        }
+        Ok(copied)
+    }
+
+    // For entries small enough to hold in memory; larger ones fail instead of being allocated.
+    pub fn read_small(&mut self, max: u64) -> Result<Vec<u8>> {
+        if self.data_left > max {
+            bail!(
+                "Tar entry of {} bytes is larger than {}",
+                self.data_left,
+                max
+            );
+        }
+        let mut data = Vec::with_capacity(self.data_left as usize);
+        self.read_data(&mut data)?;
+        Ok(data)
+    }
+
+    // Entries of any other type are skipped.
+    pub fn next_entry(&mut self) -> Result<Option<Entry>> {
+        self.skip_rest()?;
+        loop {
+            let mut header = [0u8; BLOCK];
+            self.inner
+                .read_exact(&mut header)
+                .context("Truncated tar archive")?;
+            if header.iter().all(|b| *b == 0) {
+                return Ok(None);
+            }
+
+            let expected = parse_octal(&header, 148, 8)?;
+            let mut blank = header;
+            put(&mut blank, 148, b"        ");
+            let checksum: u64 = blank.iter().map(|b| *b as u64).sum();
+            if checksum != expected {
+                bail!("Not a tar archive, or a corrupted one");
+            }
+
+            let size = parse_octal(&header, 124, 12)?;
+            self.data_left = size;
+            self.padding_left = padding(size);
+
+            let kind = match header[156] {
+                b'0' | 0 => EntryKind::File,
+                b'5' => EntryKind::Dir,
+                b'2' => EntryKind::Symlink,
+                b'3' => EntryKind::Whiteout,
+                _ => {
+                    self.skip_rest()?;
+                    continue;
+                }
+            };
+            let name = field(&header, 0, NAME_LEN);
+            let prefix = field(&header, 345, PREFIX_LEN);
+            let path = if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+
+            return Ok(Some(Entry {
+                path: path.trim_end_matches('/').to_string(),
+                kind,
+                mode: parse_octal(&header, 100, 8)? as u32,
+                link: field(&header, 157, NAME_LEN),
+                size,
+            }));
+        }
+    }
+
+    // Creates the current entry at `root/rel`, which must not exist yet unless both are
+    // directories. Every directory on the way is opened with O_NOFOLLOW, so a symlink an earlier
+    // entry left behind cannot send this one outside `root`.
+    pub fn unpack(&mut self, entry: &Entry, root: &Path, rel: &Path) -> Result<()> {
+        let mut dir = openat(
+            CWD,
+            root,
+            OFlags::DIRECTORY | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+            Mode::empty(),
+        )
+        .with_context(|| format!("Failed to open {}", root.display()))?;
+
+        let mut names: Vec<&OsStr> = Vec::new();
+        for component in rel.components() {
+            match component {
+                Component::Normal(name) => names.push(name),
+                _ => bail!("Tar entry escapes its root: {}", entry.path),
+            }
+        }
+        let Some(last) = names.pop() else {
+            if entry.kind != EntryKind::Dir {
+                bail!("Tar entry replaces its root: {}", entry.path);
+            }
+            fchmod(&dir, Mode::from_raw_mode(entry.mode))?;
+            return Ok(());
+        };
+        for name in names {
+            dir = open_subdir(&dir, name)?;
+        }
+
+        let mode = Mode::from_raw_mode(entry.mode);
+        match entry.kind {
+            EntryKind::Dir => {
+                let sub = open_subdir(&dir, last)?;
+                fchmod(&sub, mode)?;
+            }
+            EntryKind::File => {
+                let fd = openat(
+                    &dir,
+                    last,
+                    OFlags::WRONLY
+                        | OFlags::CREATE
+                        | OFlags::EXCL
+                        | OFlags::NOFOLLOW
+                        | OFlags::CLOEXEC,
+                    mode,
+                )?;
+                fchmod(&fd, mode)?;
+                self.read_data(&mut File::from(fd))?;
+            }
+            EntryKind::Symlink => symlinkat(entry.link.as_str(), &dir, last)?,
+            EntryKind::Whiteout => mknodat(
+                &dir,
+                last,
+                FileType::CharacterDevice,
+                Mode::from_raw_mode(0o644),
+                0,
+            )?,
+        }
+        Ok(())
+    }
+}
+
+// Opens `name` under `dir` as a directory, creating it if missing; a symlink is refused.
+fn open_subdir(dir: &OwnedFd, name: &OsStr) -> Result<OwnedFd> {
+    let flags = OFlags::DIRECTORY | OFlags::NOFOLLOW | OFlags::CLOEXEC;
+    match openat(dir, name, flags, Mode::empty()) {
+        Err(Errno::NOENT) => {
+            mkdirat(dir, name, Mode::from_raw_mode(0o755))?;
+            Ok(openat(dir, name, flags, Mode::empty())?)
+        }
+        Err(e) => Err(e).with_context(|| {
+            format!(
+                "{} is not a directory of the archive",
+                name.to_string_lossy()
+            )
+        }),
+        Ok(fd) => Ok(fd),
+    }
+}
@@ -13,6 +13,7 @@ import type {
   SystemInfo,
   DeviceInfo,
   ModuleRules,
+  CliError,
 } from "./types";
 
 interface KsuExecResult {
@@ -55,6 +56,15 @@ function stringToHex(str: string): string {
   return hex;
 }
 
+// Commands run with --json-errors report the real cause on stdout.
+function commandError(what: string, result: KsuExecResult): Error {
+  try {
+    const { error } = JSON.parse(result.stdout) as CliError;
+    if (error?.message) return new Error(`${what}: ${error.message}`);
+  } catch {}
+  return new Error(`${what}: ${result.stderr || `exit ${result.errno}`}`);
+}
+
 interface AppAPI {
   loadConfig: () => Promise<AppConfig>;
   saveConfig: (config: AppConfig) => Promise<void>;
@@ -88,15 +98,15 @@ const RealAPI: AppAPI = {
     if (!ksuExec) throw new Error("No KSU environment");
     const jsonStr = JSON.stringify(config);
     const hexPayload = stringToHex(jsonStr);
-    const cmd = `${PATHS.BINARY} save-config --payload ${hexPayload}`;
-    const { errno, stderr } = await ksuExec(cmd);
-    if (errno !== 0) throw new Error(`Failed to save config: ${stderr}`);
+    const cmd = `${PATHS.BINARY} --json-errors save-config --payload ${hexPayload}`;
+    const result = await ksuExec(cmd);
+    if (result.errno !== 0) throw commandError("Failed to save config", result);
   },
   resetConfig: async (): Promise<void> => {
     if (!ksuExec) throw new Error("No KSU environment");
-    const cmd = `${PATHS.BINARY} gen-config`;
-    const { errno, stderr } = await ksuExec(cmd);
-    if (errno !== 0) throw new Error(`Failed to reset config: ${stderr}`);
+    const cmd = `${PATHS.BINARY} --json-errors gen-config`;
+    const result = await ksuExec(cmd);
+    if (result.errno !== 0) throw commandError("Failed to reset config", result);
   },
   scanModules: async (_path?: string): Promise<Module[]> => {
     if (!ksuExec) return [];
@@ -128,9 +138,9 @@ const RealAPI: AppAPI = {
     if (!ksuExec) throw new Error("No KSU environment");
     const jsonStr = JSON.stringify(rules);
     const hexPayload = stringToHex(jsonStr);
-    const cmd = `${PATHS.BINARY} save-module-rules --module "${moduleId}" --payload ${hexPayload}`;
-    const { errno, stderr } = await ksuExec(cmd);
-    if (errno !== 0) throw new Error(`Failed to save rules: ${stderr}`);
+    const cmd = `${PATHS.BINARY} --json-errors save-module-rules --module "${moduleId}" --payload ${hexPayload}`;
+    const result = await ksuExec(cmd);
+    if (result.errno !== 0) throw commandError("Failed to save rules", result);
   },
   getStorageUsage: async (): Promise<StorageStatus> => {
     if (!ksuExec) return { type: null };
@@ -143,6 +153,7 @@ const RealAPI: AppAPI = {
         const state = JSON.parse(stdout);
         return {
           type: state.storage_mode || "unknown",
+          safe_mode: state.safe_mode,
         };
       }
     } catch {}
@@ -185,6 +196,8 @@ const RealAPI: AppAPI = {
           if (state.tmpfs_xattr_supported !== undefined) {
             info.tmpfs_xattr_supported = state.tmpfs_xattr_supported;
           }
+          info.scriptFailures = state.script_failures || [];
+          info.maintenance = state.maintenance;
         } catch {}
       }
       return info;
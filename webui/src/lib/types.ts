@@ -6,19 +6,77 @@
 export interface ModuleRules {
   default_mode: MountMode;
   paths: Record<string, string>;
+  skip_umount?: boolean;
+  stage?: "post-fs-data" | "service";
+  extends?: string;
+  companions?: Record<string, string>;
 }
 
-export type OverlayMode = "tmpfs" | "ext4" | "erofs";
+export type OverlayMode = "tmpfs" | "ext4" | "erofs" | "squashfs";
 
 export interface AppConfig {
   moduledir: string;
   mountsource: string;
   hybrid_mnt_dir: string;
+  builtin_partitions?: string[];
   partitions: string[];
+  auto_partitions?: boolean;
+  protected_paths?: string[];
+  companion_paths?: string[];
+  integrity?: boolean;
+  early_namespaces?: string[];
   overlay_mode: OverlayMode;
+  image_fs?: "ext4" | "f2fs" | "auto";
+  space_warn_boots?: number;
   disable_umount: boolean;
   allow_umount_coexistence: boolean;
   logfile?: string;
+  log_format?: "plain" | "json" | "logcat";
+  logging?: {
+    max_size: string;
+    keep: number;
+    redact?: { pattern: string; action?: "hash" | "truncate" }[];
+  };
+  suppressed_issues?: string[];
+  coexistence?: "off" | "skip" | "takeover" | "abort";
+  priority?: Record<string, number>;
+  rule_templates?: Record<string, ModuleRules>;
+  resolve?: Record<string, string>;
+  defer?: {
+    max_conflicts: number;
+    allow?: string[];
+  };
+  sepolicy?: {
+    enabled?: boolean;
+    tool?: string;
+  };
+  scripts?: {
+    enabled?: boolean;
+    timeout?: number;
+    timeouts?: Record<string, number>;
+    after?: Record<string, string[]>;
+  };
+  denylist_umount?: boolean;
+  visibility?: Record<string, string[]>;
+  maintenance?: {
+    enabled?: boolean;
+    interval_days?: number;
+    require_charging?: boolean;
+    max_load?: number;
+  };
+  artifacts?: {
+    dir?: string;
+    retention_days?: number;
+  };
+  limits?: {
+    max_entries?: number;
+    max_size?: string;
+  };
+  notify?: {
+    broadcast?: string;
+    ntfy_url?: string;
+    script?: string;
+  };
 }
 
 export type MountMode = "overlay" | "magic" | "ignore";
@@ -34,10 +92,14 @@ export interface Module {
   enabled?: boolean;
   source_path?: string;
   rules: ModuleRules;
+  source_changed?: boolean;
+  deferred?: number;
+  error?: string;
 }
 
 export interface StorageStatus {
-  type: "tmpfs" | "ext4" | "erofs" | "unknown" | null;
+  type: "tmpfs" | "ext4" | "erofs" | "squashfs" | "unknown" | null;
+  safe_mode?: "marker" | "volume_key";
   error?: string;
 }
 
@@ -49,6 +111,32 @@ export interface SystemInfo {
   zygisksuEnforce?: string;
   supported_overlay_modes?: OverlayMode[];
   tmpfs_xattr_supported?: boolean;
+  scriptFailures?: ScriptFailure[];
+  maintenance?: MaintenanceSummary;
+}
+
+export interface ScriptFailure {
+  module: string;
+  script: string;
+  reason: string;
+}
+
+export interface MaintenanceSummary {
+  started: number;
+  millis: number;
+  tasks: {
+    task: string;
+    status: "done" | "skipped" | "failed";
+    detail: string;
+  }[];
+}
+
+export interface ProfileEntry {
+  name: string;
+  format: "snapshot" | "toml";
+  created: number;
+  disabled_modules: number;
+  active: boolean;
 }
 
 export interface DeviceInfo {
@@ -75,3 +163,64 @@ export interface ModeStats {
   auto: number;
   magic: number;
 }
+
+export interface FileEntry {
+  name: string;
+  kind: "dir" | "file" | "symlink" | "whiteout" | "other";
+  size: number;
+  mode: number;
+  mtime: number;
+  context?: string;
+  target?: string;
+  opaque?: boolean;
+}
+
+export interface FilePage {
+  module: string;
+  path: string;
+  root: string;
+  total: number;
+  offset: number;
+  entries: FileEntry[];
+}
+
+export interface InstallSummary {
+  id: string;
+  name: string;
+  version: string;
+  path: string;
+  files: number;
+  updated: boolean;
+  installed: boolean;
+  conflicts: {
+    partition: string;
+    relative_path: string;
+    contending_modules: string[];
+    winner: string;
+  }[];
+  critical: {
+    code: string;
+    level: string;
+    context: string;
+    message: string;
+    path?: string;
+  }[];
+}
+
+export interface ModuleChange {
+  module: string;
+  action: "enable" | "disable" | "remove";
+  path: string;
+  pruned: boolean;
+  targets: string[];
+  pending: boolean;
+  live_error?: string;
+}
+
+export interface CliError {
+  error: {
+    code: string;
+    message: string;
+    context_chain: string[];
+  };
+}
@@ -145,6 +145,7 @@ export default function ConfigTab() {
     tmpfs: "RAM-based. Fastest I/O, reset on reboot.",
     ext4: "Loopback image. Persistent, saves RAM.",
     erofs: "Read-only compressed. High performance, space saving.",
+    squashfs: "Read-only compressed. Works on kernels without EROFS.",
   };
 
   return (
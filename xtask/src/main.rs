@@ -2,7 +2,8 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::{
-    env, fs,
+    env,
+    fs::{self, File},
     path::{Path, PathBuf},
     process::{Command, Stdio},
 };
@@ -13,7 +14,8 @@ use fs_extra::{
     dir::{self},
     file::{self},
 };
-use tempfile::NamedTempFile;
+use sha2::{Digest, Sha256};
+use tempfile::{Builder, NamedTempFile};
 use zip::{CompressionMethod, write::FileOptions};
 
 mod zip_ext;
@@ -60,6 +62,9 @@ enum Commands {
         release: bool,
         #[arg(long)]
         skip_webui: bool,
+        // Packs the built WebUI into the binary as well.
+        #[arg(long, conflicts_with = "skip_webui")]
+        embed_webui: bool,
         #[arg(long, value_enum)]
         arch: Option<Arch>,
 
@@ -70,6 +75,8 @@ enum Commands {
         cert: PathBuf,
     },
     Lint,
+    // Removes build outputs and puts generated sources back to their checked-in state.
+    Clean,
 }
 
 fn main() -> Result<()> {
@@ -78,15 +85,19 @@ fn main() -> Result<()> {
         Commands::Build {
             release,
             skip_webui,
+            embed_webui,
             arch,
             key_enc,
             cert,
         } => {
-            build_full(release, skip_webui, arch, &key_enc, &cert)?;
+            build_full(release, skip_webui, embed_webui, arch, &key_enc, &cert)?;
         }
         Commands::Lint => {
             run_clippy()?;
         }
+        Commands::Clean => {
+            clean()?;
+        }
     }
     Ok(())
 }
@@ -96,18 +107,28 @@ fn run_clippy() -> Result<()> {
 
     let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
 
+    // embed-webui compiles module/webroot in. Without a built WebUI a placeholder stands in for
+    // the lint and goes again afterwards; the lock keeps a parallel build from picking it up.
+    fs::create_dir_all(OUTPUT_DIR)?;
+    let tree_lock = File::create(BUILD_LOCK)?;
+    tree_lock
+        .lock()
+        .context("Failed to lock the source tree for the lint")?;
+    let webroot = Path::new(WEBROOT_DIR);
+    let stub = !webroot.is_dir();
+    if stub {
+        fs::create_dir_all(webroot)?;
+        fs::write(webroot.join("index.html"), "<!-- lint placeholder -->\n")?;
+    }
     let status = Command::new(cargo)
-        .args([
-            "clippy",
-            "--workspace",
-            "--all-targets",
-            "--all-features",
-            "--",
-            "-D",
-            "warnings",
-        ])
+        .args(["clippy", "--workspace", "--all-targets", "--all-features"])
+        .args(["--", "-D", "warnings"])
         .status()
-        .context("Failed to run cargo clippy")?;
+        .context("Failed to run cargo clippy");
+    if stub {
+        fs::remove_dir_all(webroot)?;
+    }
+    let status = status?;
 
     if !status.success() {
         anyhow::bail!("Clippy found issues! Please fix them before committing.");
@@ -117,20 +138,66 @@ fn run_clippy() -> Result<()> {
     Ok(())
 }
 
+const OUTPUT_DIR: &str = "output";
+const BUILD_LOCK: &str = "output/.build.lock";
+const WEBROOT_DIR: &str = "module/webroot";
+const WEBUI_CONSTANTS: &str = "webui/src/lib/constants_gen.ts";
+const WEBUI_CONSTANTS_LEGACY: &str = "webui/src/lib/constants_gen.js";
+
+fn remove_path(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else if path.exists() {
+        fs::remove_file(path)?;
+    } else {
+        return Ok(());
+    }
+    println!(":: Removed {}", path.display());
+    Ok(())
+}
+
+fn clean() -> Result<()> {
+    for path in [OUTPUT_DIR, WEBROOT_DIR, WEBUI_CONSTANTS_LEGACY] {
+        remove_path(Path::new(path))?;
+    }
+
+    // The build overwrites the checked-in placeholder with the real version.
+    let restored = Command::new("git")
+        .args(["checkout", "--", WEBUI_CONSTANTS])
+        .status()
+        .is_ok_and(|s| s.success());
+    if restored {
+        println!(":: Restored {}", WEBUI_CONSTANTS);
+    } else {
+        println!("Warning: could not restore {} with git", WEBUI_CONSTANTS);
+    }
+
+    println!(":: Clean complete");
+    Ok(())
+}
+
 fn build_full(
     release: bool,
     skip_webui: bool,
+    embed_webui: bool,
     target_arch: Option<Arch>,
     key_enc_path: &Path,
     cert_path: &Path,
 ) -> Result<()> {
-    let output_dir = Path::new("output");
-    let stage_dir = output_dir.join("staging");
-    if output_dir.exists() {
-        fs::remove_dir_all(output_dir)?;
-    }
-    fs::create_dir_all(&stage_dir)?;
+    // Each build stages into its own directory, so parallel builds (a CI matrix) can share
+    // output/; it is removed again when the build ends, failed or not.
+    let output_dir = Path::new(OUTPUT_DIR);
+    fs::create_dir_all(output_dir)?;
+    let staging = Builder::new().prefix("staging-").tempdir_in(output_dir)?;
+    let stage_dir = staging.path();
     let version = get_version()?;
+
+    // The WebUI is built into module/webroot and the generated constants in webui/, both shared
+    // by every build, so only one build at a time writes and reads them.
+    let tree_lock = File::create(BUILD_LOCK)?;
+    tree_lock
+        .lock()
+        .context("Failed to lock the source tree for the build")?;
     if !skip_webui {
         println!(":: Building WebUI...");
         build_webui(&version)?;
@@ -142,11 +209,20 @@ fn build_full(
         vec![Arch::Arm64, Arch::Arm, Arch::X86_64]
     };
 
-    for arch in archs_to_build {
+    println!(":: Copying module scripts...");
+    let module_src = Path::new("module");
+    let options = dir::CopyOptions::new().overwrite(true).content_only(true);
+    dir::copy(module_src, stage_dir, &options)?;
+    // Only an embedding compile still reads the shared webroot.
+    if !embed_webui {
+        tree_lock.unlock()?;
+    }
+
+    let profile = if release { "release" } else { "debug" };
+    for &arch in &archs_to_build {
         println!(":: Compiling Core for {:?}...", arch);
-        compile_core(release, arch)?;
+        compile_core(release, arch, embed_webui)?;
         let bin_name = "meta-hybrid";
-        let profile = if release { "release" } else { "debug" };
         let src_bin = Path::new("target")
             .join(arch.android_abi())
             .join(profile)
@@ -163,38 +239,53 @@ fn build_full(
             println!("Warning: Binary not found at {}", src_bin.display());
         }
     }
-    println!(":: Copying module scripts...");
-    let module_src = Path::new("module");
-    let options = dir::CopyOptions::new().overwrite(true).content_only(true);
-    dir::copy(module_src, &stage_dir, &options)?;
+    drop(tree_lock);
     let gitignore = stage_dir.join(".gitignore");
     if gitignore.exists() {
         fs::remove_file(gitignore)?;
     }
+    write_tool_checksums(&stage_dir.join("tools"))?;
     println!(":: Injecting version: {}", version);
     update_module_prop(&stage_dir.join("module.prop"), &version)?;
     println!(":: Creating Zip...");
-    let zip_file = output_dir.join(format!("Meta-Hybrid-{}.zip", version));
+    // Builds of other architectures or profiles get their own zip; the zip is written and signed
+    // next to the staging directory and only then renamed into place, so a reader never sees a
+    // half-written one.
+    let arch_name = match archs_to_build.as_slice() {
+        [arch] => arch.target(),
+        _ => "all",
+    };
+    let zip_name = format!("Meta-Hybrid-{}-{}-{}.zip", version, arch_name, profile);
+    let zip_file = output_dir.join(&zip_name);
+    let staged_zip = staging.path().with_extension("zip");
     let zip_options = FileOptions::default()
         .compression_method(CompressionMethod::Deflated)
         .compression_level(Some(9));
-    zip_create_from_directory_with_options(&zip_file, &stage_dir, |_| zip_options)?;
+    let packed = (|| -> Result<()> {
+        zip_create_from_directory_with_options(&staged_zip, stage_dir, |_| zip_options)?;
+        sign(&staged_zip, key_enc_path, cert_path)?;
+        fs::rename(&staged_zip, &zip_file)?;
+        Ok(())
+    })();
+    if packed.is_err() {
+        let _ = fs::remove_file(&staged_zip);
+    }
+    packed?;
     println!(":: Build Complete: {}", zip_file.display());
-    if let Ok(password) = env::var("META_HYBRID_SIGN_PASSWORD")
-        && !password.is_empty()
-    {
-        let abs_key_enc = key_enc_path;
-        let abs_cert = cert_path;
+    Ok(())
+}
 
-        if abs_key_enc.exists() && abs_cert.exists() {
-            decrypt_and_sign(&zip_file, abs_key_enc, abs_cert, &password)?;
-        } else {
-            println!(":: Skipping signature: private.enc or cert.pem not found at root.");
+fn sign(zip_file: &Path, key_enc_path: &Path, cert_path: &Path) -> Result<()> {
+    match env::var("META_HYBRID_SIGN_PASSWORD") {
+        Ok(password) if !password.is_empty() => {
+            if key_enc_path.exists() && cert_path.exists() {
+                decrypt_and_sign(zip_file, key_enc_path, cert_path, &password)?;
+            } else {
+                println!(":: Skipping signature: private.enc or cert.pem not found at root.");
+            }
         }
-    } else {
-        println!(":: Skipping signature: META_HYBRID_SIGN_PASSWORD not set.");
+        _ => println!(":: Skipping signature: META_HYBRID_SIGN_PASSWORD not set."),
     }
-
     Ok(())
 }
 
@@ -271,7 +362,7 @@ fn build_webui(version: &str) -> Result<()> {
 }
 
 fn generate_webui_constants(version: &str) -> Result<()> {
-    let path = Path::new("webui/src/lib/constants_gen.ts");
+    let path = Path::new(WEBUI_CONSTANTS);
     let content = format!(
         r#"
 export const APP_VERSION = "{version}";
@@ -289,14 +380,14 @@ export const BUILTIN_PARTITIONS = ["system", "vendor", "product", "system_ext",
         fs::create_dir_all(parent)?;
     }
     fs::write(path, content)?;
-    let old_path = Path::new("webui/src/lib/constants_gen.js");
+    let old_path = Path::new(WEBUI_CONSTANTS_LEGACY);
     if old_path.exists() {
         let _ = fs::remove_file(old_path);
     }
     Ok(())
 }
 
-fn compile_core(release: bool, arch: Arch) -> Result<()> {
+fn compile_core(release: bool, arch: Arch, embed_webui: bool) -> Result<()> {
     let mut cmd = Command::new("cargo");
     cmd.args([
         "ndk",
@@ -312,6 +403,9 @@ fn compile_core(release: bool, arch: Arch) -> Result<()> {
     if release {
         cmd.arg("-r");
     }
+    if embed_webui {
+        cmd.args(["--features", "embed-webui"]);
+    }
     let mut ret = cmd.spawn()?;
     let status = ret.wait()?;
     if !status.success() {
@@ -348,6 +442,35 @@ fn get_version() -> Result<String> {
     Ok("v0.0.0-unknown".to_string())
 }
 
+// tools/<abi>/checksums.json, which the core checks before running a bundled helper.
+fn write_tool_checksums(tools_dir: &Path) -> Result<()> {
+    let Ok(entries) = fs::read_dir(tools_dir) else {
+        return Ok(());
+    };
+    for abi_dir in entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()) {
+        let mut sums = Vec::new();
+        for tool in fs::read_dir(&abi_dir)?.flatten() {
+            let name = tool.file_name().to_string_lossy().to_string();
+            if !tool.path().is_file() || name == "checksums.json" {
+                continue;
+            }
+            let sum = Sha256::digest(fs::read(tool.path())?);
+            sums.push(format!("  \"{}\": \"{:x}\"", name, sum));
+        }
+        sums.sort();
+        println!(
+            ":: Recorded checksums for {} tool(s) in {}",
+            sums.len(),
+            abi_dir.display()
+        );
+        fs::write(
+            abi_dir.join("checksums.json"),
+            format!("{{\n{}\n}}\n", sums.join(",\n")),
+        )?;
+    }
+    Ok(())
+}
+
 fn update_module_prop(path: &Path, version: &str) -> Result<()> {
     if !path.exists() {
         return Ok(());